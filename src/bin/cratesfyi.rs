@@ -6,17 +6,22 @@ use std::str::FromStr;
 use std::sync::Arc;
 
 use anyhow::{anyhow, Context as _, Error, Result};
-use clap::{Parser, Subcommand, ValueEnum};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
 use docs_rs::cdn::CdnBackend;
 use docs_rs::db::{self, add_path_into_database, Overrides, Pool, PoolClient};
+use docs_rs::index::AlternativeIndexes;
 use docs_rs::repositories::RepositoryStatsUpdater;
 use docs_rs::utils::{
-    get_config, get_crate_pattern_and_priority, list_crate_priorities, queue_builder,
-    remove_crate_priority, set_crate_priority, ConfigName,
+    build_worker, campaign_progress, create_campaign, get_config, get_crate_pattern_and_priority,
+    list_crate_priorities, queue_builder, remove_campaign, remove_crate_priority,
+    set_crate_priority, CampaignSelector, ConfigName, DEFAULT_CAMPAIGN_BATCH_SIZE,
+    DEFAULT_CAMPAIGN_PRIORITY,
 };
 use docs_rs::{
-    start_background_metrics_webserver, start_web_server, BuildQueue, Config, Context, Index,
-    InstanceMetrics, PackageKind, RustwideBuilder, ServiceMetrics, Storage,
+    start_background_metrics_webserver, start_web_server, suggest_overrides, BuildQueue, Config,
+    Context, Index, InstanceMetrics, PackageKind, RustwideBuilder, ServiceMetrics, Storage,
+    WorkerClient,
 };
 use humantime::Duration;
 use once_cell::sync::OnceCell;
@@ -130,6 +135,10 @@ enum CommandLine {
         metric_server_socket_addr: SocketAddr,
     },
 
+    /// Starts a build worker, which builds crates leased from the build server at
+    /// `DOCSRS_WORKER_SERVER_URL` instead of using the database
+    StartBuildWorker,
+
     /// Starts the daemon
     Daemon {
         /// Enable or disable the registry watcher to automatically enqueue newly published crates
@@ -173,17 +182,24 @@ impl CommandLine {
 
                 start_background_metrics_webserver(Some(metric_server_socket_addr), &ctx)?;
 
+                docs_rs::utils::daemon::start_alternative_registry_watchers(&ctx)?;
                 docs_rs::utils::watch_registry(ctx.build_queue()?, ctx.config()?, ctx.index()?)?;
             }
             Self::StartBuildServer {
                 metric_server_socket_addr,
             } => {
                 start_background_metrics_webserver(Some(metric_server_socket_addr), &ctx)?;
+                docs_rs::utils::daemon::start_background_running_build_reaper(&ctx)?;
 
                 let build_queue = ctx.build_queue()?;
                 let rustwide_builder = RustwideBuilder::init(&ctx)?;
                 queue_builder(rustwide_builder, build_queue)?;
             }
+            Self::StartBuildWorker => {
+                let client = Arc::new(WorkerClient::new(&*ctx.config()?)?);
+                let rustwide_builder = RustwideBuilder::init_worker(&ctx, client.clone())?;
+                build_worker(rustwide_builder, client)?;
+            }
             Self::StartWebServer { socket_addr } => {
                 // Blocks indefinitely
                 start_web_server(Some(socket_addr), &ctx)?;
@@ -226,8 +242,18 @@ enum QueueSubcommand {
         subcommand: PrioritySubcommand,
     },
 
+    /// Mass rebuilds of releases, queued in batches at a low priority
+    Campaign {
+        #[command(subcommand)]
+        subcommand: CampaignSubcommand,
+    },
+
     /// Get the registry watcher's last seen reference
-    GetLastSeenReference,
+    GetLastSeenReference {
+        /// Name of the alternative registry, instead of the main index
+        #[arg(long)]
+        registry: Option<String>,
+    },
 
     /// Set the registry watcher's last seen reference
     #[command(arg_required_else_help(true))]
@@ -236,9 +262,14 @@ enum QueueSubcommand {
         #[arg(conflicts_with("head"))]
         reference: Option<crates_index_diff::gix::ObjectId>,
 
-        /// Fetch the current HEAD of the remote index and use it
+        /// Fetch the current HEAD of the remote index and use it,
+        /// or the current time for sparse indexes
         #[arg(long, conflicts_with("reference"))]
         head: bool,
+
+        /// Name of the alternative registry, instead of the main index
+        #[arg(long)]
+        registry: Option<String>,
     },
 }
 
@@ -249,37 +280,176 @@ impl QueueSubcommand {
                 crate_name,
                 crate_version,
                 build_priority,
-            } => ctx.build_queue()?.add_crate(
-                &crate_name,
-                &crate_version,
-                build_priority,
-                ctx.config()?.registry_url.as_deref(),
-            )?,
+            } => {
+                let config = ctx.config()?;
+                ctx.build_queue()?.add_crate(
+                    &crate_name,
+                    &crate_version,
+                    build_priority,
+                    config.index_url_for_crate(&crate_name)?,
+                )?
+            }
 
-            Self::GetLastSeenReference => {
-                if let Some(reference) = ctx.build_queue()?.last_seen_reference()? {
+            Self::GetLastSeenReference { registry } => {
+                if let Some(reference) = ctx
+                    .build_queue()?
+                    .last_seen_reference(registry.as_deref())?
+                {
                     println!("Last seen reference: {reference}");
                 } else {
                     println!("No last seen reference available");
                 }
             }
 
-            Self::SetLastSeenReference { reference, head } => {
+            Self::SetLastSeenReference {
+                reference,
+                head,
+                registry,
+            } => {
                 let reference = match (reference, head) {
                     (Some(reference), false) => reference,
                     (None, true) => {
+                        let index = match registry.as_deref() {
+                            Some(registry) => ctx
+                                .alternative_indexes()?
+                                .get(registry)
+                                .cloned()
+                                .with_context(|| format!("unknown registry {registry}"))?,
+                            None => ctx.index()?,
+                        };
+                        // sparse indexes have no history, changes are tracked by the time
+                        // of the last update instead.
+                        if index.is_sparse() {
+                            let now = Utc::now();
+                            ctx.build_queue()?
+                                .set_last_seen_index_update(registry.as_deref(), now)?;
+                            println!("Set last seen index update: {now}");
+                            return Ok(());
+                        }
+
                         println!("Fetching changes to set reference to HEAD");
-                        let (_, oid) = ctx.index()?.diff()?.peek_changes()?;
+                        let (_, oid) = index.diff()?.peek_changes()?;
                         oid
                     }
                     (_, _) => unreachable!(),
                 };
 
-                ctx.build_queue()?.set_last_seen_reference(reference)?;
+                ctx.build_queue()?
+                    .set_last_seen_reference(registry.as_deref(), reference)?;
                 println!("Set last seen reference: {reference}");
             }
 
             Self::DefaultPriority { subcommand } => subcommand.handle_args(ctx)?,
+
+            Self::Campaign { subcommand } => subcommand.handle_args(ctx)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+enum CampaignSubcommand {
+    /// Create a campaign rebuilding the releases matching one selector
+    #[command(group(ArgGroup::new("selector").required(true)))]
+    Create {
+        /// Name of the campaign
+        name: String,
+
+        /// Rebuild releases documented with a nightly toolchain older than this date
+        #[arg(long, group = "selector", value_name = "YYYY-MM-DD")]
+        rustc_older_than: Option<NaiveDate>,
+
+        /// Rebuild releases whose latest build failed after this time
+        #[arg(long, group = "selector", value_name = "RFC3339")]
+        failed_after: Option<DateTime<Utc>>,
+
+        /// Rebuild releases whose latest build failed before this time, defaults to now
+        #[arg(long, requires = "failed_after", value_name = "RFC3339")]
+        failed_before: Option<DateTime<Utc>>,
+
+        /// Rebuild releases of crates matching this pattern, see `queue default-priority set`
+        #[arg(long, group = "selector")]
+        pattern: Option<String>,
+
+        /// Priority of the queued builds
+        #[arg(long, default_value_t = DEFAULT_CAMPAIGN_PRIORITY, allow_negative_numbers = true)]
+        priority: i32,
+
+        /// Number of builds of the campaign in the queue at the same time
+        #[arg(long, default_value_t = DEFAULT_CAMPAIGN_BATCH_SIZE)]
+        batch_size: i32,
+    },
+
+    /// Show the progress of all campaigns
+    List,
+
+    /// Remove a campaign, its builds that are queued already stay in the queue
+    Remove {
+        /// Name of the campaign
+        name: String,
+    },
+}
+
+impl CampaignSubcommand {
+    fn handle_args(self, ctx: BinContext) -> Result<()> {
+        let conn = &mut *ctx.conn()?;
+        match self {
+            Self::Create {
+                name,
+                rustc_older_than,
+                failed_after,
+                failed_before,
+                pattern,
+                priority,
+                batch_size,
+            } => {
+                let selector = if let Some(date) = rustc_older_than {
+                    CampaignSelector::RustcOlderThan { date }
+                } else if let Some(from) = failed_after {
+                    CampaignSelector::FailedBetween {
+                        from,
+                        to: failed_before.unwrap_or_else(Utc::now),
+                    }
+                } else if let Some(pattern) = pattern {
+                    CampaignSelector::CratePattern { pattern }
+                } else {
+                    unreachable!()
+                };
+
+                let releases = create_campaign(conn, &name, &selector, priority, batch_size)
+                    .context("Could not create the campaign")?;
+                println!("Created campaign '{name}' rebuilding {releases} releases {selector}");
+            }
+
+            Self::List => {
+                for campaign in campaign_progress(conn)? {
+                    println!(
+                        "{} ({}): {}/{} rebuilt, {} queued, {} succeeded, {} failed, {} fixed, {} broken{}",
+                        campaign.name,
+                        campaign.selector,
+                        campaign.succeeded + campaign.failed,
+                        campaign.total,
+                        campaign.queued,
+                        campaign.succeeded,
+                        campaign.failed,
+                        campaign.fixed,
+                        campaign.broken,
+                        if campaign.finished_at.is_some() {
+                            ", finished"
+                        } else {
+                            ""
+                        },
+                    );
+                }
+            }
+
+            Self::Remove { name } => {
+                if remove_campaign(conn, &name)? {
+                    println!("Removed campaign '{name}'");
+                } else {
+                    println!("Campaign '{name}' did not exist and so was not removed");
+                }
+            }
         }
         Ok(())
     }
@@ -482,6 +652,9 @@ enum DatabaseSubcommand {
     /// Backfill GitHub/Gitlab stats for crates.
     BackfillRepositoryStats,
 
+    /// Backfill the reverse dependencies from the dependencies of all releases.
+    BackfillDependencies,
+
     /// Updates info for a crate from the registry's API
     UpdateCrateRegistryFields {
         #[arg(name = "CRATE")]
@@ -537,6 +710,12 @@ impl DatabaseSubcommand {
                 ctx.repository_stats_updater()?.backfill_repositories()?;
             }
 
+            Self::BackfillDependencies => {
+                let count = db::backfill_dependencies(&mut *ctx.conn()?)
+                    .context("Failed to backfill the dependencies")?;
+                println!("Backfilled the dependencies of {count} releases");
+            }
+
             Self::UpdateCrateRegistryFields { name } => {
                 let index = ctx.index()?;
 
@@ -605,6 +784,16 @@ enum LimitsSubcommand {
 
     /// Remove sandbox limits overrides for a crate
     Remove { crate_name: String },
+
+    /// Suggest sandbox limit overrides for crates whose builds keep hitting their limits
+    Suggest {
+        /// How far back to look at builds
+        #[arg(long, default_value = "30days")]
+        since: Duration,
+        /// How many builds have to hit the limits before suggesting an override
+        #[arg(long, default_value_t = 2)]
+        min_builds: usize,
+    },
 }
 
 impl LimitsSubcommand {
@@ -634,6 +823,7 @@ impl LimitsSubcommand {
                     memory,
                     targets,
                     timeout: timeout.map(Into::into),
+                    automatic: false,
                 };
                 Overrides::save(conn, &crate_name, overrides)?;
                 let overrides = Overrides::for_crate(conn, &crate_name)?;
@@ -645,6 +835,19 @@ impl LimitsSubcommand {
                 println!("previous overrides for {crate_name} = {overrides:?}");
                 Overrides::remove(conn, &crate_name)?;
             }
+
+            Self::Suggest { since, min_builds } => {
+                let config = ctx.config()?;
+                for suggestion in suggest_overrides(&config, conn, since.into(), min_builds)? {
+                    println!(
+                        "{} hit the sandbox limits in {} builds, overrides = {:?}, suggested = {:?}",
+                        suggestion.crate_name,
+                        suggestion.builds,
+                        suggestion.current,
+                        suggestion.suggested,
+                    );
+                }
+            }
         }
         Ok(())
     }
@@ -720,6 +923,7 @@ struct BinContext {
     service_metrics: OnceCell<Arc<ServiceMetrics>>,
     instance_metrics: OnceCell<Arc<InstanceMetrics>>,
     index: OnceCell<Arc<Index>>,
+    alternative_indexes: OnceCell<Arc<AlternativeIndexes>>,
     repository_stats_updater: OnceCell<Arc<RepositoryStatsUpdater>>,
     runtime: OnceCell<Arc<Runtime>>,
 }
@@ -735,6 +939,7 @@ impl BinContext {
             service_metrics: OnceCell::new(),
             instance_metrics: OnceCell::new(),
             index: OnceCell::new(),
+            alternative_indexes: OnceCell::new(),
             repository_stats_updater: OnceCell::new(),
             runtime: OnceCell::new(),
         }
@@ -791,6 +996,7 @@ impl Context for BinContext {
                 Index::new(path, config.crates_io_api_call_retries)
             }?
         };
+        fn alternative_indexes(self) -> AlternativeIndexes = AlternativeIndexes::new(&*self.config()?)?;
         fn repository_stats_updater(self) -> RepositoryStatsUpdater = {
             let config = self.config()?;
             let pool = self.pool()?;
@@ -1,17 +1,43 @@
 use crate::cdn;
 use crate::db::{delete_crate, delete_version, Pool};
-use crate::docbuilder::PackageKind;
+use crate::docbuilder::{
+    finish_running_build, raise_limits, start_running_build, FailureCategory, PackageKind,
+    RUNNING_BUILD_TIMEOUT,
+};
 use crate::error::Result;
+use crate::index::{qualified_crate_name, sparse::SparseIndex};
 use crate::storage::Storage;
-use crate::utils::{get_config, get_crate_priority, report_error, retry, set_config, ConfigName};
+use crate::utils::{
+    get_config, get_config_by_key, get_crate_priority, report_error, retry, set_config,
+    set_config_by_key, ConfigName,
+};
 use crate::{Config, Index, InstanceMetrics, RustwideBuilder};
 use anyhow::Context;
+use chrono::{DateTime, Utc};
 use fn_error_context::context;
+use postgres::GenericClient;
+use semver::{Version, VersionReq};
 
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Builds started for an owner in this time count against their next turns.
+const OWNER_BUILDS_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// The number of recent builds of a crate its expected build duration is averaged over.
+const ESTIMATE_RECENT_BUILDS: i64 = 5;
+
+/// The expected build duration when there are no recent builds to estimate it from.
+const DEFAULT_BUILD_DURATION: Duration = Duration::from_secs(2 * 60);
+
+/// How long the CDN in front of a sparse index may serve outdated index files.
+const SPARSE_INDEX_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// How long the expected build durations are reused for before they're loaded again.
+const ESTIMATE_CACHE_TTL: Duration = Duration::from_secs(60);
 
 #[derive(Debug, Clone, Eq, PartialEq, serde::Serialize)]
 pub(crate) struct QueuedCrate {
@@ -21,6 +47,22 @@ pub(crate) struct QueuedCrate {
     pub(crate) version: String,
     pub(crate) priority: i32,
     pub(crate) registry: Option<String>,
+    /// The owner the build is scheduled for, `None` if the crate has no known owners yet.
+    pub(crate) owner: Option<String>,
+    /// The newer queued version of the crate this build is deprioritised for.
+    pub(crate) superseded_by: Option<String>,
+    /// When the build is expected to start, only set by [`BuildQueue::queued_crates_with_estimates`].
+    pub(crate) estimated_start: Option<DateTime<Utc>>,
+}
+
+/// The expected build durations the queue estimates are made from, loaded at `loaded_at`.
+#[derive(Debug)]
+struct ExpectedDurations {
+    loaded_at: Instant,
+    /// The duration of crates without recent builds.
+    default: Duration,
+    /// The durations of the crates looked up so far, `None` if they have no recent builds.
+    crates: HashMap<String, Option<Duration>>,
 }
 
 #[derive(Debug)]
@@ -30,6 +72,7 @@ pub struct BuildQueue {
     pub(crate) db: Pool,
     metrics: Arc<InstanceMetrics>,
     max_attempts: i32,
+    expected_durations: Mutex<Option<ExpectedDurations>>,
 }
 
 impl BuildQueue {
@@ -45,12 +88,29 @@ impl BuildQueue {
             db,
             metrics,
             storage,
+            expected_durations: Mutex::new(None),
         }
     }
 
-    pub fn last_seen_reference(&self) -> Result<Option<crates_index_diff::gix::ObjectId>> {
+    /// The name of a setting about a registry index, alternative registries each have their own.
+    fn registry_config_key(name: ConfigName, registry: Option<&str>) -> String {
+        let name: &'static str = name.into();
+        match registry {
+            Some(registry) => format!("{name}:{registry}"),
+            None => name.to_owned(),
+        }
+    }
+
+    /// The last seen reference of the main index, or of the given alternative registry.
+    pub fn last_seen_reference(
+        &self,
+        registry: Option<&str>,
+    ) -> Result<Option<crates_index_diff::gix::ObjectId>> {
         let mut conn = self.db.get()?;
-        if let Some(value) = get_config::<String>(&mut conn, ConfigName::LastSeenIndexReference)? {
+        if let Some(value) = get_config_by_key::<String>(
+            &mut conn,
+            &Self::registry_config_key(ConfigName::LastSeenIndexReference, registry),
+        )? {
             return Ok(Some(crates_index_diff::gix::ObjectId::from_hex(
                 value.as_bytes(),
             )?));
@@ -58,16 +118,43 @@ impl BuildQueue {
         Ok(None)
     }
 
-    pub fn set_last_seen_reference(&self, oid: crates_index_diff::gix::ObjectId) -> Result<()> {
+    pub fn set_last_seen_reference(
+        &self,
+        registry: Option<&str>,
+        oid: crates_index_diff::gix::ObjectId,
+    ) -> Result<()> {
         let mut conn = self.db.get()?;
-        set_config(
+        set_config_by_key(
             &mut conn,
-            ConfigName::LastSeenIndexReference,
+            &Self::registry_config_key(ConfigName::LastSeenIndexReference, registry),
             oid.to_string(),
         )?;
         Ok(())
     }
 
+    /// The time of the last update seen in the sparse main index, or of the given alternative
+    /// registry.
+    pub fn last_seen_index_update(&self, registry: Option<&str>) -> Result<Option<DateTime<Utc>>> {
+        let mut conn = self.db.get()?;
+        get_config_by_key(
+            &mut conn,
+            &Self::registry_config_key(ConfigName::LastSeenIndexUpdate, registry),
+        )
+    }
+
+    pub fn set_last_seen_index_update(
+        &self,
+        registry: Option<&str>,
+        updated_at: DateTime<Utc>,
+    ) -> Result<()> {
+        let mut conn = self.db.get()?;
+        set_config_by_key(
+            &mut conn,
+            &Self::registry_config_key(ConfigName::LastSeenIndexUpdate, registry),
+            updated_at,
+        )
+    }
+
     #[context("error trying to add {name}-{version} to build queue")]
     pub fn add_crate(
         &self,
@@ -76,7 +163,19 @@ impl BuildQueue {
         priority: i32,
         registry: Option<&str>,
     ) -> Result<()> {
-        self.db.get()?.execute(
+        self.add_crate_with(&mut *self.db.get()?, name, version, priority, registry)
+    }
+
+    /// Same as [`BuildQueue::add_crate`], on an existing connection or transaction.
+    pub(crate) fn add_crate_with(
+        &self,
+        conn: &mut impl GenericClient,
+        name: &str,
+        version: &str,
+        priority: i32,
+        registry: Option<&str>,
+    ) -> Result<()> {
+        conn.execute(
             "INSERT INTO queue (name, version, priority, registry)
              VALUES ($1, $2, $3, $4)
              ON CONFLICT (name, version) DO UPDATE
@@ -86,6 +185,50 @@ impl BuildQueue {
             ;",
             &[&name, &version, &priority, &registry],
         )?;
+
+        self.update_superseded_builds(conn, name)?;
+        Ok(())
+    }
+
+    /// Marks the queued builds of `name` that are superseded by a newer semver-compatible
+    /// version queued as well, only the newest of them is likely to be visited.
+    ///
+    /// Builds whose newer version left the queue or failed for the last time aren't superseded
+    /// anymore, so this runs again whenever a build of `name` finishes.
+    fn update_superseded_builds(&self, conn: &mut impl GenericClient, name: &str) -> Result<()> {
+        if !self.config.deprioritize_superseded_builds {
+            return Ok(());
+        }
+
+        let queued: Vec<(i32, Version, Option<String>)> = conn
+            .query(
+                "SELECT id, version, superseded_by FROM queue WHERE name = $1 AND attempt < $2",
+                &[&name, &self.max_attempts],
+            )?
+            .into_iter()
+            .filter_map(|row| {
+                let version = Version::parse(row.get("version")).ok()?;
+                Some((row.get("id"), version, row.get("superseded_by")))
+            })
+            .collect();
+
+        for (id, version, superseded_by) in &queued {
+            let Ok(compatible) = VersionReq::parse(&format!("^{version}")) else {
+                continue;
+            };
+            let newest = queued
+                .iter()
+                .map(|(_, newer, _)| newer)
+                .filter(|newer| *newer > version && compatible.matches(newer))
+                .max()
+                .map(|newest| newest.to_string());
+            if newest != *superseded_by {
+                conn.execute(
+                    "UPDATE queue SET superseded_by = $2 WHERE id = $1",
+                    &[id, &newest],
+                )?;
+            }
+        }
         Ok(())
     }
 
@@ -126,12 +269,23 @@ impl BuildQueue {
         Ok(res[0].get::<_, i64>(0) as usize)
     }
 
+    /// The queued builds in the order they are built in.
     pub(crate) fn queued_crates(&self) -> Result<Vec<QueuedCrate>> {
         let query = self.db.get()?.query(
-            "SELECT id, name, version, priority, registry
-             FROM queue
-             WHERE attempt < $1
-             ORDER BY priority ASC, attempt ASC, id ASC",
+            &format!(
+                "{}
+                 SELECT
+                    queue.id, queue.name, queue.version, queue.priority, queue.registry,
+                    queue.superseded_by, ordered_queue.owner
+                 FROM queue
+                 INNER JOIN ordered_queue ON ordered_queue.id = queue.id
+                 ORDER BY
+                    ordered_queue.priority ASC,
+                    ordered_queue.turn ASC,
+                    queue.attempt ASC,
+                    queue.id ASC",
+                self.ordered_queue()
+            ),
             &[&self.max_attempts],
         )?;
 
@@ -143,11 +297,271 @@ impl BuildQueue {
                 version: row.get("version"),
                 priority: row.get("priority"),
                 registry: row.get("registry"),
+                owner: row.get("owner"),
+                superseded_by: row.get("superseded_by"),
+                estimated_start: None,
             })
             .collect())
     }
 
-    fn has_build_queued(&self, name: &str, version: &str) -> Result<bool> {
+    /// The queued crates in build order, with the time each build is expected to start.
+    ///
+    /// The estimate replays the queue on the build servers seen in the last day: each build is
+    /// expected to take the average duration of the last [`ESTIMATE_RECENT_BUILDS`] builds of
+    /// its crate, falling back to the average of all builds in the last week. Builds already
+    /// running keep the server they run on until their expected end. Builds held back by
+    /// `build_owner_max_concurrent` aren't accounted for, so their estimate is a lower bound.
+    pub(crate) fn queued_crates_with_estimates(&self) -> Result<Vec<QueuedCrate>> {
+        self.estimate_starts(self.queued_crates()?)
+    }
+
+    /// The queued build of a release with the time it's expected to start, `None` if it isn't
+    /// queued. Only the builds up to it are replayed, see
+    /// [`BuildQueue::queued_crates_with_estimates`].
+    pub(crate) fn queued_crate_with_estimate(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<Option<QueuedCrate>> {
+        let mut queue = self.queued_crates()?;
+        let Some(position) = queue
+            .iter()
+            .position(|krate| krate.name == name && krate.version == version)
+        else {
+            return Ok(None);
+        };
+        queue.truncate(position + 1);
+
+        Ok(self.estimate_starts(queue)?.pop())
+    }
+
+    /// Sets the expected start of the builds in `queue`, which has to be the start of the queue
+    /// in build order.
+    fn estimate_starts(&self, mut queue: Vec<QueuedCrate>) -> Result<Vec<QueuedCrate>> {
+        let mut conn = self.db.get()?;
+
+        let servers: i64 = conn
+            .query_one(
+                "SELECT COUNT(DISTINCT build_server)
+                 FROM (
+                    SELECT build_server FROM builds WHERE build_time > NOW() - INTERVAL '1 day'
+                    UNION
+                    SELECT build_server FROM running_builds
+                 ) AS build_servers",
+                &[],
+            )?
+            .get(0);
+        let running: HashMap<(String, String), DateTime<Utc>> = conn
+            .query("SELECT name, version, started_at FROM running_builds", &[])?
+            .into_iter()
+            .map(|row| ((row.get("name"), row.get("version")), row.get("started_at")))
+            .collect();
+
+        let names: Vec<&str> = queue
+            .iter()
+            .map(|krate| krate.name.as_str())
+            .chain(running.keys().map(|(name, _)| name.as_str()))
+            .collect();
+        let durations = self.expected_durations(&mut conn, &names)?;
+        let expected_duration = |name: &str| durations[name];
+
+        let now = Utc::now();
+        // the time each build server is free for the next build
+        let mut free_at: Vec<DateTime<Utc>> = running
+            .iter()
+            .map(|((name, _), started_at)| (*started_at + expected_duration(name)).max(now))
+            .collect();
+        free_at.resize(free_at.len().max(servers.max(1) as usize), now);
+
+        for krate in queue.iter_mut() {
+            if let Some(started_at) = running.get(&(krate.name.clone(), krate.version.clone())) {
+                krate.estimated_start = Some(*started_at);
+                continue;
+            }
+            let server = free_at
+                .iter_mut()
+                .min()
+                .expect("there is always at least one build server");
+            krate.estimated_start = Some(*server);
+            *server += expected_duration(&krate.name);
+        }
+
+        Ok(queue)
+    }
+
+    /// The expected build duration of each crate in `names`.
+    ///
+    /// Durations are cached for [`ESTIMATE_CACHE_TTL`], only crates not looked up since are
+    /// loaded from the database.
+    fn expected_durations(
+        &self,
+        conn: &mut postgres::Client,
+        names: &[&str],
+    ) -> Result<HashMap<String, Duration>> {
+        let mut cache = self
+            .expected_durations
+            .lock()
+            .expect("could not lock expected build durations");
+
+        let cache = match &mut *cache {
+            Some(cache) if cache.loaded_at.elapsed() < ESTIMATE_CACHE_TTL => cache,
+            cache => {
+                let default = conn
+                    .query_one(
+                        "SELECT AVG(duration)::BIGINT
+                         FROM (
+                            SELECT SUM(build_resource_usage.wall_time_ms) AS duration
+                            FROM builds
+                            INNER JOIN build_resource_usage
+                                ON build_resource_usage.build_id = builds.id
+                            WHERE builds.build_time > NOW() - INTERVAL '7 days'
+                            GROUP BY builds.id
+                         ) AS recent_builds",
+                        &[],
+                    )?
+                    .get::<_, Option<i64>>(0)
+                    .map_or(DEFAULT_BUILD_DURATION, |ms| {
+                        Duration::from_millis(ms.max(0) as u64)
+                    });
+                cache.insert(ExpectedDurations {
+                    loaded_at: Instant::now(),
+                    default,
+                    crates: HashMap::new(),
+                })
+            }
+        };
+
+        let mut missing: Vec<&str> = names
+            .iter()
+            .copied()
+            .filter(|name| !cache.crates.contains_key(*name))
+            .collect();
+        missing.sort_unstable();
+        missing.dedup();
+        if !missing.is_empty() {
+            let loaded: HashMap<String, i64> = conn
+                .query(
+                    "SELECT name, AVG(duration)::BIGINT AS duration
+                     FROM (
+                        SELECT
+                            crates.name,
+                            SUM(build_resource_usage.wall_time_ms) AS duration,
+                            ROW_NUMBER() OVER (
+                                PARTITION BY crates.name
+                                ORDER BY builds.build_time DESC
+                            ) AS n
+                        FROM builds
+                        INNER JOIN build_resource_usage ON build_resource_usage.build_id = builds.id
+                        INNER JOIN releases ON releases.id = builds.rid
+                        INNER JOIN crates ON crates.id = releases.crate_id
+                        WHERE crates.name = ANY($1)
+                        GROUP BY builds.id, builds.build_time, crates.name
+                     ) AS recent_builds
+                     WHERE n <= $2
+                     GROUP BY name",
+                    &[&missing, &ESTIMATE_RECENT_BUILDS],
+                )?
+                .into_iter()
+                .map(|row| (row.get("name"), row.get("duration")))
+                .collect();
+            for name in missing {
+                let duration = loaded
+                    .get(name)
+                    .map(|&ms| Duration::from_millis(ms.max(0) as u64));
+                cache.crates.insert(name.to_owned(), duration);
+            }
+        }
+
+        Ok(names
+            .iter()
+            .map(|&name| (name.to_owned(), cache.crates[name].unwrap_or(cache.default)))
+            .collect())
+    }
+
+    /// The queue in build order, as the `ordered_queue` common table expression to join the
+    /// `queue` table with. The maximum number of attempts has to be the first parameter.
+    ///
+    /// Superseded builds have their priority lowered by `superseded_build_priority_penalty`.
+    /// Builds with the same priority are built in turns across the owners of the crates, so one
+    /// owner publishing many crates at once doesn't block everyone else. Each owner gets
+    /// `build_owner_max_consecutive` builds per turn, the builds started for them in the last
+    /// [`OWNER_BUILDS_WINDOW`] count as their earlier turns. Crates without known owners are
+    /// treated as having an owner of their own. Builds of owners with
+    /// `build_owner_max_concurrent` running builds are `blocked`, where only builds with a live
+    /// lease or updated in the last [`RUNNING_BUILD_TIMEOUT`] count, so the builds of a builder
+    /// that died don't block the owner.
+    fn ordered_queue(&self) -> String {
+        // WARNING: these are numbers, NEVER user input.
+        let window = OWNER_BUILDS_WINDOW.as_secs();
+        let timeout = RUNNING_BUILD_TIMEOUT.as_secs();
+        let priority = format!(
+            "queue.priority + CASE WHEN queue.superseded_by IS NULL THEN 0 ELSE {} END",
+            self.config.superseded_build_priority_penalty
+        );
+        let consecutive = self.config.build_owner_max_consecutive.max(1);
+        let concurrent = self
+            .config
+            .build_owner_max_concurrent
+            .map_or_else(|| "NULL".to_string(), |concurrent| concurrent.to_string());
+        format!(
+            "WITH
+                crate_owners AS (
+                    SELECT DISTINCT ON (crates.name) crates.name, owners.login AS owner
+                    FROM crates
+                    INNER JOIN owner_rels ON owner_rels.cid = crates.id
+                    INNER JOIN owners ON owners.id = owner_rels.oid
+                    WHERE crates.name IN (
+                        SELECT name FROM queue UNION SELECT name FROM running_builds
+                    )
+                    ORDER BY crates.name, owners.id
+                ),
+                recent_by_owner AS (
+                    SELECT owner, COUNT(*) AS recent
+                    FROM queue_owner_builds
+                    WHERE started_at > NOW() - make_interval(secs => {window})
+                    GROUP BY owner
+                ),
+                running_by_owner AS (
+                    SELECT crate_owners.owner, COUNT(*) AS running
+                    FROM running_builds
+                    INNER JOIN crate_owners ON crate_owners.name = running_builds.name
+                    WHERE
+                        running_builds.updated_at > NOW() - make_interval(secs => {timeout}) OR
+                        EXISTS (
+                            SELECT 1 FROM queue AS leased
+                            WHERE
+                                leased.name = running_builds.name AND
+                                leased.version = running_builds.version AND
+                                leased.lease_expires_at >= NOW()
+                        )
+                    GROUP BY crate_owners.owner
+                ),
+                ordered_queue AS (
+                    SELECT
+                        queue.id,
+                        {priority} AS priority,
+                        crate_owners.owner,
+                        (
+                            COALESCE(recent_by_owner.recent, 0) +
+                            ROW_NUMBER() OVER (
+                                PARTITION BY
+                                    {priority},
+                                    crate_owners.owner,
+                                    CASE WHEN crate_owners.owner IS NULL THEN queue.name END
+                                ORDER BY queue.attempt, queue.id
+                            ) - 1
+                        ) / {consecutive} AS turn,
+                        COALESCE(running_by_owner.running >= {concurrent}, FALSE) AS blocked
+                    FROM queue
+                    LEFT JOIN crate_owners ON crate_owners.name = queue.name
+                    LEFT JOIN recent_by_owner ON recent_by_owner.owner = crate_owners.owner
+                    LEFT JOIN running_by_owner ON running_by_owner.owner = crate_owners.owner
+                    WHERE queue.attempt < $1
+                )"
+        )
+    }
+
+    pub(crate) fn has_build_queued(&self, name: &str, version: &str) -> Result<bool> {
         Ok(self
             .db
             .get()?
@@ -176,12 +590,25 @@ impl BuildQueue {
         // available one.
         let to_process = match transaction
             .query_opt(
-                "SELECT id, name, version, priority, registry
-                 FROM queue
-                 WHERE attempt < $1
-                 ORDER BY priority ASC, attempt ASC, id ASC
-                 LIMIT 1
-                 FOR UPDATE SKIP LOCKED",
+                &format!(
+                    "{}
+                     SELECT
+                        queue.id, queue.name, queue.version, queue.priority, queue.registry,
+                        queue.superseded_by, ordered_queue.owner
+                     FROM queue
+                     INNER JOIN ordered_queue ON ordered_queue.id = queue.id
+                     WHERE
+                        (queue.lease_expires_at IS NULL OR queue.lease_expires_at < NOW()) AND
+                        NOT ordered_queue.blocked
+                     ORDER BY
+                        ordered_queue.priority ASC,
+                        ordered_queue.turn ASC,
+                        queue.attempt ASC,
+                        queue.id ASC
+                     LIMIT 1
+                     FOR UPDATE OF queue SKIP LOCKED",
+                    self.ordered_queue()
+                ),
                 &[&self.max_attempts],
             )?
             .map(|row| QueuedCrate {
@@ -190,10 +617,15 @@ impl BuildQueue {
                 version: row.get("version"),
                 priority: row.get("priority"),
                 registry: row.get("registry"),
+                owner: row.get("owner"),
+                superseded_by: row.get("superseded_by"),
+                estimated_start: None,
             }) {
             Some(krate) => krate,
             None => return Ok(()),
         };
+        let started: DateTime<Utc> = transaction.query_one("SELECT NOW()", &[])?.get(0);
+        Self::record_owner_build(&mut transaction, to_process.owner.as_deref())?;
 
         let res = self.metrics.build_time.observe_closure_duration(|| {
             f(&to_process).with_context(|| {
@@ -212,7 +644,11 @@ impl BuildQueue {
 
         match res {
             Ok(()) => {
-                transaction.execute("DELETE FROM queue WHERE id = $1;", &[&to_process.id])?;
+                if self.raise_limits_for_retry(&to_process.name, &to_process.version, started)? {
+                    // the queue entry stays and is picked up again with the raised limits
+                } else {
+                    transaction.execute("DELETE FROM queue WHERE id = $1;", &[&to_process.id])?;
+                }
             }
             Err(e) => {
                 // Increase attempt count
@@ -232,9 +668,87 @@ impl BuildQueue {
         }
 
         transaction.commit()?;
+        self.update_superseded_builds(&mut *conn, &to_process.name)?;
 
         Ok(())
     }
+
+    /// Records that a build for `owner` was started, so their next builds wait for their turn.
+    fn record_owner_build(
+        transaction: &mut postgres::Transaction,
+        owner: Option<&str>,
+    ) -> Result<()> {
+        let Some(owner) = owner else {
+            return Ok(());
+        };
+        transaction.execute(
+            "DELETE FROM queue_owner_builds
+             WHERE started_at < NOW() - make_interval(secs => $1)",
+            &[&(OWNER_BUILDS_WINDOW.as_secs() as f64)],
+        )?;
+        transaction.execute(
+            "INSERT INTO queue_owner_builds (owner) VALUES ($1)",
+            &[&owner],
+        )?;
+        Ok(())
+    }
+
+    /// Raises the limits of a crate whose build of `version`, recorded after `since`, ran out of
+    /// memory or time, so the build can be retried.
+    ///
+    /// Every release is only retried once: builds of a release that hit its limits before aren't
+    /// retried again, neither are builds whose limits are at the configured ceiling already.
+    fn raise_limits_for_retry(
+        &self,
+        name: &str,
+        version: &str,
+        since: DateTime<Utc>,
+    ) -> Result<bool> {
+        let mut conn = self.db.get()?;
+        let builds: Vec<(Option<FailureCategory>, DateTime<Utc>)> = conn
+            .query(
+                "SELECT builds.failure_category, builds.build_time
+                 FROM builds
+                 INNER JOIN releases ON releases.id = builds.rid
+                 INNER JOIN crates ON crates.id = releases.crate_id
+                 WHERE crates.name = $1 AND releases.version = $2
+                 ORDER BY builds.id DESC",
+                &[&name, &version],
+            )?
+            .into_iter()
+            .map(|row| {
+                let category = row
+                    .get::<_, Option<&str>>("failure_category")
+                    .and_then(|category| category.parse().ok());
+                (category, row.get("build_time"))
+            })
+            .collect();
+
+        let hit_limits = |category: &Option<FailureCategory>| {
+            matches!(
+                category,
+                Some(FailureCategory::Oom | FailureCategory::Timeout)
+            )
+        };
+        let Some(((latest, build_time), previous)) = builds.split_first() else {
+            return Ok(false);
+        };
+        let Some(category) = latest.filter(|_| hit_limits(latest)) else {
+            return Ok(false);
+        };
+        if *build_time < since || previous.iter().any(|(category, _)| hit_limits(category)) {
+            return Ok(false);
+        }
+
+        if !raise_limits(&self.config, &mut conn, name, category)? {
+            return Ok(false);
+        }
+        info!(
+            "raised the limits of {} after the build of {} hit them ({}), retrying",
+            name, version, category
+        );
+        Ok(true)
+    }
 }
 
 /// Locking functions.
@@ -259,17 +773,267 @@ impl BuildQueue {
     }
 }
 
+/// A queued build leased by a build worker.
+///
+/// The worker has to renew the lease before it expires, otherwise the build is handed out again.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BuildLease {
+    pub id: i32,
+    pub name: String,
+    pub version: String,
+    pub registry: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Build worker leases.
+impl BuildQueue {
+    /// Releases the expired leases of workers that stopped sending heartbeats, so their builds
+    /// are picked up again. The lost lease counts as a failed attempt.
+    ///
+    /// Returns the number of expired leases.
+    pub(crate) fn expire_leases(&self) -> Result<usize> {
+        let mut conn = self.db.get()?;
+        let mut transaction = conn.transaction()?;
+        let expired = transaction.query(
+            "UPDATE queue
+             SET leased_by = NULL, lease_expires_at = NULL, attempt = attempt + 1
+             FROM (
+                SELECT id, leased_by FROM queue WHERE lease_expires_at < NOW() FOR UPDATE
+             ) AS expired
+             WHERE queue.id = expired.id
+             RETURNING queue.name, queue.version, expired.leased_by, queue.attempt",
+            &[],
+        )?;
+        for row in &expired {
+            let (name, version): (String, String) = (row.get("name"), row.get("version"));
+            warn!(
+                "lease of {}-{} expired, the worker {} stopped sending heartbeats",
+                name,
+                version,
+                row.get::<_, String>("leased_by")
+            );
+            if row.get::<_, i32>("attempt") >= self.max_attempts {
+                self.metrics.failed_builds.inc();
+            }
+            finish_running_build(&mut transaction, &name, &version)?;
+        }
+        transaction.commit()?;
+        for row in &expired {
+            self.update_superseded_builds(&mut *conn, row.get("name"))?;
+        }
+        Ok(expired.len())
+    }
+
+    /// Leases the next queued build to `worker` until `duration` has passed.
+    ///
+    /// Returns `None` when the queue is locked or there's nothing to build.
+    pub(crate) fn lease_next_crate(
+        &self,
+        worker: &str,
+        duration: Duration,
+    ) -> Result<Option<BuildLease>> {
+        if self.is_locked()? {
+            return Ok(None);
+        }
+        self.expire_leases()?;
+
+        let mut conn = self.db.get()?;
+        loop {
+            let mut transaction = conn.transaction()?;
+            let Some(row) = transaction.query_opt(
+                &format!(
+                    "{}
+                     SELECT
+                        queue.id, queue.name, queue.version, queue.registry,
+                        ordered_queue.owner,
+                        EXISTS (
+                            SELECT 1 FROM blacklisted_crates WHERE crate_name = queue.name
+                        ) AS blacklisted
+                     FROM queue
+                     INNER JOIN ordered_queue ON ordered_queue.id = queue.id
+                     WHERE queue.lease_expires_at IS NULL AND NOT ordered_queue.blocked
+                     ORDER BY
+                        ordered_queue.priority ASC,
+                        ordered_queue.turn ASC,
+                        queue.attempt ASC,
+                        queue.id ASC
+                     LIMIT 1
+                     FOR UPDATE OF queue SKIP LOCKED",
+                    self.ordered_queue()
+                ),
+                &[&self.max_attempts],
+            )?
+            else {
+                return Ok(None);
+            };
+            let (id, name, version): (i32, String, String) =
+                (row.get("id"), row.get("name"), row.get("version"));
+
+            if row.get("blacklisted") {
+                info!("skipping build of {}, crate has been blacklisted", name);
+                transaction.execute("DELETE FROM queue WHERE id = $1", &[&id])?;
+                transaction.commit()?;
+                continue;
+            }
+
+            let expires_at: DateTime<Utc> = transaction
+                .query_one(
+                    "UPDATE queue
+                     SET leased_by = $2, lease_expires_at = NOW() + make_interval(secs => $3)
+                     WHERE id = $1
+                     RETURNING lease_expires_at",
+                    &[&id, &worker, &duration.as_secs_f64()],
+                )?
+                .get(0);
+            start_running_build(&mut transaction, &name, &version, worker)?;
+            Self::record_owner_build(&mut transaction, row.get("owner"))?;
+            transaction.commit()?;
+
+            debug!("{}-{} leased to worker {}", name, version, worker);
+            return Ok(Some(BuildLease {
+                id,
+                name,
+                version,
+                registry: row.get("registry"),
+                expires_at,
+            }));
+        }
+    }
+
+    /// Loads the lease `id` if it's held by `worker` and didn't expire yet.
+    pub(crate) fn active_lease(&self, id: i32, worker: &str) -> Result<Option<BuildLease>> {
+        Ok(self
+            .db
+            .get()?
+            .query_opt(
+                "SELECT id, name, version, registry, lease_expires_at
+                 FROM queue
+                 WHERE id = $1 AND leased_by = $2 AND lease_expires_at >= NOW()",
+                &[&id, &worker],
+            )?
+            .map(|row| BuildLease {
+                id: row.get("id"),
+                name: row.get("name"),
+                version: row.get("version"),
+                registry: row.get("registry"),
+                expires_at: row.get("lease_expires_at"),
+            }))
+    }
+
+    /// Extends the lease `id` of `worker` until `duration` from now has passed.
+    ///
+    /// Returns the new expiry, or `None` when the worker doesn't hold the lease anymore.
+    pub(crate) fn renew_lease(
+        &self,
+        id: i32,
+        worker: &str,
+        duration: Duration,
+    ) -> Result<Option<DateTime<Utc>>> {
+        Ok(self
+            .db
+            .get()?
+            .query_opt(
+                "UPDATE queue
+                 SET lease_expires_at = NOW() + make_interval(secs => $3)
+                 WHERE id = $1 AND leased_by = $2 AND lease_expires_at >= NOW()
+                 RETURNING lease_expires_at",
+                &[&id, &worker, &duration.as_secs_f64()],
+            )?
+            .map(|row| row.get(0)))
+    }
+
+    /// Ends the lease `id` of `worker` after the build finished, with `Err` when it failed.
+    ///
+    /// Successful builds are removed from the queue, failed ones are attempted again like the
+    /// builds of the local builder.
+    ///
+    /// Returns `false` when the worker doesn't hold the lease anymore.
+    pub(crate) fn finish_lease(
+        &self,
+        lease: &BuildLease,
+        worker: &str,
+        result: std::result::Result<(), String>,
+    ) -> Result<bool> {
+        let mut conn = self.db.get()?;
+        let mut transaction = conn.transaction()?;
+
+        let attempt: Option<i32> = transaction
+            .query_opt(
+                "SELECT attempt FROM queue
+                 WHERE id = $1 AND leased_by = $2 AND lease_expires_at >= NOW()
+                 FOR UPDATE",
+                &[&lease.id, &worker],
+            )?
+            .map(|row| row.get(0));
+        if attempt.is_none() {
+            return Ok(false);
+        }
+
+        self.metrics.total_builds.inc();
+        if let Err(err) = cdn::queue_crate_invalidation(&mut transaction, &self.config, &lease.name)
+        {
+            report_error(&err);
+        }
+
+        match result {
+            Ok(()) => {
+                // the build was recorded after the last heartbeat of the lease
+                let since = lease.expires_at
+                    - chrono::Duration::seconds(self.config.build_lease_duration as i64);
+                if self.raise_limits_for_retry(&lease.name, &lease.version, since)? {
+                    transaction.execute(
+                        "UPDATE queue SET leased_by = NULL, lease_expires_at = NULL WHERE id = $1",
+                        &[&lease.id],
+                    )?;
+                } else {
+                    transaction.execute("DELETE FROM queue WHERE id = $1", &[&lease.id])?;
+                }
+            }
+            Err(message) => {
+                let attempt: i32 = transaction
+                    .query_one(
+                        "UPDATE queue
+                         SET attempt = attempt + 1, leased_by = NULL, lease_expires_at = NULL
+                         WHERE id = $1
+                         RETURNING attempt",
+                        &[&lease.id],
+                    )?
+                    .get(0);
+                if attempt >= self.max_attempts {
+                    self.metrics.failed_builds.inc();
+                }
+                error!(
+                    "worker {} failed to build {}-{}: {}",
+                    worker, lease.name, lease.version, message
+                );
+            }
+        }
+        finish_running_build(&mut transaction, &lease.name, &lease.version)?;
+
+        transaction.commit()?;
+        self.update_superseded_builds(&mut *conn, &lease.name)?;
+        Ok(true)
+    }
+}
+
 /// Index methods.
 impl BuildQueue {
     /// Updates registry index repository and adds new crates into build queue.
     ///
+    /// Crates of alternative registries are queued with their registry-qualified name.
+    ///
     /// Returns the number of crates added
     pub fn get_new_crates(&self, index: &Index) -> Result<usize> {
+        if let Some(sparse) = index.sparse() {
+            return self.get_new_crates_from_sparse_index(index, sparse);
+        }
+
         let mut conn = self.db.get()?;
         let diff = index.diff()?;
+        let registry = index.registry();
 
         let last_seen_reference = self
-            .last_seen_reference()?
+            .last_seen_reference(registry)?
             .context("no last_seen_reference set in database")?;
         diff.set_last_seen_reference(last_seen_reference)?;
 
@@ -282,8 +1046,9 @@ impl BuildQueue {
         changes.reverse();
 
         for change in &changes {
-            if let Some((ref krate, ..)) = change.crate_deleted() {
-                match delete_crate(&mut conn, &self.storage, &self.config, krate)
+            if let Some((krate, ..)) = change.crate_deleted() {
+                let krate = qualified_crate_name(registry, krate);
+                match delete_crate(&mut *conn, &self.storage, &self.config, &krate)
                     .with_context(|| format!("failed to delete crate {krate}"))
                 {
                     Ok(_) => info!(
@@ -292,61 +1057,49 @@ impl BuildQueue {
                     ),
                     Err(err) => report_error(&err),
                 }
-                if let Err(err) = cdn::queue_crate_invalidation(&mut *conn, &self.config, krate) {
+                if let Err(err) = cdn::queue_crate_invalidation(&mut *conn, &self.config, &krate) {
                     report_error(&err);
                 }
                 continue;
             }
 
             if let Some(release) = change.version_deleted() {
+                let name = qualified_crate_name(registry, &release.name);
                 match delete_version(
-                    &mut conn,
+                    &mut *conn,
                     &self.storage,
                     &self.config,
-                    &release.name,
+                    &name,
                     &release.version,
                 )
-                .with_context(|| {
-                    format!(
-                        "failed to delete version {}-{}",
-                        release.name, release.version
-                    )
-                }) {
+                .with_context(|| format!("failed to delete version {}-{}", name, release.version))
+                {
                     Ok(_) => info!(
                         "release {}-{} was deleted from the index and the database",
-                        release.name, release.version
+                        name, release.version
                     ),
                     Err(err) => report_error(&err),
                 }
-                if let Err(err) =
-                    cdn::queue_crate_invalidation(&mut *conn, &self.config, &release.name)
-                {
+                if let Err(err) = cdn::queue_crate_invalidation(&mut *conn, &self.config, &name) {
                     report_error(&err);
                 }
                 continue;
             }
 
             if let Some(release) = change.added() {
-                let priority = get_crate_priority(&mut conn, &release.name)?;
+                let name = qualified_crate_name(registry, &release.name);
+                let priority = get_crate_priority(&mut conn, &name)?;
 
                 match self
-                    .add_crate(
-                        &release.name,
-                        &release.version,
-                        priority,
-                        index.repository_url(),
-                    )
+                    .add_crate(&name, &release.version, priority, index.repository_url())
                     .with_context(|| {
                         format!(
                             "failed adding {}-{} into build queue",
-                            release.name, release.version
+                            name, release.version
                         )
                     }) {
                     Ok(()) => {
-                        debug!(
-                            "{}-{} added into build queue",
-                            release.name, release.version
-                        );
+                        debug!("{}-{} added into build queue", name, release.version);
                         self.metrics.queued_builds.inc();
                         crates_added += 1;
                     }
@@ -357,20 +1110,16 @@ impl BuildQueue {
             let yanked = change.yanked();
             let unyanked = change.unyanked();
             if let Some(release) = yanked.or(unyanked) {
+                let name = qualified_crate_name(registry, &release.name);
                 // FIXME: delay yanks of crates that have not yet finished building
                 // https://github.com/rust-lang/docs.rs/issues/1934
-                if let Err(err) = self.set_yanked(
-                    &mut conn,
-                    release.name.as_str(),
-                    release.version.as_str(),
-                    yanked.is_some(),
-                ) {
+                if let Err(err) =
+                    self.set_yanked(&mut conn, &name, release.version.as_str(), yanked.is_some())
+                {
                     report_error(&err);
                 }
 
-                if let Err(err) =
-                    cdn::queue_crate_invalidation(&mut *conn, &self.config, &release.name)
-                {
+                if let Err(err) = cdn::queue_crate_invalidation(&mut *conn, &self.config, &name) {
                     report_error(&err);
                 }
             }
@@ -379,7 +1128,166 @@ impl BuildQueue {
         // set the reference in the database
         // so this survives recreating the registry watcher
         // server.
-        self.set_last_seen_reference(new_reference)?;
+        self.set_last_seen_reference(registry, new_reference)?;
+
+        Ok(crates_added)
+    }
+
+    /// Like [`get_new_crates`](Self::get_new_crates), for indexes served over the sparse HTTP
+    /// protocol, which don't have a history of changes.
+    ///
+    /// The crates updated since the last seen update are taken from the registry's API. Their
+    /// versions in the index are compared to the database: unknown versions are queued, yank
+    /// states are updated and releases which are gone from the index are deleted, once the API
+    /// confirms they're gone.
+    ///
+    /// The last seen update is kept [`SPARSE_INDEX_CACHE_TTL`] behind, so crates whose index file
+    /// could still be outdated are checked again next time.
+    fn get_new_crates_from_sparse_index(
+        &self,
+        index: &Index,
+        sparse: &SparseIndex,
+    ) -> Result<usize> {
+        let registry = index.registry();
+        let last_seen_update = self
+            .last_seen_index_update(registry)?
+            .context("no last seen index update set in database")?;
+
+        let updates = index.api().get_crates_updated_since(last_seen_update)?;
+        let cache_horizon = Utc::now()
+            - chrono::Duration::from_std(SPARSE_INDEX_CACHE_TTL)
+                .expect("the cache TTL fits into a chrono duration");
+        let seen_update =
+            |updated_at: DateTime<Utc>| updated_at.min(cache_horizon).max(last_seen_update);
+        debug!(
+            "queueing changes of {} crates updated since {last_seen_update}",
+            updates.len()
+        );
+
+        let mut conn = self.db.get()?;
+        let mut crates_added = 0;
+        for (krate, updated_at) in updates {
+            let versions = sparse.crate_versions(&krate)?;
+            let name = qualified_crate_name(registry, &krate);
+
+            let releases: HashMap<String, bool> = conn
+                .query(
+                    "SELECT releases.version, COALESCE(releases.yanked, FALSE) AS yanked
+                     FROM releases
+                     INNER JOIN crates ON crates.id = releases.crate_id
+                     WHERE crates.name = $1",
+                    &[&name],
+                )?
+                .into_iter()
+                .map(|row| (row.get("version"), row.get("yanked")))
+                .collect();
+
+            let Some(versions) = versions else {
+                if releases.is_empty() {
+                    // nothing to delete
+                } else if index.api().get_crate_versions(&krate)?.is_some() {
+                    warn!(
+                        "crate {} is missing from the index but not from the API, keeping it",
+                        name
+                    );
+                } else {
+                    match delete_crate(&mut *conn, &self.storage, &self.config, &name)
+                        .with_context(|| format!("failed to delete crate {name}"))
+                    {
+                        Ok(_) => {
+                            info!("crate {} was deleted from the index and the database", name)
+                        }
+                        Err(err) => report_error(&err),
+                    }
+                    if let Err(err) = cdn::queue_crate_invalidation(&mut *conn, &self.config, &name)
+                    {
+                        report_error(&err);
+                    }
+                }
+                self.set_last_seen_index_update(registry, seen_update(updated_at))?;
+                continue;
+            };
+
+            let queued: Vec<String> = conn
+                .query("SELECT version FROM queue WHERE name = $1", &[&name])?
+                .into_iter()
+                .map(|row| row.get(0))
+                .collect();
+
+            for release in &versions {
+                match releases.get(&release.version) {
+                    Some(&yanked) if yanked != release.yanked => {
+                        if let Err(err) =
+                            self.set_yanked(&mut conn, &name, &release.version, release.yanked)
+                        {
+                            report_error(&err);
+                        }
+                        if let Err(err) =
+                            cdn::queue_crate_invalidation(&mut *conn, &self.config, &name)
+                        {
+                            report_error(&err);
+                        }
+                    }
+                    Some(_) => {}
+                    None if queued.contains(&release.version) => {}
+                    None => {
+                        let priority = get_crate_priority(&mut conn, &name)?;
+                        // alternative registries can't be sparse, these are crates.io crates
+                        // which the builder fetches without a registry url
+                        match self
+                            .add_crate(&name, &release.version, priority, None)
+                            .with_context(|| {
+                                format!(
+                                    "failed adding {}-{} into build queue",
+                                    name, release.version
+                                )
+                            }) {
+                            Ok(()) => {
+                                debug!("{}-{} added into build queue", name, release.version);
+                                self.metrics.queued_builds.inc();
+                                crates_added += 1;
+                            }
+                            Err(err) => report_error(&err),
+                        }
+                    }
+                }
+            }
+
+            let missing: Vec<&String> = releases
+                .keys()
+                .filter(|version| !versions.iter().any(|release| &release.version == *version))
+                .collect();
+            let api_versions = if missing.is_empty() {
+                Vec::new()
+            } else {
+                index.api().get_crate_versions(&krate)?.unwrap_or_default()
+            };
+            for version in missing {
+                if api_versions.contains(version) {
+                    warn!(
+                        "release {}-{} is missing from the index but not from the API, keeping it",
+                        name, version
+                    );
+                    continue;
+                }
+                match delete_version(&mut *conn, &self.storage, &self.config, &name, version)
+                    .with_context(|| format!("failed to delete version {name}-{version}"))
+                {
+                    Ok(_) => info!(
+                        "release {}-{} was deleted from the index and the database",
+                        name, version
+                    ),
+                    Err(err) => report_error(&err),
+                }
+                if let Err(err) = cdn::queue_crate_invalidation(&mut *conn, &self.config, &name) {
+                    report_error(&err);
+                }
+            }
+
+            // store the progress after each crate, so a failure doesn't re-check
+            // crates that were already handled.
+            self.set_last_seen_index_update(registry, seen_update(updated_at))?;
+        }
 
         Ok(crates_added)
     }
@@ -484,6 +1392,10 @@ impl BuildQueue {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::Overrides;
+    use crate::docbuilder::{Limits, ResourceUsage};
+    use crate::index::api::CrateOwner;
+    use crate::test::FakeBuild;
 
     #[test]
     fn test_add_duplicate_doesnt_fail_last_priority_wins() {
@@ -784,6 +1696,521 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_lease_and_finish_builds() {
+        crate::test::wrapper(|env| {
+            let queue = env.build_queue();
+            let duration = Duration::from_secs(60);
+            queue.add_crate("foo", "1.0.0", 0, None)?;
+            queue.add_crate("bar", "1.0.0", 1, None)?;
+
+            let foo = queue.lease_next_crate("worker-1", duration)?.unwrap();
+            assert_eq!((foo.name.as_str(), foo.version.as_str()), ("foo", "1.0.0"));
+            let build_server: String = env
+                .db()
+                .conn()
+                .query_one(
+                    "SELECT build_server FROM running_builds WHERE name = 'foo'",
+                    &[],
+                )?
+                .get(0);
+            assert_eq!(build_server, "worker-1");
+
+            // leased builds are neither leased again nor built locally
+            let bar = queue.lease_next_crate("worker-2", duration)?.unwrap();
+            assert_eq!(bar.name, "bar");
+            assert!(queue.lease_next_crate("worker-2", duration)?.is_none());
+            queue.process_next_crate(|_| panic!("no crate should be built locally"))?;
+
+            // only the holder of a lease can use it
+            assert!(queue.active_lease(foo.id, "worker-2")?.is_none());
+            assert!(queue.renew_lease(foo.id, "worker-2", duration)?.is_none());
+            assert_eq!(queue.active_lease(foo.id, "worker-1")?, Some(foo.clone()));
+            assert!(queue.renew_lease(foo.id, "worker-1", duration)?.is_some());
+
+            assert!(queue.finish_lease(&foo, "worker-1", Ok(()))?);
+            assert!(!queue.finish_lease(&foo, "worker-1", Ok(()))?);
+            assert!(queue.finish_lease(&bar, "worker-2", Err("oh no".into()))?);
+
+            // the failed build is attempted again
+            let queued = queue.queued_crates()?;
+            assert_eq!(queued.len(), 1);
+            assert_eq!(queued[0].name, "bar");
+            let attempt: i32 = env
+                .db()
+                .conn()
+                .query_one("SELECT attempt FROM queue WHERE name = 'bar'", &[])?
+                .get(0);
+            assert_eq!(attempt, 1);
+            assert_eq!(
+                queue.lease_next_crate("worker-1", duration)?.unwrap().name,
+                "bar"
+            );
+
+            let running_builds: i64 = env
+                .db()
+                .conn()
+                .query_one(
+                    "SELECT COUNT(*) FROM running_builds WHERE name = 'foo'",
+                    &[],
+                )?
+                .get(0);
+            assert_eq!(running_builds, 0);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_estimated_build_starts() {
+        crate::test::wrapper(|env| {
+            for (name, secs) in [("slow", 600), ("fast", 60)] {
+                env.fake_release()
+                    .name(name)
+                    .version("1.0.0")
+                    .builds(vec![FakeBuild::default().resource_usage(ResourceUsage {
+                        target: "x86_64-unknown-linux-gnu".into(),
+                        wall_time: Duration::from_secs(secs),
+                        peak_memory: None,
+                        output_size: 0,
+                    })])
+                    .create()?;
+            }
+
+            let queue = env.build_queue();
+            queue.add_crate("slow", "2.0.0", 0, None)?;
+            queue.add_crate("fast", "2.0.0", 0, None)?;
+            queue.add_crate("new", "1.0.0", 0, None)?;
+            queue.add_crate("fast", "3.0.0", 0, None)?;
+
+            let starts = |queue: &BuildQueue| -> Result<Vec<_>> {
+                Ok(queue
+                    .queued_crates_with_estimates()?
+                    .into_iter()
+                    .map(|krate| (krate.name, krate.estimated_start.unwrap()))
+                    .collect())
+            };
+
+            // a single build server builds one crate after the other, crates without builds
+            // take the average build time
+            let queued = starts(&queue)?;
+            let first = queued[0].1;
+            let offsets: Vec<_> = queued
+                .into_iter()
+                .map(|(name, start)| (name, (start - first).num_seconds()))
+                .collect();
+            assert_eq!(
+                offsets,
+                vec![
+                    ("slow".into(), 0),
+                    ("fast".into(), 600),
+                    ("new".into(), 660),
+                    ("fast".into(), 990),
+                ]
+            );
+
+            // a running build keeps its build server busy, the other one takes the rest
+            start_running_build(&mut *env.db().conn(), "slow", "2.0.0", "other-server")?;
+            let started_at: DateTime<Utc> = env
+                .db()
+                .conn()
+                .query_one(
+                    "SELECT started_at FROM running_builds WHERE name = 'slow'",
+                    &[],
+                )?
+                .get(0);
+            let queued = starts(&queue)?;
+            assert_eq!(queued[0], ("slow".into(), started_at));
+            let first = queued[1].1;
+            let offsets: Vec<_> = queued[1..]
+                .iter()
+                .map(|(name, start)| (name.as_str(), (*start - first).num_seconds()))
+                .collect();
+            assert_eq!(offsets, vec![("fast", 0), ("new", 60), ("fast", 390)]);
+
+            // a single queued crate is estimated the same way
+            let krate = queue.queued_crate_with_estimate("slow", "2.0.0")?.unwrap();
+            assert_eq!(krate.estimated_start, Some(started_at));
+            let krate = queue.queued_crate_with_estimate("fast", "3.0.0")?.unwrap();
+            let offset = (krate.estimated_start.unwrap() - first).num_seconds();
+            assert!((389..=391).contains(&offset), "{offset}");
+            assert!(queue.queued_crate_with_estimate("fast", "1.0.0")?.is_none());
+
+            // the expected durations are cached for a while
+            env.fake_release()
+                .name("new")
+                .version("0.1.0")
+                .builds(vec![FakeBuild::default().resource_usage(ResourceUsage {
+                    target: "x86_64-unknown-linux-gnu".into(),
+                    wall_time: Duration::from_secs(6000),
+                    peak_memory: None,
+                    output_size: 0,
+                })])
+                .create()?;
+            let krate = queue.queued_crate_with_estimate("fast", "3.0.0")?.unwrap();
+            let offset = (krate.estimated_start.unwrap() - first).num_seconds();
+            assert!((389..=391).contains(&offset), "{offset}");
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_superseded_builds_are_deprioritized() {
+        crate::test::wrapper(|env| {
+            let queue = env.build_queue();
+            queue.add_crate("foo", "1.0.0", 0, None)?;
+            queue.add_crate("foo", "1.0.1", 0, None)?;
+            queue.add_crate("bar", "1.0.0", 5, None)?;
+            queue.add_crate("foo", "2.0.0", 0, None)?;
+            queue.add_crate("foo", "1.0.2", 0, None)?;
+            queue.add_crate("baz", "0.1.0", 0, None)?;
+            queue.add_crate("baz", "0.2.0", 0, None)?;
+
+            let queued: Vec<_> = queue
+                .queued_crates()?
+                .into_iter()
+                .map(|krate| (krate.name, krate.version, krate.superseded_by))
+                .collect();
+            let krate = |name: &str, version: &str, superseded_by: Option<&str>| {
+                (
+                    name.to_string(),
+                    version.to_string(),
+                    superseded_by.map(String::from),
+                )
+            };
+            assert_eq!(
+                queued,
+                vec![
+                    krate("foo", "2.0.0", None),
+                    krate("baz", "0.1.0", None),
+                    krate("foo", "1.0.2", None),
+                    krate("baz", "0.2.0", None),
+                    krate("bar", "1.0.0", None),
+                    krate("foo", "1.0.0", Some("1.0.2")),
+                    krate("foo", "1.0.1", Some("1.0.2")),
+                ]
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_superseded_builds_are_cleared() {
+        const MAX_ATTEMPTS: u16 = 2;
+        crate::test::wrapper(|env| {
+            env.override_config(|config| {
+                config.build_attempts = MAX_ATTEMPTS;
+            });
+            let queue = env.build_queue();
+            let superseded_by = |version: &str| -> Result<Option<String>> {
+                Ok(queue
+                    .queued_crates()?
+                    .into_iter()
+                    .find(|krate| krate.version == version)
+                    .and_then(|krate| krate.superseded_by))
+            };
+
+            queue.add_crate("foo", "1.0.0", 0, None)?;
+            queue.add_crate("foo", "1.0.1", 0, None)?;
+            queue.add_crate("foo", "1.0.2", 0, None)?;
+            assert_eq!(superseded_by("1.0.0")?.as_deref(), Some("1.0.2"));
+
+            // the newest version failed for the last time
+            for _ in 0..MAX_ATTEMPTS {
+                queue.process_next_crate(|krate| {
+                    assert_eq!(krate.version, "1.0.2");
+                    anyhow::bail!("this failed");
+                })?;
+            }
+            assert_eq!(superseded_by("1.0.0")?.as_deref(), Some("1.0.1"));
+
+            // the next newest version was built and left the queue
+            queue.process_next_crate(|krate| {
+                assert_eq!(krate.version, "1.0.1");
+                Ok(())
+            })?;
+            assert_eq!(superseded_by("1.0.0")?, None);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_superseded_builds_policy_disabled() {
+        crate::test::wrapper(|env| {
+            env.override_config(|config| {
+                config.deprioritize_superseded_builds = false;
+            });
+            let queue = env.build_queue();
+            queue.add_crate("foo", "1.0.0", 0, None)?;
+            queue.add_crate("foo", "1.0.1", 0, None)?;
+
+            let queued: Vec<_> = queue
+                .queued_crates()?
+                .into_iter()
+                .map(|krate| (krate.version, krate.superseded_by))
+                .collect();
+            assert_eq!(queued, vec![("1.0.0".into(), None), ("1.0.1".into(), None)]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_owners_are_built_in_turns() {
+        crate::test::wrapper(|env| {
+            env.override_config(|config| {
+                config.build_owner_max_consecutive = 2;
+            });
+            for (name, owner) in [
+                ("a1", "alice"),
+                ("a2", "alice"),
+                ("a3", "alice"),
+                ("b1", "bob"),
+            ] {
+                env.fake_release()
+                    .name(name)
+                    .add_owner(CrateOwner {
+                        login: owner.into(),
+                        avatar: String::new(),
+                    })
+                    .create()?;
+            }
+
+            let queue = env.build_queue();
+            for name in ["a1", "a2", "a3", "b1", "new"] {
+                queue.add_crate(name, "2.0.0", 0, None)?;
+            }
+            queue.add_crate("a4", "1.0.0", -10, None)?;
+
+            let queued: Vec<_> = queue
+                .queued_crates()?
+                .into_iter()
+                .map(|krate| (krate.name, krate.owner))
+                .collect();
+            let alice = Some("alice".to_string());
+            assert_eq!(
+                queued,
+                vec![
+                    ("a4".into(), None),
+                    ("a1".into(), alice.clone()),
+                    ("a2".into(), alice.clone()),
+                    ("b1".into(), Some("bob".into())),
+                    ("new".into(), None),
+                    ("a3".into(), alice),
+                ]
+            );
+
+            let mut built = Vec::new();
+            while queue.pending_count()? > 0 {
+                queue.process_next_crate(|krate| {
+                    built.push(krate.name.clone());
+                    Ok(())
+                })?;
+            }
+            assert_eq!(built, vec!["a4", "a1", "a2", "b1", "new", "a3"]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_concurrent_builds_per_owner() {
+        crate::test::wrapper(|env| {
+            env.override_config(|config| {
+                config.build_owner_max_concurrent = Some(1);
+            });
+            for (name, owner) in [("a1", "alice"), ("a2", "alice"), ("b1", "bob")] {
+                env.fake_release()
+                    .name(name)
+                    .add_owner(CrateOwner {
+                        login: owner.into(),
+                        avatar: String::new(),
+                    })
+                    .create()?;
+            }
+
+            let queue = env.build_queue();
+            for name in ["a1", "a2", "b1"] {
+                queue.add_crate(name, "2.0.0", 0, None)?;
+            }
+
+            let duration = Duration::from_secs(60);
+            let a1 = queue.lease_next_crate("worker-1", duration)?.unwrap();
+            assert_eq!(a1.name, "a1");
+            // alice has a build running already
+            let b1 = queue.lease_next_crate("worker-2", duration)?.unwrap();
+            assert_eq!(b1.name, "b1");
+            assert!(queue.lease_next_crate("worker-3", duration)?.is_none());
+            queue.process_next_crate(|_| panic!("alice has a build running already"))?;
+
+            assert!(queue.finish_lease(&a1, "worker-1", Ok(()))?);
+            assert_eq!(
+                queue.lease_next_crate("worker-3", duration)?.unwrap().name,
+                "a2"
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_stale_running_builds_dont_block_owner() {
+        crate::test::wrapper(|env| {
+            env.override_config(|config| {
+                config.build_owner_max_concurrent = Some(1);
+            });
+            env.fake_release()
+                .name("a1")
+                .add_owner(CrateOwner {
+                    login: "alice".into(),
+                    avatar: String::new(),
+                })
+                .create()?;
+
+            let queue = env.build_queue();
+            queue.add_crate("a1", "2.0.0", 0, None)?;
+
+            // the builder died while building the crate
+            let mut conn = env.db().conn();
+            start_running_build(&mut *conn, "a1", "2.0.0", "builder")?;
+            conn.execute(
+                "UPDATE running_builds SET updated_at = NOW() - INTERVAL '1 hour'",
+                &[],
+            )?;
+
+            let mut built = Vec::new();
+            queue.process_next_crate(|krate| {
+                built.push(krate.name.clone());
+                Ok(())
+            })?;
+            assert_eq!(built, vec!["a1"]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_retry_once_with_raised_limits() {
+        crate::test::wrapper(|env| {
+            env.override_config(|config| {
+                config.build_max_timeout = 60 * 60;
+            });
+            let queue = env.build_queue();
+            let build = |name: &str, category| {
+                env.fake_release()
+                    .name(name)
+                    .version("1.0.0")
+                    .builds(vec![FakeBuild::default().failure_category(category)])
+                    .create()
+                    .map(|_| ())
+            };
+            let queued = || -> Result<Vec<String>> {
+                Ok(queue
+                    .queued_crates()?
+                    .into_iter()
+                    .map(|krate| krate.name)
+                    .collect())
+            };
+
+            // a build running out of memory is retried once with more memory
+            queue.add_crate("oom", "1.0.0", 0, None)?;
+            queue.process_next_crate(|_| build("oom", FailureCategory::Oom))?;
+            assert_eq!(queued()?, vec!["oom"]);
+            let overrides = Overrides::for_crate(&mut *env.db().conn(), "oom")?.unwrap();
+            assert!(overrides.automatic);
+            assert_eq!(
+                overrides.memory,
+                Some(Limits::new(&env.config()).memory() * 2)
+            );
+            queue.process_next_crate(|_| build("oom", FailureCategory::Oom))?;
+            assert!(queued()?.is_empty());
+
+            // other failures aren't retried
+            queue.add_crate("broken", "1.0.0", 0, None)?;
+            queue.process_next_crate(|_| build("broken", FailureCategory::CompileErrorInCrate))?;
+            assert!(queued()?.is_empty());
+            assert_eq!(Overrides::for_crate(&mut *env.db().conn(), "broken")?, None);
+
+            // builds by workers are retried too
+            let duration = Duration::from_secs(60);
+            queue.add_crate("slow", "1.0.0", 0, None)?;
+            let lease = queue.lease_next_crate("worker-1", duration)?.unwrap();
+            build("slow", FailureCategory::Timeout)?;
+            assert!(queue.finish_lease(&lease, "worker-1", Ok(()))?);
+            assert_eq!(queued()?, vec!["slow"]);
+            let overrides = Overrides::for_crate(&mut *env.db().conn(), "slow")?.unwrap();
+            assert!(overrides.automatic);
+            assert_eq!(
+                overrides.timeout,
+                Some(Limits::new(&env.config()).timeout() * 2)
+            );
+            assert_eq!(
+                queue.lease_next_crate("worker-1", duration)?.unwrap().name,
+                "slow"
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_expired_lease_is_requeued() {
+        crate::test::wrapper(|env| {
+            let queue = env.build_queue();
+            let duration = Duration::from_secs(60);
+            queue.add_crate("foo", "1.0.0", 0, None)?;
+
+            let lease = queue.lease_next_crate("worker-1", duration)?.unwrap();
+            assert!(queue.lease_next_crate("worker-2", duration)?.is_none());
+
+            env.db().conn().execute(
+                "UPDATE queue SET lease_expires_at = NOW() - INTERVAL '1 minute'",
+                &[],
+            )?;
+            assert!(queue.active_lease(lease.id, "worker-1")?.is_none());
+            assert!(queue.renew_lease(lease.id, "worker-1", duration)?.is_none());
+
+            let new_lease = queue.lease_next_crate("worker-2", duration)?.unwrap();
+            assert_eq!(new_lease.id, lease.id);
+            assert!(!queue.finish_lease(&lease, "worker-1", Ok(()))?);
+
+            let attempt: i32 = env
+                .db()
+                .conn()
+                .query_one("SELECT attempt FROM queue WHERE name = 'foo'", &[])?
+                .get(0);
+            assert_eq!(attempt, 1);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_lease_skips_blacklisted_crates_and_locked_queue() {
+        crate::test::wrapper(|env| {
+            let queue = env.build_queue();
+            let duration = Duration::from_secs(60);
+            queue.add_crate("foo", "1.0.0", 0, None)?;
+            queue.add_crate("bar", "1.0.0", 1, None)?;
+            crate::db::blacklist::add_crate(&mut *env.db().conn(), "foo")?;
+
+            queue.lock()?;
+            assert!(queue.lease_next_crate("worker-1", duration)?.is_none());
+            queue.unlock()?;
+
+            assert_eq!(
+                queue.lease_next_crate("worker-1", duration)?.unwrap().name,
+                "bar"
+            );
+            assert!(!queue.has_build_queued("foo", "1.0.0")?);
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn test_queued_crates() {
         crate::test::wrapper(|env| {
@@ -822,17 +2249,242 @@ mod tests {
             queue.unlock()?;
             assert!(!queue.is_locked()?);
             // initial db ref is empty
-            assert_eq!(queue.last_seen_reference()?, None);
+            assert_eq!(queue.last_seen_reference(None)?, None);
             assert!(!queue.is_locked()?);
 
             let oid = crates_index_diff::gix::ObjectId::from_hex(
                 b"ffffffffffffffffffffffffffffffffffffffff",
             )?;
-            queue.set_last_seen_reference(oid)?;
+            queue.set_last_seen_reference(None, oid)?;
 
-            assert_eq!(queue.last_seen_reference()?, Some(oid));
+            assert_eq!(queue.last_seen_reference(None)?, Some(oid));
             assert!(!queue.is_locked()?);
 
+            // alternative registries have their own reference
+            assert_eq!(queue.last_seen_reference(Some("acme"))?, None);
+            let other = crates_index_diff::gix::ObjectId::from_hex(
+                b"eeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee",
+            )?;
+            queue.set_last_seen_reference(Some("acme"), other)?;
+            assert_eq!(queue.last_seen_reference(Some("acme"))?, Some(other));
+            assert_eq!(queue.last_seen_reference(None)?, Some(oid));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_get_new_crates_from_sparse_index() {
+        crate::test::wrapper(|env| {
+            let mut registry = mockito::Server::new();
+            let _config = registry
+                .mock("GET", "/index/config.json")
+                .with_body(serde_json::json!({ "api": registry.url() }).to_string())
+                .create();
+            // the pages are followed with the seek cursor of the previous response
+            let _updates = registry
+                .mock("GET", "/api/v1/crates")
+                .match_query(mockito::Matcher::Exact(
+                    "sort=recent-updates&per_page=100".into(),
+                ))
+                .with_body(
+                    serde_json::json!({
+                        "crates": [
+                            { "name": "foo", "updated_at": "2023-01-03T00:00:00Z" },
+                            { "name": "gone", "updated_at": "2023-01-02T00:00:00Z" },
+                        ],
+                        "meta": {
+                            "next_page": "?sort=recent-updates&per_page=100&seek=cursor",
+                        },
+                    })
+                    .to_string(),
+                )
+                .create();
+            let _next_updates = registry
+                .mock("GET", "/api/v1/crates")
+                .match_query(mockito::Matcher::UrlEncoded("seek".into(), "cursor".into()))
+                .with_body(
+                    serde_json::json!({
+                        "crates": [
+                            { "name": "old", "updated_at": "2022-12-01T00:00:00Z" },
+                        ],
+                        "meta": {
+                            "next_page": "?sort=recent-updates&per_page=100&seek=next",
+                        },
+                    })
+                    .to_string(),
+                )
+                .expect_at_least(1)
+                .create();
+            let _foo = registry
+                .mock("GET", "/index/3/f/foo")
+                .with_body(concat!(
+                    r#"{"name":"foo","vers":"0.1.0","yanked":true}"#,
+                    "\n",
+                    r#"{"name":"foo","vers":"0.2.0","yanked":false}"#,
+                    "\n",
+                ))
+                .create();
+            let _gone = registry
+                .mock("GET", "/index/go/ne/gone")
+                .with_status(404)
+                .create();
+            // deletions are confirmed with the API, which still has 0.0.2
+            let _foo_versions = registry
+                .mock("GET", "/api/v1/crates/foo/versions")
+                .with_body(
+                    serde_json::json!({
+                        "versions": [{ "num": "0.2.0" }, { "num": "0.1.0" }, { "num": "0.0.2" }],
+                    })
+                    .to_string(),
+                )
+                .create();
+            let _gone_versions = registry
+                .mock("GET", "/api/v1/crates/gone/versions")
+                .with_status(404)
+                .create();
+
+            env.fake_release().name("foo").version("0.0.1").create()?;
+            env.fake_release().name("foo").version("0.0.2").create()?;
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            env.fake_release().name("gone").version("1.0.0").create()?;
+            env.fake_release().name("old").version("1.0.0").create()?;
+
+            let index = Index::from_url(
+                env.config().registry_index_path.clone(),
+                format!("sparse+{}/index/", registry.url()),
+                0,
+            )?;
+            assert!(index.is_sparse());
+
+            let queue = env.build_queue();
+            assert!(queue.get_new_crates(&index).is_err());
+
+            let cursor = "2023-01-01T00:00:00Z".parse()?;
+            queue.set_last_seen_index_update(None, cursor)?;
+            assert_eq!(queue.get_new_crates(&index)?, 1);
+
+            let queued: Vec<_> = queue
+                .queued_crates()?
+                .into_iter()
+                .map(|krate| (krate.name, krate.version, krate.registry))
+                .collect();
+            assert_eq!(queued, vec![("foo".into(), "0.2.0".into(), None)]);
+
+            let mut conn = env.db().conn();
+            let releases: Vec<(String, String, bool)> = conn
+                .query(
+                    "SELECT crates.name, releases.version, releases.yanked
+                     FROM releases
+                     INNER JOIN crates ON crates.id = releases.crate_id
+                     ORDER BY crates.name, releases.version",
+                    &[],
+                )?
+                .into_iter()
+                .map(|row| (row.get(0), row.get(1), row.get(2)))
+                .collect();
+            assert_eq!(
+                releases,
+                vec![
+                    ("foo".into(), "0.0.2".into(), false),
+                    ("foo".into(), "0.1.0".into(), true),
+                    ("old".into(), "1.0.0".into(), false),
+                ]
+            );
+
+            assert_eq!(
+                queue.last_seen_index_update(None)?,
+                Some("2023-01-03T00:00:00Z".parse()?)
+            );
+
+            // nothing changed since the last update
+            assert_eq!(queue.get_new_crates(&index)?, 0);
+            _next_updates.assert();
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_sparse_index_outages_dont_delete_crates() {
+        crate::test::wrapper(|env| {
+            let mut registry = mockito::Server::new();
+            let _config = registry
+                .mock("GET", "/index/config.json")
+                .with_body(serde_json::json!({ "api": registry.url() }).to_string())
+                .create();
+            let updates = |registry: &mut mockito::ServerGuard, crates: serde_json::Value| {
+                registry
+                    .mock("GET", "/api/v1/crates")
+                    .match_query(mockito::Matcher::Any)
+                    .with_body(serde_json::json!({ "crates": crates }).to_string())
+                    .create()
+            };
+            let _stale = registry
+                .mock("GET", "/index/st/al/stale")
+                .with_status(404)
+                .create();
+            let _stale_versions = registry
+                .mock("GET", "/api/v1/crates/stale/versions")
+                .with_body(serde_json::json!({ "versions": [{ "num": "1.0.0" }] }).to_string())
+                .create();
+            let _forbidden = registry
+                .mock("GET", "/index/fo/rb/forbidden")
+                .with_status(403)
+                .create();
+            let _fresh = registry
+                .mock("GET", "/index/fr/es/fresh")
+                .with_body(r#"{"name":"fresh","vers":"1.0.0","yanked":false}"#)
+                .expect(2)
+                .create();
+
+            env.fake_release().name("stale").version("1.0.0").create()?;
+            env.fake_release()
+                .name("forbidden")
+                .version("1.0.0")
+                .create()?;
+
+            let index = Index::from_url(
+                env.config().registry_index_path.clone(),
+                format!("sparse+{}/index/", registry.url()),
+                0,
+            )?;
+            let queue = env.build_queue();
+            let cursor: DateTime<Utc> = "2023-01-01T00:00:00Z".parse()?;
+            queue.set_last_seen_index_update(None, cursor)?;
+
+            // the crate is missing from an outdated index file, but the API still has it
+            let mock = updates(
+                &mut registry,
+                serde_json::json!([
+                { "name": "forbidden", "updated_at": "2023-01-03T00:00:00Z" },
+                { "name": "stale", "updated_at": "2023-01-02T00:00:00Z" },
+                ]),
+            );
+            // a 403 of the CDN isn't a deleted crate
+            assert!(queue.get_new_crates(&index).is_err());
+            mock.remove();
+
+            let mut conn = env.db().conn();
+            let count: i64 = conn.query_one("SELECT COUNT(*) FROM releases", &[])?.get(0);
+            assert_eq!(count, 2);
+            assert_eq!(
+                queue.last_seen_index_update(None)?,
+                Some("2023-01-02T00:00:00Z".parse()?)
+            );
+
+            // crates updated within the cache TTL of the index are checked again
+            let now = Utc::now();
+            let _mock = updates(
+                &mut registry,
+                serde_json::json!([{ "name": "fresh", "updated_at": now }]),
+            );
+            assert_eq!(queue.get_new_crates(&index)?, 1);
+            let last_seen = queue.last_seen_index_update(None)?.unwrap();
+            assert!(last_seen < now - chrono::Duration::minutes(4));
+            assert_eq!(queue.get_new_crates(&index)?, 0);
+            _fresh.assert();
+
             Ok(())
         });
     }
@@ -844,7 +2496,7 @@ mod tests {
             set_config(&mut conn, ConfigName::LastSeenIndexReference, "invalid")?;
 
             let queue = env.build_queue();
-            assert!(queue.last_seen_reference().is_err());
+            assert!(queue.last_seen_reference(None).is_err());
 
             Ok(())
         });
@@ -1,14 +1,28 @@
-use crate::{cdn::CdnKind, storage::StorageKind};
+use crate::{
+    cdn::CdnKind,
+    index::{sparse::SPARSE_PREFIX, split_qualified_crate_name},
+    storage::StorageKind,
+};
 use anyhow::{anyhow, bail, Context, Result};
-use std::{env::VarError, error::Error, path::PathBuf, str::FromStr, time::Duration};
+use std::{
+    env::VarError,
+    error::Error,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 use tracing::trace;
 
 #[derive(Debug)]
 pub struct Config {
     pub prefix: PathBuf,
     pub registry_index_path: PathBuf,
+    // URLs starting with `sparse+` are read over the sparse HTTP protocol,
+    // without a local clone in `registry_index_path`.
     pub registry_url: Option<String>,
     pub registry_api_host: String,
+    // Registries docs.rs builds documentation for next to crates.io.
+    pub alternative_registries: Vec<AlternativeRegistry>,
 
     // Database connection params
     pub(crate) database_url: String,
@@ -20,6 +34,8 @@ pub struct Config {
 
     // Storage params
     pub(crate) storage_backend: StorageKind,
+    // root directory for the filesystem storage backend
+    pub(crate) storage_filesystem_root: PathBuf,
 
     // AWS SDK configuration
     pub(crate) aws_sdk_max_retries: u32,
@@ -56,6 +72,24 @@ pub struct Config {
     pub(crate) max_parse_memory: usize,
     // Time between 'git gc --auto' calls in seconds
     pub(crate) registry_gc_interval: u64,
+    // Secret used to validate the signature of calls to the index webhook.
+    // When absent, the signature isn't checked.
+    pub(crate) index_webhook_secret: Option<String>,
+    // When the index webhook wasn't called for this many seconds,
+    // the registry watcher checks the index for new crates itself.
+    pub(crate) index_poll_fallback_interval: u64,
+    // Tokens granting access to the admin interface, as `(name, token)` pairs.
+    // The name is recorded in the audit log.
+    pub(crate) admin_tokens: Vec<(String, String)>,
+
+    // The public origin of this instance, without trailing slash. Used for
+    // canonical links, the sitemap, the release feed and the search plugin.
+    pub(crate) public_url: String,
+    // Branding of self-hosted instances: the name shown in the top bar and page titles,
+    // an optional logo replacing the default icon, and `(title, url)` links in the footer.
+    pub(crate) site_name: String,
+    pub(crate) site_logo: Option<String>,
+    pub(crate) footer_links: Vec<(String, String)>,
 
     /// amount of threads for CPU intensive rendering
     pub(crate) render_threads: usize,
@@ -69,6 +103,10 @@ pub struct Config {
     // For unit-tests the number has to be higher.
     pub(crate) random_crate_search_view_size: u32,
 
+    // when our own full-text search doesn't find anything (or fails),
+    // forward the search to the registry API instead.
+    pub(crate) search_crates_io_fallback: bool,
+
     // where do we want to store the locally cached index files
     // for the remote archives?
     pub(crate) local_archive_cache_path: PathBuf,
@@ -104,9 +142,72 @@ pub struct Config {
     pub(crate) build_default_memory_limit: Option<usize>,
     pub(crate) include_default_targets: bool,
     pub(crate) disable_memory_limit: bool,
+    // Builds running out of memory or time are retried once with raised limits, up to these
+    // ceilings. The timeout is in seconds.
+    pub(crate) build_max_memory_limit: usize,
+    pub(crate) build_max_timeout: u64,
+    // Queued builds with the same priority are built in turns across the owners of the crates,
+    // each owner gets this many consecutive builds per turn.
+    pub(crate) build_owner_max_consecutive: u32,
+    // The maximum number of builds of crates of one owner running at the same time.
+    pub(crate) build_owner_max_concurrent: Option<u32>,
+    // Older queued versions of a crate are superseded by newer semver-compatible versions queued
+    // after them, and built after them with their priority lowered by the penalty.
+    pub(crate) deprioritize_superseded_builds: bool,
+    pub(crate) superseded_build_priority_penalty: i32,
+
+    // Tokens of the build workers allowed to lease queued builds, as `(name, token)` pairs.
+    // The name is recorded as the build server of their builds.
+    pub(crate) worker_tokens: Vec<(String, String)>,
+    // How long the lease of a build worker lasts without a heartbeat, in seconds.
+    pub(crate) build_lease_duration: u64,
+    // The maximum size of the compressed archives build workers upload, in bytes.
+    pub(crate) worker_upload_max_size: u64,
+    // Build workers lease their builds from the docs.rs instance at this URL and send the
+    // results back to it, authenticated with `worker_token`, instead of using the database.
+    pub(crate) worker_server_url: Option<String>,
+    pub(crate) worker_token: Option<String>,
+}
+
+/// A Cargo registry besides crates.io whose crates are built.
+///
+/// Crates of alternative registries are stored with a registry-qualified name, see
+/// [`qualified_crate_name`](crate::index::qualified_crate_name).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlternativeRegistry {
+    /// The name used in crate names and URLs.
+    pub name: String,
+    /// URL of the git repository containing the index.
+    pub index_url: String,
+    /// Where the local clone of the index is kept.
+    pub index_path: PathBuf,
 }
 
 impl Config {
+    /// The alternative registry with the given name, if it's configured.
+    pub fn alternative_registry(&self, name: &str) -> Option<&AlternativeRegistry> {
+        self.alternative_registries
+            .iter()
+            .find(|registry| registry.name == name)
+    }
+
+    /// The index URL a crate is queued with, based on its possibly registry-qualified name.
+    pub fn index_url_for_crate(&self, name: &str) -> Result<Option<&str>> {
+        Ok(match split_qualified_crate_name(name).0 {
+            Some(registry) => Some(
+                self.alternative_registry(registry)
+                    .with_context(|| format!("unknown registry {registry}"))?
+                    .index_url
+                    .as_str(),
+            ),
+            // crates of the sparse crates.io index are built from crates.io itself
+            None => self
+                .registry_url
+                .as_deref()
+                .filter(|url| !url.starts_with(SPARSE_PREFIX)),
+        })
+    }
+
     pub fn from_env() -> Result<Self> {
         let old_vars = [
             ("CRATESFYI_PREFIX", "DOCSRS_PREFIX"),
@@ -138,14 +239,25 @@ impl Config {
             registry_index_path: env("REGISTRY_INDEX_PATH", prefix.join("crates.io-index"))?,
             registry_url: maybe_env("REGISTRY_URL")?,
             registry_api_host: env("DOCSRS_REGISTRY_API_HOST", "https://crates.io".into())?,
+            alternative_registries: maybe_env::<String>("DOCSRS_ALTERNATIVE_REGISTRIES")?
+                .map(|registries| parse_alternative_registries(&registries, &prefix))
+                .transpose()
+                .context("failed to parse configuration variable DOCSRS_ALTERNATIVE_REGISTRIES")?
+                .unwrap_or_default(),
             prefix: prefix.clone(),
 
-            database_url: require_env("DOCSRS_DATABASE_URL")?,
+            // build workers don't have access to the database
+            database_url: if maybe_env::<String>("DOCSRS_WORKER_SERVER_URL")?.is_some() {
+                maybe_env("DOCSRS_DATABASE_URL")?.unwrap_or_default()
+            } else {
+                require_env("DOCSRS_DATABASE_URL")?
+            },
             max_pool_size: env("DOCSRS_MAX_POOL_SIZE", 90)?,
             max_sqlite_pool_size: env("DOCSRS_MAX_SQLITE_POOL_SIZE", 500)?,
             min_pool_idle: env("DOCSRS_MIN_POOL_IDLE", 10)?,
 
             storage_backend: env("DOCSRS_STORAGE_BACKEND", StorageKind::Database)?,
+            storage_filesystem_root: env("DOCSRS_STORAGE_FILESYSTEM_ROOT", prefix.join("storage"))?,
 
             aws_sdk_max_retries: env("DOCSRS_AWS_SDK_MAX_RETRIES", 6)?,
 
@@ -174,11 +286,33 @@ impl Config {
             // https://github.com/rust-lang/docs.rs/pull/930#issuecomment-667729380
             max_parse_memory: env("DOCSRS_MAX_PARSE_MEMORY", 5 * 1024 * 1024)?,
             registry_gc_interval: env("DOCSRS_REGISTRY_GC_INTERVAL", 60 * 60)?,
+            index_webhook_secret: maybe_env("DOCSRS_INDEX_WEBHOOK_SECRET")?,
+            index_poll_fallback_interval: env("DOCSRS_INDEX_POLL_FALLBACK_INTERVAL", 60)?,
+            admin_tokens: maybe_env::<String>("DOCSRS_ADMIN_TOKENS")?
+                .map(|tokens| parse_tokens(&tokens, "admin"))
+                .transpose()
+                .context("failed to parse configuration variable DOCSRS_ADMIN_TOKENS")?
+                .unwrap_or_default(),
+            public_url: env(
+                "DOCSRS_PUBLIC_URL",
+                url::Url::parse("https://docs.rs").expect("valid default URL"),
+            )?
+            .as_str()
+            .trim_end_matches('/')
+            .to_owned(),
+            site_name: env("DOCSRS_SITE_NAME", "Docs.rs".to_string())?,
+            site_logo: maybe_env("DOCSRS_SITE_LOGO")?,
+            footer_links: maybe_env::<String>("DOCSRS_FOOTER_LINKS")?
+                .map(|links| parse_footer_links(&links))
+                .transpose()
+                .context("failed to parse configuration variable DOCSRS_FOOTER_LINKS")?
+                .unwrap_or_default(),
             render_threads: env("DOCSRS_RENDER_THREADS", num_cpus::get())?,
             request_timeout: maybe_env::<u64>("DOCSRS_REQUEST_TIMEOUT")?.map(Duration::from_secs),
             report_request_timeouts: env("DOCSRS_REPORT_REQUEST_TIMEOUTS", false)?,
 
             random_crate_search_view_size: env("DOCSRS_RANDOM_CRATE_SEARCH_VIEW_SIZE", 500)?,
+            search_crates_io_fallback: env("DOCSRS_SEARCH_CRATES_IO_FALLBACK", true)?,
 
             csp_report_only: env("DOCSRS_CSP_REPORT_ONLY", false)?,
 
@@ -207,10 +341,116 @@ impl Config {
             build_default_memory_limit: maybe_env("DOCSRS_BUILD_DEFAULT_MEMORY_LIMIT")?,
             include_default_targets: env("DOCSRS_INCLUDE_DEFAULT_TARGETS", true)?,
             disable_memory_limit: env("DOCSRS_DISABLE_MEMORY_LIMIT", false)?,
+            build_max_memory_limit: env("DOCSRS_BUILD_MAX_MEMORY_LIMIT", 8 * 1024 * 1024 * 1024)?,
+            build_max_timeout: env("DOCSRS_BUILD_MAX_TIMEOUT", 60 * 60)?,
+            build_owner_max_consecutive: env("DOCSRS_BUILD_OWNER_MAX_CONSECUTIVE", 1)?,
+            build_owner_max_concurrent: maybe_env("DOCSRS_BUILD_OWNER_MAX_CONCURRENT")?,
+            deprioritize_superseded_builds: env("DOCSRS_DEPRIORITIZE_SUPERSEDED_BUILDS", true)?,
+            superseded_build_priority_penalty: env("DOCSRS_SUPERSEDED_BUILD_PRIORITY_PENALTY", 10)?,
+
+            worker_tokens: maybe_env::<String>("DOCSRS_WORKER_TOKENS")?
+                .map(|tokens| parse_tokens(&tokens, "worker"))
+                .transpose()
+                .context("failed to parse configuration variable DOCSRS_WORKER_TOKENS")?
+                .unwrap_or_default(),
+            build_lease_duration: env("DOCSRS_BUILD_LEASE_DURATION", 5 * 60)?,
+            worker_upload_max_size: env("DOCSRS_WORKER_UPLOAD_MAX_SIZE", 4 * 1024 * 1024 * 1024)?,
+            worker_server_url: maybe_env("DOCSRS_WORKER_SERVER_URL")?,
+            worker_token: maybe_env("DOCSRS_WORKER_TOKEN")?,
         })
     }
 }
 
+/// Parses a comma-separated list of `name=index-url` pairs.
+fn parse_alternative_registries(value: &str, prefix: &Path) -> Result<Vec<AlternativeRegistry>> {
+    let mut registries: Vec<AlternativeRegistry> = Vec::new();
+    for entry in value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let Some((name, index_url)) = entry.split_once('=') else {
+            bail!("missing index URL for alternative registry `{entry}`");
+        };
+        let (name, index_url) = (name.trim(), index_url.trim());
+
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            bail!("invalid alternative registry name `{name}`");
+        }
+        if name == "crates-io" {
+            bail!("crates.io can't be configured as alternative registry");
+        }
+        // builds fetch the crates of a registry by its index URL, which cargo only supports
+        // for git indexes
+        if index_url.starts_with(SPARSE_PREFIX) {
+            bail!("the sparse index of alternative registry `{name}` isn't supported");
+        }
+        if registries.iter().any(|registry| registry.name == name) {
+            bail!("alternative registry `{name}` is configured twice");
+        }
+
+        registries.push(AlternativeRegistry {
+            name: name.into(),
+            index_url: index_url.into(),
+            index_path: prefix.join("registries").join(name),
+        });
+    }
+    Ok(registries)
+}
+
+/// Parses a comma-separated list of `name=token` pairs.
+fn parse_tokens(value: &str, kind: &str) -> Result<Vec<(String, String)>> {
+    let mut tokens: Vec<(String, String)> = Vec::new();
+    for entry in value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let Some((name, token)) = entry.split_once('=') else {
+            bail!("missing token for {kind} `{entry}`");
+        };
+        let (name, token) = (name.trim(), token.trim());
+
+        if name.is_empty() {
+            bail!("{kind} tokens need a name");
+        }
+        if token.len() < 16 {
+            bail!("the {kind} token of `{name}` is shorter than 16 characters");
+        }
+        if tokens.iter().any(|(_, existing)| existing == token) {
+            bail!("the {kind} token of `{name}` is used twice");
+        }
+
+        tokens.push((name.into(), token.into()));
+    }
+    Ok(tokens)
+}
+
+/// Parses a comma-separated list of `title=url` pairs.
+fn parse_footer_links(value: &str) -> Result<Vec<(String, String)>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let Some((title, url)) = entry.split_once('=') else {
+                bail!("missing URL for footer link `{entry}`");
+            };
+            let (title, url) = (title.trim(), url.trim());
+            if title.is_empty() {
+                bail!("footer links need a title");
+            }
+            url::Url::parse(url)
+                .with_context(|| format!("invalid URL for footer link `{title}`"))?;
+            Ok((title.into(), url.into()))
+        })
+        .collect()
+}
+
 fn env<T>(var: &str, default: T) -> Result<T>
 where
     T: FromStr,
@@ -244,3 +484,84 @@ where
         Err(VarError::NotUnicode(_)) => Err(anyhow!("configuration variable {} is not UTF-8", var)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alternative_registries() {
+        let prefix = Path::new("/prefix");
+        assert_eq!(parse_alternative_registries("", prefix).unwrap(), vec![]);
+        assert_eq!(
+            parse_alternative_registries(
+                "acme=https://git.acme.corp/index, other_registry = ssh://git@example.com/index,",
+                prefix
+            )
+            .unwrap(),
+            vec![
+                AlternativeRegistry {
+                    name: "acme".into(),
+                    index_url: "https://git.acme.corp/index".into(),
+                    index_path: "/prefix/registries/acme".into(),
+                },
+                AlternativeRegistry {
+                    name: "other_registry".into(),
+                    index_url: "ssh://git@example.com/index".into(),
+                    index_path: "/prefix/registries/other_registry".into(),
+                },
+            ]
+        );
+
+        for invalid in [
+            "acme",
+            "=https://example.com",
+            "ac~me=https://example.com",
+            "crates-io=https://example.com",
+            "acme=https://example.com,acme=https://example.org",
+            "acme=sparse+https://example.com/index/",
+        ] {
+            assert!(parse_alternative_registries(invalid, prefix).is_err());
+        }
+    }
+
+    #[test]
+    fn admin_tokens() {
+        assert_eq!(parse_tokens("", "admin").unwrap(), vec![]);
+        assert_eq!(
+            parse_tokens("alice=0123456789abcdef, bob = fedcba9876543210 ,", "admin").unwrap(),
+            vec![
+                ("alice".into(), "0123456789abcdef".into()),
+                ("bob".into(), "fedcba9876543210".into()),
+            ]
+        );
+
+        for invalid in [
+            "alice",
+            "=0123456789abcdef",
+            "alice=short",
+            "alice=0123456789abcdef,bob=0123456789abcdef",
+        ] {
+            assert!(parse_tokens(invalid, "admin").is_err());
+        }
+    }
+
+    #[test]
+    fn footer_links() {
+        assert_eq!(parse_footer_links("").unwrap(), vec![]);
+        assert_eq!(
+            parse_footer_links(
+                "Imprint=https://example.com/imprint, Status = https://status.example.com"
+            )
+            .unwrap(),
+            vec![
+                ("Imprint".into(), "https://example.com/imprint".into()),
+                ("Status".into(), "https://status.example.com".into()),
+            ]
+        );
+
+        for invalid in ["Imprint", "=https://example.com", "Imprint=not a url"] {
+            assert!(parse_footer_links(invalid).is_err());
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use crate::cdn::CdnBackend;
 use crate::db::Pool;
 use crate::error::Result;
+use crate::index::AlternativeIndexes;
 use crate::repositories::RepositoryStatsUpdater;
 use crate::{BuildQueue, Config, Index, InstanceMetrics, ServiceMetrics, Storage};
 use std::sync::Arc;
@@ -15,6 +16,7 @@ pub trait Context {
     fn service_metrics(&self) -> Result<Arc<ServiceMetrics>>;
     fn instance_metrics(&self) -> Result<Arc<InstanceMetrics>>;
     fn index(&self) -> Result<Arc<Index>>;
+    fn alternative_indexes(&self) -> Result<Arc<AlternativeIndexes>>;
     fn repository_stats_updater(&self) -> Result<Arc<RepositoryStatsUpdater>>;
     fn runtime(&self) -> Result<Arc<Runtime>>;
 }
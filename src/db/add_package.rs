@@ -1,6 +1,6 @@
 use crate::{
     db::types::Feature,
-    docbuilder::{BuildResult, DocCoverage},
+    docbuilder::{BuildResult, DocCoverage, PublicItem, ResourceUsage, SearchIndexItem},
     error::Result,
     index::api::{CrateData, CrateOwner, ReleaseData},
     storage::CompressionAlgorithm,
@@ -8,7 +8,8 @@ use crate::{
     web::crate_details::CrateDetails,
 };
 use anyhow::{anyhow, Context};
-use postgres::Client;
+use postgres::{Client, GenericClient};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use slug::slugify;
 use std::{
@@ -19,6 +20,27 @@ use std::{
 };
 use tracing::{debug, info};
 
+/// The documentation of a package that's read from its sources.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct PackageDocs {
+    /// The readme, `README.md` unless the manifest names another file.
+    pub(crate) readme: Option<String>,
+    /// The crate-level documentation of the first target.
+    pub(crate) rustdoc: Option<String>,
+}
+
+impl PackageDocs {
+    /// Reads the documentation from the sources of the package in `source_dir`.
+    ///
+    /// Files that can't be read are skipped.
+    pub(crate) fn read(pkg: &MetadataPackage, source_dir: &Path) -> Self {
+        Self {
+            readme: get_readme(pkg, source_dir).unwrap_or(None),
+            rustdoc: get_rustdoc(pkg, source_dir).unwrap_or(None),
+        }
+    }
+}
+
 /// Adds a package into database.
 ///
 /// Package must be built first.
@@ -29,11 +51,12 @@ use tracing::{debug, info};
 pub(crate) fn add_package_into_database(
     conn: &mut Client,
     metadata_pkg: &MetadataPackage,
-    source_dir: &Path,
+    docs: &PackageDocs,
     res: &BuildResult,
     default_target: &str,
     source_files: Value,
     doc_targets: Vec<String>,
+    json_targets: Vec<String>,
     registry_data: &ReleaseData,
     has_docs: bool,
     has_examples: bool,
@@ -44,8 +67,6 @@ pub(crate) fn add_package_into_database(
     debug!("Adding package into database");
     let crate_id = initialize_package_in_database(conn, metadata_pkg)?;
     let dependencies = convert_dependencies(metadata_pkg);
-    let rustdoc = get_rustdoc(metadata_pkg, source_dir).unwrap_or(None);
-    let readme = get_readme(metadata_pkg, source_dir).unwrap_or(None);
     let features = get_features(metadata_pkg);
     let is_library = metadata_pkg.is_library();
 
@@ -58,12 +79,13 @@ pub(crate) fn add_package_into_database(
             keywords, have_examples, downloads, files,
             doc_targets, is_library, doc_rustc_version,
             documentation_url, default_target, features,
-            repository_id, archive_storage
+            repository_id, archive_storage, json_targets
          )
          VALUES (
             $1,  $2,  $3,  $4,  $5,  $6,  $7,  $8,  $9,
             $10, $11, $12, $13, $14, $15, $16, $17, $18,
-            $19, $20, $21, $22, $23, $24, $25, $26, $27
+            $19, $20, $21, $22, $23, $24, $25, $26, $27,
+            $28
          )
          ON CONFLICT (crate_id, version) DO UPDATE
             SET release_time = $3,
@@ -90,13 +112,14 @@ pub(crate) fn add_package_into_database(
                 default_target = $24,
                 features = $25,
                 repository_id = $26,
-                archive_storage = $27
+                archive_storage = $27,
+                json_targets = $28
          RETURNING id",
         &[
             &crate_id,
             &metadata_pkg.version,
             &registry_data.release_time,
-            &serde_json::to_value(dependencies)?,
+            &serde_json::to_value(&dependencies)?,
             &metadata_pkg.package_name(),
             &registry_data.yanked,
             &res.successful,
@@ -106,8 +129,8 @@ pub(crate) fn add_package_into_database(
             &metadata_pkg.repository,
             &metadata_pkg.homepage,
             &metadata_pkg.description,
-            &rustdoc,
-            &readme,
+            &docs.rustdoc,
+            &docs.readme,
             &serde_json::to_value(&metadata_pkg.keywords)?,
             &has_examples,
             &registry_data.downloads,
@@ -120,12 +143,14 @@ pub(crate) fn add_package_into_database(
             &features,
             &repository_id,
             &archive_storage,
+            &serde_json::to_value(json_targets)?,
         ],
     )?;
 
     let release_id: i32 = rows[0].get(0);
 
     add_keywords_into_database(conn, metadata_pkg, release_id)?;
+    add_dependencies_into_database(conn, &dependencies, release_id)?;
     add_compression_into_database(conn, compression_algorithms.into_iter(), release_id)?;
 
     let crate_details = CrateDetails::new(
@@ -140,10 +165,15 @@ pub(crate) fn add_package_into_database(
 
     conn.execute(
         "UPDATE crates
-         SET latest_version_id = $2
+         SET
+            latest_version_id = $2,
+            downloads_total = (
+                SELECT COALESCE(SUM(downloads), 0) FROM releases WHERE crate_id = $1
+            )
          WHERE id = $1",
         &[&crate_id, &crate_details.latest_release().id],
     )?;
+    update_latest_dependencies(conn, crate_id)?;
 
     Ok(release_id)
 }
@@ -154,7 +184,8 @@ pub(crate) fn add_doc_coverage(
     doc_coverage: DocCoverage,
 ) -> Result<i32> {
     debug!("Adding doc coverage into database");
-    let rows = conn.query(
+    let mut transaction = conn.transaction()?;
+    let rows = transaction.query(
         "INSERT INTO doc_coverage (
             release_id, total_items, documented_items,
             total_items_needing_examples, items_with_examples
@@ -175,31 +206,202 @@ pub(crate) fn add_doc_coverage(
             &doc_coverage.items_with_examples,
         ],
     )?;
+
+    transaction.execute(
+        "DELETE FROM doc_coverage_files WHERE release_id = $1",
+        &[&release_id],
+    )?;
+    let files = &doc_coverage.files;
+    transaction.execute(
+        "INSERT INTO doc_coverage_files (
+            release_id, path, total_items, documented_items,
+            total_items_needing_examples, items_with_examples
+         )
+         SELECT $1, path, total_items, documented_items, total_items_needing_examples, items_with_examples
+         FROM UNNEST($2::TEXT[], $3::INT[], $4::INT[], $5::INT[], $6::INT[])
+            AS files(path, total_items, documented_items, total_items_needing_examples, items_with_examples)",
+        &[
+            &release_id,
+            &files.iter().map(|file| &file.path).collect::<Vec<_>>(),
+            &files.iter().map(|file| file.total_items).collect::<Vec<_>>(),
+            &files.iter().map(|file| file.documented_items).collect::<Vec<_>>(),
+            &files
+                .iter()
+                .map(|file| file.total_items_needing_examples)
+                .collect::<Vec<_>>(),
+            &files
+                .iter()
+                .map(|file| file.items_with_examples)
+                .collect::<Vec<_>>(),
+        ],
+    )?;
+
+    transaction.execute(
+        "DELETE FROM doc_coverage_items WHERE release_id = $1",
+        &[&release_id],
+    )?;
+    let items = &doc_coverage.undocumented_items;
+    transaction.execute(
+        "INSERT INTO doc_coverage_items (release_id, path, line, name, kind)
+         SELECT $1, path, line, name, kind
+         FROM UNNEST($2::TEXT[], $3::INT[], $4::TEXT[], $5::TEXT[]) AS items(path, line, name, kind)",
+        &[
+            &release_id,
+            &items.iter().map(|item| &item.path).collect::<Vec<_>>(),
+            &items.iter().map(|item| item.line).collect::<Vec<_>>(),
+            &items.iter().map(|item| &item.name).collect::<Vec<_>>(),
+            &items.iter().map(|item| &item.kind).collect::<Vec<_>>(),
+        ],
+    )?;
+    transaction.commit()?;
+
     Ok(rows[0].get(0))
 }
 
-/// Adds a build into database
+/// Replaces the items from the rustdoc search index of a release
+pub(crate) fn add_search_items(
+    conn: &mut Client,
+    release_id: i32,
+    items: &[SearchIndexItem],
+) -> Result<()> {
+    debug!("Adding {} search items into database", items.len());
+    let mut transaction = conn.transaction()?;
+    transaction.execute(
+        "DELETE FROM release_items WHERE release_id = $1",
+        &[&release_id],
+    )?;
+    transaction.execute(
+        "INSERT INTO release_items (release_id, name, path, kind, link)
+         SELECT $1, name, path, kind, link
+         FROM UNNEST($2::TEXT[], $3::TEXT[], $4::TEXT[], $5::TEXT[]) AS items(name, path, kind, link)",
+        &[
+            &release_id,
+            &items.iter().map(|item| &item.name).collect::<Vec<_>>(),
+            &items.iter().map(|item| &item.path).collect::<Vec<_>>(),
+            &items.iter().map(|item| &item.kind).collect::<Vec<_>>(),
+            &items.iter().map(|item| &item.link).collect::<Vec<_>>(),
+        ],
+    )?;
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Replaces the public items of a release read from its rustdoc JSON output.
+pub(crate) fn add_public_items(
+    conn: &mut Client,
+    release_id: i32,
+    items: &[PublicItem],
+) -> Result<()> {
+    debug!("Adding {} public items into database", items.len());
+    let mut transaction = conn.transaction()?;
+    transaction.execute(
+        "DELETE FROM release_public_items WHERE release_id = $1",
+        &[&release_id],
+    )?;
+    transaction.execute(
+        "INSERT INTO release_public_items (release_id, path, kind, link, signature)
+         SELECT $1, path, kind, link, signature
+         FROM UNNEST($2::TEXT[], $3::TEXT[], $4::TEXT[], $5::TEXT[]) AS items(path, kind, link, signature)",
+        &[
+            &release_id,
+            &items.iter().map(|item| &item.path).collect::<Vec<_>>(),
+            &items.iter().map(|item| &item.kind).collect::<Vec<_>>(),
+            &items.iter().map(|item| &item.link).collect::<Vec<_>>(),
+            &items.iter().map(|item| &item.signature).collect::<Vec<_>>(),
+        ],
+    )?;
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Replaces the dependency versions that were resolved when building a release.
+pub(crate) fn add_locked_dependencies(
+    conn: &mut Client,
+    release_id: i32,
+    dependencies: &[(String, String)],
+) -> Result<()> {
+    let mut transaction = conn.transaction()?;
+    transaction.execute(
+        "DELETE FROM release_locked_dependencies WHERE release_id = $1",
+        &[&release_id],
+    )?;
+    transaction.execute(
+        "INSERT INTO release_locked_dependencies (release_id, name, version)
+         SELECT $1, name, version
+         FROM UNNEST($2::TEXT[], $3::TEXT[]) AS deps(name, version)",
+        &[
+            &release_id,
+            &dependencies.iter().map(|dep| &dep.0).collect::<Vec<_>>(),
+            &dependencies.iter().map(|dep| &dep.1).collect::<Vec<_>>(),
+        ],
+    )?;
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Adds a build into database, built on `build_server`.
 pub(crate) fn add_build_into_database(
     conn: &mut Client,
     release_id: i32,
     res: &BuildResult,
+    build_server: &str,
 ) -> Result<i32> {
     debug!("Adding build into database");
     let rows = conn.query(
-        "INSERT INTO builds (rid, rustc_version, docsrs_version, build_status, build_server)
-        VALUES ($1, $2, $3, $4, $5)
+        "INSERT INTO builds (
+            rid, rustc_version, docsrs_version, build_status, build_server, failure_category
+        )
+        VALUES ($1, $2, $3, $4, $5, $6)
         RETURNING id",
         &[
             &release_id,
             &res.rustc_version,
             &res.docsrs_version,
             &res.successful,
-            &hostname::get()?.to_str().unwrap_or(""),
+            &build_server,
+            &res.failure_category.map(|category| category.to_string()),
         ],
     )?;
     Ok(rows[0].get(0))
 }
 
+/// Records the targets a build log was stored for, at `build-logs/{build_id}/{target}.txt`.
+pub(crate) fn add_build_log_targets(
+    conn: &mut Client,
+    build_id: i32,
+    targets: &[String],
+) -> Result<()> {
+    conn.execute(
+        "UPDATE builds SET log_targets = $2 WHERE id = $1",
+        &[&build_id, &targets],
+    )?;
+    Ok(())
+}
+
+/// Records the resources the build used for each target.
+pub(crate) fn add_build_resource_usage(
+    conn: &mut Client,
+    build_id: i32,
+    usage: &[ResourceUsage],
+) -> Result<()> {
+    for usage in usage {
+        conn.execute(
+            "INSERT INTO build_resource_usage (
+                build_id, target, wall_time_ms, peak_memory_bytes, output_size_bytes
+            )
+            VALUES ($1, $2, $3, $4, $5)",
+            &[
+                &build_id,
+                &usage.target,
+                &(usage.wall_time.as_millis() as i64),
+                &usage.peak_memory.map(|bytes| bytes as i64),
+                &(usage.output_size as i64),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
 fn initialize_package_in_database(conn: &mut Client, pkg: &MetadataPackage) -> Result<i32> {
     let mut rows = conn.query("SELECT id FROM crates WHERE name = $1", &[&pkg.name])?;
     // insert crate into database if it is not exists
@@ -229,6 +431,113 @@ fn convert_dependencies(pkg: &MetadataPackage) -> Vec<(String, String, String)>
         .collect()
 }
 
+/// Replaces the rows of a release in `release_dependencies`, used to find the dependents of a crate.
+fn add_dependencies_into_database(
+    conn: &mut impl GenericClient,
+    dependencies: &[(String, String, String)],
+    release_id: i32,
+) -> Result<()> {
+    conn.execute(
+        "DELETE FROM release_dependencies WHERE release_id = $1",
+        &[&release_id],
+    )?;
+    conn.execute(
+        "INSERT INTO release_dependencies (release_id, dependency, req, kind, latest)
+         SELECT
+            $1, dependency, req, kind,
+            EXISTS(SELECT 1 FROM crates WHERE latest_version_id = $1)
+         FROM UNNEST($2::TEXT[], $3::TEXT[], $4::TEXT[]) AS deps(dependency, req, kind)",
+        &[
+            &release_id,
+            &dependencies.iter().map(|dep| &dep.0).collect::<Vec<_>>(),
+            &dependencies.iter().map(|dep| &dep.1).collect::<Vec<_>>(),
+            &dependencies.iter().map(|dep| &dep.2).collect::<Vec<_>>(),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Marks the rows in `release_dependencies` of the latest release of a crate, the only ones
+/// used to find dependents, after `crates.latest_version_id` changed.
+pub(crate) fn update_latest_dependencies(
+    conn: &mut impl GenericClient,
+    crate_id: i32,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE release_dependencies
+         SET latest = NOT latest
+         FROM releases, crates
+         WHERE
+            releases.id = release_dependencies.release_id AND
+            crates.id = releases.crate_id AND
+            crates.id = $1 AND
+            release_dependencies.latest <>
+                COALESCE(release_dependencies.release_id = crates.latest_version_id, FALSE)",
+        &[&crate_id],
+    )?;
+    Ok(())
+}
+
+/// Parses `releases.dependencies`, a list of `[name, requirement, kind]`.
+///
+/// Releases added by older versions of docs.rs don't have the kind of the dependencies.
+fn parse_dependencies(dependencies: &Value) -> Vec<(String, String, String)> {
+    dependencies
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|dep| {
+            let dep = dep.as_array()?;
+            Some((
+                dep.first()?.as_str()?.to_owned(),
+                dep.get(1)?.as_str()?.to_owned(),
+                dep.get(2)
+                    .and_then(Value::as_str)
+                    .unwrap_or("normal")
+                    .to_owned(),
+            ))
+        })
+        .collect()
+}
+
+/// Populates `release_dependencies` from the dependencies stored for every release.
+///
+/// Returns the number of releases that were processed.
+pub fn backfill_dependencies(conn: &mut Client) -> Result<usize> {
+    const BATCH_SIZE: i64 = 1000;
+
+    let mut last_id = 0;
+    let mut count = 0;
+    loop {
+        let rows = conn.query(
+            "SELECT id, dependencies FROM releases WHERE id > $1 ORDER BY id LIMIT $2",
+            &[&last_id, &BATCH_SIZE],
+        )?;
+        let Some(last) = rows.last() else {
+            break;
+        };
+        last_id = last.get("id");
+
+        let mut transaction = conn.transaction()?;
+        for row in &rows {
+            let dependencies: Option<Value> = row.get("dependencies");
+            add_dependencies_into_database(
+                &mut transaction,
+                &dependencies
+                    .map(|deps| parse_dependencies(&deps))
+                    .unwrap_or_default(),
+                row.get("id"),
+            )?;
+        }
+        transaction.commit()?;
+
+        count += rows.len();
+        info!("backfilled the dependencies of {} releases", count);
+    }
+
+    Ok(count)
+}
+
 /// Reads features and converts them to Vec<Feature> with default being first
 fn get_features(pkg: &MetadataPackage) -> Vec<Feature> {
     let mut features = Vec::with_capacity(pkg.features.len());
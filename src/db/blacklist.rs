@@ -1,5 +1,5 @@
 use crate::error::Result;
-use postgres::Client;
+use postgres::{Client, GenericClient};
 
 #[derive(Debug, thiserror::Error)]
 enum BlacklistError {
@@ -11,7 +11,7 @@ enum BlacklistError {
 }
 
 /// Returns whether the given name is blacklisted.
-pub fn is_blacklisted(conn: &mut Client, name: &str) -> Result<bool> {
+pub fn is_blacklisted(conn: &mut impl GenericClient, name: &str) -> Result<bool> {
     let rows = conn.query(
         "SELECT COUNT(*) FROM blacklisted_crates WHERE crate_name = $1;",
         &[&name],
@@ -32,7 +32,7 @@ pub fn list_crates(conn: &mut Client) -> Result<Vec<String>> {
 }
 
 /// Adds a crate to the blacklist.
-pub fn add_crate(conn: &mut Client, name: &str) -> Result<()> {
+pub fn add_crate(conn: &mut impl GenericClient, name: &str) -> Result<()> {
     if is_blacklisted(conn, name)? {
         return Err(BlacklistError::CrateAlreadyOnBlacklist(name.into()).into());
     }
@@ -46,7 +46,7 @@ pub fn add_crate(conn: &mut Client, name: &str) -> Result<()> {
 }
 
 /// Removes a crate from the blacklist.
-pub fn remove_crate(conn: &mut Client, name: &str) -> Result<()> {
+pub fn remove_crate(conn: &mut impl GenericClient, name: &str) -> Result<()> {
     if !is_blacklisted(conn, name)? {
         return Err(BlacklistError::CrateNotOnBlacklist(name.into()).into());
     }
@@ -69,9 +69,9 @@ mod tests {
             let db = env.db();
 
             // crates are added out of order to verify sorting
-            add_crate(&mut db.conn(), "crate A")?;
-            add_crate(&mut db.conn(), "crate C")?;
-            add_crate(&mut db.conn(), "crate B")?;
+            add_crate(&mut *db.conn(), "crate A")?;
+            add_crate(&mut *db.conn(), "crate C")?;
+            add_crate(&mut *db.conn(), "crate B")?;
 
             assert!(list_crates(&mut db.conn())? == vec!["crate A", "crate B", "crate C"]);
             Ok(())
@@ -83,11 +83,11 @@ mod tests {
         crate::test::wrapper(|env| {
             let db = env.db();
 
-            assert!(!is_blacklisted(&mut db.conn(), "crate foo")?);
-            add_crate(&mut db.conn(), "crate foo")?;
-            assert!(is_blacklisted(&mut db.conn(), "crate foo")?);
-            remove_crate(&mut db.conn(), "crate foo")?;
-            assert!(!is_blacklisted(&mut db.conn(), "crate foo")?);
+            assert!(!is_blacklisted(&mut *db.conn(), "crate foo")?);
+            add_crate(&mut *db.conn(), "crate foo")?;
+            assert!(is_blacklisted(&mut *db.conn(), "crate foo")?);
+            remove_crate(&mut *db.conn(), "crate foo")?;
+            assert!(!is_blacklisted(&mut *db.conn(), "crate foo")?);
             Ok(())
         });
     }
@@ -97,9 +97,9 @@ mod tests {
         crate::test::wrapper(|env| {
             let db = env.db();
 
-            add_crate(&mut db.conn(), "crate foo")?;
-            assert!(add_crate(&mut db.conn(), "crate foo").is_err());
-            add_crate(&mut db.conn(), "crate bar")?;
+            add_crate(&mut *db.conn(), "crate foo")?;
+            assert!(add_crate(&mut *db.conn(), "crate foo").is_err());
+            add_crate(&mut *db.conn(), "crate bar")?;
 
             Ok(())
         });
@@ -110,7 +110,7 @@ mod tests {
         crate::test::wrapper(|env| {
             let db = env.db();
 
-            assert!(remove_crate(&mut db.conn(), "crate foo").is_err());
+            assert!(remove_crate(&mut *db.conn(), "crate foo").is_err());
 
             Ok(())
         });
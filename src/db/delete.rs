@@ -1,14 +1,15 @@
+use crate::db::update_latest_dependencies;
 use crate::error::Result;
 use crate::storage::{rustdoc_archive_path, source_archive_path, Storage};
 use crate::Config;
 use anyhow::Context as _;
 use fn_error_context::context;
-use postgres::Client;
+use postgres::GenericClient;
 use std::fs;
 
 /// List of directories in docs.rs's underlying storage (either the database or S3) containing a
 /// subdirectory named after the crate. Those subdirectories will be deleted.
-static LIBRARY_STORAGE_PATHS_TO_DELETE: &[&str] = &["rustdoc", "sources"];
+static LIBRARY_STORAGE_PATHS_TO_DELETE: &[&str] = &["rustdoc", "rustdoc-json", "sources"];
 static BINARY_STORAGE_PATHS_TO_DELETE: &[&str] = &["sources"];
 
 #[derive(Debug, thiserror::Error)]
@@ -19,13 +20,23 @@ enum CrateDeletionError {
 
 #[context("error trying to delete crate {name} from database")]
 pub fn delete_crate(
-    conn: &mut Client,
+    conn: &mut impl GenericClient,
     storage: &Storage,
     config: &Config,
     name: &str,
 ) -> Result<()> {
-    let crate_id = get_id(conn, name)?;
-    let is_library = delete_crate_from_database(conn, name, crate_id)?;
+    let is_library = delete_crate_from_database(conn, name)?;
+    delete_crate_from_storage(storage, config, name, is_library)
+}
+
+/// Deletes the files of a crate deleted with [`delete_crate_from_database`], which must be
+/// committed already when deleting from the database storage.
+pub(crate) fn delete_crate_from_storage(
+    storage: &Storage,
+    config: &Config,
+    name: &str,
+    is_library: bool,
+) -> Result<()> {
     // #899
     let paths = if is_library {
         LIBRARY_STORAGE_PATHS_TO_DELETE
@@ -56,13 +67,25 @@ pub fn delete_crate(
 
 #[context("error trying to delete release {name}-{version} from database")]
 pub fn delete_version(
-    conn: &mut Client,
+    conn: &mut impl GenericClient,
     storage: &Storage,
     config: &Config,
     name: &str,
     version: &str,
 ) -> Result<()> {
     let is_library = delete_version_from_database(conn, name, version)?;
+    delete_version_from_storage(storage, config, name, version, is_library)
+}
+
+/// Deletes the files of a release deleted with [`delete_version_from_database`], which must be
+/// committed already when deleting from the database storage.
+pub(crate) fn delete_version_from_storage(
+    storage: &Storage,
+    config: &Config,
+    name: &str,
+    version: &str,
+    is_library: bool,
+) -> Result<()> {
     let paths = if is_library {
         LIBRARY_STORAGE_PATHS_TO_DELETE
     } else {
@@ -95,7 +118,7 @@ pub fn delete_version(
     Ok(())
 }
 
-fn get_id(conn: &mut Client, name: &str) -> Result<i32> {
+fn get_id(conn: &mut impl GenericClient, name: &str) -> Result<i32> {
     let crate_id_res = conn.query("SELECT id FROM crates WHERE name = $1", &[&name])?;
     if let Some(row) = crate_id_res.into_iter().next() {
         Ok(row.get("id"))
@@ -111,12 +134,33 @@ const METADATA: &[(&str, &str)] = &[
     ("builds", "rid"),
     ("compression_rels", "release"),
     ("doc_coverage", "release_id"),
+    ("doc_coverage_files", "release_id"),
+    ("doc_coverage_items", "release_id"),
+    ("release_items", "release_id"),
+    ("release_public_items", "release_id"),
+    ("release_dependencies", "release_id"),
+    ("release_locked_dependencies", "release_id"),
+    ("rebuild_campaign_releases", "release_id"),
 ];
 
 /// Returns whether this release was a library
-fn delete_version_from_database(conn: &mut Client, name: &str, version: &str) -> Result<bool> {
+/// Returns whether the release was a library
+pub(crate) fn delete_version_from_database(
+    conn: &mut impl GenericClient,
+    name: &str,
+    version: &str,
+) -> Result<bool> {
     let crate_id = get_id(conn, name)?;
     let mut transaction = conn.transaction()?;
+    transaction.execute(
+        "DELETE FROM build_resource_usage WHERE build_id IN (
+            SELECT builds.id
+            FROM builds
+            INNER JOIN releases ON releases.id = builds.rid
+            WHERE releases.crate_id = $1 AND releases.version = $2
+        )",
+        &[&crate_id, &version],
+    )?;
     for &(table, column) in METADATA {
         transaction.execute(
             format!("DELETE FROM {table} WHERE {column} IN (SELECT id FROM releases WHERE crate_id = $1 AND version = $2)").as_str(),
@@ -130,13 +174,19 @@ fn delete_version_from_database(conn: &mut Client, name: &str, version: &str) ->
         )?
         .get("is_library");
     transaction.execute(
-        "UPDATE crates SET latest_version_id = (
-            SELECT id FROM releases WHERE release_time = (
-                SELECT MAX(release_time) FROM releases WHERE crate_id = $1
+        "UPDATE crates SET
+            latest_version_id = (
+                SELECT id FROM releases WHERE release_time = (
+                    SELECT MAX(release_time) FROM releases WHERE crate_id = $1
+                )
+            ),
+            downloads_total = (
+                SELECT COALESCE(SUM(downloads), 0) FROM releases WHERE crate_id = $1
             )
-        ) WHERE id = $1",
+        WHERE id = $1",
         &[&crate_id],
     )?;
+    update_latest_dependencies(&mut transaction, crate_id)?;
 
     let paths = if is_library {
         LIBRARY_STORAGE_PATHS_TO_DELETE
@@ -155,13 +205,26 @@ fn delete_version_from_database(conn: &mut Client, name: &str, version: &str) ->
 }
 
 /// Returns whether any release in this crate was a library
-fn delete_crate_from_database(conn: &mut Client, name: &str, crate_id: i32) -> Result<bool> {
+pub(crate) fn delete_crate_from_database(
+    conn: &mut impl GenericClient,
+    name: &str,
+) -> Result<bool> {
+    let crate_id = get_id(conn, name)?;
     let mut transaction = conn.transaction()?;
 
     transaction.execute(
         "DELETE FROM sandbox_overrides WHERE crate_name = $1",
         &[&name],
     )?;
+    transaction.execute(
+        "DELETE FROM build_resource_usage WHERE build_id IN (
+            SELECT builds.id
+            FROM builds
+            INNER JOIN releases ON releases.id = builds.rid
+            WHERE releases.crate_id = $1
+        )",
+        &[&crate_id],
+    )?;
     for &(table, column) in METADATA {
         transaction.execute(
             format!(
@@ -254,7 +317,7 @@ mod tests {
                 )?);
             }
 
-            delete_crate(&mut db.conn(), &env.storage(), &env.config(), "package-1")?;
+            delete_crate(&mut *db.conn(), &env.storage(), &env.config(), "package-1")?;
 
             assert!(!crate_exists(&mut db.conn(), "package-1")?);
             assert!(crate_exists(&mut db.conn(), "package-2")?);
@@ -366,7 +429,7 @@ mod tests {
                 vec!["Peter Rabbit".to_string()]
             );
 
-            delete_version(&mut db.conn(), &env.storage(), &env.config(), "a", "1.0.0")?;
+            delete_version(&mut *db.conn(), &env.storage(), &env.config(), "a", "1.0.0")?;
             assert!(!release_exists(&mut db.conn(), v1)?);
             if archive_storage {
                 // for archive storage the archive and index files
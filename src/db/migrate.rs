@@ -896,6 +896,314 @@ pub fn migrate(version: Option<Version>, conn: &mut Client) -> crate::error::Res
             "ALTER TYPE feature DROP ATTRIBUTE optional_dependency;",
             "ALTER TYPE feature ADD ATTRIBUTE optional_dependency BOOL;"
         ),
+        sql_migration!(
+            context, 39, "add targets with rustdoc JSON output to releases",
+            "ALTER TABLE releases ADD COLUMN json_targets JSON NOT NULL DEFAULT '[]';",
+            "ALTER TABLE releases DROP COLUMN json_targets;",
+        ),
+        sql_migration!(
+            context, 40, "add full-text search vector to releases",
+            "ALTER TABLE releases ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+                setweight(to_tsvector('english', COALESCE(target_name, '')), 'A') ||
+                setweight(to_tsvector('english', COALESCE(description, '')), 'B') ||
+                setweight(to_tsvector('english', COALESCE(keywords::text, '')), 'B') ||
+                setweight(to_tsvector('english', COALESCE(readme, '')), 'D')
+             ) STORED;
+             CREATE INDEX releases_search_vector_idx ON releases USING GIN (search_vector);",
+            "DROP INDEX releases_search_vector_idx;
+             ALTER TABLE releases DROP COLUMN search_vector;",
+        ),
+        sql_migration!(
+            context, 41, "add items from the rustdoc search index",
+            "CREATE TABLE release_items (
+                id BIGSERIAL PRIMARY KEY,
+                release_id INT NOT NULL REFERENCES releases(id),
+                name TEXT NOT NULL,
+                path TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                link TEXT NOT NULL
+             );
+             CREATE INDEX release_items_release_id_idx ON release_items (release_id);
+             CREATE INDEX release_items_name_idx ON release_items (LOWER(name));",
+            "DROP TABLE release_items;",
+        ),
+        sql_migration!(
+            context, 42, "add per-target build logs and the logs of running builds",
+            "ALTER TABLE builds ADD COLUMN log_targets TEXT[] NOT NULL DEFAULT '{}';
+             CREATE TABLE running_builds (
+                name TEXT NOT NULL,
+                version TEXT NOT NULL,
+                target TEXT,
+                build_server TEXT NOT NULL,
+                log TEXT NOT NULL DEFAULT '',
+                started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                PRIMARY KEY (name, version)
+             );",
+            "DROP TABLE running_builds;
+             ALTER TABLE builds DROP COLUMN log_targets;",
+        ),
+        sql_migration!(
+            context, 43, "add audit log of the admin interface",
+            "CREATE TABLE admin_audit_log (
+                id SERIAL PRIMARY KEY,
+                actor TEXT NOT NULL,
+                action TEXT NOT NULL,
+                details TEXT NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+             );
+             CREATE INDEX admin_audit_log_created_at_idx ON admin_audit_log (created_at DESC);",
+            "DROP TABLE admin_audit_log;",
+        ),
+        sql_migration!(
+            context, 44, "add documentation coverage per source file",
+            "CREATE TABLE doc_coverage_files (
+                release_id INT NOT NULL REFERENCES releases(id),
+                path TEXT NOT NULL,
+                total_items INT NOT NULL,
+                documented_items INT NOT NULL,
+                total_items_needing_examples INT NOT NULL,
+                items_with_examples INT NOT NULL,
+                PRIMARY KEY (release_id, path)
+             );",
+            "DROP TABLE doc_coverage_files;",
+        ),
+        sql_migration!(
+            context, 45, "add reverse dependencies",
+            "CREATE TABLE release_dependencies (
+                release_id INT NOT NULL REFERENCES releases(id),
+                dependency TEXT NOT NULL,
+                req TEXT NOT NULL,
+                kind TEXT NOT NULL
+             );
+             CREATE INDEX release_dependencies_release_id_idx ON release_dependencies (release_id);
+             CREATE INDEX release_dependencies_dependency_idx ON release_dependencies (dependency);",
+            "DROP TABLE release_dependencies;",
+        ),
+        sql_migration!(
+            context, 46, "add the dependency versions resolved in the build",
+            "CREATE TABLE release_locked_dependencies (
+                release_id INT NOT NULL REFERENCES releases(id),
+                name TEXT NOT NULL,
+                version TEXT NOT NULL,
+                PRIMARY KEY (release_id, name, version)
+             );",
+            "DROP TABLE release_locked_dependencies;",
+        ),
+        sql_migration!(
+            context, 47, "add leases of queued builds taken by build workers",
+            "ALTER TABLE queue
+                ADD COLUMN leased_by TEXT,
+                ADD COLUMN lease_expires_at TIMESTAMPTZ;",
+            "ALTER TABLE queue
+                DROP COLUMN leased_by,
+                DROP COLUMN lease_expires_at;",
+        ),
+        sql_migration!(
+            context, 48, "add the category of failed builds",
+            "ALTER TABLE builds ADD COLUMN failure_category TEXT;
+             CREATE INDEX builds_failure_category_idx ON builds (failure_category);",
+            "ALTER TABLE builds DROP COLUMN failure_category;",
+        ),
+        sql_migration!(
+            context, 49, "add the resources used by the builds of each target",
+            "CREATE TABLE build_resource_usage (
+                build_id INT NOT NULL,
+                target TEXT NOT NULL,
+                wall_time_ms BIGINT NOT NULL,
+                peak_memory_bytes BIGINT,
+                output_size_bytes BIGINT NOT NULL,
+                PRIMARY KEY (build_id, target)
+             );",
+            "DROP TABLE build_resource_usage;",
+        ),
+        sql_migration!(
+            context, 50, "record whether sandbox overrides were set automatically",
+            "ALTER TABLE sandbox_overrides ADD COLUMN automatic BOOLEAN NOT NULL DEFAULT FALSE;",
+            "ALTER TABLE sandbox_overrides DROP COLUMN automatic;",
+        ),
+        sql_migration!(
+            context, 51, "add rebuild campaigns",
+            "CREATE TABLE rebuild_campaigns (
+                id SERIAL PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                selector JSON NOT NULL,
+                priority INT NOT NULL,
+                batch_size INT NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+                finished_at TIMESTAMPTZ
+             );
+             CREATE TABLE rebuild_campaign_releases (
+                campaign_id INT NOT NULL REFERENCES rebuild_campaigns(id),
+                release_id INT NOT NULL REFERENCES releases(id),
+                successful_before BOOLEAN NOT NULL,
+                queued_at TIMESTAMPTZ,
+                PRIMARY KEY (campaign_id, release_id)
+             );
+             CREATE INDEX rebuild_campaign_releases_release_id_idx
+                ON rebuild_campaign_releases (release_id);",
+            "DROP TABLE rebuild_campaign_releases;
+             DROP TABLE rebuild_campaigns;",
+        ),
+        sql_migration!(
+            context, 52, "record the builds started for each crate owner",
+            "CREATE TABLE queue_owner_builds (
+                owner TEXT NOT NULL,
+                started_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+             );
+             CREATE INDEX queue_owner_builds_owner_started_at_idx
+                ON queue_owner_builds (owner, started_at);",
+            "DROP TABLE queue_owner_builds;",
+        ),
+        sql_migration!(
+            context, 53, "record which newer version supersedes a queued build",
+            "ALTER TABLE queue ADD COLUMN superseded_by TEXT;",
+            "ALTER TABLE queue DROP COLUMN superseded_by;",
+        ),
+        sql_migration!(
+            context, 54, "index crate names for search and keep the total downloads of crates",
+            // Only the start of the readme is indexed, keeping the vectors far below the 1 MB limit
+            // of a tsvector and the index small.
+            "DROP INDEX releases_search_vector_idx;
+             ALTER TABLE releases DROP COLUMN search_vector;
+             ALTER TABLE releases ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+                setweight(to_tsvector('english', COALESCE(description, '')), 'B') ||
+                setweight(to_tsvector('english', COALESCE(keywords::text, '')), 'B') ||
+                setweight(to_tsvector('english', LEFT(COALESCE(readme, ''), 20000)), 'D')
+             ) STORED;
+             CREATE INDEX releases_search_vector_idx ON releases USING GIN (search_vector);
+             CREATE INDEX crates_name_search_idx ON crates USING GIN (to_tsvector('english', name));
+             ALTER TABLE crates ALTER COLUMN downloads_total TYPE BIGINT;
+             UPDATE crates SET downloads_total = (
+                SELECT COALESCE(SUM(releases.downloads), 0)
+                FROM releases
+                WHERE releases.crate_id = crates.id
+             );",
+            "DROP INDEX crates_name_search_idx;
+             ALTER TABLE crates ALTER COLUMN downloads_total TYPE INT;
+             DROP INDEX releases_search_vector_idx;
+             ALTER TABLE releases DROP COLUMN search_vector;
+             ALTER TABLE releases ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+                setweight(to_tsvector('english', COALESCE(target_name, '')), 'A') ||
+                setweight(to_tsvector('english', COALESCE(description, '')), 'B') ||
+                setweight(to_tsvector('english', COALESCE(keywords::text, '')), 'B') ||
+                setweight(to_tsvector('english', COALESCE(readme, '')), 'D')
+             ) STORED;
+             CREATE INDEX releases_search_vector_idx ON releases USING GIN (search_vector);",
+        ),
+        sql_migration!(
+            context, 55, "store the undocumented items of releases",
+            "CREATE TABLE doc_coverage_items (
+                release_id INT NOT NULL REFERENCES releases(id),
+                path TEXT NOT NULL,
+                line INT NOT NULL,
+                name TEXT NOT NULL,
+                kind TEXT NOT NULL
+             );
+             CREATE INDEX doc_coverage_items_release_id_idx ON doc_coverage_items (release_id);",
+            "DROP TABLE doc_coverage_items;",
+        ),
+        sql_migration!(
+            context, 56, "store the public items of releases with their members and signatures",
+            "CREATE TABLE release_public_items (
+                release_id INT NOT NULL REFERENCES releases(id),
+                path TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                link TEXT NOT NULL,
+                signature TEXT NOT NULL
+             );
+             CREATE INDEX release_public_items_release_id_idx ON release_public_items (release_id);",
+            "DROP TABLE release_public_items;",
+        ),
+        sql_migration!(
+            context, 57, "mark the dependencies of the latest release of every crate",
+            "ALTER TABLE release_dependencies ADD COLUMN latest BOOLEAN NOT NULL DEFAULT FALSE;
+             UPDATE release_dependencies
+             SET latest = TRUE
+             FROM crates
+             WHERE crates.latest_version_id = release_dependencies.release_id;
+             CREATE INDEX release_dependencies_latest_idx
+                ON release_dependencies (dependency, req)
+                WHERE latest;",
+            "DROP INDEX release_dependencies_latest_idx;
+             ALTER TABLE release_dependencies DROP COLUMN latest;",
+        ),
+        sql_migration!(
+            context, 58, "count the progress of rebuild campaigns as their builds finish",
+            "ALTER TABLE rebuild_campaign_releases ADD COLUMN successful_after BOOLEAN;
+             ALTER TABLE rebuild_campaigns
+                ADD COLUMN release_count INT NOT NULL DEFAULT 0,
+                ADD COLUMN queued_count INT NOT NULL DEFAULT 0,
+                ADD COLUMN succeeded_count INT NOT NULL DEFAULT 0,
+                ADD COLUMN failed_count INT NOT NULL DEFAULT 0,
+                ADD COLUMN fixed_count INT NOT NULL DEFAULT 0,
+                ADD COLUMN broken_count INT NOT NULL DEFAULT 0;
+             UPDATE rebuild_campaign_releases AS campaign_releases
+             SET successful_after = COALESCE((
+                SELECT builds.build_status
+                FROM builds
+                WHERE
+                    builds.rid = campaign_releases.release_id AND
+                    builds.build_time >= campaign_releases.queued_at
+                ORDER BY builds.id DESC
+                LIMIT 1
+             ), FALSE)
+             FROM releases, crates, rebuild_campaigns
+             WHERE
+                releases.id = campaign_releases.release_id AND
+                crates.id = releases.crate_id AND
+                rebuild_campaigns.id = campaign_releases.campaign_id AND
+                campaign_releases.queued_at IS NOT NULL AND (
+                    rebuild_campaigns.finished_at IS NOT NULL OR
+                    NOT EXISTS (
+                        SELECT 1
+                        FROM queue
+                        WHERE queue.name = crates.name AND queue.version = releases.version
+                    )
+                );
+             UPDATE rebuild_campaigns
+             SET
+                release_count = counts.release_count,
+                queued_count = counts.queued_count,
+                succeeded_count = counts.succeeded_count,
+                failed_count = counts.failed_count,
+                fixed_count = counts.fixed_count,
+                broken_count = counts.broken_count
+             FROM (
+                SELECT
+                    campaign_id,
+                    COUNT(*) AS release_count,
+                    COUNT(queued_at) AS queued_count,
+                    COUNT(*) FILTER (WHERE successful_after) AS succeeded_count,
+                    COUNT(*) FILTER (WHERE NOT successful_after) AS failed_count,
+                    COUNT(*) FILTER (
+                        WHERE successful_after AND NOT successful_before
+                    ) AS fixed_count,
+                    COUNT(*) FILTER (
+                        WHERE NOT successful_after AND successful_before
+                    ) AS broken_count
+                FROM rebuild_campaign_releases
+                GROUP BY campaign_id
+             ) AS counts
+             WHERE counts.campaign_id = rebuild_campaigns.id;
+             CREATE INDEX rebuild_campaign_releases_unfinished_idx
+                ON rebuild_campaign_releases (campaign_id)
+                WHERE queued_at IS NOT NULL AND successful_after IS NULL;",
+            "DROP INDEX rebuild_campaign_releases_unfinished_idx;
+             ALTER TABLE rebuild_campaigns
+                DROP COLUMN release_count,
+                DROP COLUMN queued_count,
+                DROP COLUMN succeeded_count,
+                DROP COLUMN failed_count,
+                DROP COLUMN fixed_count,
+                DROP COLUMN broken_count;
+             ALTER TABLE rebuild_campaign_releases DROP COLUMN successful_after;",
+        ),
+        sql_migration!(
+            context, 59, "index the queue in the order builds of an owner take turns",
+            "CREATE INDEX queue_priority_attempt_id_idx ON queue (priority, attempt, id);",
+            "DROP INDEX queue_priority_attempt_id_idx;",
+        ),
     ];
 
     for migration in migrations {
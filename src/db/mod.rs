@@ -1,10 +1,12 @@
 //! Database operations
 
 pub(crate) use self::add_package::{
-    add_build_into_database, add_doc_coverage, add_package_into_database,
+    add_build_into_database, add_build_log_targets, add_build_resource_usage, add_doc_coverage,
+    add_locked_dependencies, add_package_into_database, add_public_items, add_search_items,
+    update_latest_dependencies, PackageDocs,
 };
 pub use self::{
-    add_package::update_crate_data_in_database,
+    add_package::{backfill_dependencies, update_crate_data_in_database},
     delete::{delete_crate, delete_version},
     file::{add_path_into_database, add_path_into_remote_archive},
     migrate::migrate,
@@ -1,5 +1,5 @@
 use crate::error::Result;
-use postgres::Client;
+use postgres::{Client, GenericClient};
 use std::time::Duration;
 
 #[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
@@ -7,6 +7,9 @@ pub struct Overrides {
     pub memory: Option<usize>,
     pub targets: Option<usize>,
     pub timeout: Option<Duration>,
+    /// Whether the overrides were set by the build queue after a build hit its limits, instead of
+    /// by an admin.
+    pub automatic: bool,
 }
 
 impl Overrides {
@@ -18,7 +21,7 @@ impl Overrides {
             .collect())
     }
 
-    pub fn for_crate(conn: &mut Client, krate: &str) -> Result<Option<Self>> {
+    pub fn for_crate(conn: &mut impl GenericClient, krate: &str) -> Result<Option<Self>> {
         Ok(conn
             .query_opt(
                 "SELECT * FROM sandbox_overrides WHERE crate_name = $1",
@@ -36,10 +39,11 @@ impl Overrides {
             timeout: row
                 .get::<_, Option<i32>>("timeout_seconds")
                 .map(|i| Duration::from_secs(i as u64)),
+            automatic: row.get("automatic"),
         }
     }
 
-    pub fn save(conn: &mut Client, krate: &str, overrides: Self) -> Result<()> {
+    pub fn save(conn: &mut impl GenericClient, krate: &str, overrides: Self) -> Result<()> {
         if overrides.timeout.is_some() && overrides.targets.is_none() {
             tracing::warn!("setting `Overrides::timeout` implies a default `Overrides::targets = 1`, prefer setting this explicitly");
         }
@@ -54,26 +58,28 @@ impl Overrides {
         conn.execute(
             "
                 INSERT INTO sandbox_overrides (
-                    crate_name, max_memory_bytes, max_targets, timeout_seconds
+                    crate_name, max_memory_bytes, max_targets, timeout_seconds, automatic
                 )
-                VALUES ($1, $2, $3, $4)
+                VALUES ($1, $2, $3, $4, $5)
                 ON CONFLICT (crate_name) DO UPDATE
                     SET
                         max_memory_bytes = $2,
                         max_targets = $3,
-                        timeout_seconds = $4
+                        timeout_seconds = $4,
+                        automatic = $5
                 ",
             &[
                 &krate,
                 &overrides.memory.map(|i| i as i64),
                 &overrides.targets.map(|i| i as i32),
                 &overrides.timeout.map(|d| d.as_secs() as i32),
+                &overrides.automatic,
             ],
         )?;
         Ok(())
     }
 
-    pub fn remove(conn: &mut Client, krate: &str) -> Result<()> {
+    pub fn remove(conn: &mut impl GenericClient, krate: &str) -> Result<()> {
         conn.execute(
             "DELETE FROM sandbox_overrides WHERE crate_name = $1",
             &[&krate],
@@ -95,7 +101,7 @@ mod test {
             let krate = "hexponent";
 
             // no overrides
-            let actual = Overrides::for_crate(&mut db.conn(), krate)?;
+            let actual = Overrides::for_crate(&mut *db.conn(), krate)?;
             assert_eq!(actual, None);
 
             // add partial overrides
@@ -103,8 +109,8 @@ mod test {
                 targets: Some(1),
                 ..Overrides::default()
             };
-            Overrides::save(&mut db.conn(), krate, expected)?;
-            let actual = Overrides::for_crate(&mut db.conn(), krate)?;
+            Overrides::save(&mut *db.conn(), krate, expected)?;
+            let actual = Overrides::for_crate(&mut *db.conn(), krate)?;
             assert_eq!(actual, Some(expected));
 
             // overwrite with full overrides
@@ -112,9 +118,10 @@ mod test {
                 memory: Some(100_000),
                 targets: Some(1),
                 timeout: Some(Duration::from_secs(300)),
+                automatic: true,
             };
-            Overrides::save(&mut db.conn(), krate, expected)?;
-            let actual = Overrides::for_crate(&mut db.conn(), krate)?;
+            Overrides::save(&mut *db.conn(), krate, expected)?;
+            let actual = Overrides::for_crate(&mut *db.conn(), krate)?;
             assert_eq!(actual, Some(expected));
 
             // overwrite with partial overrides
@@ -122,13 +129,13 @@ mod test {
                 memory: Some(1),
                 ..Overrides::default()
             };
-            Overrides::save(&mut db.conn(), krate, expected)?;
-            let actual = Overrides::for_crate(&mut db.conn(), krate)?;
+            Overrides::save(&mut *db.conn(), krate, expected)?;
+            let actual = Overrides::for_crate(&mut *db.conn(), krate)?;
             assert_eq!(actual, Some(expected));
 
             // remove overrides
-            Overrides::remove(&mut db.conn(), krate)?;
-            let actual = Overrides::for_crate(&mut db.conn(), krate)?;
+            Overrides::remove(&mut *db.conn(), krate)?;
+            let actual = Overrides::for_crate(&mut *db.conn(), krate)?;
             assert_eq!(actual, None);
 
             Ok(())
@@ -0,0 +1,223 @@
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexSet};
+use rustwide::cmd::CommandError;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter, EnumString};
+
+/// Why a build failed, as far as we can tell from the sandbox and the build log.
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, EnumString, Display, EnumIter,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub(crate) enum FailureCategory {
+    /// The sandbox ran out of memory.
+    Oom,
+    /// The build took longer than the timeout, or didn't print anything for too long.
+    Timeout,
+    /// Something tried to access the network, which is disabled in the sandbox.
+    NetworkDenied,
+    /// A build script couldn't find a system library or its headers.
+    MissingSystemLibrary,
+    /// The crate itself doesn't compile.
+    CompileErrorInCrate,
+    /// One of the dependencies of the crate doesn't compile.
+    CompileErrorInDependency,
+    /// The compiler panicked while documenting the crate.
+    RustdocIce,
+    /// The crate or the toolchain doesn't support the target.
+    UnsupportedTarget,
+    /// None of the above.
+    Other,
+}
+
+impl FailureCategory {
+    /// The name of the category shown on the website.
+    pub(crate) fn description(&self) -> &'static str {
+        match self {
+            Self::Oom => "Out of memory",
+            Self::Timeout => "Timeout",
+            Self::NetworkDenied => "Network access denied",
+            Self::MissingSystemLibrary => "Missing system library",
+            Self::CompileErrorInCrate => "Compile error in the crate",
+            Self::CompileErrorInDependency => "Compile error in a dependency",
+            Self::RustdocIce => "Rustdoc internal compiler error",
+            Self::UnsupportedTarget => "Unsupported target",
+            Self::Other => "Other",
+        }
+    }
+}
+
+static RUSTDOC_ICE: Lazy<RegexSet> = Lazy::new(|| {
+    RegexSet::new([
+        r"error: internal compiler error",
+        r"the compiler unexpectedly panicked",
+        r"thread 'rustc' panicked",
+    ])
+    .unwrap()
+});
+
+static OOM: Lazy<RegexSet> = Lazy::new(|| {
+    RegexSet::new([
+        r"memory allocation of \d+ bytes failed",
+        r"signal: 9, SIGKILL",
+    ])
+    .unwrap()
+});
+
+static UNSUPPORTED_TARGET: Lazy<RegexSet> = Lazy::new(|| {
+    RegexSet::new([
+        r"can't find crate for `(core|std)`",
+        r"the `[^`]+` target may not be installed",
+        r"could not find specification for target",
+    ])
+    .unwrap()
+});
+
+static MISSING_SYSTEM_LIBRARY: Lazy<RegexSet> = Lazy::new(|| {
+    RegexSet::new([
+        r"was not found in the pkg-config search path",
+        r"Could not run `.*pkg-config",
+        r"(?i)could not find system library",
+        r"could not find native static library",
+        r"cannot find -l\S+",
+        r"unable to find library -l\S+",
+        r"fatal error: \S+\.h: No such file or directory",
+    ])
+    .unwrap()
+});
+
+static NETWORK_DENIED: Lazy<RegexSet> = Lazy::new(|| {
+    RegexSet::new([
+        r"(?i)could not resolve host",
+        r"failed to lookup address information",
+        r"Temporary failure in name resolution",
+        r"Network is unreachable",
+        r"dns error",
+    ])
+    .unwrap()
+});
+
+static COMPILE_ERROR: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"error: could not (?:compile|document) `([^`]+)`").unwrap());
+
+/// Classifies a failed build of `crate_name`, from the error of the build command and its log.
+pub(crate) fn classify_failure(
+    crate_name: &str,
+    error: Option<&CommandError>,
+    log: &str,
+) -> FailureCategory {
+    match error {
+        Some(CommandError::SandboxOOM) => return FailureCategory::Oom,
+        Some(CommandError::Timeout(_) | CommandError::NoOutputFor(_)) => {
+            return FailureCategory::Timeout
+        }
+        _ => {}
+    }
+
+    if RUSTDOC_ICE.is_match(log) {
+        FailureCategory::RustdocIce
+    } else if OOM.is_match(log) {
+        FailureCategory::Oom
+    } else if UNSUPPORTED_TARGET.is_match(log) {
+        FailureCategory::UnsupportedTarget
+    } else if MISSING_SYSTEM_LIBRARY.is_match(log) {
+        FailureCategory::MissingSystemLibrary
+    } else if NETWORK_DENIED.is_match(log) {
+        FailureCategory::NetworkDenied
+    } else if let Some(failed) = COMPILE_ERROR.captures(log) {
+        // cargo names the package, which might use `-` where the crate uses `_`
+        if failed[1].replace('-', "_") == crate_name.replace('-', "_") {
+            FailureCategory::CompileErrorInCrate
+        } else {
+            FailureCategory::CompileErrorInDependency
+        }
+    } else {
+        FailureCategory::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(
+        "[INFO] [stderr] error: could not compile `foo-bar` due to 2 previous errors",
+        FailureCategory::CompileErrorInCrate
+    )]
+    #[test_case(
+        "[INFO] [stderr] error: could not compile `serde` (lib) due to previous error",
+        FailureCategory::CompileErrorInDependency
+    )]
+    #[test_case(
+        "[INFO] [stderr] error: could not document `foo_bar`",
+        FailureCategory::CompileErrorInCrate
+    )]
+    #[test_case(
+        "[INFO] [stderr] error: internal compiler error: unexpected panic\n\
+         [INFO] [stderr] error: could not document `foo_bar`",
+        FailureCategory::RustdocIce
+    )]
+    #[test_case(
+        "[INFO] [stderr] The system library `openssl` required by crate `openssl-sys` was not \
+         found.\n[INFO] [stderr] Package openssl was not found in the pkg-config search path.",
+        FailureCategory::MissingSystemLibrary
+    )]
+    #[test_case(
+        "[INFO] [stderr] fatal error: zlib.h: No such file or directory",
+        FailureCategory::MissingSystemLibrary
+    )]
+    #[test_case(
+        "[INFO] [stderr] Error: reqwest::Error { kind: Request, source: hyper::Error(Connect, \
+         ConnectError(\"dns error\", Custom { kind: Uncategorized, error: \"failed to lookup \
+         address information: Temporary failure in name resolution\" })) }",
+        FailureCategory::NetworkDenied
+    )]
+    #[test_case(
+        "[INFO] [stderr] error[E0463]: can't find crate for `core`\n\
+         [INFO] [stderr]   = note: the `x86_64-unknown-uefi` target may not be installed",
+        FailureCategory::UnsupportedTarget
+    )]
+    #[test_case(
+        "[INFO] [stderr] memory allocation of 1073741824 bytes failed",
+        FailureCategory::Oom
+    )]
+    #[test_case("[INFO] [stderr] something else went wrong", FailureCategory::Other)]
+    fn classify_log(log: &str, expected: FailureCategory) {
+        assert_eq!(classify_failure("foo_bar", None, log), expected);
+    }
+
+    #[test]
+    fn classify_sandbox_errors() {
+        let log = "[INFO] [stderr] error: could not compile `foo_bar`";
+        assert_eq!(
+            classify_failure("foo_bar", Some(&CommandError::SandboxOOM), log),
+            FailureCategory::Oom
+        );
+        assert_eq!(
+            classify_failure("foo_bar", Some(&CommandError::Timeout(900)), log),
+            FailureCategory::Timeout
+        );
+        assert_eq!(
+            classify_failure("foo_bar", Some(&CommandError::NoOutputFor(900)), log),
+            FailureCategory::Timeout
+        );
+    }
+
+    #[test]
+    fn roundtrip_names() {
+        use std::str::FromStr;
+        use strum::IntoEnumIterator;
+
+        for category in FailureCategory::iter() {
+            let name = category.to_string();
+            assert_eq!(FailureCategory::from_str(&name).unwrap(), category);
+            assert_eq!(serde_json::to_value(category).unwrap(), name.as_str());
+        }
+        assert_eq!(
+            FailureCategory::MissingSystemLibrary.to_string(),
+            "missing-system-library"
+        );
+    }
+}
@@ -1,11 +1,15 @@
-use crate::{db::Overrides, error::Result, Config};
+use crate::{db::Overrides, docbuilder::FailureCategory, error::Result, Config};
 use postgres::Client;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::time::Duration;
 
 const GB: usize = 1024 * 1024 * 1024;
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// Builds using more than this percentage of a limit count as hitting it.
+const LIMIT_THRESHOLD_PERCENT: u128 = 90;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) struct Limits {
     memory: usize,
     targets: usize,
@@ -65,9 +69,155 @@ impl Limits {
     }
 }
 
+/// Raises the limit a build of `name` failing with `category` ran into, doubling it up to the
+/// ceiling in the config, and saves the raised limits as automatic overrides.
+///
+/// Returns `false` if the failure wasn't caused by a limit, or the limit is at its ceiling already.
+pub(crate) fn raise_limits(
+    config: &Config,
+    conn: &mut Client,
+    name: &str,
+    category: FailureCategory,
+) -> Result<bool> {
+    let limits = Limits::for_crate(config, conn, name)?;
+    let mut overrides = Overrides::for_crate(conn, name)?.unwrap_or_default();
+    match category {
+        FailureCategory::Oom => {
+            let memory = (limits.memory * 2).min(config.build_max_memory_limit);
+            if memory <= limits.memory {
+                return Ok(false);
+            }
+            overrides.memory = Some(memory);
+        }
+        FailureCategory::Timeout => {
+            let timeout = (limits.timeout * 2).min(Duration::from_secs(config.build_max_timeout));
+            if timeout <= limits.timeout {
+                return Ok(false);
+            }
+            overrides.timeout = Some(timeout);
+            // keep building as many targets as before, the timeout applies to each of them
+            overrides.targets = Some(limits.targets);
+        }
+        _ => return Ok(false),
+    }
+    overrides.automatic = true;
+    Overrides::save(conn, name, overrides)?;
+    Ok(true)
+}
+
+/// A crate whose builds keep hitting their limits, with the overrides proposed for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitSuggestion {
+    pub crate_name: String,
+    /// The number of recent builds that hit the limits.
+    pub builds: usize,
+    pub current: Option<Overrides>,
+    pub suggested: Overrides,
+}
+
+/// Finds the crates with at least `min_builds` builds in the last `since` that ran out of memory
+/// or time, or came close to it, and proposes overrides doubling the limits they hit.
+pub fn suggest_overrides(
+    config: &Config,
+    conn: &mut Client,
+    since: Duration,
+    min_builds: usize,
+) -> Result<Vec<LimitSuggestion>> {
+    #[derive(Default)]
+    struct Hits {
+        builds: usize,
+        memory: bool,
+        timeout: bool,
+    }
+
+    let rows = conn.query(
+        "SELECT
+            crates.name,
+            builds.failure_category,
+            usage.wall_time_ms,
+            usage.peak_memory_bytes
+         FROM builds
+         INNER JOIN releases ON releases.id = builds.rid
+         INNER JOIN crates ON crates.id = releases.crate_id
+         LEFT JOIN LATERAL (
+             SELECT
+                 MAX(wall_time_ms) AS wall_time_ms,
+                 MAX(peak_memory_bytes) AS peak_memory_bytes
+             FROM build_resource_usage
+             WHERE build_resource_usage.build_id = builds.id
+         ) AS usage ON TRUE
+         WHERE builds.build_time > NOW() - make_interval(secs => $1)",
+        &[&since.as_secs_f64()],
+    )?;
+
+    let mut crates: BTreeMap<String, Hits> = BTreeMap::new();
+    let mut limits: BTreeMap<String, Limits> = BTreeMap::new();
+    for row in rows {
+        let name: String = row.get("name");
+        if !limits.contains_key(&name) {
+            limits.insert(name.clone(), Limits::for_crate(config, conn, &name)?);
+        }
+        let limits = &limits[&name];
+
+        let category: Option<FailureCategory> = row
+            .get::<_, Option<&str>>("failure_category")
+            .and_then(|category| category.parse().ok());
+        let wall_time = row
+            .get::<_, Option<i64>>("wall_time_ms")
+            .map(|ms| Duration::from_millis(ms as u64));
+        let peak_memory = row
+            .get::<_, Option<i64>>("peak_memory_bytes")
+            .map(|bytes| bytes as u128);
+
+        let memory = category == Some(FailureCategory::Oom)
+            || peak_memory
+                .is_some_and(|peak| peak * 100 >= limits.memory as u128 * LIMIT_THRESHOLD_PERCENT);
+        let timeout = category == Some(FailureCategory::Timeout)
+            || wall_time.is_some_and(|wall_time| {
+                wall_time.as_millis() * 100 >= limits.timeout.as_millis() * LIMIT_THRESHOLD_PERCENT
+            });
+        if memory || timeout {
+            let hits = crates.entry(name).or_default();
+            hits.builds += 1;
+            hits.memory |= memory;
+            hits.timeout |= timeout;
+        }
+    }
+
+    let mut suggestions = Vec::new();
+    for (crate_name, hits) in crates {
+        if hits.builds < min_builds {
+            continue;
+        }
+        let limits = &limits[&crate_name];
+        let current = Overrides::for_crate(conn, &crate_name)?;
+        let mut suggested = Overrides {
+            // applying the suggestion is a manual override
+            automatic: false,
+            ..current.unwrap_or_default()
+        };
+        if hits.memory {
+            suggested.memory = Some(limits.memory * 2);
+        }
+        if hits.timeout {
+            suggested.timeout = Some(limits.timeout * 2);
+            // a timeout override implies a single target, make that explicit
+            suggested.targets = Some(suggested.targets.unwrap_or(1));
+        }
+        suggestions.push(LimitSuggestion {
+            crate_name,
+            builds: hits.builds,
+            current,
+            suggested,
+        });
+    }
+    Ok(suggestions)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::docbuilder::ResourceUsage;
     use crate::test::*;
 
     #[test]
@@ -83,7 +233,7 @@ mod test {
             assert_eq!(hexponent, defaults);
 
             Overrides::save(
-                &mut db.conn(),
+                &mut *db.conn(),
                 krate,
                 Overrides {
                     targets: Some(15),
@@ -109,12 +259,13 @@ mod test {
                 ..defaults
             };
             Overrides::save(
-                &mut db.conn(),
+                &mut *db.conn(),
                 krate,
                 Overrides {
                     memory: Some(limits.memory),
                     targets: Some(limits.targets),
                     timeout: Some(limits.timeout),
+                    ..Overrides::default()
                 },
             )?;
             assert_eq!(
@@ -131,7 +282,7 @@ mod test {
             let db = env.db();
             let krate = "hexponent";
             Overrides::save(
-                &mut db.conn(),
+                &mut *db.conn(),
                 krate,
                 Overrides {
                     timeout: Some(Duration::from_secs(20 * 60)),
@@ -161,6 +312,167 @@ mod test {
         });
     }
 
+    #[test]
+    fn suggest_overrides_for_crates_hitting_limits() {
+        wrapper(|env| {
+            let defaults = Limits::new(&env.config());
+            let usage = |peak_memory: usize, wall_time: Duration| {
+                FakeBuild::default().resource_usage(ResourceUsage {
+                    target: "x86_64-unknown-linux-gnu".into(),
+                    wall_time,
+                    peak_memory: Some(peak_memory as u64),
+                    output_size: 0,
+                })
+            };
+
+            // ran out of memory twice
+            for version in ["0.1.0", "0.2.0"] {
+                env.fake_release()
+                    .name("oom")
+                    .version(version)
+                    .builds(vec![
+                        FakeBuild::default().failure_category(FailureCategory::Oom)
+                    ])
+                    .create()?;
+            }
+            // close to the timeout twice, with an existing memory override
+            Overrides::save(
+                &mut *env.db().conn(),
+                "slow",
+                Overrides {
+                    memory: Some(defaults.memory * 2),
+                    ..Overrides::default()
+                },
+            )?;
+            for version in ["0.1.0", "0.2.0"] {
+                env.fake_release()
+                    .name("slow")
+                    .version(version)
+                    .builds(vec![usage(GB, defaults.timeout - Duration::from_secs(10))])
+                    .create()?;
+            }
+            // close to the limits only once
+            env.fake_release()
+                .name("once")
+                .builds(vec![usage(defaults.memory, Duration::from_secs(1))])
+                .create()?;
+            // comfortably within the limits
+            for version in ["0.1.0", "0.2.0"] {
+                env.fake_release()
+                    .name("fine")
+                    .version(version)
+                    .builds(vec![usage(GB, Duration::from_secs(60))])
+                    .create()?;
+            }
+
+            let suggestions = suggest_overrides(
+                &env.config(),
+                &mut env.db().conn(),
+                Duration::from_secs(60 * 60),
+                2,
+            )?;
+            assert_eq!(
+                suggestions,
+                vec![
+                    LimitSuggestion {
+                        crate_name: "oom".into(),
+                        builds: 2,
+                        current: None,
+                        suggested: Overrides {
+                            memory: Some(defaults.memory * 2),
+                            ..Overrides::default()
+                        },
+                    },
+                    LimitSuggestion {
+                        crate_name: "slow".into(),
+                        builds: 2,
+                        current: Some(Overrides {
+                            memory: Some(defaults.memory * 2),
+                            ..Overrides::default()
+                        }),
+                        suggested: Overrides {
+                            memory: Some(defaults.memory * 2),
+                            targets: Some(1),
+                            timeout: Some(defaults.timeout * 2),
+                            automatic: false,
+                        },
+                    },
+                ]
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn raise_limits_up_to_the_ceiling() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.build_default_memory_limit = Some(3 * GB);
+                config.build_max_memory_limit = 8 * GB;
+                config.build_max_timeout = 20 * 60;
+            });
+            let config = env.config();
+            let mut conn = env.db().conn();
+            let defaults = Limits::new(&config);
+
+            assert!(!raise_limits(
+                &config,
+                &mut conn,
+                "krate",
+                FailureCategory::CompileErrorInCrate
+            )?);
+            assert_eq!(Overrides::for_crate(&mut *conn, "krate")?, None);
+
+            for expected in [6 * GB, 8 * GB] {
+                assert!(raise_limits(
+                    &config,
+                    &mut conn,
+                    "krate",
+                    FailureCategory::Oom
+                )?);
+                assert_eq!(
+                    Overrides::for_crate(&mut *conn, "krate")?,
+                    Some(Overrides {
+                        memory: Some(expected),
+                        automatic: true,
+                        ..Overrides::default()
+                    })
+                );
+            }
+            assert!(!raise_limits(
+                &config,
+                &mut conn,
+                "krate",
+                FailureCategory::Oom
+            )?);
+
+            assert!(raise_limits(
+                &config,
+                &mut conn,
+                "krate",
+                FailureCategory::Timeout
+            )?);
+            assert_eq!(
+                Overrides::for_crate(&mut *conn, "krate")?,
+                Some(Overrides {
+                    memory: Some(8 * GB),
+                    targets: Some(defaults.targets),
+                    timeout: Some(Duration::from_secs(20 * 60)),
+                    automatic: true,
+                })
+            );
+            assert!(!raise_limits(
+                &config,
+                &mut conn,
+                "krate",
+                FailureCategory::Timeout
+            )?);
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn overrides_dont_lower_memory_limit() {
         wrapper(|env| {
@@ -169,7 +481,7 @@ mod test {
             let defaults = Limits::new(&env.config());
 
             Overrides::save(
-                &mut db.conn(),
+                &mut *db.conn(),
                 "krate",
                 Overrides {
                     memory: Some(defaults.memory / 2),
@@ -1,7 +1,30 @@
 mod crates;
+mod failure_category;
 mod limits;
+mod report;
+mod resource_usage;
+mod running_build;
+mod rustdoc_json;
 mod rustwide_builder;
+mod search_index;
+mod worker;
 
-pub(crate) use self::limits::Limits;
-pub(crate) use self::rustwide_builder::{BuildResult, DocCoverage};
+pub(crate) use self::failure_category::FailureCategory;
+pub(crate) use self::limits::{raise_limits, Limits};
+pub use self::limits::{suggest_overrides, LimitSuggestion};
+pub(crate) use self::report::{record_build, BuildReport};
+pub(crate) use self::resource_usage::ResourceUsage;
+pub(crate) use self::running_build::{
+    finish_running_build, remove_stale_running_builds, start_running_build,
+    update_running_build_log, RUNNING_BUILD_TIMEOUT,
+};
+#[cfg(test)]
+pub(crate) use self::rustdoc_json::read_rustdoc_json;
+pub(crate) use self::rustdoc_json::{PublicItem, UndocumentedItem};
+pub(crate) use self::rustwide_builder::{BuildResult, DocCoverage, FileDocCoverage};
 pub use self::rustwide_builder::{PackageKind, RustwideBuilder};
+pub(crate) use self::search_index::{read_search_index, SearchIndexItem};
+pub use self::worker::WorkerClient;
+pub(crate) use self::worker::{
+    unzip_into, BuildFailure, BuildLog, Heartbeat, LeasedBuild, StoredArchive,
+};
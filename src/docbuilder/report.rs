@@ -0,0 +1,129 @@
+use crate::db::{
+    add_build_into_database, add_build_log_targets, add_build_resource_usage, add_doc_coverage,
+    add_locked_dependencies, add_package_into_database, add_public_items, add_search_items,
+    update_crate_data_in_database, PackageDocs,
+};
+use crate::docbuilder::{BuildResult, DocCoverage, PublicItem, ResourceUsage, SearchIndexItem};
+use crate::error::Result;
+use crate::index::api::{CrateData, ReleaseData};
+use crate::repositories::RepositoryStatsUpdater;
+use crate::storage::CompressionAlgorithm;
+use crate::utils::MetadataPackage;
+use crate::{InstanceMetrics, Storage};
+use postgres::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use tracing::debug;
+
+/// Everything a finished build adds to the database.
+///
+/// The archives are already in the storage when the report is recorded, so a build worker only
+/// has to send this over once it's done.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct BuildReport {
+    /// The root package of the crate, named by its qualified name.
+    pub(crate) package: MetadataPackage,
+    pub(crate) locked_dependencies: Vec<(String, String)>,
+    pub(crate) docs: PackageDocs,
+    pub(crate) result: BuildResult,
+    pub(crate) default_target: String,
+    pub(crate) source_files: Value,
+    pub(crate) doc_targets: Vec<String>,
+    pub(crate) json_targets: Vec<String>,
+    pub(crate) has_docs: bool,
+    pub(crate) has_examples: bool,
+    pub(crate) compression_algorithms: HashSet<CompressionAlgorithm>,
+    pub(crate) doc_coverage: Option<DocCoverage>,
+    pub(crate) search_items: Vec<SearchIndexItem>,
+    /// The public items read from the rustdoc JSON output of the default target, including
+    /// members like methods and fields.
+    #[serde(default)]
+    pub(crate) public_items: Vec<PublicItem>,
+    /// The log of every target that was built, as `(target, log)`.
+    pub(crate) build_logs: Vec<(String, String)>,
+    /// The resources the build of every target used.
+    #[serde(default)]
+    pub(crate) resource_usage: Vec<ResourceUsage>,
+    /// `None` for local packages, or when the registry API couldn't be reached.
+    pub(crate) release_data: Option<ReleaseData>,
+    pub(crate) crate_data: Option<CrateData>,
+}
+
+/// Adds the release and the build in `report` to the database, built on `build_server`.
+///
+/// Returns whether the build was successful.
+pub(crate) fn record_build(
+    conn: &mut Client,
+    storage: &Storage,
+    metrics: &InstanceMetrics,
+    repository_stats_updater: &RepositoryStatsUpdater,
+    report: BuildReport,
+    build_server: &str,
+) -> Result<bool> {
+    let name = &report.package.name;
+    let version = &report.package.version;
+
+    if report.result.successful {
+        metrics.successful_builds.inc();
+    } else if report.package.is_library() {
+        metrics.failed_builds.inc();
+    } else {
+        metrics.non_library_builds.inc();
+    }
+
+    let repository = repository_stats_updater.load_repository(&report.package)?;
+
+    let release_id = add_package_into_database(
+        conn,
+        &report.package,
+        &report.docs,
+        &report.result,
+        &report.default_target,
+        report.source_files,
+        report.doc_targets,
+        report.json_targets,
+        &report.release_data.unwrap_or_default(),
+        report.has_docs,
+        report.has_examples,
+        report.compression_algorithms,
+        repository,
+        true,
+    )?;
+
+    if let Some(doc_coverage) = report.doc_coverage {
+        add_doc_coverage(conn, release_id, doc_coverage)?;
+    }
+
+    add_search_items(conn, release_id, &report.search_items)?;
+    add_public_items(conn, release_id, &report.public_items)?;
+    add_locked_dependencies(conn, release_id, &report.locked_dependencies)?;
+
+    let build_id = add_build_into_database(conn, release_id, &report.result, build_server)?;
+    let mut log_targets = Vec::with_capacity(report.build_logs.len());
+    for (target, build_log) in report.build_logs {
+        let build_log_path = format!("build-logs/{build_id}/{target}.txt");
+        storage.store_one(build_log_path, build_log)?;
+        log_targets.push(target);
+    }
+    add_build_log_targets(conn, build_id, &log_targets)?;
+    add_build_resource_usage(conn, build_id, &report.resource_usage)?;
+
+    // Some crates.io crate data is mutable, so we proactively update it during a release
+    if let Some(crate_data) = &report.crate_data {
+        update_crate_data_in_database(conn, name, crate_data)?;
+    }
+
+    if report.result.successful {
+        // delete eventually existing files from pre-archive storage.
+        // we're doing this in the end so eventual problems in the build
+        // won't lead to non-existing docs.
+        for prefix in &["rustdoc", "sources"] {
+            let prefix = format!("{prefix}/{name}/{version}/");
+            debug!("cleaning old storage folder {}", prefix);
+            storage.delete_prefix(&prefix)?;
+        }
+    }
+
+    Ok(report.result.successful)
+}
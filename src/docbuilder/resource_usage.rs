@@ -0,0 +1,247 @@
+//! Measuring how close builds come to their [`Limits`](super::Limits).
+//!
+//! Rustwide doesn't report the memory usage of its sandboxes, so while a build runs we sample
+//! the memory usage of its sandbox container instead. The container is found through a
+//! [`SandboxMarker`] mounted into every sandbox of the builder.
+
+use rustwide::cmd::{MountKind, SandboxBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+use walkdir::WalkDir;
+
+/// How often the memory usage of the sandbox is sampled while a build runs.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An empty directory mounted into the sandboxes of one builder at a path unique to the builder,
+/// which `docker ps` can filter on.
+///
+/// Other builders on the same docker host use a different path, and a builder only runs one
+/// sandbox at a time, so the filter finds the container of the running build only.
+#[derive(Debug, Clone)]
+pub(crate) struct SandboxMarker {
+    host_dir: PathBuf,
+    sandbox_path: PathBuf,
+}
+
+impl SandboxMarker {
+    /// The directory is kept in the rustwide workspace, which rustwide knows how to mount when
+    /// the builder itself runs in a container.
+    pub(crate) fn new(workspace: &Path) -> Self {
+        Self {
+            host_dir: workspace.join("sandbox-marker"),
+            sandbox_path: Path::new("/opt/docsrs/sandbox").join(Uuid::new_v4().to_string()),
+        }
+    }
+
+    pub(crate) fn mount(&self, sandbox: SandboxBuilder) -> SandboxBuilder {
+        sandbox.mount(&self.host_dir, &self.sandbox_path, MountKind::ReadOnly)
+    }
+}
+
+/// The resources the build of one target used.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ResourceUsage {
+    pub(crate) target: String,
+    pub(crate) wall_time: Duration,
+    /// The highest memory usage of the sandbox in bytes, `None` when it couldn't be sampled.
+    pub(crate) peak_memory: Option<u64>,
+    /// The size of the generated documentation in bytes.
+    pub(crate) output_size: u64,
+}
+
+/// Runs the sandboxed build in `f`, returning its result with the time it took and the peak
+/// memory usage of the sandbox with `marker`.
+pub(crate) fn measure<T>(
+    marker: &SandboxMarker,
+    f: impl FnOnce() -> T,
+) -> (T, Duration, Option<u64>) {
+    let start = Instant::now();
+    let (stop, stopped) = mpsc::channel::<()>();
+    thread::scope(|scope| {
+        let sampler = scope.spawn(move || {
+            let mut peak_memory = None;
+            loop {
+                peak_memory = peak_memory.max(sandbox_memory(marker));
+                if let Ok(()) | Err(RecvTimeoutError::Disconnected) =
+                    stopped.recv_timeout(SAMPLE_INTERVAL)
+                {
+                    return peak_memory;
+                }
+            }
+        });
+
+        let result = f();
+        drop(stop);
+        let peak_memory = sampler.join().unwrap_or_default();
+        (result, start.elapsed(), peak_memory)
+    })
+}
+
+/// The size of all files below `dir`, 0 if it doesn't exist.
+pub(crate) fn dir_size(dir: &Path) -> u64 {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// The memory used by the running sandbox container with `marker`, `None` if there is none or
+/// the usage couldn't be read.
+fn sandbox_memory(marker: &SandboxMarker) -> Option<u64> {
+    let containers = docker(&[
+        "ps",
+        "--quiet",
+        "--no-trunc",
+        "--filter",
+        &format!("volume={}", marker.sandbox_path.display()),
+    ])?;
+    let container = containers.lines().next()?;
+
+    // the cgroup of the container has its peak usage, but it's only visible when the builder
+    // runs directly on the docker host
+    let cgroup = docker(&["inspect", "--format", "{{.State.Pid}}", container])
+        .and_then(|pid| std::fs::read_to_string(format!("/proc/{}/cgroup", pid.trim())).ok());
+    if let Some(memory) = cgroup.and_then(|cgroup| {
+        memory_files(&cgroup).iter().find_map(|file| {
+            std::fs::read_to_string(file)
+                .ok()?
+                .trim()
+                .parse::<u64>()
+                .ok()
+        })
+    }) {
+        return Some(memory);
+    }
+
+    docker(&[
+        "stats",
+        "--no-stream",
+        "--format",
+        "{{.MemUsage}}",
+        container,
+    ])
+    .and_then(|usage| parse_memory_usage(&usage))
+}
+
+/// Parses the current usage from the `MemUsage` column of `docker stats`, like `1.5GiB / 4GiB`.
+fn parse_memory_usage(usage: &str) -> Option<u64> {
+    let usage = usage.split('/').next()?.trim();
+    let split = usage.find(|c: char| c.is_ascii_alphabetic())?;
+    let (value, unit) = usage.split_at(split);
+    let multiplier: u64 = match unit {
+        "B" => 1,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        "TiB" => 1 << 40,
+        "kB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        "TB" => 1_000_000_000_000,
+        _ => return None,
+    };
+    Some((value.trim().parse::<f64>().ok()? * multiplier as f64) as u64)
+}
+
+fn docker(args: &[&str]) -> Option<String> {
+    let output = Command::new("docker").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+/// The files with the memory usage of a process in the cgroup hierarchy, given the content of
+/// its `/proc/<pid>/cgroup`, with the most accurate ones first.
+fn memory_files(cgroup: &str) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for line in cgroup.lines() {
+        let mut parts = line.splitn(3, ':');
+        let (Some(_), Some(controllers), Some(path)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        let path = path.trim_start_matches('/');
+        if controllers.is_empty() {
+            // cgroup v2 has a single hierarchy
+            let dir = Path::new("/sys/fs/cgroup").join(path);
+            files.push(dir.join("memory.peak"));
+            files.push(dir.join("memory.current"));
+        } else if controllers
+            .split(',')
+            .any(|controller| controller == "memory")
+        {
+            let dir = Path::new("/sys/fs/cgroup/memory").join(path);
+            files.push(dir.join("memory.max_usage_in_bytes"));
+            files.push(dir.join("memory.usage_in_bytes"));
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cgroup_v2_memory_files() {
+        assert_eq!(
+            memory_files("0::/system.slice/docker-1234.scope\n"),
+            vec![
+                PathBuf::from("/sys/fs/cgroup/system.slice/docker-1234.scope/memory.peak"),
+                PathBuf::from("/sys/fs/cgroup/system.slice/docker-1234.scope/memory.current"),
+            ]
+        );
+    }
+
+    #[test]
+    fn cgroup_v1_memory_files() {
+        assert_eq!(
+            memory_files("12:cpu,cpuacct:/docker/1234\n9:memory:/docker/1234\n1:name=systemd:/\n"),
+            vec![
+                PathBuf::from("/sys/fs/cgroup/memory/docker/1234/memory.max_usage_in_bytes"),
+                PathBuf::from("/sys/fs/cgroup/memory/docker/1234/memory.usage_in_bytes"),
+            ]
+        );
+    }
+
+    #[test]
+    fn docker_stats_memory_usage() {
+        assert_eq!(parse_memory_usage("512B / 4GiB"), Some(512));
+        assert_eq!(parse_memory_usage("1.5GiB / 4GiB\n"), Some(3 << 29));
+        assert_eq!(parse_memory_usage("20MB / 1GB"), Some(20_000_000));
+        assert_eq!(parse_memory_usage("-- / --"), None);
+        assert_eq!(parse_memory_usage(""), None);
+    }
+
+    #[test]
+    fn measure_without_sandbox() {
+        let marker = SandboxMarker::new(Path::new("/nonexistent"));
+        let (result, wall_time, _) = measure(&marker, || {
+            thread::sleep(Duration::from_millis(10));
+            42
+        });
+        assert_eq!(result, 42);
+        assert!(wall_time >= Duration::from_millis(10));
+    }
+
+    #[test]
+    fn size_of_directory() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        assert_eq!(dir_size(&dir.path().join("missing")), 0);
+
+        std::fs::create_dir(dir.path().join("sub"))?;
+        std::fs::write(dir.path().join("a.html"), [0; 10])?;
+        std::fs::write(dir.path().join("sub/b.html"), [0; 5])?;
+        assert_eq!(dir_size(dir.path()), 15);
+        Ok(())
+    }
+}
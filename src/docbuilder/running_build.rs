@@ -0,0 +1,331 @@
+use crate::build_queue::BuildLease;
+use crate::db::Pool;
+use crate::docbuilder::{BuildLog, WorkerClient};
+use crate::error::Result;
+use crate::utils::report_error;
+use postgres::GenericClient;
+use rustwide::logging::{self, LogStorage};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tracing::{error, warn};
+
+/// How often the log of a running build is copied into the database.
+const LOG_UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a running build of the local builder can go without updates before it's considered
+/// lost, a dozen log updates.
+pub(crate) const RUNNING_BUILD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A build which is currently running on this builder.
+///
+/// While it exists, the build is listed in the `running_builds` table, where the web server
+/// picks up the log to show it on the queue page.
+///
+/// Builds of the local builder are listed by themselves. Their `updated_at` is kept current in
+/// the background, so rows of builders that died can be told apart and removed with
+/// [`remove_stale_running_builds`], and the row is removed again when this is dropped, regardless
+/// of whether the build succeeded or not.
+///
+/// Builds of workers were listed by the build server when they were leased, and are removed
+/// when the lease ends. Their log is sent to the build server with heartbeats of the lease.
+pub(crate) struct RunningBuild {
+    name: String,
+    version: String,
+    publisher: Publisher,
+}
+
+enum Publisher {
+    Local {
+        pool: Pool,
+        /// Keeps `updated_at` current until the sender is dropped.
+        keepalive: Option<(mpsc::Sender<()>, JoinHandle<()>)>,
+    },
+    Worker {
+        client: Arc<WorkerClient>,
+        lease: BuildLease,
+    },
+}
+
+impl RunningBuild {
+    pub(crate) fn start(pool: &Pool, name: &str, version: &str) -> Result<Self> {
+        start_running_build(
+            &mut *pool.get()?,
+            name,
+            version,
+            hostname::get()?.to_str().unwrap_or(""),
+        )?;
+
+        let (stop, stopped) = mpsc::channel::<()>();
+        let keepalive = thread::spawn({
+            let pool = pool.clone();
+            let (name, version) = (name.to_owned(), version.to_owned());
+            move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(LOG_UPDATE_INTERVAL)
+                {
+                    if let Err(err) = touch_running_build(&pool, &name, &version) {
+                        report_error(&err.context("could not keep running build alive"));
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            name: name.into(),
+            version: version.into(),
+            publisher: Publisher::Local {
+                pool: pool.clone(),
+                keepalive: Some((stop, keepalive)),
+            },
+        })
+    }
+
+    /// A build leased to this worker, which the build server already lists.
+    pub(crate) fn leased(client: Arc<WorkerClient>, lease: BuildLease) -> Self {
+        Self {
+            name: lease.name.clone(),
+            version: lease.version.clone(),
+            publisher: Publisher::Worker { client, lease },
+        }
+    }
+
+    /// Replace the published log of this build with the current output of `target`.
+    pub(crate) fn update_log(&self, target: &str, log: &str) -> Result<()> {
+        match &self.publisher {
+            Publisher::Local { pool, .. } => {
+                update_running_build_log(&mut *pool.get()?, &self.name, &self.version, target, log)
+            }
+            Publisher::Worker { client, lease } => {
+                let log = BuildLog {
+                    target: target.into(),
+                    log: log.into(),
+                };
+                if client.heartbeat(lease, Some(&log))?.is_none() {
+                    warn!(
+                        "lost the lease of {} {}, could not publish the build log",
+                        self.name, self.version
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Like [`logging::capture`], but periodically publishes the captured log while `f` runs.
+    pub(crate) fn capture<T>(
+        &self,
+        target: &str,
+        storage: &LogStorage,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        let publish = || {
+            if let Err(err) = self.update_log(target, &storage.to_string()) {
+                report_error(&err.context("could not publish build log"));
+            }
+        };
+
+        let (stop, stopped) = mpsc::channel::<()>();
+        let result = thread::scope(|scope| {
+            let publish = &publish;
+            scope.spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(LOG_UPDATE_INTERVAL)
+                {
+                    publish();
+                }
+            });
+
+            let result = logging::capture(storage, f);
+            drop(stop);
+            result
+        });
+
+        publish();
+        result
+    }
+}
+
+/// Lists a build running on `build_server` in the `running_builds` table, replacing an earlier
+/// entry of the same release.
+pub(crate) fn start_running_build(
+    conn: &mut impl GenericClient,
+    name: &str,
+    version: &str,
+    build_server: &str,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO running_builds (name, version, build_server)
+         VALUES ($1, $2, $3)
+         ON CONFLICT (name, version) DO UPDATE
+            SET build_server = EXCLUDED.build_server,
+                target = NULL,
+                log = '',
+                started_at = NOW(),
+                updated_at = NOW()",
+        &[&name, &version, &build_server],
+    )?;
+    Ok(())
+}
+
+/// Replaces the published log of a running build with the current output of `target`.
+pub(crate) fn update_running_build_log(
+    conn: &mut impl GenericClient,
+    name: &str,
+    version: &str,
+    target: &str,
+    log: &str,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE running_builds
+         SET target = $3, log = $4, updated_at = NOW()
+         WHERE name = $1 AND version = $2",
+        &[&name, &version, &target, &log],
+    )?;
+    Ok(())
+}
+
+fn touch_running_build(pool: &Pool, name: &str, version: &str) -> Result<()> {
+    pool.get()?.execute(
+        "UPDATE running_builds SET updated_at = NOW() WHERE name = $1 AND version = $2",
+        &[&name, &version],
+    )?;
+    Ok(())
+}
+
+/// Removes the running builds of builders that died without removing them, so they aren't
+/// shown or counted anymore.
+///
+/// Builds leased to workers are kept until their lease expires, the others once they weren't
+/// updated for [`RUNNING_BUILD_TIMEOUT`].
+///
+/// Returns the number of removed builds.
+pub(crate) fn remove_stale_running_builds(conn: &mut impl GenericClient) -> Result<u64> {
+    Ok(conn.execute(
+        "DELETE FROM running_builds
+         WHERE
+            updated_at < NOW() - make_interval(secs => $1) AND
+            NOT EXISTS (
+                SELECT 1 FROM queue
+                WHERE
+                    queue.name = running_builds.name AND
+                    queue.version = running_builds.version AND
+                    queue.lease_expires_at >= NOW()
+            )",
+        &[&RUNNING_BUILD_TIMEOUT.as_secs_f64()],
+    )?)
+}
+
+/// Removes a build from the `running_builds` table.
+pub(crate) fn finish_running_build(
+    conn: &mut impl GenericClient,
+    name: &str,
+    version: &str,
+) -> Result<()> {
+    conn.execute(
+        "DELETE FROM running_builds WHERE name = $1 AND version = $2",
+        &[&name, &version],
+    )?;
+    Ok(())
+}
+
+impl Drop for RunningBuild {
+    fn drop(&mut self) {
+        // the build server removes the builds of workers when their lease ends
+        let Publisher::Local { pool, keepalive } = &mut self.publisher else {
+            return;
+        };
+        if let Some((stop, keepalive)) = keepalive.take() {
+            drop(stop);
+            if keepalive.join().is_err() {
+                error!(
+                    "keepalive of running build {} {} panicked",
+                    self.name, self.version
+                );
+            }
+        }
+        let finish = || finish_running_build(&mut *pool.get()?, &self.name, &self.version);
+        if let Err(err) = finish() {
+            report_error(&err.context(format!(
+                "could not remove running build {} {}",
+                self.name, self.version
+            )));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::wrapper;
+
+    #[test]
+    fn running_build_lifecycle() {
+        wrapper(|env| {
+            let running = |env: &crate::test::TestEnvironment| -> Result<Vec<(String, String)>> {
+                Ok(env
+                    .db()
+                    .conn()
+                    .query(
+                        "SELECT COALESCE(target, ''), log FROM running_builds
+                         WHERE name = 'foo' AND version = '0.1.0'",
+                        &[],
+                    )?
+                    .iter()
+                    .map(|row| (row.get(0), row.get(1)))
+                    .collect())
+            };
+
+            let build = RunningBuild::start(&env.db().pool(), "foo", "0.1.0")?;
+            assert_eq!(running(env)?, vec![("".into(), "".into())]);
+
+            build.update_log("x86_64-unknown-linux-gnu", "compiling foo")?;
+            assert_eq!(
+                running(env)?,
+                vec![("x86_64-unknown-linux-gnu".into(), "compiling foo".into())]
+            );
+
+            // restarting a build resets the log
+            let restarted = RunningBuild::start(&env.db().pool(), "foo", "0.1.0")?;
+            assert_eq!(running(env)?, vec![("".into(), "".into())]);
+
+            drop(build);
+            drop(restarted);
+            assert!(running(env)?.is_empty());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn remove_stale_running_builds() {
+        wrapper(|env| {
+            let queue = env.build_queue();
+            queue.add_crate("leased", "0.1.0", 0, None)?;
+            queue
+                .lease_next_crate("worker", Duration::from_secs(60))?
+                .unwrap();
+
+            let mut conn = env.db().conn();
+            start_running_build(&mut *conn, "fresh", "0.1.0", "builder")?;
+            start_running_build(&mut *conn, "stale", "0.1.0", "builder")?;
+            conn.execute(
+                "UPDATE running_builds
+                 SET updated_at = NOW() - INTERVAL '1 hour'
+                 WHERE name IN ('stale', 'leased')",
+                &[],
+            )?;
+
+            // the lease of a worker keeps its build alive
+            assert_eq!(super::remove_stale_running_builds(&mut *conn)?, 1);
+            let mut names: Vec<String> = conn
+                .query("SELECT name FROM running_builds", &[])?
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+            names.sort();
+            assert_eq!(names, vec!["fresh", "leased"]);
+
+            Ok(())
+        });
+    }
+}
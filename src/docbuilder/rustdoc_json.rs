@@ -0,0 +1,573 @@
+//! Read the items of a crate from the JSON output of rustdoc.
+//!
+//! The format of the output isn't stable yet, so only the parts that didn't change between the
+//! format versions we have seen are used: the `index` of all items, and the `paths` of the items
+//! that have their own page.
+
+use crate::error::Result;
+use anyhow::Context as _;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+
+/// More undocumented items than this aren't recorded, listing them wouldn't be useful anymore.
+const MAX_UNDOCUMENTED_ITEMS: usize = 5000;
+
+/// Crates with more public items than this don't get their items recorded at all,
+/// an incomplete list would show items as removed in the API diff.
+const MAX_PUBLIC_ITEMS: usize = 50_000;
+
+/// Item kinds that aren't documented on their own.
+const SKIPPED_KINDS: &[&str] = &["impl", "use", "import", "extern_crate", "primitive"];
+
+/// Keys of the item details that list the members of an item.
+const MEMBER_KEYS: &[&str] = &["fields", "variants", "items", "tuple"];
+
+/// Keys of the item details that aren't part of the signature of an item.
+///
+/// The members of an item are compared on their own, and IDs change between builds.
+const NON_SIGNATURE_KEYS: &[&str] = &[
+    "id",
+    "items",
+    "fields",
+    "variants",
+    "impls",
+    "implementations",
+    "links",
+    "has_body",
+    "has_stripped_fields",
+    "fields_stripped",
+    "variants_stripped",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct UndocumentedItem {
+    /// The source file the item is defined in, relative to the root of the crate.
+    pub(crate) path: String,
+    pub(crate) line: i32,
+    /// The full path of the item, for example `serde::de::Deserializer::deserialize_any`.
+    pub(crate) name: String,
+    /// The rustdoc JSON kind of the item, for example `function`.
+    pub(crate) kind: String,
+}
+
+/// A public item of a crate, including the members of other items like methods and fields.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct PublicItem {
+    /// The full path of the item, for example `serde::de::Deserializer::deserialize_any`.
+    pub(crate) path: String,
+    /// The rustdoc item type, for example `tymethod`, named like in [`super::SearchIndexItem`].
+    pub(crate) kind: String,
+    /// The page of the item relative to the rustdoc root, with the anchor of members,
+    /// for example `serde/de/trait.Deserializer.html#tymethod.deserialize_any`.
+    pub(crate) link: String,
+    /// A hash of the signature of the item, without its members.
+    pub(crate) signature: String,
+}
+
+/// The items read from the rustdoc JSON output of the default target.
+#[derive(Debug, Default)]
+pub(crate) struct RustdocItems {
+    pub(crate) public_items: Vec<PublicItem>,
+    pub(crate) undocumented_items: Vec<UndocumentedItem>,
+}
+
+/// The ID of an item, a string in older format versions and a number in newer ones.
+fn id_of(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// The kind of an item, either in `kind` or as the only key of `inner`.
+fn kind_of(item: &Value) -> Option<&str> {
+    item.get("kind").and_then(Value::as_str).or_else(|| {
+        item.get("inner")
+            .and_then(Value::as_object)
+            .and_then(|inner| inner.keys().next())
+            .map(String::as_str)
+    })
+}
+
+/// The details of an item of `kind`.
+fn inner_of<'a>(item: &'a Value, kind: &str) -> Option<&'a Value> {
+    let inner = item.get("inner")?;
+    if item.get("kind").is_some() {
+        Some(inner)
+    } else {
+        inner.get(kind)
+    }
+}
+
+/// The IDs of the items defined inside an item: fields, variants and associated items.
+fn child_ids(inner: &Map<String, Value>, depth: usize, ids: &mut Vec<String>) {
+    for (key, value) in inner {
+        match value {
+            Value::Array(children) if MEMBER_KEYS.contains(&key.as_str()) => {
+                ids.extend(children.iter().filter_map(id_of))
+            }
+            Value::Object(nested) if depth > 0 && key != "generics" => {
+                child_ids(nested, depth - 1, ids)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The rustdoc item type of an item of the rustdoc JSON `kind`, as used in the file names of
+/// item pages and in the anchors of members.
+fn item_type(kind: &str, inner: Option<&Value>, is_member: bool) -> Option<&'static str> {
+    Some(match kind {
+        "module" => "mod",
+        "struct" => "struct",
+        "union" => "union",
+        "enum" => "enum",
+        "variant" => "variant",
+        "struct_field" => "structfield",
+        // required trait methods don't have a body
+        "function" if is_member => match inner.and_then(|inner| inner.get("has_body")) {
+            Some(Value::Bool(false)) => "tymethod",
+            _ => "method",
+        },
+        "function" => "fn",
+        "trait" => "trait",
+        "trait_alias" => "traitalias",
+        "typedef" | "type_alias" => "type",
+        "opaque_ty" => "opaque",
+        "constant" | "assoc_const" if is_member => "associatedconstant",
+        "constant" => "constant",
+        "assoc_type" => "associatedtype",
+        "static" => "static",
+        "foreign_type" | "extern_type" => "foreigntype",
+        "macro" => "macro",
+        "proc_attribute" => "attr",
+        "proc_derive" => "derive",
+        "proc_macro" => match inner.and_then(|inner| inner.get("kind")?.as_str()) {
+            Some("attr") => "attr",
+            Some("derive") => "derive",
+            _ => "macro",
+        },
+        "keyword" => "keyword",
+        _ => return None,
+    })
+}
+
+/// The signature of an item, the details of the item without its members and IDs.
+fn signature(inner: &Value) -> Value {
+    match inner {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(key, _)| !NON_SIGNATURE_KEYS.contains(&key.as_str()))
+                .map(|(key, value)| match (key.as_str(), value) {
+                    // the fields of tuple structs and variants, only their number matters here
+                    ("tuple", Value::Array(fields)) => (key.clone(), fields.len().into()),
+                    _ => (key.clone(), signature(value)),
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.iter().map(signature).collect()),
+        value => value.clone(),
+    }
+}
+
+fn signature_hash(inner: Option<&Value>) -> String {
+    let signature = inner.map(signature).unwrap_or_default();
+    let hash = Sha256::digest(signature.to_string().as_bytes());
+    hex::encode(&hash[..16])
+}
+
+/// The index of a crate in its rustdoc JSON output.
+struct CrateIndex<'a> {
+    index: &'a Map<String, Value>,
+    paths: Option<&'a Map<String, Value>>,
+    /// The item every member is defined in: the struct of a field, the enum of a variant,
+    /// the trait of an associated item, and the type of a method from an inherent impl.
+    parents: HashMap<String, String>,
+    /// The items of trait implementations, they use the documentation of the trait.
+    trait_impl_items: HashSet<String>,
+}
+
+impl<'a> CrateIndex<'a> {
+    fn new(krate: &'a Value) -> Result<Self> {
+        let index = krate
+            .get("index")
+            .and_then(Value::as_object)
+            .context("missing item index")?;
+        let paths = krate.get("paths").and_then(Value::as_object);
+
+        let mut parents = HashMap::new();
+        let mut trait_impl_items = HashSet::new();
+        for (id, item) in index {
+            let Some(kind) = kind_of(item) else { continue };
+            let Some(inner) = inner_of(item, kind).and_then(Value::as_object) else {
+                continue;
+            };
+
+            let mut children = Vec::new();
+            child_ids(inner, 2, &mut children);
+
+            if kind == "impl" {
+                if inner.get("trait").is_some_and(|t| !t.is_null()) {
+                    trait_impl_items.extend(children);
+                    continue;
+                }
+                // inherent methods belong to the type the impl is for
+                let Some(parent) = inner
+                    .get("for")
+                    .and_then(|ty| ty.get("resolved_path"))
+                    .and_then(|path| path.get("id"))
+                    .and_then(id_of)
+                else {
+                    continue;
+                };
+                for child in children {
+                    parents.insert(child, parent.clone());
+                }
+            } else {
+                for child in children {
+                    parents.insert(child, id.clone());
+                }
+            }
+        }
+
+        Ok(Self {
+            index,
+            paths,
+            parents,
+            trait_impl_items,
+        })
+    }
+
+    /// The public items defined in the crate, as `(id, item, kind, name)`.
+    fn public_items(&self) -> impl Iterator<Item = (&'a String, &'a Value, &'a str, &'a str)> + '_ {
+        self.index.iter().filter_map(|(id, item)| {
+            let local = item.get("crate_id").and_then(Value::as_u64) == Some(0);
+            let visible = matches!(
+                item.get("visibility").and_then(Value::as_str),
+                Some("public" | "default")
+            );
+            let kind = kind_of(item)?;
+            let name = item.get("name").and_then(Value::as_str)?;
+            (local
+                && visible
+                && !SKIPPED_KINDS.contains(&kind)
+                && !self.trait_impl_items.contains(id))
+            .then_some((id, item, kind, name))
+        })
+    }
+
+    /// The path of an item with its own page, and its rustdoc JSON kind.
+    fn page(&self, id: &str) -> Option<(Vec<&'a str>, &'a str)> {
+        let summary = self.paths?.get(id)?;
+        let path = summary
+            .get("path")?
+            .as_array()?
+            .iter()
+            .filter_map(Value::as_str)
+            .collect();
+        Some((path, summary.get("kind")?.as_str()?))
+    }
+
+    /// The full path of an item, from `paths` or built from the item it's defined in.
+    fn full_name(&self, id: &str, depth: usize) -> Option<String> {
+        if let Some((path, _)) = self.page(id) {
+            return Some(path.join("::"));
+        }
+
+        let name = self.index.get(id)?.get("name")?.as_str()?;
+        match self.parents.get(id) {
+            Some(parent) if depth > 0 => {
+                Some(format!("{}::{name}", self.full_name(parent, depth - 1)?))
+            }
+            _ => Some(name.to_owned()),
+        }
+    }
+
+    /// The full path and the link of an item.
+    ///
+    /// Members link to the page of the item they are defined in, with an anchor
+    /// when that item has its own page.
+    fn locate(&self, id: &str, depth: usize) -> Option<(String, String)> {
+        if let Some((path, kind)) = self.page(id) {
+            let (name, module) = path.split_last()?;
+            let link = match item_type(kind, None, false)? {
+                "mod" => format!("{}/index.html", path.join("/")),
+                item_type => format!("{}/{item_type}.{name}.html", module.join("/")),
+            };
+            return Some((path.join("::"), link));
+        }
+
+        let item = self.index.get(id)?;
+        let name = item.get("name")?.as_str()?;
+        let parent = self.parents.get(id).filter(|_| depth > 0)?;
+        let (parent_path, parent_link) = self.locate(parent, depth - 1)?;
+        let link = if parent_link.contains('#') {
+            parent_link
+        } else {
+            let kind = kind_of(item)?;
+            let item_type = item_type(kind, inner_of(item, kind), true)?;
+            format!("{parent_link}#{item_type}.{name}")
+        };
+        Some((format!("{parent_path}::{name}"), link))
+    }
+}
+
+/// Find the public items of the crate that have no documentation.
+fn undocumented_items(krate: &CrateIndex<'_>) -> Vec<UndocumentedItem> {
+    let mut items = Vec::new();
+    for (id, item, kind, name) in krate.public_items() {
+        let documented = item.get("docs").is_some_and(|docs| !docs.is_null());
+        // fields of tuple structs
+        if documented || name.parse::<usize>().is_ok() {
+            continue;
+        }
+
+        let Some(span) = item.get("span") else {
+            continue;
+        };
+        let Some(path) = span.get("filename").and_then(Value::as_str) else {
+            continue;
+        };
+        let line = span
+            .get("begin")
+            .and_then(|begin| begin.get(0))
+            .and_then(Value::as_i64)
+            .unwrap_or(0);
+
+        items.push(UndocumentedItem {
+            path: path.to_owned(),
+            line: line as i32,
+            name: krate.full_name(id, 8).unwrap_or_else(|| name.to_owned()),
+            kind: kind.to_owned(),
+        });
+    }
+
+    items.sort_by(|a, b| (&a.path, a.line, &a.name).cmp(&(&b.path, b.line, &b.name)));
+    items.truncate(MAX_UNDOCUMENTED_ITEMS);
+    items
+}
+
+/// Find the public items of the crate that are part of its documentation.
+fn public_items(krate: &CrateIndex<'_>) -> Vec<PublicItem> {
+    let mut items = Vec::new();
+    for (id, item, kind, _) in krate.public_items() {
+        let page = krate.page(id);
+        // the root module is the crate itself
+        if page.as_ref().is_some_and(|(path, _)| path.len() < 2) {
+            continue;
+        }
+        let inner = inner_of(item, kind);
+        let Some(item_type) = item_type(kind, inner, page.is_none()) else {
+            continue;
+        };
+        let Some((path, link)) = krate.locate(id, 8) else {
+            continue;
+        };
+
+        items.push(PublicItem {
+            path,
+            kind: item_type.to_owned(),
+            link,
+            signature: signature_hash(inner),
+        });
+    }
+
+    if items.len() > MAX_PUBLIC_ITEMS {
+        return Vec::new();
+    }
+    items.sort_by(|a, b| (&a.path, &a.kind).cmp(&(&b.path, &b.kind)));
+    items.dedup_by(|a, b| a.path == b.path && a.kind == b.kind);
+    items
+}
+
+/// Read the public and the undocumented items of a crate from its rustdoc JSON output.
+///
+/// Items of trait implementations are skipped, they use the documentation of the trait and
+/// are part of the API through the trait.
+pub(crate) fn read_rustdoc_json(json: &[u8]) -> Result<RustdocItems> {
+    let krate: Value = serde_json::from_slice(json)?;
+    let index = CrateIndex::new(&krate)?;
+    Ok(RustdocItems {
+        public_items: public_items(&index),
+        undocumented_items: undocumented_items(&index),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn item(name: &str, kind: &str, docs: Option<&str>, line: i64, inner: Value) -> Value {
+        json!({
+            "crate_id": 0,
+            "name": name,
+            "docs": docs,
+            "visibility": "public",
+            "span": { "filename": "src/lib.rs", "begin": [line, 0], "end": [line, 10] },
+            "inner": { kind: inner },
+        })
+    }
+
+    /// A crate with the struct `Foo` with two fields, an inherent method and a trait
+    /// implementation, the enum `Baz` with a tuple variant, and the function `bar`.
+    fn krate(new_signature: Value) -> Value {
+        json!({
+            "root": 0,
+            "index": {
+                "0": item("foo", "module", Some("crate docs"), 1, json!({ "items": [1, 2, 5] })),
+                "1": item("Foo", "struct", None, 3, json!({
+                    "kind": { "plain": { "fields": [3, 4] } },
+                    "generics": { "params": [] },
+                })),
+                "2": item("bar", "function", Some("docs"), 10, json!({})),
+                "3": item("documented", "struct_field", Some("docs"), 4, json!({})),
+                "4": item("field", "struct_field", None, 5, json!({})),
+                "5": item("Baz", "enum", Some("docs"), 12, json!({ "variants": [6] })),
+                "6": item("Variant", "variant", None, 13, json!({ "kind": { "tuple": [12] } })),
+                "7": item("new", "function", None, 20, new_signature),
+                "8": {
+                    "crate_id": 0,
+                    "name": null,
+                    "docs": null,
+                    "visibility": "default",
+                    "span": { "filename": "src/lib.rs", "begin": [19, 0], "end": [25, 1] },
+                    "inner": { "impl": {
+                        "trait": null,
+                        "for": { "resolved_path": { "name": "Foo", "id": 1 } },
+                        "items": [7],
+                    } },
+                },
+                "9": item("clone", "function", None, 30, json!({})),
+                "10": {
+                    "crate_id": 0,
+                    "name": null,
+                    "docs": null,
+                    "visibility": "default",
+                    "span": { "filename": "src/lib.rs", "begin": [29, 0], "end": [31, 1] },
+                    "inner": { "impl": {
+                        "trait": { "name": "Clone", "id": 20 },
+                        "for": { "resolved_path": { "name": "Foo", "id": 1 } },
+                        "items": [9],
+                    } },
+                },
+                "11": {
+                    "crate_id": 1,
+                    "name": "External",
+                    "docs": null,
+                    "visibility": "public",
+                    "inner": { "struct": {} },
+                },
+                "12": item("0", "struct_field", None, 13, json!({})),
+            },
+            "paths": {
+                "0": { "crate_id": 0, "path": ["foo"], "kind": "module" },
+                "1": { "crate_id": 0, "path": ["foo", "Foo"], "kind": "struct" },
+                "2": { "crate_id": 0, "path": ["foo", "bar"], "kind": "function" },
+                "5": { "crate_id": 0, "path": ["foo", "Baz"], "kind": "enum" },
+            },
+        })
+    }
+
+    fn read(krate: &Value) -> RustdocItems {
+        read_rustdoc_json(&serde_json::to_vec(krate).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn finds_undocumented_items() {
+        let items = read(&krate(json!({}))).undocumented_items;
+        let items: Vec<_> = items
+            .iter()
+            .map(|item| (item.line, item.name.as_str(), item.kind.as_str()))
+            .collect();
+        assert_eq!(
+            items,
+            vec![
+                (3, "foo::Foo", "struct"),
+                (5, "foo::Foo::field", "struct_field"),
+                (13, "foo::Baz::Variant", "variant"),
+                (20, "foo::Foo::new", "function"),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_public_items() {
+        let items = read(&krate(json!({}))).public_items;
+        let items: Vec<_> = items
+            .iter()
+            .map(|item| (item.path.as_str(), item.kind.as_str(), item.link.as_str()))
+            .collect();
+        assert_eq!(
+            items,
+            vec![
+                ("foo::Baz", "enum", "foo/enum.Baz.html"),
+                (
+                    "foo::Baz::Variant",
+                    "variant",
+                    "foo/enum.Baz.html#variant.Variant"
+                ),
+                (
+                    "foo::Baz::Variant::0",
+                    "structfield",
+                    "foo/enum.Baz.html#variant.Variant"
+                ),
+                ("foo::Foo", "struct", "foo/struct.Foo.html"),
+                (
+                    "foo::Foo::documented",
+                    "structfield",
+                    "foo/struct.Foo.html#structfield.documented"
+                ),
+                (
+                    "foo::Foo::field",
+                    "structfield",
+                    "foo/struct.Foo.html#structfield.field"
+                ),
+                ("foo::Foo::new", "method", "foo/struct.Foo.html#method.new"),
+                ("foo::bar", "fn", "foo/fn.bar.html"),
+            ]
+        );
+    }
+
+    #[test]
+    fn signatures() {
+        let signature = |krate: &Value, path: &str| {
+            read(krate)
+                .public_items
+                .into_iter()
+                .find(|item| item.path == path)
+                .unwrap()
+                .signature
+        };
+        let old = krate(json!({ "decl": { "inputs": [], "output": null } }));
+        let new = krate(json!({
+            "decl": { "inputs": [["x", { "primitive": "u8" }]], "output": null }
+        }));
+        assert_ne!(
+            signature(&old, "foo::Foo::new"),
+            signature(&new, "foo::Foo::new")
+        );
+        assert_eq!(signature(&old, "foo::Foo"), signature(&new, "foo::Foo"));
+
+        // IDs and members don't change the signature
+        let mut renumbered = old.clone();
+        renumbered["index"]["1"]["inner"]["struct"]["kind"]["plain"]["fields"] = json!([4, 3, 99]);
+        renumbered["index"]["6"]["inner"]["variant"]["kind"]["tuple"] = json!([42]);
+        assert_eq!(
+            signature(&old, "foo::Foo"),
+            signature(&renumbered, "foo::Foo")
+        );
+        assert_eq!(
+            signature(&old, "foo::Baz::Variant"),
+            signature(&renumbered, "foo::Baz::Variant")
+        );
+    }
+
+    #[test]
+    fn invalid_json() {
+        assert!(read_rustdoc_json(b"{}").is_err());
+        assert!(read_rustdoc_json(b"not json").is_err());
+    }
+}
@@ -1,18 +1,29 @@
+use crate::build_queue::BuildLease;
+use crate::db::blacklist::is_blacklisted;
 use crate::db::file::add_path_into_database;
-use crate::db::{
-    add_build_into_database, add_doc_coverage, add_package_into_database,
-    add_path_into_remote_archive, update_crate_data_in_database, Pool,
+use crate::db::{add_path_into_remote_archive, PackageDocs, Pool};
+use crate::docbuilder::{
+    crates::crates_from_path,
+    failure_category::classify_failure,
+    read_search_index,
+    report::{record_build, BuildReport},
+    resource_usage::{self, ResourceUsage, SandboxMarker},
+    running_build::RunningBuild,
+    rustdoc_json::{read_rustdoc_json, RustdocItems, UndocumentedItem},
+    worker::{LeasedBuild, WorkerClient},
+    FailureCategory, Limits,
 };
-use crate::docbuilder::{crates::crates_from_path, Limits};
 use crate::error::Result;
+use crate::index::{split_qualified_crate_name, AlternativeIndexes};
 use crate::repositories::RepositoryStatsUpdater;
-use crate::storage::{rustdoc_archive_path, source_archive_path};
+use crate::storage::{
+    rustdoc_archive_path, rustdoc_json_path, source_archive_path, CompressionAlgorithm,
+};
 use crate::utils::{
     copy_dir_all, parse_rustc_version, queue_builder, report_error, set_config, CargoMetadata,
     ConfigName,
 };
 use crate::RUSTDOC_STATIC_STORAGE_PREFIX;
-use crate::{db::blacklist::is_blacklisted, utils::MetadataPackage};
 use crate::{Config, Context, Index, InstanceMetrics, Storage};
 use anyhow::{anyhow, bail, Context as _, Error};
 use docsrs_metadata::{BuildTargets, Metadata, DEFAULT_TARGETS, HOST_TARGET};
@@ -23,6 +34,7 @@ use rustwide::cmd::{Command, CommandError, SandboxBuilder, SandboxImage};
 use rustwide::logging::{self, LogStorage};
 use rustwide::toolchain::ToolchainError;
 use rustwide::{AlternativeRegistry, Build, Crate, Toolchain, Workspace, WorkspaceBuilder};
+use serde_json::Value;
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use std::sync::Arc;
@@ -43,17 +55,127 @@ pub struct RustwideBuilder {
     workspace: Workspace,
     toolchain: Toolchain,
     config: Arc<Config>,
+    backend: Backend,
+    index: Arc<Index>,
+    alternative_indexes: Arc<AlternativeIndexes>,
+    rustc_version: String,
+    skip_build_if_exists: bool,
+    sandbox_marker: SandboxMarker,
+}
+
+/// Where the builder gets its builds from and puts their results.
+enum Backend {
+    /// Builds from the queue in the database, with the results going straight into the database
+    /// and storage.
+    Local(LocalBackend),
+    /// Builds leased from the build server, which the results are sent to.
+    Worker(Arc<WorkerClient>),
+}
+
+struct LocalBackend {
     db: Pool,
     storage: Arc<Storage>,
     metrics: Arc<InstanceMetrics>,
-    index: Arc<Index>,
-    rustc_version: String,
     repository_stats_updater: Arc<RepositoryStatsUpdater>,
-    skip_build_if_exists: bool,
+}
+
+/// Where the results of a single build go.
+enum BuildOutput<'a> {
+    Local(&'a LocalBackend),
+    Worker(&'a WorkerClient, &'a BuildLease),
+}
+
+impl BuildOutput<'_> {
+    fn store_rustdoc_archive(
+        &self,
+        name: &str,
+        version: &str,
+        dir: &Path,
+    ) -> Result<(Value, CompressionAlgorithm)> {
+        match self {
+            Self::Local(local) => add_path_into_remote_archive(
+                &local.storage,
+                &rustdoc_archive_path(name, version),
+                dir,
+                true,
+            ),
+            Self::Worker(client, lease) => client.upload_archive(lease, "rustdoc", dir),
+        }
+    }
+
+    fn store_source_archive(
+        &self,
+        name: &str,
+        version: &str,
+        dir: &Path,
+    ) -> Result<(Value, CompressionAlgorithm)> {
+        match self {
+            Self::Local(local) => add_path_into_remote_archive(
+                &local.storage,
+                &source_archive_path(name, version),
+                dir,
+                false,
+            ),
+            Self::Worker(client, lease) => client.upload_archive(lease, "sources", dir),
+        }
+    }
+
+    fn store_rustdoc_json(
+        &self,
+        name: &str,
+        version: &str,
+        target: &str,
+        content: Vec<u8>,
+    ) -> Result<()> {
+        match self {
+            Self::Local(local) => {
+                local
+                    .storage
+                    .store_one(rustdoc_json_path(name, version, target), content)?;
+            }
+            Self::Worker(client, lease) => client.upload_rustdoc_json(lease, target, content)?,
+        }
+        Ok(())
+    }
+
+    /// Adds the build to the database, returning whether it was successful.
+    fn record(&self, report: BuildReport) -> Result<bool> {
+        match self {
+            Self::Local(local) => record_build(
+                &mut *local.db.get()?,
+                &local.storage,
+                &local.metrics,
+                &local.repository_stats_updater,
+                report,
+                hostname::get()?.to_str().unwrap_or(""),
+            ),
+            Self::Worker(client, lease) => {
+                let successful = report.result.successful;
+                client.complete(lease, &report)?;
+                Ok(successful)
+            }
+        }
+    }
 }
 
 impl RustwideBuilder {
     pub fn init(context: &dyn Context) -> Result<Self> {
+        let backend = Backend::Local(LocalBackend {
+            db: context.pool()?,
+            storage: context.storage()?,
+            metrics: context.instance_metrics()?,
+            repository_stats_updater: context.repository_stats_updater()?,
+        });
+        Self::init_with_backend(context, backend)
+    }
+
+    /// Creates a builder for a build worker, which leases its builds from the build server
+    /// instead of using the database.
+    pub fn init_worker(context: &dyn Context, client: Arc<WorkerClient>) -> Result<Self> {
+        Self::init_with_backend(context, Backend::Worker(client))
+    }
+
+    fn init_with_backend(context: &dyn Context, backend: Backend) -> Result<Self> {
         let config = context.config()?;
 
         let mut builder = WorkspaceBuilder::new(&config.rustwide_workspace, USER_AGENT)
@@ -87,28 +209,36 @@ impl RustwideBuilder {
         };
 
         Ok(RustwideBuilder {
+            sandbox_marker: SandboxMarker::new(&config.rustwide_workspace),
             workspace,
             toolchain,
             config,
-            db: context.pool()?,
-            storage: context.storage()?,
-            metrics: context.instance_metrics()?,
+            backend,
             index: context.index()?,
+            alternative_indexes: context.alternative_indexes()?,
             rustc_version: String::new(),
-            repository_stats_updater: context.repository_stats_updater()?,
             skip_build_if_exists: false,
         })
     }
 
+    fn local(&self) -> Result<&LocalBackend> {
+        match &self.backend {
+            Backend::Local(local) => Ok(local),
+            Backend::Worker(_) => bail!("build workers don't have access to the database"),
+        }
+    }
+
     pub fn set_skip_build_if_exists(&mut self, should: bool) {
         self.skip_build_if_exists = should;
     }
 
     fn prepare_sandbox(&self, limits: &Limits) -> SandboxBuilder {
-        SandboxBuilder::new()
-            .cpu_limit(self.config.build_cpu_limit.map(|limit| limit as f32))
-            .memory_limit(Some(limits.memory()))
-            .enable_networking(limits.networking())
+        self.sandbox_marker.mount(
+            SandboxBuilder::new()
+                .cpu_limit(self.config.build_cpu_limit.map(|limit| limit as f32))
+                .memory_limit(Some(limits.memory()))
+                .enable_networking(limits.networking()),
+        )
     }
 
     pub fn purge_caches(&self) -> Result<()> {
@@ -227,8 +357,12 @@ impl RustwideBuilder {
 
         info!("building a dummy crate to get essential files");
 
-        let mut conn = self.db.get()?;
-        let limits = Limits::for_crate(&self.config, &mut conn, DUMMY_CRATE_NAME)?;
+        let limits = match &self.backend {
+            Backend::Local(local) => {
+                Limits::for_crate(&self.config, &mut *local.db.get()?, DUMMY_CRATE_NAME)?
+            }
+            Backend::Worker(_) => Limits::new(&self.config),
+        };
 
         let mut build_dir = self
             .workspace
@@ -245,8 +379,15 @@ impl RustwideBuilder {
                 (|| -> Result<()> {
                     let metadata = Metadata::from_crate_root(build.host_source_dir())?;
 
-                    let res =
-                        self.execute_build(HOST_TARGET, true, build, &limits, &metadata, true)?;
+                    let res = self.execute_build(
+                        HOST_TARGET,
+                        true,
+                        build,
+                        &limits,
+                        &metadata,
+                        true,
+                        None,
+                    )?;
                     if !res.result.successful {
                         bail!("failed to build dummy crate for {}", self.rustc_version);
                     }
@@ -264,24 +405,10 @@ impl RustwideBuilder {
                     // available at --static-root-path, we add files from that subdirectory, if present.
                     let static_files = dest.as_ref().join("static.files");
                     if static_files.try_exists()? {
-                        add_path_into_database(
-                            &self.storage,
-                            RUSTDOC_STATIC_STORAGE_PREFIX,
-                            &static_files,
-                        )?;
+                        self.store_essential_files(&static_files)?;
                     } else {
-                        add_path_into_database(
-                            &self.storage,
-                            RUSTDOC_STATIC_STORAGE_PREFIX,
-                            &dest,
-                        )?;
+                        self.store_essential_files(dest.as_ref())?;
                     }
-
-                    set_config(
-                        &mut conn,
-                        ConfigName::RustcVersion,
-                        self.rustc_version.clone(),
-                    )?;
                     Ok(())
                 })()
                 .map_err(|e| failure::Error::from_boxed_compat(e.into()))
@@ -295,6 +422,21 @@ impl RustwideBuilder {
         Ok(())
     }
 
+    fn store_essential_files(&self, dir: &Path) -> Result<()> {
+        match &self.backend {
+            Backend::Local(local) => {
+                add_path_into_database(&local.storage, RUSTDOC_STATIC_STORAGE_PREFIX, dir)?;
+                set_config(
+                    &mut *local.db.get()?,
+                    ConfigName::RustcVersion,
+                    self.rustc_version.clone(),
+                )?;
+            }
+            Backend::Worker(client) => client.upload_essential_files(&self.rustc_version, dir)?,
+        }
+        Ok(())
+    }
+
     pub fn build_world(&mut self) -> Result<()> {
         crates_from_path(
             &self.config.registry_index_path.clone(),
@@ -327,7 +469,8 @@ impl RustwideBuilder {
         version: &str,
         kind: PackageKind<'_>,
     ) -> Result<bool> {
-        let mut conn = self.db.get()?;
+        let db = self.local()?.db.clone();
+        let mut conn = db.get()?;
 
         if !self.should_build(&mut conn, name, version)? {
             return Ok(false);
@@ -337,12 +480,71 @@ impl RustwideBuilder {
 
         info!("building package {} {}", name, version);
 
-        if is_blacklisted(&mut conn, name)? {
+        if is_blacklisted(&mut *conn, name)? {
             info!("skipping build of {}, crate has been blacklisted", name);
             return Ok(false);
         }
 
         let limits = Limits::for_crate(&self.config, &mut conn, name)?;
+        drop(conn);
+
+        // removes the build from the running builds when it's dropped at the end of the build
+        let running_build = RunningBuild::start(&db, name, version)?;
+
+        let output = BuildOutput::Local(self.local()?);
+        self.build_crate(name, version, kind, &limits, Some(&running_build), &output)
+    }
+
+    /// Builds a crate leased from the build server, and sends the results to it.
+    ///
+    /// The toolchain has to be up to date already.
+    pub(crate) fn build_leased_package(&self, leased: &LeasedBuild) -> Result<bool> {
+        let Backend::Worker(client) = &self.backend else {
+            bail!("only build workers build leased crates");
+        };
+        let lease = &leased.lease;
+        info!("building leased package {} {}", lease.name, lease.version);
+
+        let kind = lease
+            .registry
+            .as_deref()
+            .map(PackageKind::Registry)
+            .unwrap_or(PackageKind::CratesIo);
+        // the build server lists the build, its log is sent with heartbeats
+        let running_build = RunningBuild::leased(client.clone(), lease.clone());
+
+        let output = BuildOutput::Worker(client, lease);
+        self.build_crate(
+            &lease.name,
+            &lease.version,
+            kind,
+            &leased.limits,
+            Some(&running_build),
+            &output,
+        )
+    }
+
+    fn build_crate(
+        &self,
+        name: &str,
+        version: &str,
+        kind: PackageKind<'_>,
+        limits: &Limits,
+        running_build: Option<&RunningBuild>,
+        output: &BuildOutput<'_>,
+    ) -> Result<bool> {
+        // crates of alternative registries are built under their qualified name, but the
+        // registry only knows them by their own name.
+        let (registry, crate_name) = split_qualified_crate_name(name);
+        let index = match registry {
+            Some(registry) => self
+                .alternative_indexes
+                .get(registry)
+                .cloned()
+                .with_context(|| format!("registry {registry} is not configured"))?,
+            None => self.index.clone(),
+        };
+
         #[cfg(target_os = "linux")]
         if !self.config.disable_memory_limit {
             use anyhow::Context;
@@ -369,9 +571,9 @@ impl RustwideBuilder {
         let is_local = matches!(kind, PackageKind::Local(_));
         let krate = match kind {
             PackageKind::Local(path) => Crate::local(path),
-            PackageKind::CratesIo => Crate::crates_io(name, version),
+            PackageKind::CratesIo => Crate::crates_io(crate_name, version),
             PackageKind::Registry(registry) => {
-                Crate::registry(AlternativeRegistry::new(registry), name, version)
+                Crate::registry(AlternativeRegistry::new(registry), crate_name, version)
             }
         };
         krate.fetch(&self.workspace).map_err(FailureError::compat)?;
@@ -381,7 +583,7 @@ impl RustwideBuilder {
             .tempdir()?;
 
         let successful = build_dir
-            .build(&self.toolchain, &krate, self.prepare_sandbox(&limits))
+            .build(&self.toolchain, &krate, self.prepare_sandbox(limits))
             .run(|build| {
                 let metadata = Metadata::from_crate_root(build.host_source_dir())?;
                 let BuildTargets {
@@ -396,10 +598,20 @@ impl RustwideBuilder {
                 (|| -> Result<bool> {
                     let mut has_docs = false;
                     let mut successful_targets = Vec::new();
+                    let mut json_targets = Vec::new();
+                    let mut search_items = Vec::new();
+                    let mut public_items = Vec::new();
 
                     // Perform an initial build
-                    let mut res =
-                        self.execute_build(default_target, true, build, &limits, &metadata, false)?;
+                    let mut res = self.execute_build(
+                        default_target,
+                        true,
+                        build,
+                        limits,
+                        &metadata,
+                        false,
+                        running_build,
+                    )?;
 
                     // If the build fails with the lockfile given, try using only the dependencies listed in Cargo.toml.
                     let cargo_lock = build.host_source_dir().join("Cargo.lock");
@@ -418,9 +630,10 @@ impl RustwideBuilder {
                             default_target,
                             true,
                             build,
-                            &limits,
+                            limits,
                             &metadata,
                             false,
+                            running_build,
                         )?;
                     }
 
@@ -435,6 +648,11 @@ impl RustwideBuilder {
                         }
                     }
 
+                    let mut build_logs = vec![(
+                        default_target.to_owned(),
+                        std::mem::take(&mut res.build_log),
+                    )];
+                    let mut resource_usage = vec![res.resource_usage.clone()];
                     let mut algs = HashSet::new();
                     if has_docs {
                         debug!("adding documentation for the default target to the database");
@@ -445,57 +663,72 @@ impl RustwideBuilder {
                             true,
                         )?;
 
+                        if let Some(lib_name) = res.cargo_metadata.root().library_name() {
+                            match read_search_index(local_storage.path(), &lib_name) {
+                                Ok(items) => search_items = items.unwrap_or_default(),
+                                Err(err) => report_error(&err.context(format!(
+                                    "could not read search index for {name} {version}"
+                                ))),
+                            }
+                        }
+
                         successful_targets.push(res.target.clone());
+                        let mut rustdoc_items = RustdocItems::default();
+                        self.build_json(
+                            name,
+                            version,
+                            default_target,
+                            build,
+                            limits,
+                            &metadata,
+                            &mut json_targets,
+                            Some(&mut rustdoc_items),
+                            output,
+                        );
+                        public_items = rustdoc_items.public_items;
+                        if let Some(coverage) = &mut res.doc_coverage {
+                            coverage.undocumented_items = rustdoc_items.undocumented_items;
+                        }
 
                         // Then build the documentation for all the targets
                         // Limit the number of targets so that no one can try to build all 200000 possible targets
                         for target in other_targets.into_iter().take(limits.targets()) {
                             debug!("building package {} {} for {}", name, version, target);
-                            self.build_target(
+                            let target_res = self.build_target(
+                                name,
+                                version,
                                 target,
                                 build,
-                                &limits,
+                                limits,
                                 local_storage.path(),
                                 &mut successful_targets,
+                                &mut json_targets,
                                 &metadata,
+                                running_build,
+                                output,
                             )?;
+                            build_logs.push((target.to_owned(), target_res.build_log));
+                            resource_usage.push(target_res.resource_usage);
                         }
-                        let (_, new_alg) = add_path_into_remote_archive(
-                            &self.storage,
-                            &rustdoc_archive_path(name, version),
-                            local_storage.path(),
-                            true,
-                        )?;
+                        let (_, new_alg) =
+                            output.store_rustdoc_archive(name, version, local_storage.path())?;
                         algs.insert(new_alg);
                     };
 
                     // Store the sources even if the build fails
                     debug!("adding sources into database");
                     let files_list = {
-                        let (files_list, new_alg) = add_path_into_remote_archive(
-                            &self.storage,
-                            &source_archive_path(name, version),
-                            build.host_source_dir(),
-                            false,
-                        )?;
+                        let (files_list, new_alg) =
+                            output.store_source_archive(name, version, &build.host_source_dir())?;
                         algs.insert(new_alg);
                         files_list
                     };
 
                     let has_examples = build.host_source_dir().join("examples").is_dir();
-                    if res.result.successful {
-                        self.metrics.successful_builds.inc();
-                    } else if res.cargo_metadata.root().is_library() {
-                        self.metrics.failed_builds.inc();
-                    } else {
-                        self.metrics.non_library_builds.inc();
-                    }
-
                     let release_data = if !is_local {
-                        match self
-                            .index
+                        match index
                             .api()
-                            .get_release_data(name, version)
+                            .get_release_data(crate_name, version)
                             .with_context(|| {
                                 format!("could not fetch releases-data for {name}-{version}")
                             }) {
@@ -507,58 +740,48 @@ impl RustwideBuilder {
                         }
                     } else {
                         None
-                    }
-                    .unwrap_or_default();
-
-                    let cargo_metadata = res.cargo_metadata.root();
-                    let repository = self.get_repo(cargo_metadata)?;
-
-                    let release_id = add_package_into_database(
-                        &mut conn,
-                        cargo_metadata,
-                        &build.host_source_dir(),
-                        &res.result,
-                        &res.target,
-                        files_list,
-                        successful_targets,
-                        &release_data,
-                        has_docs,
-                        has_examples,
-                        algs,
-                        repository,
-                        true,
-                    )?;
-
-                    if let Some(doc_coverage) = res.doc_coverage {
-                        add_doc_coverage(&mut conn, release_id, doc_coverage)?;
-                    }
-
-                    let build_id = add_build_into_database(&mut conn, release_id, &res.result)?;
-                    let build_log_path = format!("build-logs/{build_id}/{default_target}.txt");
-                    self.storage.store_one(build_log_path, res.build_log)?;
+                    };
 
                     // Some crates.io crate data is mutable, so we proactively update it during a release
-                    if !is_local {
-                        match self.index.api().get_crate_data(name) {
-                            Ok(crate_data) => {
-                                update_crate_data_in_database(&mut conn, name, &crate_data)?
+                    let crate_data = if !is_local {
+                        match index.api().get_crate_data(crate_name) {
+                            Ok(crate_data) => Some(crate_data),
+                            Err(err) => {
+                                warn!("{:#?}", err);
+                                None
                             }
-                            Err(err) => warn!("{:#?}", err),
                         }
-                    }
+                    } else {
+                        None
+                    };
 
-                    if res.result.successful {
-                        // delete eventually existing files from pre-archive storage.
-                        // we're doing this in the end so eventual problems in the build
-                        // won't lead to non-existing docs.
-                        for prefix in &["rustdoc", "sources"] {
-                            let prefix = format!("{prefix}/{name}/{version}/");
-                            debug!("cleaning old storage folder {}", prefix);
-                            self.storage.delete_prefix(&prefix)?;
-                        }
+                    if registry.is_some() {
+                        res.cargo_metadata.root_mut().name = name.to_owned();
                     }
-
-                    Ok(res.result.successful)
+                    let docs =
+                        PackageDocs::read(res.cargo_metadata.root(), &build.host_source_dir());
+                    let (package, locked_dependencies) = res.cargo_metadata.into_parts();
+
+                    output.record(BuildReport {
+                        package,
+                        locked_dependencies,
+                        docs,
+                        result: res.result,
+                        default_target: res.target,
+                        source_files: files_list,
+                        doc_targets: successful_targets,
+                        json_targets,
+                        has_docs,
+                        has_examples,
+                        compression_algorithms: algs,
+                        doc_coverage: res.doc_coverage,
+                        search_items,
+                        public_items,
+                        build_logs,
+                        resource_usage,
+                        release_data,
+                        crate_data,
+                    })
                 })()
                 .map_err(|e| failure::Error::from_boxed_compat(e.into()))
             })
@@ -572,16 +795,23 @@ impl RustwideBuilder {
         Ok(successful)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn build_target(
         &self,
+        name: &str,
+        version: &str,
         target: &str,
         build: &Build,
         limits: &Limits,
         local_storage: &Path,
         successful_targets: &mut Vec<String>,
+        json_targets: &mut Vec<String>,
         metadata: &Metadata,
-    ) -> Result<()> {
-        let target_res = self.execute_build(target, false, build, limits, metadata, false)?;
+        running_build: Option<&RunningBuild>,
+        output: &BuildOutput<'_>,
+    ) -> Result<FullBuildResult> {
+        let target_res =
+            self.execute_build(target, false, build, limits, metadata, false, running_build)?;
         if target_res.result.successful {
             // Cargo is not giving any error and not generating documentation of some crates
             // when we use a target compile options. Check documentation exists before
@@ -590,9 +820,109 @@ impl RustwideBuilder {
                 debug!("adding documentation for target {} to the database", target,);
                 self.copy_docs(&build.host_target_dir(), local_storage, target, false)?;
                 successful_targets.push(target.to_string());
+                self.build_json(
+                    name,
+                    version,
+                    target,
+                    build,
+                    limits,
+                    metadata,
+                    json_targets,
+                    None,
+                    output,
+                );
             }
         }
-        Ok(())
+        Ok(target_res)
+    }
+
+    /// Build the rustdoc JSON output for a target whose HTML documentation was
+    /// already built successfully, and store it next to the documentation.
+    ///
+    /// A failing JSON build doesn't fail the whole build, the target is just
+    /// missing from `json_targets`.
+    ///
+    /// When `items` is given, the items read from the output are stored in it.
+    #[allow(clippy::too_many_arguments)]
+    fn build_json(
+        &self,
+        name: &str,
+        version: &str,
+        target: &str,
+        build: &Build,
+        limits: &Limits,
+        metadata: &Metadata,
+        json_targets: &mut Vec<String>,
+        items: Option<&mut RustdocItems>,
+        output: &BuildOutput<'_>,
+    ) {
+        match self.execute_json_build(
+            name, version, target, build, limits, metadata, items, output,
+        ) {
+            Ok(true) => json_targets.push(target.to_string()),
+            Ok(false) => info!(
+                "rustdoc JSON build for {} {} {} failed",
+                name, version, target
+            ),
+            Err(err) => report_error(&err.context(format!(
+                "error building rustdoc JSON for {name} {version} {target}"
+            ))),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn execute_json_build(
+        &self,
+        name: &str,
+        version: &str,
+        target: &str,
+        build: &Build,
+        limits: &Limits,
+        metadata: &Metadata,
+        items: Option<&mut RustdocItems>,
+        output: &BuildOutput<'_>,
+    ) -> Result<bool> {
+        let rustdoc_flags = vec!["--output-format".to_string(), "json".to_string()];
+
+        let successful = self
+            .prepare_command(build, target, metadata, limits, rustdoc_flags)?
+            .log_output(false)
+            .run()
+            .is_ok();
+        if !successful {
+            return Ok(false);
+        }
+
+        // Like the HTML docs, cargo puts the output of proc-macros into `target/doc`.
+        let json_dir = if metadata.proc_macro {
+            build.host_target_dir().join("doc")
+        } else {
+            build.host_target_dir().join(target).join("doc")
+        };
+
+        let json_file = std::fs::read_dir(&json_dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .find(|path| {
+                path.is_file() && path.extension().and_then(|ext| ext.to_str()) == Some("json")
+            })
+            .ok_or_else(|| anyhow!("no JSON output found in {}", json_dir.display()))?;
+
+        let json = std::fs::read(&json_file)?;
+        if let Some(items) = items {
+            match read_rustdoc_json(&json) {
+                Ok(read) => *items = read,
+                Err(err) => report_error(&err.context(format!(
+                    "could not read the items of the rustdoc JSON for {name} {version}"
+                ))),
+            }
+        }
+
+        debug!("storing rustdoc JSON from {}", json_file.display());
+        output.store_rustdoc_json(name, version, target, json)?;
+        // don't leave the file lying around for the next target.
+        std::fs::remove_file(&json_file)?;
+
+        Ok(true)
     }
 
     fn get_coverage(
@@ -621,6 +951,8 @@ impl RustwideBuilder {
             documented_items: 0,
             total_items_needing_examples: 0,
             items_with_examples: 0,
+            files: Vec::new(),
+            undocumented_items: Vec::new(),
         };
 
         self.prepare_command(build, target, metadata, limits, rustdoc_flags)?
@@ -630,17 +962,26 @@ impl RustwideBuilder {
                         Ok(parsed) => parsed,
                         Err(_) => return,
                     };
-                    for file in parsed.values() {
+                    for (path, file) in parsed {
                         coverage.total_items += file.total;
                         coverage.documented_items += file.with_docs;
                         coverage.total_items_needing_examples += file.total_examples;
                         coverage.items_with_examples += file.with_examples;
+                        coverage.files.push(FileDocCoverage {
+                            path,
+                            total_items: file.total,
+                            documented_items: file.with_docs,
+                            total_items_needing_examples: file.total_examples,
+                            items_with_examples: file.with_examples,
+                        });
                     }
                 }
             })
             .log_output(false)
             .run()?;
 
+        coverage.files.sort_by(|a, b| a.path.cmp(&b.path));
+
         Ok(
             if coverage.total_items == 0 && coverage.documented_items == 0 {
                 None
@@ -650,6 +991,7 @@ impl RustwideBuilder {
         )
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn execute_build(
         &self,
         target: &str,
@@ -658,6 +1000,7 @@ impl RustwideBuilder {
         limits: &Limits,
         metadata: &Metadata,
         create_essential_files: bool,
+        running_build: Option<&RunningBuild>,
     ) -> Result<FullBuildResult> {
         let cargo_metadata = CargoMetadata::load_from_rustwide(
             &self.workspace,
@@ -691,10 +1034,24 @@ impl RustwideBuilder {
             }
         };
 
-        let successful = logging::capture(&storage, || {
+        let run_build = || {
             self.prepare_command(build, target, metadata, limits, rustdoc_flags)
                 .and_then(|command| command.run().map_err(Error::from))
-                .is_ok()
+        };
+        // publish the log of crate builds while they run, so it can be followed on the queue page.
+        let (build_result, wall_time, peak_memory) =
+            resource_usage::measure(&self.sandbox_marker, || match running_build {
+                Some(running_build) => running_build.capture(target, &storage, run_build),
+                None => logging::capture(&storage, run_build),
+            });
+        let build_log = storage.to_string();
+        let successful = build_result.is_ok();
+        let failure_category = build_result.err().map(|err| {
+            classify_failure(
+                &cargo_metadata.root().name,
+                err.downcast_ref::<CommandError>(),
+                &build_log,
+            )
         });
 
         // For proc-macros, cargo will put the output in `target/doc`.
@@ -714,16 +1071,27 @@ impl RustwideBuilder {
             std::fs::rename(old_dir, new_dir)?;
         }
 
+        let resource_usage = ResourceUsage {
+            target: target.to_string(),
+            wall_time,
+            peak_memory,
+            output_size: resource_usage::dir_size(
+                &build.host_target_dir().join(target).join("doc"),
+            ),
+        };
+
         Ok(FullBuildResult {
             result: BuildResult {
                 rustc_version: self.rustc_version.clone(),
                 docsrs_version: format!("docsrs {}", crate::BUILD_VERSION),
                 successful,
+                failure_category,
             },
             doc_coverage,
             cargo_metadata,
-            build_log: storage.to_string(),
+            build_log,
             target: target.to_string(),
+            resource_usage,
         })
     }
 
@@ -748,9 +1116,7 @@ impl RustwideBuilder {
             // necessary).
             //
             // FIXME: host-only crates like proc-macros should probably not have this passed? but #1417 should make it OK
-            format!(
-                r#"--config=doc.extern-map.registries.crates-io="https://docs.rs/{{pkg_name}}/{{version}}/{target}""#
-            ),
+            extern_map_config(&self.config.public_url, target),
             // Enables the unstable rustdoc-scrape-examples feature. We are "soft launching" this feature on
             // docs.rs, but once it's stable we can remove this flag.
             "-Zrustdoc-scrape-examples".into(),
@@ -845,12 +1211,14 @@ impl RustwideBuilder {
             Ok(true)
         }
     }
+}
 
-    fn get_repo(&self, metadata: &MetadataPackage) -> Result<Option<i32>> {
-        self.repository_stats_updater
-            .load_repository(metadata)
-            .map_err(Into::into)
-    }
+/// The cargo argument making rustdoc link dependencies from crates.io to their documentation
+/// on this instance.
+fn extern_map_config(public_url: &str, target: &str) -> String {
+    format!(
+        r#"--config=doc.extern-map.registries.crates-io="{public_url}/{{pkg_name}}/{{version}}/{target}""#
+    )
 }
 
 struct FullBuildResult {
@@ -859,9 +1227,10 @@ struct FullBuildResult {
     cargo_metadata: CargoMetadata,
     doc_coverage: Option<DocCoverage>,
     build_log: String,
+    resource_usage: ResourceUsage,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct DocCoverage {
     /// The total items that could be documented in the current crate, used to calculate
     /// documentation coverage.
@@ -873,12 +1242,33 @@ pub(crate) struct DocCoverage {
     pub(crate) total_items_needing_examples: i32,
     /// The items of the crate that have a code example, used to calculate documentation coverage.
     pub(crate) items_with_examples: i32,
+    /// The same numbers for every source file, as reported by rustdoc.
+    pub(crate) files: Vec<FileDocCoverage>,
+    /// The public items without documentation, read from the rustdoc JSON output.
+    #[serde(default)]
+    pub(crate) undocumented_items: Vec<UndocumentedItem>,
 }
 
+/// Documentation coverage of a single source file.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct FileDocCoverage {
+    /// The path of the file, relative to the root of the crate.
+    pub(crate) path: String,
+    pub(crate) total_items: i32,
+    pub(crate) documented_items: i32,
+    pub(crate) total_items_needing_examples: i32,
+    pub(crate) items_with_examples: i32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
 pub(crate) struct BuildResult {
     pub(crate) rustc_version: String,
     pub(crate) docsrs_version: String,
     pub(crate) successful: bool,
+    /// Why the build failed, `None` for successful builds and builds from before the
+    /// classification.
+    #[serde(default)]
+    pub(crate) failure_category: Option<FailureCategory>,
 }
 
 #[cfg(test)]
@@ -917,6 +1307,14 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn extern_map_uses_public_url() {
+        assert_eq!(
+            extern_map_config("https://docs.example.com", "x86_64-unknown-linux-gnu"),
+            r#"--config=doc.extern-map.registries.crates-io="https://docs.example.com/{pkg_name}/{version}/x86_64-unknown-linux-gnu""#
+        );
+    }
+
     #[test]
     #[ignore]
     fn test_build_crate() {
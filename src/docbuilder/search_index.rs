@@ -0,0 +1,404 @@
+//! Extract the items of a crate from the `search-index.js` generated by rustdoc.
+
+use crate::error::Result;
+use anyhow::{bail, Context as _};
+use serde_json::Value;
+use std::{fs, path::Path};
+
+/// Names of rustdoc's `ItemType`s, indexed by their discriminant.
+///
+/// These are the same names rustdoc uses as prefix in the filenames of item pages,
+/// for example `struct.Foo.html`.
+const ITEM_TYPES: &[&str] = &[
+    "mod",
+    "externcrate",
+    "import",
+    "struct",
+    "enum",
+    "fn",
+    "type",
+    "static",
+    "trait",
+    "impl",
+    "tymethod",
+    "method",
+    "structfield",
+    "variant",
+    "macro",
+    "primitive",
+    "associatedtype",
+    "constant",
+    "associatedconstant",
+    "union",
+    "foreigntype",
+    "keyword",
+    "opaque",
+    "attr",
+    "derive",
+    "traitalias",
+];
+
+/// Item types we want to find in the item search.
+///
+/// Everything that is defined inside another item (methods, fields, variants, ...)
+/// is skipped, as are re-exports and impls.
+const SEARCHABLE_ITEM_TYPES: &[&str] = &[
+    "mod",
+    "struct",
+    "enum",
+    "fn",
+    "type",
+    "static",
+    "trait",
+    "macro",
+    "primitive",
+    "constant",
+    "union",
+    "foreigntype",
+    "keyword",
+    "opaque",
+    "attr",
+    "derive",
+    "traitalias",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct SearchIndexItem {
+    /// the name of the item, for example `Deserializer`
+    pub(crate) name: String,
+    /// the full path of the item, for example `serde::de::Deserializer`
+    pub(crate) path: String,
+    /// the rustdoc item type, for example `trait`
+    pub(crate) kind: String,
+    /// the path of the item page, relative to the rustdoc root,
+    /// for example `serde/de/trait.Deserializer.html`
+    pub(crate) link: String,
+}
+
+/// Find the `search-index*.js` in the rustdoc output directory and return the items
+/// defined in `crate_name`.
+///
+/// Returns `None` if there is no search index.
+pub(crate) fn read_search_index(
+    doc_dir: &Path,
+    crate_name: &str,
+) -> Result<Option<Vec<SearchIndexItem>>> {
+    for entry in fs::read_dir(doc_dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+        if file_name.starts_with("search-index") && file_name.ends_with(".js") {
+            let content = fs::read_to_string(entry.path())?;
+            return parse_search_index(&content, crate_name)
+                .with_context(|| format!("could not parse {file_name}"))
+                .map(Some);
+        }
+    }
+
+    Ok(None)
+}
+
+/// Parse the content of a `search-index.js` and return the items defined in `crate_name`.
+///
+/// Supports the formats generated by rustdoc since the search index was moved into
+/// a single JSON object (`var searchIndex = JSON.parse('...')`), including the later
+/// `new Map(...)` variant, as well as the older per-crate assignments
+/// (`searchIndex["name"] = {...};`).
+pub(crate) fn parse_search_index(content: &str, crate_name: &str) -> Result<Vec<SearchIndexItem>> {
+    let crate_name = crate_name.replace('-', "_");
+
+    let data = if let Some(start) = content.find("JSON.parse('") {
+        let rest = &content[start + "JSON.parse('".len()..];
+        let json = unescape_js_string(rest).context("unterminated JSON string")?;
+        let index: Value = serde_json::from_str(&json)?;
+
+        match index {
+            Value::Object(mut crates) => crates.remove(&crate_name),
+            Value::Array(crates) => crates.into_iter().find_map(|entry| match entry {
+                Value::Array(mut pair) if pair.len() == 2 && pair[0] == *crate_name => {
+                    Some(pair.remove(1))
+                }
+                _ => None,
+            }),
+            _ => bail!("unexpected search index format"),
+        }
+    } else {
+        let prefix = format!("searchIndex[\"{crate_name}\"] = ");
+        content
+            .lines()
+            .find_map(|line| line.strip_prefix(&prefix))
+            .map(|json| serde_json::from_str(json.trim_end().trim_end_matches(';')))
+            .transpose()?
+    };
+
+    let Some(data) = data else {
+        return Ok(Vec::new());
+    };
+
+    let raw_items = if data.get("n").is_some() {
+        columnar_items(&data)?
+    } else {
+        tuple_items(&data)?
+    };
+
+    Ok(raw_items
+        .into_iter()
+        .filter_map(|(ty, name, path)| {
+            let kind = *ITEM_TYPES.get(ty)?;
+            if !SEARCHABLE_ITEM_TYPES.contains(&kind) || name.is_empty() {
+                return None;
+            }
+            if path.is_empty() {
+                // the crate root itself
+                return None;
+            }
+
+            let dir = path.replace("::", "/");
+            let link = if kind == "mod" {
+                format!("{dir}/{name}/index.html")
+            } else {
+                format!("{dir}/{kind}.{name}.html")
+            };
+
+            Some(SearchIndexItem {
+                path: format!("{path}::{name}"),
+                name,
+                kind: kind.to_string(),
+                link,
+            })
+        })
+        .collect())
+}
+
+/// Items in the column-based format used since Rust 1.50.
+///
+/// * `t`: item types, either as array of numbers or as a string where each
+///   character is `'A' + type`.
+/// * `n`: item names.
+/// * `q`: item paths, either as array where an empty string means "same as the previous
+///   item", or as sparse list of `[index, path]` pairs.
+fn columnar_items(data: &Value) -> Result<Vec<(usize, String, String)>> {
+    let names = data["n"].as_array().context("missing names")?;
+
+    let types: Vec<usize> = match &data["t"] {
+        Value::String(types) => types
+            .bytes()
+            .map(|t| t.wrapping_sub(b'A') as usize)
+            .collect(),
+        Value::Array(types) => types
+            .iter()
+            .map(|t| t.as_u64().map(|t| t as usize))
+            .collect::<Option<_>>()
+            .context("invalid item types")?,
+        _ => bail!("missing item types"),
+    };
+
+    let mut paths = vec![None; names.len()];
+    if let Some(entries) = data["q"].as_array() {
+        for (idx, entry) in entries.iter().enumerate() {
+            match entry {
+                Value::String(path) if !path.is_empty() => {
+                    if let Some(slot) = paths.get_mut(idx) {
+                        *slot = Some(path.clone());
+                    }
+                }
+                Value::Array(pair) => {
+                    if let (Some(idx), Some(path)) = (
+                        pair.first().and_then(Value::as_u64),
+                        pair.get(1).and_then(Value::as_str),
+                    ) {
+                        if let Some(slot) = paths.get_mut(idx as usize) {
+                            *slot = Some(path.to_string());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut current_path = String::new();
+    Ok(names
+        .iter()
+        .zip(types)
+        .zip(paths)
+        .map(|((name, ty), path)| {
+            if let Some(path) = path {
+                current_path = path;
+            }
+            (
+                ty,
+                name.as_str().unwrap_or_default().to_string(),
+                current_path.clone(),
+            )
+        })
+        .collect())
+}
+
+/// Items in the tuple-based format used until Rust 1.50.
+///
+/// `i` is a list of `[type, name, path, description, parent, ...]`, where an empty
+/// path means "same as the previous item".
+fn tuple_items(data: &Value) -> Result<Vec<(usize, String, String)>> {
+    let items = data["i"].as_array().context("missing items")?;
+
+    let mut current_path = String::new();
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            let ty = item.get(0)?.as_u64()? as usize;
+            let name = item.get(1)?.as_str()?.to_string();
+            match item.get(2).and_then(Value::as_str) {
+                Some(path) if !path.is_empty() => current_path = path.to_string(),
+                _ => {}
+            }
+            Some((ty, name, current_path.clone()))
+        })
+        .collect())
+}
+
+/// Unescape a single-quoted JavaScript string literal, starting right after the
+/// opening quote.
+///
+/// Only handles the escapes rustdoc generates. Returns `None` when the string is
+/// not terminated.
+fn unescape_js_string(input: &str) -> Option<String> {
+    let mut result = String::with_capacity(input.len());
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => return Some(result),
+            '\\' => match chars.next()? {
+                // line continuation
+                '\n' => {}
+                'n' => result.push('\n'),
+                't' => result.push('\t'),
+                other => result.push(other),
+            },
+            c => result.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, path: &str, kind: &str, link: &str) -> SearchIndexItem {
+        SearchIndexItem {
+            name: name.into(),
+            path: path.into(),
+            kind: kind.into(),
+            link: link.into(),
+        }
+    }
+
+    #[test]
+    fn parse_string_types_and_sparse_paths() {
+        let content = r#"var searchIndex = JSON.parse('{\
+"other":{"doc":"","t":"F","n":["nope"],"q":[[0,"other"]]},\
+"my_crate":{"doc":"it\'s a crate","t":"ADIFALNNK","n":["my_crate","Foo","Deserializer","helper","inner","bar","A","B","de"],"q":[[1,"my_crate"],[3,"my_crate::inner"],[4,"my_crate"]],"d":["","","","","","","","",""],"i":[0,0,0,0,0,1,2,2,3],"f":"","c":"OjAAAAAAAAA=","p":[[3,"Foo"],[4,"Kind"],[8,"Deserializer"]]}\
+}');
+if (typeof window !== 'undefined' && window.initSearch) {window.initSearch(searchIndex)};
+if (typeof exports !== 'undefined') {exports.searchIndex = searchIndex};"#;
+
+        assert_eq!(
+            parse_search_index(content, "my-crate").unwrap(),
+            vec![
+                item("Foo", "my_crate::Foo", "struct", "my_crate/struct.Foo.html"),
+                item(
+                    "Deserializer",
+                    "my_crate::Deserializer",
+                    "trait",
+                    "my_crate/trait.Deserializer.html"
+                ),
+                item(
+                    "helper",
+                    "my_crate::inner::helper",
+                    "fn",
+                    "my_crate/inner/fn.helper.html"
+                ),
+                item(
+                    "inner",
+                    "my_crate::inner",
+                    "mod",
+                    "my_crate/inner/index.html"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_map_format() {
+        let content = r#"var searchIndex = new Map(JSON.parse('[\
+["my_crate",{"t":"OR","n":["mac","MAX"],"q":[[0,"my_crate"]]}]\
+]'));"#;
+
+        assert_eq!(
+            parse_search_index(content, "my_crate").unwrap(),
+            vec![
+                item("mac", "my_crate::mac", "macro", "my_crate/macro.mac.html"),
+                item(
+                    "MAX",
+                    "my_crate::MAX",
+                    "constant",
+                    "my_crate/constant.MAX.html"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_numeric_types_and_dense_paths() {
+        let content = r#"var searchIndex = JSON.parse('{\
+"my_crate":{"doc":"","t":[0,3,5,11,4],"n":["my_crate","Foo","run","bar","Kind"],"q":["","my_crate","my_crate::exec","",""],"d":["","","","",""],"i":[0,0,0,1,0],"f":[],"p":[[3,"Foo"]]}\
+}');"#;
+
+        assert_eq!(
+            parse_search_index(content, "my_crate").unwrap(),
+            vec![
+                item("Foo", "my_crate::Foo", "struct", "my_crate/struct.Foo.html"),
+                item(
+                    "run",
+                    "my_crate::exec::run",
+                    "fn",
+                    "my_crate/exec/fn.run.html"
+                ),
+                item(
+                    "Kind",
+                    "my_crate::exec::Kind",
+                    "enum",
+                    "my_crate/exec/enum.Kind.html"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_old_tuple_format() {
+        let content = r#"var searchIndex = {};
+searchIndex["my_crate"] = {"doc":"","items":[],"i":[[3,"Foo","my_crate","A struct",null,null],[11,"bar","","",0,null],[8,"Tr","my_crate::x","",null,null]],"paths":[[3,"Foo"]]};
+initSearch(searchIndex);"#;
+
+        assert_eq!(
+            parse_search_index(content, "my_crate").unwrap(),
+            vec![
+                item("Foo", "my_crate::Foo", "struct", "my_crate/struct.Foo.html"),
+                item("Tr", "my_crate::x::Tr", "trait", "my_crate/x/trait.Tr.html"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_missing_crate() {
+        let content = r#"var searchIndex = JSON.parse('{"other":{"t":"F","n":["f"],"q":[]}}');"#;
+        assert!(parse_search_index(content, "my_crate").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(parse_search_index("var searchIndex = JSON.parse('{", "my_crate").is_err());
+        assert!(parse_search_index("var searchIndex = JSON.parse('{]}');", "my_crate").is_err());
+    }
+}
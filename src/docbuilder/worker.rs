@@ -0,0 +1,318 @@
+//! Build workers, which build crates on machines without access to the database or the storage.
+//!
+//! A worker leases a queued build from the build server over HTTP and keeps the lease alive with
+//! heartbeats while it builds, which also carry the log of the running build so it can be
+//! followed on the queue page. The archives are uploaded to the build server as compressed zip
+//! files streamed from disk, and the [`BuildReport`] is sent once the build is done. Leases of
+//! workers that stop sending heartbeats expire, which puts their builds back into the queue.
+
+use crate::build_queue::BuildLease;
+use crate::docbuilder::{report::BuildReport, Limits};
+use crate::error::Result;
+use crate::storage::CompressionAlgorithm;
+use crate::Config;
+use anyhow::{anyhow, bail, Context as _};
+use chrono::{DateTime, Utc};
+use reqwest::{
+    blocking::{Body, RequestBuilder, Response},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+use std::time::Duration;
+use url::Url;
+use walkdir::WalkDir;
+
+/// Uploading the archives of large crates takes a while.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// A build leased to a worker, together with the limits to build it with.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct LeasedBuild {
+    pub(crate) lease: BuildLease,
+    pub(crate) limits: Limits,
+}
+
+/// The answer to a heartbeat, with the new expiry of the lease.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Heartbeat {
+    pub(crate) expires_at: DateTime<Utc>,
+}
+
+/// The current log of a running build, sent with a heartbeat.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct BuildLog {
+    pub(crate) target: String,
+    pub(crate) log: String,
+}
+
+/// An archive the build server stored for a worker.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct StoredArchive {
+    pub(crate) files: Value,
+    pub(crate) algorithm: CompressionAlgorithm,
+}
+
+/// Sent instead of a [`BuildReport`] when the build couldn't be run at all.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct BuildFailure {
+    pub(crate) error: String,
+}
+
+/// The client of a build worker for the worker endpoints of the build server.
+#[derive(Debug)]
+pub struct WorkerClient {
+    base: Url,
+    token: String,
+    max_retries: u32,
+    client: reqwest::blocking::Client,
+}
+
+impl WorkerClient {
+    pub fn new(config: &Config) -> Result<Self> {
+        let base = config
+            .worker_server_url
+            .as_deref()
+            .context("DOCSRS_WORKER_SERVER_URL is not set")?;
+        let token = config
+            .worker_token
+            .clone()
+            .context("DOCSRS_WORKER_TOKEN is not set")?;
+        Self::with_server(base, token, config.crates_io_api_call_retries)
+    }
+
+    pub(crate) fn with_server(base: &str, token: String, max_retries: u32) -> Result<Self> {
+        Ok(Self {
+            base: Url::parse(base).with_context(|| format!("invalid build server url {base}"))?,
+            token,
+            max_retries,
+            client: reqwest::blocking::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()?,
+        })
+    }
+
+    fn post(&self, path: &str) -> Result<RequestBuilder> {
+        Ok(self
+            .client
+            .post(self.base.join(&format!("/_/worker/{path}"))?)
+            .bearer_auth(&self.token))
+    }
+
+    /// Sends the request, retrying errors of the connection but not of the build server.
+    fn send(&self, request: impl Fn() -> Result<RequestBuilder>) -> Result<Response> {
+        crate::utils::retry(|| Ok(request()?.send()?), self.max_retries)
+    }
+
+    /// Leases the next build from the queue, `None` when there is nothing to build.
+    pub(crate) fn lease(&self) -> Result<Option<LeasedBuild>> {
+        let response = self
+            .send(|| self.post("lease"))?
+            .error_for_status()
+            .context("could not lease a build")?;
+        if response.status() == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        Ok(Some(response.json()?))
+    }
+
+    /// Renews the lease, returning its new expiry, or `None` when the lease was lost.
+    ///
+    /// The build server publishes `log` as the current log of the build.
+    pub(crate) fn heartbeat(
+        &self,
+        lease: &BuildLease,
+        log: Option<&BuildLog>,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let response = self.send(|| {
+            let request = self.post(&format!("builds/{}/heartbeat", lease.id))?;
+            Ok(match log {
+                Some(log) => request.json(log),
+                None => request,
+            })
+        })?;
+        if response.status() == StatusCode::CONFLICT {
+            return Ok(None);
+        }
+        let heartbeat: Heartbeat = lease_response(response)?.json()?;
+        Ok(Some(heartbeat.expires_at))
+    }
+
+    /// Uploads the `rustdoc` or `sources` archive of the build, created from `dir`.
+    pub(crate) fn upload_archive(
+        &self,
+        lease: &BuildLease,
+        kind: &str,
+        dir: &Path,
+    ) -> Result<(Value, CompressionAlgorithm)> {
+        let archive = zip_directory(dir)?;
+        let response = self.send(|| {
+            Ok(self
+                .post(&format!("builds/{}/archives/{kind}", lease.id))?
+                .body(file_body(&archive)?))
+        })?;
+        let archive: StoredArchive = lease_response(response)
+            .with_context(|| format!("could not upload {kind} archive"))?
+            .json()?;
+        Ok((archive.files, archive.algorithm))
+    }
+
+    /// Uploads the rustdoc JSON output of the build for `target`.
+    pub(crate) fn upload_rustdoc_json(
+        &self,
+        lease: &BuildLease,
+        target: &str,
+        content: Vec<u8>,
+    ) -> Result<()> {
+        let response = self.send(|| {
+            Ok(self
+                .post(&format!("builds/{}/rustdoc-json/{target}", lease.id))?
+                .body(content.clone()))
+        })?;
+        lease_response(response)
+            .with_context(|| format!("could not upload rustdoc JSON for {target}"))?;
+        Ok(())
+    }
+
+    /// Finishes the lease with the report of the build.
+    pub(crate) fn complete(&self, lease: &BuildLease, report: &BuildReport) -> Result<()> {
+        let response = self.send(|| {
+            Ok(self
+                .post(&format!("builds/{}/complete", lease.id))?
+                .json(report))
+        })?;
+        lease_response(response).context("could not report the build")?;
+        Ok(())
+    }
+
+    /// Finishes the lease of a build that couldn't be run, which is retried later.
+    pub(crate) fn fail(&self, lease: &BuildLease, error: &str) -> Result<()> {
+        let failure = BuildFailure {
+            error: error.to_owned(),
+        };
+        let response = self.send(|| {
+            Ok(self
+                .post(&format!("builds/{}/fail", lease.id))?
+                .json(&failure))
+        })?;
+        lease_response(response).context("could not report the failed build")?;
+        Ok(())
+    }
+
+    /// Uploads the static rustdoc files of `rustc_version`, found in `dir`.
+    pub(crate) fn upload_essential_files(&self, rustc_version: &str, dir: &Path) -> Result<()> {
+        let archive = zip_directory(dir)?;
+        self.send(|| {
+            Ok(self
+                .post("essential-files")?
+                .query(&[("rustc_version", rustc_version)])
+                .body(file_body(&archive)?))
+        })?
+        .error_for_status()
+        .context("could not upload the essential files")?;
+        Ok(())
+    }
+}
+
+/// Checks the response to a request about a lease, which fails when the lease was lost.
+fn lease_response(response: Response) -> Result<Response> {
+    if response.status() == StatusCode::CONFLICT {
+        bail!("the lease of the build was lost");
+    }
+    Ok(response.error_for_status()?)
+}
+
+/// Streams `file` from its start as request body, so retries send the whole file again.
+fn file_body(file: &File) -> Result<Body> {
+    let mut file = file.try_clone()?;
+    file.rewind()?;
+    let size = file.metadata()?.len();
+    Ok(Body::sized(file, size))
+}
+
+/// Packs all files below `dir` into a compressed zip file on disk, which is deleted once it's
+/// dropped.
+pub(crate) fn zip_directory(dir: &Path) -> Result<File> {
+    let mut zip = zip::ZipWriter::new(tempfile::tempfile()?);
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Bzip2);
+
+    for entry in WalkDir::new(dir) {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = entry.path().strip_prefix(dir)?;
+        let name = path
+            .to_str()
+            .ok_or_else(|| anyhow!("invalid file name {}", path.display()))?;
+        zip.start_file(name.replace('\\', "/"), options)?;
+        std::io::copy(&mut File::open(entry.path())?, &mut zip)?;
+    }
+
+    Ok(zip.finish()?)
+}
+
+/// Unpacks a zip file created by [`zip_directory`] into `dir`.
+pub(crate) fn unzip_into(zip: impl Read + Seek, dir: &Path) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(zip)?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let path = file
+            .enclosed_name()
+            .map(|path| dir.join(path))
+            .ok_or_else(|| anyhow!("invalid path in archive: {}", file.name()))?;
+        if file.is_dir() {
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::io::copy(&mut file, &mut File::create(path)?)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn zip_roundtrip() -> Result<()> {
+        let source = tempfile::tempdir()?;
+        std::fs::create_dir_all(source.path().join("foo/bar"))?;
+        std::fs::write(source.path().join("index.html"), "<html>")?;
+        std::fs::write(source.path().join("foo/bar/lib.rs"), "fn main() {}")?;
+
+        let dest = tempfile::tempdir()?;
+        unzip_into(zip_directory(source.path())?, dest.path())?;
+
+        assert_eq!(
+            std::fs::read_to_string(dest.path().join("index.html"))?,
+            "<html>"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dest.path().join("foo/bar/lib.rs"))?,
+            "fn main() {}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unzip_rejects_paths_outside_of_the_directory() -> Result<()> {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("../escaped.txt", zip::write::FileOptions::default())?;
+        zip.write_all(b"nope")?;
+        let zip = zip.finish()?;
+
+        let dir = tempfile::tempdir()?;
+        let dest = dir.path().join("dest");
+        assert!(unzip_into(zip, &dest).is_err());
+        assert!(!dir.path().join("escaped.txt").exists());
+        Ok(())
+    }
+}
@@ -1,12 +1,15 @@
 use crate::{error::Result, utils::retry};
 use anyhow::{anyhow, Context};
 use chrono::{DateTime, Utc};
-use reqwest::header::{HeaderValue, ACCEPT, USER_AGENT};
+use reqwest::{
+    header::{HeaderValue, ACCEPT, USER_AGENT},
+    StatusCode,
+};
 use semver::Version;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use url::Url;
 
-const APP_USER_AGENT: &str = concat!(
+pub(super) const APP_USER_AGENT: &str = concat!(
     env!("CARGO_PKG_NAME"),
     " ",
     include_str!(concat!(env!("OUT_DIR"), "/git_version"))
@@ -19,12 +22,12 @@ pub struct Api {
     client: reqwest::blocking::Client,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CrateData {
     pub(crate) owners: Vec<CrateOwner>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct ReleaseData {
     pub(crate) release_time: DateTime<Utc>,
     pub(crate) yanked: bool,
@@ -41,7 +44,7 @@ impl Default for ReleaseData {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CrateOwner {
     pub(crate) avatar: String,
     pub(crate) login: String,
@@ -139,6 +142,122 @@ impl Api {
         Ok((version.created_at, version.yanked, version.downloads))
     }
 
+    /// The versions of a crate according to the registry's API, `None` when the crate doesn't
+    /// exist.
+    ///
+    /// The index alone can't tell a deleted crate from a stale or failing CDN, so deletions are
+    /// confirmed with this.
+    pub(crate) fn get_crate_versions(&self, name: &str) -> Result<Option<Vec<String>>> {
+        let url = {
+            let mut url = self.api_base()?;
+            url.path_segments_mut()
+                .map_err(|()| anyhow!("Invalid API url"))?
+                .extend(&["api", "v1", "crates", name, "versions"]);
+            url
+        };
+
+        #[derive(Deserialize)]
+        struct Response {
+            versions: Vec<VersionData>,
+        }
+
+        #[derive(Deserialize)]
+        struct VersionData {
+            num: String,
+        }
+
+        let response = retry(
+            || {
+                let response = self.client.get(url.clone()).send()?;
+                if response.status() == StatusCode::NOT_FOUND {
+                    return Ok(None);
+                }
+                Ok(Some(response.error_for_status()?))
+            },
+            self.max_retries,
+        )?;
+        let Some(response) = response else {
+            return Ok(None);
+        };
+
+        let response: Response = response.json()?;
+        Ok(Some(
+            response
+                .versions
+                .into_iter()
+                .map(|version| version.num)
+                .collect(),
+        ))
+    }
+
+    /// The names of the crates updated after `since` according to the registry's API, with the
+    /// time of their last update, oldest first.
+    ///
+    /// The pages are followed through the `next_page` link of every response, which carries a
+    /// `seek` cursor on crates.io. Unlike page numbers, the cursor doesn't shift when crates are
+    /// updated while the pages are fetched.
+    pub(crate) fn get_crates_updated_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<(String, DateTime<Utc>)>> {
+        #[derive(Deserialize)]
+        struct Response {
+            crates: Vec<CrateUpdate>,
+            #[serde(default)]
+            meta: Meta,
+        }
+
+        #[derive(Deserialize)]
+        struct CrateUpdate {
+            name: String,
+            updated_at: DateTime<Utc>,
+        }
+
+        #[derive(Default, Deserialize)]
+        struct Meta {
+            #[serde(default)]
+            next_page: Option<String>,
+        }
+
+        let mut url = self.api_base()?;
+        url.path_segments_mut()
+            .map_err(|()| anyhow!("Invalid API url"))?
+            .extend(&["api", "v1", "crates"]);
+        url.query_pairs_mut()
+            .append_pair("sort", "recent-updates")
+            .append_pair("per_page", "100");
+
+        let mut updates = Vec::new();
+        loop {
+            let response: Response = retry(
+                || Ok(self.client.get(url.clone()).send()?.error_for_status()?),
+                self.max_retries,
+            )?
+            .json()?;
+
+            // the crates are sorted by their last update, newest first
+            let page_len = response.crates.len();
+            let before = updates.len();
+            updates.extend(
+                response
+                    .crates
+                    .into_iter()
+                    .take_while(|krate| krate.updated_at > since)
+                    .map(|krate| (krate.name, krate.updated_at)),
+            );
+
+            match response.meta.next_page {
+                Some(next_page) if page_len > 0 && updates.len() - before == page_len => {
+                    url.set_query(Some(next_page.trim_start_matches('?')));
+                }
+                _ => break,
+            }
+        }
+
+        updates.reverse();
+        Ok(updates)
+    }
+
     /// Fetch owners from the registry's API
     fn get_owners(&self, name: &str) -> Result<Vec<CrateOwner>> {
         let url = {
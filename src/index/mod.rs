@@ -1,4 +1,5 @@
 use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 use std::{path::PathBuf, process::Command};
 
 use anyhow::Context;
@@ -6,15 +7,46 @@ use crates_index_diff::gix;
 use url::Url;
 
 use self::api::Api;
+use self::sparse::{SparseIndex, SPARSE_PREFIX};
 use crate::error::Result;
 use crate::utils::report_error;
+use crate::{AlternativeRegistry, Config};
 
 pub(crate) mod api;
+pub(crate) mod sparse;
+
+/// Separates the registry name from the crate name in the names of crates from alternative
+/// registries. It can't be part of crate names and doesn't need to be escaped in URLs.
+const REGISTRY_SEPARATOR: char = '~';
+
+/// The name a crate is stored and served under.
+///
+/// Crates from alternative registries are prefixed with the registry name, e.g. `acme~foo`, so
+/// they don't collide with crates.io crates in the database, the storage and URLs.
+pub fn qualified_crate_name(registry: Option<&str>, name: &str) -> String {
+    match registry {
+        Some(registry) => format!("{registry}{REGISTRY_SEPARATOR}{name}"),
+        None => name.to_owned(),
+    }
+}
+
+/// Splits a name created by [`qualified_crate_name`] into the registry and the crate name.
+pub fn split_qualified_crate_name(name: &str) -> (Option<&str>, &str) {
+    match name.split_once(REGISTRY_SEPARATOR) {
+        Some((registry, name)) => (Some(registry), name),
+        None => (None, name),
+    }
+}
 
 pub struct Index {
     path: PathBuf,
     api: Api,
     repository_url: Option<String>,
+    /// The name of the alternative registry, `None` for the main index.
+    registry: Option<String>,
+    /// Set when the index is served over the sparse HTTP protocol, there's no local
+    /// repository then.
+    sparse: Option<SparseIndex>,
 }
 
 #[derive(Debug, serde::Deserialize, Clone)]
@@ -39,7 +71,15 @@ fn load_config(repo: &gix::Repository) -> Result<IndexConfig> {
 }
 
 impl Index {
+    /// Opens the index at `url`, cloning it into `path` first when needed.
+    ///
+    /// URLs starting with `sparse+` are read over the sparse HTTP protocol instead, without a
+    /// local repository.
     pub fn from_url(path: PathBuf, url: String, max_api_call_retries: u32) -> Result<Self> {
+        if url.starts_with(SPARSE_PREFIX) {
+            return Self::from_sparse_url(path, url, max_api_call_retries);
+        }
+
         let diff = crates_index_diff::Index::from_path_or_cloned_with_options(
             &path,
             gix::progress::Discard,
@@ -55,6 +95,39 @@ impl Index {
             path,
             api,
             repository_url: Some(url),
+            registry: None,
+            sparse: None,
+        })
+    }
+
+    fn from_sparse_url(path: PathBuf, url: String, max_api_call_retries: u32) -> Result<Self> {
+        let sparse = SparseIndex::new(&url, max_api_call_retries)
+            .context("initialising sparse registry index client")?;
+        let config = sparse.config().context("loading registry config")?;
+        let api = Api::new(config.api, max_api_call_retries)
+            .context("initialising registry api client")?;
+        Ok(Self {
+            path,
+            api,
+            repository_url: Some(url),
+            registry: None,
+            sparse: Some(sparse),
+        })
+    }
+
+    pub fn for_alternative_registry(
+        registry: &AlternativeRegistry,
+        max_api_call_retries: u32,
+    ) -> Result<Self> {
+        let index = Self::from_url(
+            registry.index_path.clone(),
+            registry.index_url.clone(),
+            max_api_call_retries,
+        )
+        .with_context(|| format!("initialising index of registry {}", registry.name))?;
+        Ok(Self {
+            registry: Some(registry.name.clone()),
+            ..index
         })
     }
 
@@ -70,10 +143,15 @@ impl Index {
             path,
             api,
             repository_url: None,
+            registry: None,
+            sparse: None,
         })
     }
 
     pub fn diff(&self) -> Result<crates_index_diff::Index> {
+        if self.sparse.is_some() {
+            anyhow::bail!("the sparse registry index has no git history to diff");
+        }
         let options = self
             .repository_url
             .clone()
@@ -91,6 +169,9 @@ impl Index {
 
     #[cfg(feature = "consistency_check")]
     pub(crate) fn crates(&self) -> Result<crates_index::GitIndex> {
+        if self.sparse.is_some() {
+            anyhow::bail!("the sparse registry index can't list all crates");
+        }
         tracing::debug!("Opening with `crates_index`");
         // crates_index requires the repo url to match the existing origin or it tries to reinitialize the repo
         let repo_url = self
@@ -106,7 +187,22 @@ impl Index {
         &self.api
     }
 
+    /// The client for the sparse HTTP protocol, when the index is served that way.
+    pub(crate) fn sparse(&self) -> Option<&SparseIndex> {
+        self.sparse.as_ref()
+    }
+
+    /// Whether the index is read over the sparse HTTP protocol.
+    pub fn is_sparse(&self) -> bool {
+        self.sparse.is_some()
+    }
+
     pub fn run_git_gc(&self) {
+        // there's no local repository to clean up
+        if self.sparse.is_some() {
+            return;
+        }
+
         let gc = Command::new("git")
             .arg("-C")
             .arg(&self.path)
@@ -122,4 +218,52 @@ impl Index {
     pub fn repository_url(&self) -> Option<&str> {
         self.repository_url.as_deref()
     }
+
+    /// The name of the alternative registry this is the index of, `None` for the main index.
+    pub fn registry(&self) -> Option<&str> {
+        self.registry.as_deref()
+    }
+}
+
+/// The indexes of all configured alternative registries.
+#[derive(Default)]
+pub struct AlternativeIndexes(Vec<Arc<Index>>);
+
+impl AlternativeIndexes {
+    pub fn new(config: &Config) -> Result<Self> {
+        Ok(Self(
+            config
+                .alternative_registries
+                .iter()
+                .map(|registry| {
+                    Index::for_alternative_registry(registry, config.crates_io_api_call_retries)
+                        .map(Arc::new)
+                })
+                .collect::<Result<_>>()?,
+        ))
+    }
+
+    pub fn get(&self, registry: &str) -> Option<&Arc<Index>> {
+        self.0
+            .iter()
+            .find(|index| index.registry() == Some(registry))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Index>> {
+        self.0.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(None, "foo", "foo")]
+    #[test_case(Some("acme"), "foo", "acme~foo")]
+    #[test_case(Some("acme-corp"), "foo-bar", "acme-corp~foo-bar")]
+    fn qualified_crate_names(registry: Option<&str>, name: &str, qualified: &str) {
+        assert_eq!(qualified_crate_name(registry, name), qualified);
+        assert_eq!(split_qualified_crate_name(qualified), (registry, name));
+    }
 }
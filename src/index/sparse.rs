@@ -0,0 +1,175 @@
+//! A client for registry indexes served over the [sparse HTTP protocol][sparse].
+//!
+//! [sparse]: https://doc.rust-lang.org/cargo/reference/registry-index.html#sparse-protocol
+
+use super::{api::APP_USER_AGENT, IndexConfig};
+use crate::{error::Result, utils::retry};
+use anyhow::{anyhow, Context};
+use reqwest::{
+    header::{HeaderValue, USER_AGENT},
+    StatusCode,
+};
+use serde::Deserialize;
+use url::Url;
+
+/// The prefix cargo uses to mark sparse registry URLs.
+pub(crate) const SPARSE_PREFIX: &str = "sparse+";
+
+#[derive(Debug)]
+pub(crate) struct SparseIndex {
+    base: Url,
+    max_retries: u32,
+    client: reqwest::blocking::Client,
+}
+
+/// A version of a crate, as listed in its index file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct IndexVersion {
+    pub(crate) name: String,
+    #[serde(rename = "vers")]
+    pub(crate) version: String,
+    #[serde(default)]
+    pub(crate) yanked: bool,
+}
+
+/// The path of the index file of a crate, relative to the index root.
+fn crate_path(name: &str) -> String {
+    let name = name.to_lowercase();
+    match name.len() {
+        1 => format!("1/{name}"),
+        2 => format!("2/{name}"),
+        3 => format!("3/{}/{name}", &name[..1]),
+        _ => format!("{}/{}/{name}", &name[..2], &name[2..4]),
+    }
+}
+
+impl SparseIndex {
+    /// Creates a client for the index at `url`, with or without the `sparse+` prefix.
+    pub(crate) fn new(url: &str, max_retries: u32) -> Result<Self> {
+        let url = url.strip_prefix(SPARSE_PREFIX).unwrap_or(url);
+        let mut base = Url::parse(url).with_context(|| format!("invalid index url {url}"))?;
+        // the crate paths are relative to the index root
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+
+        let client = reqwest::blocking::Client::builder()
+            .default_headers(
+                [(USER_AGENT, HeaderValue::from_static(APP_USER_AGENT))]
+                    .into_iter()
+                    .collect(),
+            )
+            .build()?;
+
+        Ok(Self {
+            base,
+            max_retries,
+            client,
+        })
+    }
+
+    /// Fetches `path` relative to the index root, `None` when it doesn't exist.
+    ///
+    /// Only `404 Not Found` and `410 Gone` mean the file doesn't exist, a `403 Forbidden` of the
+    /// CDN in front of the index is an error like any other.
+    fn fetch(&self, path: &str) -> Result<Option<String>> {
+        let url = self.base.join(path)?;
+        let response = retry(
+            || {
+                let response = self.client.get(url.clone()).send()?;
+                if matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::GONE) {
+                    return Ok(None);
+                }
+                Ok(Some(response.error_for_status()?))
+            },
+            self.max_retries,
+        )
+        .with_context(|| format!("failed to fetch {url}"))?;
+
+        response.map(|response| Ok(response.text()?)).transpose()
+    }
+
+    pub(super) fn config(&self) -> Result<IndexConfig> {
+        let config = self
+            .fetch("config.json")?
+            .with_context(|| anyhow!("registry index missing ./config.json in root"))?;
+        Ok(serde_json::from_str(&config)?)
+    }
+
+    /// The versions of a crate in the order they were published,
+    /// `None` when the crate isn't in the index.
+    pub(crate) fn crate_versions(&self, name: &str) -> Result<Option<Vec<IndexVersion>>> {
+        let Some(file) = self.fetch(&crate_path(name))? else {
+            return Ok(None);
+        };
+
+        let versions = file
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("invalid index entry for crate {name}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Some(versions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("a", "1/a")]
+    #[test_case("ab", "2/ab")]
+    #[test_case("abc", "3/a/abc")]
+    #[test_case("Serde", "se/rd/serde")]
+    #[test_case("cargo-docs", "ca/rg/cargo-docs")]
+    fn crate_paths(name: &str, path: &str) {
+        assert_eq!(crate_path(name), path);
+    }
+
+    #[test]
+    fn crate_versions() -> Result<()> {
+        let mut server = mockito::Server::new();
+        let _file = server
+            .mock("GET", "/index/3/f/foo")
+            .with_body(concat!(
+                r#"{"name":"foo","vers":"0.1.0","deps":[],"cksum":"","features":{},"yanked":false}"#,
+                "\n",
+                r#"{"name":"foo","vers":"0.2.0","deps":[],"cksum":"","features":{},"yanked":true}"#,
+                "\n",
+            ))
+            .create();
+        let _missing = server
+            .mock("GET", "/index/3/b/bar")
+            .with_status(404)
+            .create();
+        let _forbidden = server
+            .mock("GET", "/index/3/b/baz")
+            .with_status(403)
+            .create();
+
+        let index = SparseIndex::new(&format!("sparse+{}/index", server.url()), 0)?;
+        assert_eq!(
+            index.crate_versions("foo")?.unwrap(),
+            vec![
+                IndexVersion {
+                    name: "foo".into(),
+                    version: "0.1.0".into(),
+                    yanked: false,
+                },
+                IndexVersion {
+                    name: "foo".into(),
+                    version: "0.2.0".into(),
+                    yanked: true,
+                },
+            ]
+        );
+        assert!(index.crate_versions("bar")?.is_none());
+        assert!(index.crate_versions("baz").is_err());
+
+        Ok(())
+    }
+}
@@ -3,10 +3,10 @@
 #![allow(clippy::cognitive_complexity)]
 
 pub use self::build_queue::BuildQueue;
-pub use self::config::Config;
+pub use self::config::{AlternativeRegistry, Config};
 pub use self::context::Context;
 pub use self::docbuilder::PackageKind;
-pub use self::docbuilder::RustwideBuilder;
+pub use self::docbuilder::{suggest_overrides, LimitSuggestion, RustwideBuilder, WorkerClient};
 pub use self::index::Index;
 pub use self::metrics::{InstanceMetrics, ServiceMetrics};
 pub use self::storage::Storage;
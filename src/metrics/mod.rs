@@ -2,7 +2,7 @@
 mod macros;
 
 use self::macros::MetricFromOpts;
-use crate::{cdn, db::Pool, target::TargetAtom, BuildQueue, Config};
+use crate::{cdn, db::Pool, target::TargetAtom, utils::campaign_progress, BuildQueue, Config};
 use anyhow::Error;
 use dashmap::DashMap;
 use prometheus::proto::MetricFamily;
@@ -246,6 +246,11 @@ pub struct ServiceMetrics {
     pub queue_is_locked: IntGauge,
     pub queued_crates_count_by_priority: IntGaugeVec,
     pub queued_cdn_invalidations_by_distribution: IntGaugeVec,
+    pub rebuild_campaign_remaining: IntGaugeVec,
+    pub rebuild_campaign_succeeded: IntGaugeVec,
+    pub rebuild_campaign_failed: IntGaugeVec,
+    pub rebuild_campaign_fixed: IntGaugeVec,
+    pub rebuild_campaign_broken: IntGaugeVec,
 
     registry: prometheus::Registry,
 }
@@ -291,6 +296,36 @@ impl ServiceMetrics {
                 "queued CDN invalidations",
                 Some("distribution"),
             )?,
+            rebuild_campaign_remaining: metric_from_opts(
+                &registry,
+                "rebuild_campaign_remaining",
+                "releases of rebuild campaigns that weren't rebuilt yet",
+                Some("campaign"),
+            )?,
+            rebuild_campaign_succeeded: metric_from_opts(
+                &registry,
+                "rebuild_campaign_succeeded",
+                "releases of rebuild campaigns that were rebuilt successfully",
+                Some("campaign"),
+            )?,
+            rebuild_campaign_failed: metric_from_opts(
+                &registry,
+                "rebuild_campaign_failed",
+                "releases of rebuild campaigns that failed to rebuild",
+                Some("campaign"),
+            )?,
+            rebuild_campaign_fixed: metric_from_opts(
+                &registry,
+                "rebuild_campaign_fixed",
+                "releases of rebuild campaigns that failed before and were rebuilt successfully",
+                Some("campaign"),
+            )?,
+            rebuild_campaign_broken: metric_from_opts(
+                &registry,
+                "rebuild_campaign_broken",
+                "releases of rebuild campaigns that were successful before and failed to rebuild",
+                Some("campaign"),
+            )?,
         })
     }
 
@@ -323,6 +358,38 @@ impl ServiceMetrics {
         }
 
         self.failed_crates_count.set(queue.failed_count()? as i64);
+
+        // removed and finished campaigns aren't exported anymore
+        for gauge in [
+            &self.rebuild_campaign_remaining,
+            &self.rebuild_campaign_succeeded,
+            &self.rebuild_campaign_failed,
+            &self.rebuild_campaign_fixed,
+            &self.rebuild_campaign_broken,
+        ] {
+            gauge.reset();
+        }
+        for campaign in campaign_progress(&mut conn)? {
+            if campaign.finished_at.is_some() {
+                continue;
+            }
+            let name = [campaign.name.as_str()];
+            self.rebuild_campaign_remaining
+                .with_label_values(&name)
+                .set(campaign.pending + campaign.queued);
+            self.rebuild_campaign_succeeded
+                .with_label_values(&name)
+                .set(campaign.succeeded);
+            self.rebuild_campaign_failed
+                .with_label_values(&name)
+                .set(campaign.failed);
+            self.rebuild_campaign_fixed
+                .with_label_values(&name)
+                .set(campaign.fixed);
+            self.rebuild_campaign_broken
+                .with_label_values(&name)
+                .set(campaign.broken);
+        }
         Ok(self.registry.gather())
     }
 }
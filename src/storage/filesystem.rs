@@ -0,0 +1,258 @@
+use super::{Blob, CompressionAlgorithm, FileRange, StorageTransaction};
+use crate::error::Result;
+use crate::InstanceMetrics;
+use anyhow::{bail, Context as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+/// Sub-directory of the storage root containing the file contents.
+const FILES_DIR: &str = "files";
+/// Sub-directory of the storage root containing one metadata file per stored file.
+const METADATA_DIR: &str = "metadata";
+
+/// Metadata stored next to the file contents, since the filesystem can't hold it for us.
+#[derive(Debug, Serialize, Deserialize)]
+struct FileMetadata {
+    mime: String,
+    compression: Option<CompressionAlgorithm>,
+    public: bool,
+}
+
+/// Storage backend keeping all files in a local directory.
+///
+/// Every stored path is mapped to a file below `{root}/files/`, while the mime type,
+/// compression and public access flag are stored as JSON in `{root}/metadata/{path}.json`.
+/// The modification time of the file is used as `date_updated`.
+///
+/// Unlike S3, a path can't be both a file and a directory, so storing `foo` and
+/// `foo/bar` at the same time is not supported.
+pub(crate) struct FilesystemBackend {
+    root: PathBuf,
+    metrics: Arc<InstanceMetrics>,
+}
+
+impl FilesystemBackend {
+    pub(crate) fn new(root: PathBuf, metrics: Arc<InstanceMetrics>) -> Result<Self> {
+        fs::create_dir_all(root.join(FILES_DIR))
+            .with_context(|| format!("could not create storage root {}", root.display()))?;
+        fs::create_dir_all(root.join(METADATA_DIR))?;
+        Ok(Self { root, metrics })
+    }
+
+    /// Returns the location of the content and the metadata for a storage path.
+    ///
+    /// Paths trying to escape the storage root are treated as not found.
+    fn locate(&self, path: &str) -> Result<(PathBuf, PathBuf)> {
+        let relative = Path::new(path);
+        if path.is_empty()
+            || !relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(super::PathNotFoundError.into());
+        }
+
+        Ok((
+            self.root.join(FILES_DIR).join(relative),
+            self.root.join(METADATA_DIR).join(format!("{path}.json")),
+        ))
+    }
+
+    fn read_metadata(&self, path: &str) -> Result<FileMetadata> {
+        let (_, metadata_path) = self.locate(path)?;
+        match fs::read(metadata_path) {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                Err(super::PathNotFoundError.into())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn write_metadata(&self, path: &str, metadata: &FileMetadata) -> Result<()> {
+        let (_, metadata_path) = self.locate(path)?;
+        write_atomic(&metadata_path, &serde_json::to_vec(metadata)?)
+    }
+
+    pub(super) fn exists(&self, path: &str) -> Result<bool> {
+        match self.locate(path) {
+            Ok((file_path, _)) => Ok(file_path.is_file()),
+            Err(_) => Ok(false),
+        }
+    }
+
+    pub(super) fn get_public_access(&self, path: &str) -> Result<bool> {
+        Ok(self.read_metadata(path)?.public)
+    }
+
+    pub(super) fn set_public_access(&self, path: &str, public: bool) -> Result<()> {
+        let mut metadata = self.read_metadata(path)?;
+        metadata.public = public;
+        self.write_metadata(path, &metadata)
+    }
+
+    pub(super) fn get(
+        &self,
+        path: &str,
+        max_size: usize,
+        range: Option<FileRange>,
+    ) -> Result<Blob> {
+        let (file_path, _) = self.locate(path)?;
+        let mut file = match fs::File::open(&file_path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(super::PathNotFoundError.into())
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let metadata = self.read_metadata(path)?;
+        let file_metadata = file.metadata()?;
+
+        let (start, len) = match range {
+            Some(r) => (*r.start(), r.end() - r.start() + 1),
+            None => (0, file_metadata.len()),
+        };
+        if len > max_size as u64 {
+            return Err(io::Error::other(crate::error::SizeLimitReached).into());
+        }
+
+        let mut content = Vec::with_capacity(len as usize);
+        file.seek(SeekFrom::Start(start))?;
+        file.take(len).read_to_end(&mut content)?;
+
+        Ok(Blob {
+            path: path.into(),
+            mime: metadata.mime,
+            date_updated: DateTime::<Utc>::from(file_metadata.modified()?),
+            content,
+            compression: metadata.compression,
+        })
+    }
+
+    pub(super) fn start_storage_transaction(&self) -> FilesystemStorageTransaction<'_> {
+        FilesystemStorageTransaction { fs: self }
+    }
+
+    #[cfg(test)]
+    pub(super) fn cleanup_after_test(&self) -> Result<()> {
+        if cfg!(not(test)) {
+            panic!("safeguard to prevent deleting the production storage");
+        }
+
+        fs::remove_dir_all(&self.root)?;
+        Ok(())
+    }
+}
+
+/// Write a file by writing a temporary file first and moving it into place,
+/// so readers never see partially written files.
+fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let Some(parent) = path.parent() else {
+        bail!("invalid storage path {}", path.display());
+    };
+    fs::create_dir_all(parent)?;
+
+    let mut file = tempfile::NamedTempFile::new_in(parent)?;
+    file.write_all(content)?;
+    file.persist(path)?;
+    Ok(())
+}
+
+pub(super) struct FilesystemStorageTransaction<'a> {
+    fs: &'a FilesystemBackend,
+}
+
+impl<'a> StorageTransaction for FilesystemStorageTransaction<'a> {
+    fn store_batch(&mut self, batch: Vec<Blob>) -> Result<()> {
+        for blob in batch {
+            let (file_path, _) = self.fs.locate(&blob.path)?;
+
+            // keep the public flag of existing files, like the database backend does
+            let public = match self.fs.read_metadata(&blob.path) {
+                Ok(metadata) => metadata.public,
+                Err(_) => false,
+            };
+            self.fs.write_metadata(
+                &blob.path,
+                &FileMetadata {
+                    mime: blob.mime,
+                    compression: blob.compression,
+                    public,
+                },
+            )?;
+            write_atomic(&file_path, &blob.content)?;
+            self.fs.metrics.uploaded_files_total.inc();
+        }
+        Ok(())
+    }
+
+    fn delete_prefix(&mut self, prefix: &str) -> Result<()> {
+        // The prefix doesn't have to end at a path separator, so we look at all entries
+        // in the parent directory of the prefix that start with the remaining part.
+        let (directory, name_prefix) = match prefix.rfind('/') {
+            Some(idx) => (&prefix[..idx], &prefix[idx + 1..]),
+            None => ("", prefix),
+        };
+
+        let files_root = self.fs.root.join(FILES_DIR);
+        let directory_path = if directory.is_empty() {
+            files_root.clone()
+        } else {
+            match self.fs.locate(directory) {
+                Ok((path, _)) => path,
+                Err(_) => return Ok(()),
+            }
+        };
+
+        let entries = match fs::read_dir(&directory_path) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        for entry in entries {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let Some(file_name) = file_name.to_str() else {
+                continue;
+            };
+            if !file_name.starts_with(name_prefix) {
+                continue;
+            }
+
+            let relative = entry
+                .path()
+                .strip_prefix(&files_root)?
+                .to_string_lossy()
+                .into_owned();
+            let metadata_path = self.fs.root.join(METADATA_DIR).join(&relative);
+
+            if entry.file_type()?.is_dir() {
+                fs::remove_dir_all(entry.path())?;
+                if metadata_path.is_dir() {
+                    fs::remove_dir_all(metadata_path)?;
+                }
+            } else {
+                fs::remove_file(entry.path())?;
+                let metadata_file = metadata_path.with_file_name(format!("{file_name}.json"));
+                if metadata_file.is_file() {
+                    fs::remove_file(metadata_file)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn complete(self: Box<Self>) -> Result<()> {
+        Ok(())
+    }
+}
+
+// The tests for this module are in src/storage/mod.rs, as part of the backend tests. Please add
+// any test checking the public interface there.
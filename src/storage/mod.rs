@@ -1,11 +1,13 @@
 mod archive_index;
 mod compression;
 mod database;
+mod filesystem;
 mod s3;
 mod sqlite_pool;
 
 pub use self::compression::{compress, decompress, CompressionAlgorithm, CompressionAlgorithms};
 use self::database::DatabaseBackend;
+use self::filesystem::FilesystemBackend;
 use self::s3::S3Backend;
 use self::sqlite_pool::SqliteConnectionPool;
 use crate::error::Result;
@@ -95,6 +97,7 @@ pub(crate) struct InvalidStorageBackendError;
 pub(crate) enum StorageKind {
     Database,
     S3,
+    Filesystem,
 }
 
 impl std::str::FromStr for StorageKind {
@@ -104,6 +107,7 @@ impl std::str::FromStr for StorageKind {
         match input {
             "database" => Ok(StorageKind::Database),
             "s3" => Ok(StorageKind::S3),
+            "filesystem" => Ok(StorageKind::Filesystem),
             _ => Err(InvalidStorageBackendError),
         }
     }
@@ -112,6 +116,7 @@ impl std::str::FromStr for StorageKind {
 enum StorageBackend {
     Database(DatabaseBackend),
     S3(Box<S3Backend>),
+    Filesystem(FilesystemBackend),
 }
 
 pub struct Storage {
@@ -140,6 +145,10 @@ impl Storage {
                 StorageKind::S3 => {
                     StorageBackend::S3(Box::new(S3Backend::new(metrics, &config, runtime)?))
                 }
+                StorageKind::Filesystem => StorageBackend::Filesystem(FilesystemBackend::new(
+                    config.storage_filesystem_root.clone(),
+                    metrics,
+                )?),
             },
         })
     }
@@ -148,6 +157,7 @@ impl Storage {
         match &self.backend {
             StorageBackend::Database(db) => db.exists(path),
             StorageBackend::S3(s3) => s3.exists(path),
+            StorageBackend::Filesystem(fs) => fs.exists(path),
         }
     }
 
@@ -155,6 +165,7 @@ impl Storage {
         match &self.backend {
             StorageBackend::Database(db) => db.get_public_access(path),
             StorageBackend::S3(s3) => s3.get_public_access(path),
+            StorageBackend::Filesystem(fs) => fs.get_public_access(path),
         }
     }
 
@@ -162,6 +173,7 @@ impl Storage {
         match &self.backend {
             StorageBackend::Database(db) => db.set_public_access(path, public),
             StorageBackend::S3(s3) => s3.set_public_access(path, public),
+            StorageBackend::Filesystem(fs) => fs.set_public_access(path, public),
         }
     }
 
@@ -256,6 +268,7 @@ impl Storage {
         let mut blob = match &self.backend {
             StorageBackend::Database(db) => db.get(path, max_size, None),
             StorageBackend::S3(s3) => s3.get(path, max_size, None),
+            StorageBackend::Filesystem(fs) => fs.get(path, max_size, None),
         }?;
         if let Some(alg) = blob.compression {
             blob.content = decompress(blob.content.as_slice(), alg, max_size)?;
@@ -274,6 +287,7 @@ impl Storage {
         let mut blob = match &self.backend {
             StorageBackend::Database(db) => db.get(path, max_size, Some(range)),
             StorageBackend::S3(s3) => s3.get(path, max_size, Some(range)),
+            StorageBackend::Filesystem(fs) => fs.get(path, max_size, Some(range)),
         }?;
         // `compression` represents the compression of the file-stream inside the archive.
         // We don't compress the whole archive, so the encoding of the archive's blob is irrelevant
@@ -428,6 +442,7 @@ impl Storage {
                 Box::new(conn.start_storage_transaction()?)
             }
             StorageBackend::S3(s3) => Box::new(s3.start_storage_transaction()),
+            StorageBackend::Filesystem(fs) => Box::new(fs.start_storage_transaction()),
         };
 
         let res = f(trans.as_mut())?;
@@ -535,8 +550,10 @@ impl Storage {
     // still holds a reference to the storage).
     #[cfg(test)]
     pub(crate) fn cleanup_after_test(&self) -> Result<()> {
-        if let StorageBackend::S3(s3) = &self.backend {
-            s3.cleanup_after_test()?;
+        match &self.backend {
+            StorageBackend::S3(s3) => s3.cleanup_after_test()?,
+            StorageBackend::Filesystem(fs) => fs.cleanup_after_test()?,
+            StorageBackend::Database(_) => {}
         }
         Ok(())
     }
@@ -547,6 +564,7 @@ impl std::fmt::Debug for Storage {
         match &self.backend {
             StorageBackend::Database(_) => write!(f, "database-backed storage"),
             StorageBackend::S3(_) => write!(f, "S3-backed storage"),
+            StorageBackend::Filesystem(_) => write!(f, "filesystem-backed storage"),
         }
     }
 }
@@ -587,6 +605,10 @@ pub(crate) fn source_archive_path(name: &str, version: &str) -> String {
     format!("sources/{name}/{version}.zip")
 }
 
+pub(crate) fn rustdoc_json_path(name: &str, version: &str, target: &str) -> String {
+    format!("rustdoc-json/{name}/{version}/{target}/{name}_{version}_{target}.json")
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1085,6 +1107,7 @@ mod backend_tests {
         backends {
             s3 => StorageKind::S3,
             database => StorageKind::Database,
+            filesystem => StorageKind::Filesystem,
         }
 
         tests {
@@ -1,9 +1,14 @@
 use super::TestDatabase;
 
-use crate::docbuilder::{BuildResult, DocCoverage};
+use crate::docbuilder::{
+    read_rustdoc_json, read_search_index, BuildResult, DocCoverage, FailureCategory, ResourceUsage,
+};
 use crate::error::Result;
-use crate::index::api::{CrateData, CrateOwner, ReleaseData};
-use crate::storage::{rustdoc_archive_path, source_archive_path, Storage};
+use crate::index::{
+    api::{CrateData, CrateOwner, ReleaseData},
+    split_qualified_crate_name,
+};
+use crate::storage::{rustdoc_archive_path, rustdoc_json_path, source_archive_path, Storage};
 use crate::utils::{Dependency, MetadataPackage, Target};
 use anyhow::Context;
 use base64::{engine::general_purpose::STANDARD as b64, Engine};
@@ -24,6 +29,8 @@ pub(crate) struct FakeRelease<'a> {
     /// name, content
     rustdoc_files: Vec<(&'a str, &'a [u8])>,
     doc_targets: Vec<String>,
+    /// target, rustdoc JSON content
+    rustdoc_json: Vec<(&'a str, &'a [u8])>,
     default_target: Option<&'a str>,
     registry_crate_data: CrateData,
     registry_release_data: ReleaseData,
@@ -34,12 +41,16 @@ pub(crate) struct FakeRelease<'a> {
     readme: Option<&'a str>,
     github_stats: Option<FakeGithubStats>,
     doc_coverage: Option<DocCoverage>,
+    /// name, version
+    locked_dependencies: Vec<(String, String)>,
     no_cargo_toml: bool,
 }
 
 pub(crate) struct FakeBuild {
     s3_build_log: Option<String>,
     db_build_log: Option<String>,
+    target_build_logs: Vec<(String, String)>,
+    resource_usage: Vec<ResourceUsage>,
     result: BuildResult,
 }
 
@@ -84,6 +95,7 @@ impl<'a> FakeRelease<'a> {
             source_files: Vec::new(),
             rustdoc_files: Vec::new(),
             doc_targets: Vec::new(),
+            rustdoc_json: Vec::new(),
             default_target: None,
             registry_crate_data: CrateData { owners: Vec::new() },
             registry_release_data: ReleaseData {
@@ -96,6 +108,7 @@ impl<'a> FakeRelease<'a> {
             readme: None,
             github_stats: None,
             doc_coverage: None,
+            locked_dependencies: Vec::new(),
             archive_storage: false,
             no_cargo_toml: false,
         }
@@ -114,7 +127,8 @@ impl<'a> FakeRelease<'a> {
     pub(crate) fn name(mut self, new: &str) -> Self {
         self.package.name = new.into();
         self.package.id = format!("{new}-id");
-        self.package.targets[0].name = new.into();
+        // crates from alternative registries only use the registry in the package name
+        self.package.targets[0].name = split_qualified_crate_name(new).1.into();
         self
     }
 
@@ -191,6 +205,11 @@ impl<'a> FakeRelease<'a> {
         self
     }
 
+    pub(crate) fn rustdoc_json(mut self, target: &'a str, data: &'a [u8]) -> Self {
+        self.rustdoc_json.push((target, data));
+        self
+    }
+
     pub(crate) fn binary(mut self, bin: bool) -> Self {
         self.has_docs = !bin;
         if bin {
@@ -232,6 +251,24 @@ impl<'a> FakeRelease<'a> {
         self
     }
 
+    pub(crate) fn add_dependency(mut self, name: &str, req: &str, kind: Option<&str>) -> Self {
+        self.package.dependencies.push(Dependency {
+            name: name.into(),
+            req: req.into(),
+            kind: kind.map(Into::into),
+            rename: None,
+            optional: false,
+        });
+        self
+    }
+
+    /// The version of a dependency that was resolved in the build.
+    pub(crate) fn locked_dependency(mut self, name: &str, version: &str) -> Self {
+        self.locked_dependencies
+            .push((name.to_owned(), version.to_owned()));
+        self
+    }
+
     pub(crate) fn doc_coverage(self, doc_coverage: DocCoverage) -> Self {
         Self {
             doc_coverage: Some(doc_coverage),
@@ -387,6 +424,7 @@ impl<'a> FakeRelease<'a> {
         }
         let last_build_result = &self.builds.last().unwrap().result;
 
+        let mut search_items = Vec::new();
         if last_build_result.successful {
             let index = [&package.name, "index.html"].join("/");
             if package.is_library() && !rustdoc_files.iter().any(|(path, _)| path == &index) {
@@ -400,6 +438,10 @@ impl<'a> FakeRelease<'a> {
             store_files_into(&rustdoc_files, rustdoc_path)?;
             debug!("added rustdoc files");
 
+            if let Some(lib_name) = package.library_name() {
+                search_items = read_search_index(rustdoc_path, &lib_name)?.unwrap_or_default();
+            }
+
             for target in &package.targets[1..] {
                 let platform = target.src_path.as_ref().unwrap();
                 let platform_dir = rustdoc_path.join(platform);
@@ -411,6 +453,13 @@ impl<'a> FakeRelease<'a> {
 
             let (rustdoc_meta, _) = upload_files(FileKind::Rustdoc, rustdoc_path)?;
             debug!("uploaded rustdoc files: {}", rustdoc_meta);
+
+            for (target, data) in &self.rustdoc_json {
+                storage.store_one(
+                    rustdoc_json_path(&package.name, &package.version, target),
+                    *data,
+                )?;
+            }
         }
 
         let repository = match self.github_stats {
@@ -431,11 +480,15 @@ impl<'a> FakeRelease<'a> {
         let release_id = crate::db::add_package_into_database(
             &mut db.conn(),
             &package,
-            crate_dir,
+            &crate::db::PackageDocs::read(&package, crate_dir),
             last_build_result,
             default_target,
             source_meta,
             self.doc_targets,
+            self.rustdoc_json
+                .iter()
+                .map(|(target, _)| target.to_string())
+                .collect(),
             &self.registry_release_data,
             self.has_docs,
             self.has_examples,
@@ -454,6 +507,20 @@ impl<'a> FakeRelease<'a> {
         if let Some(coverage) = self.doc_coverage {
             crate::db::add_doc_coverage(&mut db.conn(), release_id, coverage)?;
         }
+        crate::db::add_search_items(&mut db.conn(), release_id, &search_items)?;
+        if last_build_result.successful {
+            if let Some((_, json)) = self
+                .rustdoc_json
+                .iter()
+                .find(|(target, _)| *target == default_target)
+            {
+                // like the builder, JSON without readable items is only stored
+                if let Ok(items) = read_rustdoc_json(json) {
+                    crate::db::add_public_items(&mut db.conn(), release_id, &items.public_items)?;
+                }
+            }
+        }
+        crate::db::add_locked_dependencies(&mut db.conn(), release_id, &self.locked_dependencies)?;
 
         Ok(release_id)
     }
@@ -519,6 +586,22 @@ impl FakeBuild {
         }
     }
 
+    /// Store a build log for a target other than the default target.
+    pub(crate) fn target_build_log(
+        mut self,
+        target: impl Into<String>,
+        build_log: impl Into<String>,
+    ) -> Self {
+        self.target_build_logs
+            .push((target.into(), build_log.into()));
+        self
+    }
+
+    pub(crate) fn resource_usage(mut self, usage: ResourceUsage) -> Self {
+        self.resource_usage.push(usage);
+        self
+    }
+
     pub(crate) fn no_s3_build_log(self) -> Self {
         Self {
             s3_build_log: None,
@@ -536,6 +619,18 @@ impl FakeBuild {
         }
     }
 
+    /// Marks the build as failed for the reason in `category`.
+    pub(crate) fn failure_category(self, category: FailureCategory) -> Self {
+        Self {
+            result: BuildResult {
+                successful: false,
+                failure_category: Some(category),
+                ..self.result
+            },
+            ..self
+        }
+    }
+
     fn create(
         &self,
         conn: &mut Client,
@@ -543,7 +638,12 @@ impl FakeBuild {
         release_id: i32,
         default_target: &str,
     ) -> Result<()> {
-        let build_id = crate::db::add_build_into_database(conn, release_id, &self.result)?;
+        let build_id = crate::db::add_build_into_database(
+            conn,
+            release_id,
+            &self.result,
+            hostname::get()?.to_str().unwrap_or(""),
+        )?;
 
         if let Some(db_build_log) = self.db_build_log.as_deref() {
             conn.query(
@@ -552,10 +652,20 @@ impl FakeBuild {
             )?;
         }
 
+        let mut log_targets = Vec::new();
         if let Some(s3_build_log) = self.s3_build_log.as_deref() {
             let path = format!("build-logs/{build_id}/{default_target}.txt");
             storage.store_one(path, s3_build_log)?;
+            log_targets.push(default_target.to_owned());
+        }
+
+        for (target, build_log) in &self.target_build_logs {
+            let path = format!("build-logs/{build_id}/{target}.txt");
+            storage.store_one(path, build_log.as_str())?;
+            log_targets.push(target.clone());
         }
+        crate::db::add_build_log_targets(conn, build_id, &log_targets)?;
+        crate::db::add_build_resource_usage(conn, build_id, &self.resource_usage)?;
 
         Ok(())
     }
@@ -566,10 +676,13 @@ impl Default for FakeBuild {
         Self {
             s3_build_log: Some("It works!".into()),
             db_build_log: None,
+            target_build_logs: Vec::new(),
+            resource_usage: Vec::new(),
             result: BuildResult {
                 rustc_version: "rustc 2.0.0-nightly (000000000 1970-01-01)".into(),
                 docsrs_version: "docs.rs 1.0.0 (000000000 1970-01-01)".into(),
                 successful: true,
+                failure_category: None,
             },
         }
     }
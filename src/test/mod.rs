@@ -4,6 +4,7 @@ pub(crate) use self::fakes::FakeBuild;
 use crate::cdn::CdnBackend;
 use crate::db::{Pool, PoolClient};
 use crate::error::Result;
+use crate::index::AlternativeIndexes;
 use crate::repositories::RepositoryStatsUpdater;
 use crate::storage::{Storage, StorageKind};
 use crate::web::{build_axum_app, cache, page::TemplateData};
@@ -227,6 +228,7 @@ pub(crate) struct TestEnvironment {
     storage: OnceCell<Arc<Storage>>,
     cdn: OnceCell<Arc<CdnBackend>>,
     index: OnceCell<Arc<Index>>,
+    alternative_indexes: OnceCell<Arc<AlternativeIndexes>>,
     runtime: OnceCell<Arc<Runtime>>,
     instance_metrics: OnceCell<Arc<InstanceMetrics>>,
     service_metrics: OnceCell<Arc<ServiceMetrics>>,
@@ -260,6 +262,7 @@ impl TestEnvironment {
             storage: OnceCell::new(),
             cdn: OnceCell::new(),
             index: OnceCell::new(),
+            alternative_indexes: OnceCell::new(),
             instance_metrics: OnceCell::new(),
             service_metrics: OnceCell::new(),
             frontend: OnceCell::new(),
@@ -295,6 +298,9 @@ impl TestEnvironment {
         config.max_pool_size = 4;
         config.min_pool_idle = 0;
 
+        // A single rendering thread is enough for the tests.
+        config.render_threads = 1;
+
         // Use the database for storage, as it's faster than S3.
         config.storage_backend = StorageKind::Database;
 
@@ -305,12 +311,19 @@ impl TestEnvironment {
         config.local_archive_cache_path =
             std::env::temp_dir().join(format!("docsrs-test-index-{}", rand::random::<u64>()));
 
+        // Use a temporary directory for the filesystem storage backend.
+        config.storage_filesystem_root =
+            std::env::temp_dir().join(format!("docsrs-test-storage-{}", rand::random::<u64>()));
+
         // set stale content serving so Cache::ForeverInCdn and Cache::ForeverInCdnAndStaleInBrowser
         // are actually different.
         config.cache_control_stale_while_revalidate = Some(86400);
 
         config.include_default_targets = true;
 
+        // Don't reach out to crates.io when our own search has no results.
+        config.search_crates_io_fallback = false;
+
         config
     }
 
@@ -407,6 +420,17 @@ impl TestEnvironment {
             .clone()
     }
 
+    pub(crate) fn alternative_indexes(&self) -> Arc<AlternativeIndexes> {
+        self.alternative_indexes
+            .get_or_init(|| {
+                Arc::new(
+                    AlternativeIndexes::new(&self.config())
+                        .expect("failed to initialize the alternative indexes"),
+                )
+            })
+            .clone()
+    }
+
     pub(crate) fn repository_stats_updater(&self) -> Arc<RepositoryStatsUpdater> {
         self.repository_stats_updater
             .get_or_init(|| {
@@ -425,15 +449,6 @@ impl TestEnvironment {
         })
     }
 
-    pub(crate) fn override_frontend(&self, init: impl FnOnce(&mut TestFrontend)) -> &TestFrontend {
-        let mut frontend = TestFrontend::new(self);
-        init(&mut frontend);
-        if self.frontend.set(frontend).is_err() {
-            panic!("cannot call override_frontend after frontend is initialized");
-        }
-        self.frontend.get().unwrap()
-    }
-
     pub(crate) fn frontend(&self) -> &TestFrontend {
         self.frontend.get_or_init(|| TestFrontend::new(self))
     }
@@ -476,6 +491,10 @@ impl Context for TestEnvironment {
         Ok(self.index())
     }
 
+    fn alternative_indexes(&self) -> Result<Arc<AlternativeIndexes>> {
+        Ok(self.alternative_indexes())
+    }
+
     fn repository_stats_updater(&self) -> Result<Arc<RepositoryStatsUpdater>> {
         Ok(self.repository_stats_updater())
     }
@@ -581,8 +600,13 @@ impl TestFrontend {
         }
 
         debug!("loading template data");
-        let template_data =
-            Arc::new(TemplateData::new(&mut context.pool().unwrap().get().unwrap(), 1).unwrap());
+        let template_data = Arc::new(
+            TemplateData::new(
+                &mut context.pool().unwrap().get().unwrap(),
+                &context.config().unwrap(),
+            )
+            .unwrap(),
+        );
 
         debug!("binding local TCP port for axum");
         let axum_listener =
@@ -657,4 +681,16 @@ impl TestFrontend {
         debug!("getting {url} (no redirects)");
         self.client_no_redirect.request(Method::GET, url)
     }
+
+    pub(crate) fn post(&self, url: &str) -> RequestBuilder {
+        let url = self.build_url(url);
+        debug!("posting {url}");
+        self.client.request(Method::POST, url)
+    }
+
+    pub(crate) fn post_no_redirect(&self, url: &str) -> RequestBuilder {
+        let url = self.build_url(url);
+        debug!("posting {url} (no redirects)");
+        self.client_no_redirect.request(Method::POST, url)
+    }
 }
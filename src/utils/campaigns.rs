@@ -0,0 +1,536 @@
+//! Rebuild campaigns: rebuilding a selection of releases, e.g. after a rustdoc fix.
+//!
+//! The releases matching the selector of a campaign are recorded when it is created. They are
+//! queued at a low priority in batches of limited size, the next batch is only queued when the
+//! previous one was built, so crates published in the meantime are never starved.
+
+use crate::error::Result;
+use crate::{BuildQueue, Config};
+use chrono::{DateTime, NaiveDate, Utc};
+use postgres::Client;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tracing::info;
+
+/// The default priority of queued campaign builds, lower than anything queued by the index.
+pub const DEFAULT_CAMPAIGN_PRIORITY: i32 = 20;
+
+/// The default number of builds a campaign has in the queue at the same time.
+pub const DEFAULT_CAMPAIGN_BATCH_SIZE: i32 = 100;
+
+/// Which releases a campaign rebuilds. Yanked releases are never rebuilt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum CampaignSelector {
+    /// Releases documented with a nightly toolchain older than `date`.
+    RustcOlderThan { date: NaiveDate },
+    /// Releases whose latest build failed in the given time range.
+    FailedBetween {
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    },
+    /// Releases of crates matching a `LIKE` pattern, like the patterns of the crate priorities.
+    CratePattern { pattern: String },
+}
+
+impl fmt::Display for CampaignSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RustcOlderThan { date } => write!(f, "documented with rustc older than {date}"),
+            Self::FailedBetween { from, to } => write!(
+                f,
+                "failed between {} and {}",
+                from.format("%Y-%m-%d %H:%M"),
+                to.format("%Y-%m-%d %H:%M")
+            ),
+            Self::CratePattern { pattern } => write!(f, "of crates matching `{pattern}`"),
+        }
+    }
+}
+
+impl CampaignSelector {
+    /// The ids of the releases selected, from the current state of the database.
+    fn release_ids(&self, conn: &mut Client) -> Result<Vec<i32>> {
+        let rows = match self {
+            Self::RustcOlderThan { date } => conn.query(
+                "SELECT releases.id
+                 FROM releases
+                 WHERE
+                    releases.yanked IS NOT TRUE AND
+                    substring(
+                        releases.doc_rustc_version
+                        FROM '([0-9]{4}-[0-9]{2}-[0-9]{2})\\)$'
+                    )::DATE < $1",
+                &[date],
+            )?,
+            Self::FailedBetween { from, to } => conn.query(
+                "SELECT releases.id
+                 FROM releases
+                 INNER JOIN LATERAL (
+                     SELECT build_status, build_time
+                     FROM builds
+                     WHERE builds.rid = releases.id
+                     ORDER BY builds.id DESC
+                     LIMIT 1
+                 ) AS latest_build ON TRUE
+                 WHERE
+                    releases.yanked IS NOT TRUE AND
+                    NOT latest_build.build_status AND
+                    latest_build.build_time BETWEEN $1 AND $2",
+                &[from, to],
+            )?,
+            Self::CratePattern { pattern } => conn.query(
+                "SELECT releases.id
+                 FROM releases
+                 INNER JOIN crates ON crates.id = releases.crate_id
+                 WHERE
+                    releases.yanked IS NOT TRUE AND
+                    crates.name LIKE $1",
+                &[pattern],
+            )?,
+        };
+        Ok(rows.into_iter().map(|row| row.get(0)).collect())
+    }
+}
+
+/// The progress of a campaign.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CampaignProgress {
+    pub name: String,
+    pub selector: String,
+    pub priority: i32,
+    pub batch_size: i32,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// The number of releases selected.
+    pub total: i64,
+    /// Releases not queued yet.
+    pub pending: i64,
+    /// Releases in the queue right now.
+    pub queued: i64,
+    /// Releases rebuilt successfully.
+    pub succeeded: i64,
+    /// Releases rebuilt unsuccessfully, or that couldn't be built at all.
+    pub failed: i64,
+    /// Releases that failed before and were rebuilt successfully.
+    pub fixed: i64,
+    /// Releases that were successful before and failed to rebuild.
+    pub broken: i64,
+}
+
+/// Creates a campaign rebuilding the releases matching `selector`.
+///
+/// Returns the number of releases selected.
+pub fn create_campaign(
+    conn: &mut Client,
+    name: &str,
+    selector: &CampaignSelector,
+    priority: i32,
+    batch_size: i32,
+) -> Result<usize> {
+    let release_ids = selector.release_ids(conn)?;
+
+    let mut transaction = conn.transaction()?;
+    let id: i32 = transaction
+        .query_one(
+            "INSERT INTO rebuild_campaigns (name, selector, priority, batch_size, release_count)
+             VALUES ($1, $2, $3, $4, $5)
+             RETURNING id",
+            &[
+                &name,
+                &serde_json::to_value(selector)?,
+                &priority,
+                &batch_size,
+                &(release_ids.len() as i32),
+            ],
+        )?
+        .get(0);
+    transaction.execute(
+        "INSERT INTO rebuild_campaign_releases (campaign_id, release_id, successful_before)
+         SELECT $1, releases.id, releases.build_status
+         FROM releases
+         WHERE releases.id = ANY($2)",
+        &[&id, &release_ids],
+    )?;
+    transaction.commit()?;
+
+    Ok(release_ids.len())
+}
+
+/// Removes a campaign, its builds that are queued already stay in the queue.
+///
+/// Returns whether the campaign existed.
+pub fn remove_campaign(conn: &mut Client, name: &str) -> Result<bool> {
+    let mut transaction = conn.transaction()?;
+    transaction.execute(
+        "DELETE FROM rebuild_campaign_releases
+         WHERE campaign_id IN (SELECT id FROM rebuild_campaigns WHERE name = $1)",
+        &[&name],
+    )?;
+    let removed = transaction.execute("DELETE FROM rebuild_campaigns WHERE name = $1", &[&name])?;
+    transaction.commit()?;
+    Ok(removed > 0)
+}
+
+/// The progress of all campaigns, the most recent first.
+///
+/// This only reads the counters of the campaigns, which
+/// [`queue_campaign_batches`] updates as their builds finish.
+pub fn campaign_progress(conn: &mut Client) -> Result<Vec<CampaignProgress>> {
+    conn.query(
+        "SELECT
+            name,
+            selector,
+            priority,
+            batch_size,
+            created_at,
+            finished_at,
+            release_count,
+            queued_count,
+            succeeded_count,
+            failed_count,
+            fixed_count,
+            broken_count
+         FROM rebuild_campaigns
+         ORDER BY created_at DESC, id DESC",
+        &[],
+    )?
+    .into_iter()
+    .map(|row| {
+        let selector: CampaignSelector = serde_json::from_value(row.get("selector"))?;
+        let total = i64::from(row.get::<_, i32>("release_count"));
+        let queued_count = i64::from(row.get::<_, i32>("queued_count"));
+        let succeeded = i64::from(row.get::<_, i32>("succeeded_count"));
+        let failed = i64::from(row.get::<_, i32>("failed_count"));
+        Ok(CampaignProgress {
+            name: row.get("name"),
+            selector: selector.to_string(),
+            priority: row.get("priority"),
+            batch_size: row.get("batch_size"),
+            created_at: row.get("created_at"),
+            finished_at: row.get("finished_at"),
+            total,
+            pending: total - queued_count,
+            queued: queued_count - succeeded - failed,
+            succeeded,
+            failed,
+            fixed: row.get::<_, i32>("fixed_count").into(),
+            broken: row.get::<_, i32>("broken_count").into(),
+        })
+    })
+    .collect()
+}
+
+/// Records the result of the queued releases of unfinished campaigns that left the queue, or
+/// failed for the last time, and adds them to the counters of their campaigns.
+///
+/// A release counts as failed when it left the queue without a build.
+fn record_campaign_results(conn: &mut Client, config: &Config) -> Result<()> {
+    let max_attempts = i32::from(config.build_attempts);
+    conn.execute(
+        "WITH finished AS (
+            UPDATE rebuild_campaign_releases AS campaign_releases
+            SET successful_after = COALESCE((
+                SELECT builds.build_status
+                FROM builds
+                WHERE
+                    builds.rid = campaign_releases.release_id AND
+                    builds.build_time >= campaign_releases.queued_at
+                ORDER BY builds.id DESC
+                LIMIT 1
+            ), FALSE)
+            FROM rebuild_campaigns, releases, crates
+            WHERE
+                rebuild_campaigns.id = campaign_releases.campaign_id AND
+                rebuild_campaigns.finished_at IS NULL AND
+                releases.id = campaign_releases.release_id AND
+                crates.id = releases.crate_id AND
+                campaign_releases.queued_at IS NOT NULL AND
+                campaign_releases.successful_after IS NULL AND
+                NOT EXISTS (
+                    SELECT 1
+                    FROM queue
+                    WHERE
+                        queue.name = crates.name AND
+                        queue.version = releases.version AND
+                        queue.attempt < $1
+                )
+            RETURNING
+                campaign_releases.campaign_id,
+                campaign_releases.successful_before,
+                campaign_releases.successful_after
+         )
+         UPDATE rebuild_campaigns
+         SET
+            succeeded_count = succeeded_count + counts.succeeded,
+            failed_count = failed_count + counts.failed,
+            fixed_count = fixed_count + counts.fixed,
+            broken_count = broken_count + counts.broken
+         FROM (
+            SELECT
+                campaign_id,
+                COUNT(*) FILTER (WHERE successful_after) AS succeeded,
+                COUNT(*) FILTER (WHERE NOT successful_after) AS failed,
+                COUNT(*) FILTER (WHERE successful_after AND NOT successful_before) AS fixed,
+                COUNT(*) FILTER (WHERE NOT successful_after AND successful_before) AS broken
+            FROM finished
+            GROUP BY campaign_id
+         ) AS counts
+         WHERE counts.campaign_id = rebuild_campaigns.id",
+        &[&max_attempts],
+    )?;
+    Ok(())
+}
+
+/// Queues the next batch of every unfinished campaign, up to its batch size of builds in the
+/// queue at the same time, after recording the results of the builds that finished. Campaigns
+/// without anything left to build are marked as finished.
+///
+/// Returns the number of releases queued.
+pub fn queue_campaign_batches(
+    conn: &mut Client,
+    build_queue: &BuildQueue,
+    config: &Config,
+) -> Result<usize> {
+    record_campaign_results(conn, config)?;
+
+    let campaigns: Vec<(i32, String, i32, i32, i64)> = conn
+        .query(
+            "SELECT
+                id, name, priority, batch_size,
+                queued_count - succeeded_count - failed_count AS in_queue
+             FROM rebuild_campaigns
+             WHERE finished_at IS NULL
+             ORDER BY id",
+            &[],
+        )?
+        .into_iter()
+        .map(|row| {
+            (
+                row.get(0),
+                row.get(1),
+                row.get(2),
+                row.get(3),
+                row.get::<_, i32>(4).into(),
+            )
+        })
+        .collect();
+
+    let mut total_queued = 0;
+    for (id, name, priority, batch_size, in_queue) in campaigns {
+        let next_batch: Vec<(i32, String, String)> = conn
+            .query(
+                "SELECT releases.id, crates.name, releases.version
+                 FROM rebuild_campaign_releases AS campaign_releases
+                 INNER JOIN releases ON releases.id = campaign_releases.release_id
+                 INNER JOIN crates ON crates.id = releases.crate_id
+                 WHERE
+                    campaign_releases.campaign_id = $1 AND
+                    campaign_releases.queued_at IS NULL
+                 ORDER BY releases.id
+                 LIMIT $2",
+                &[&id, &(i64::from(batch_size) - in_queue).max(0)],
+            )?
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2)))
+            .collect();
+
+        if next_batch.is_empty() {
+            if in_queue == 0 {
+                conn.execute(
+                    "UPDATE rebuild_campaigns SET finished_at = NOW() WHERE id = $1",
+                    &[&id],
+                )?;
+                info!("rebuild campaign {} finished", name);
+            }
+            continue;
+        }
+
+        for (release_id, krate, version) in &next_batch {
+            // don't lower the priority of builds queued for another reason
+            if !build_queue.has_build_queued(krate, version)? {
+                build_queue.add_crate(
+                    krate,
+                    version,
+                    priority,
+                    config.index_url_for_crate(krate)?,
+                )?;
+            }
+            conn.execute(
+                "UPDATE rebuild_campaign_releases
+                 SET queued_at = NOW()
+                 WHERE campaign_id = $1 AND release_id = $2",
+                &[&id, release_id],
+            )?;
+        }
+        conn.execute(
+            "UPDATE rebuild_campaigns SET queued_count = queued_count + $2 WHERE id = $1",
+            &[&id, &(next_batch.len() as i32)],
+        )?;
+        info!(
+            "queued {} releases of rebuild campaign {}",
+            next_batch.len(),
+            name
+        );
+        total_queued += next_batch.len();
+    }
+
+    Ok(total_queued)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{wrapper, FakeBuild};
+    use chrono::Duration;
+
+    #[test]
+    fn select_releases() {
+        wrapper(|env| {
+            let old_rustc = "rustc 1.60.0-nightly (aaaaaaaaa 2022-01-01)";
+            let new_rustc = "rustc 1.70.0-nightly (bbbbbbbbb 2023-01-01)";
+            env.fake_release()
+                .name("old")
+                .builds(vec![FakeBuild::default().rustc_version(old_rustc)])
+                .create()?;
+            env.fake_release()
+                .name("new")
+                .builds(vec![FakeBuild::default().rustc_version(new_rustc)])
+                .create()?;
+            env.fake_release()
+                .name("old-yanked")
+                .yanked(true)
+                .builds(vec![FakeBuild::default().rustc_version(old_rustc)])
+                .create()?;
+            env.fake_release()
+                .name("broken")
+                .builds(vec![FakeBuild::default()
+                    .rustc_version(new_rustc)
+                    .successful(false)])
+                .create()?;
+
+            let names = |selector: CampaignSelector| -> Result<Vec<String>> {
+                let mut conn = env.db().conn();
+                let ids = selector.release_ids(&mut conn)?;
+                let mut names: Vec<String> = conn
+                    .query(
+                        "SELECT crates.name
+                         FROM releases
+                         INNER JOIN crates ON crates.id = releases.crate_id
+                         WHERE releases.id = ANY($1)",
+                        &[&ids],
+                    )?
+                    .into_iter()
+                    .map(|row| row.get(0))
+                    .collect();
+                names.sort();
+                Ok(names)
+            };
+
+            assert_eq!(
+                names(CampaignSelector::RustcOlderThan {
+                    date: NaiveDate::from_ymd_opt(2022, 6, 1).unwrap(),
+                })?,
+                vec!["old"]
+            );
+            assert_eq!(
+                names(CampaignSelector::FailedBetween {
+                    from: Utc::now() - Duration::hours(1),
+                    to: Utc::now() + Duration::hours(1),
+                })?,
+                vec!["broken"]
+            );
+            assert!(names(CampaignSelector::FailedBetween {
+                from: Utc::now() - Duration::days(2),
+                to: Utc::now() - Duration::days(1),
+            })?
+            .is_empty());
+            assert_eq!(
+                names(CampaignSelector::CratePattern {
+                    pattern: "old%".into(),
+                })?,
+                vec!["old"]
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn queue_batches_and_track_progress() {
+        wrapper(|env| {
+            for name in ["a", "b", "c"] {
+                env.fake_release()
+                    .name(name)
+                    .builds(vec![FakeBuild::default().successful(name != "b")])
+                    .create()?;
+            }
+            // a fresh publish of a selected release keeps its priority
+            let queue = env.build_queue();
+            queue.add_crate("c", "1.0.0", 0, None)?;
+
+            let config = env.config();
+            let mut conn = env.db().conn();
+            let selector = CampaignSelector::CratePattern {
+                pattern: "_".into(),
+            };
+            assert_eq!(create_campaign(&mut conn, "all", &selector, 20, 2)?, 3);
+
+            let progress = |conn: &mut Client| -> Result<CampaignProgress> {
+                Ok(campaign_progress(conn)?.remove(0))
+            };
+            let p = progress(&mut conn)?;
+            assert_eq!((p.total, p.pending, p.queued), (3, 3, 0));
+            assert_eq!(p.selector, "of crates matching `_`");
+
+            // only a batch is queued at a time
+            assert_eq!(queue_campaign_batches(&mut conn, &queue, &config)?, 2);
+            assert_eq!(queue_campaign_batches(&mut conn, &queue, &config)?, 0);
+            let queued: Vec<_> = queue
+                .queued_crates()?
+                .into_iter()
+                .map(|krate| (krate.name, krate.priority))
+                .collect();
+            assert_eq!(
+                queued,
+                vec![("c".to_string(), 0), ("a".into(), 20), ("b".into(), 20)]
+            );
+            let p = progress(&mut conn)?;
+            assert_eq!((p.pending, p.queued), (1, 2));
+
+            // the rebuild of `a` breaks it, `b` is fixed
+            conn.execute("DELETE FROM queue WHERE name IN ('a', 'b')", &[])?;
+            env.fake_release()
+                .name("a")
+                .builds(vec![FakeBuild::default().successful(false)])
+                .create()?;
+            env.fake_release()
+                .name("b")
+                .builds(vec![FakeBuild::default()])
+                .create()?;
+            // the results are counted when the next batch is queued, the last release is
+            // queued already
+            assert_eq!(queue_campaign_batches(&mut conn, &queue, &config)?, 1);
+            assert_eq!(queue.queued_crates()?[0].priority, 0);
+            let p = progress(&mut conn)?;
+            assert_eq!((p.pending, p.queued, p.succeeded, p.failed), (0, 1, 1, 1));
+            assert_eq!((p.fixed, p.broken), (1, 1));
+            conn.execute("DELETE FROM queue", &[])?;
+            env.fake_release()
+                .name("c")
+                .builds(vec![FakeBuild::default()])
+                .create()?;
+
+            assert_eq!(queue_campaign_batches(&mut conn, &queue, &config)?, 0);
+            let p = progress(&mut conn)?;
+            assert!(p.finished_at.is_some());
+            assert_eq!((p.succeeded, p.failed), (2, 1));
+
+            assert!(remove_campaign(&mut conn, "all")?);
+            assert!(!remove_campaign(&mut conn, "all")?);
+            assert!(campaign_progress(&mut conn)?.is_empty());
+
+            Ok(())
+        });
+    }
+}
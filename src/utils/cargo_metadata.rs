@@ -7,6 +7,8 @@ use std::path::Path;
 
 pub(crate) struct CargoMetadata {
     root: Package,
+    /// `(name, version)` of the packages the root package depends on, as resolved by cargo.
+    locked_dependencies: Vec<(String, String)>,
 }
 
 impl CargoMetadata {
@@ -43,18 +45,47 @@ impl CargoMetadata {
     pub(crate) fn load_from_metadata(metadata: &str) -> Result<Self> {
         let metadata = serde_json::from_str::<DeserializedMetadata>(metadata)?;
         let root = metadata.resolve.root;
+
+        let mut locked_dependencies: Vec<(String, String)> = metadata
+            .resolve
+            .nodes
+            .iter()
+            .find(|node| node.id == root)
+            .into_iter()
+            .flat_map(|node| &node.deps)
+            .filter_map(|dep| metadata.packages.iter().find(|pkg| pkg.id == dep.pkg))
+            .map(|pkg| (pkg.name.clone(), pkg.version.clone()))
+            .collect();
+        locked_dependencies.sort();
+        locked_dependencies.dedup();
+
         Ok(CargoMetadata {
             root: metadata
                 .packages
                 .into_iter()
                 .find(|pkg| pkg.id == root)
                 .context("metadata.packages missing root package")?,
+            locked_dependencies,
         })
     }
 
     pub(crate) fn root(&self) -> &Package {
         &self.root
     }
+
+    #[cfg(test)]
+    pub(crate) fn locked_dependencies(&self) -> &[(String, String)] {
+        &self.locked_dependencies
+    }
+
+    pub(crate) fn root_mut(&mut self) -> &mut Package {
+        &mut self.root
+    }
+
+    /// The root package and the locked dependencies.
+    pub(crate) fn into_parts(self) -> (Package, Vec<(String, String)>) {
+        (self.root, self.locked_dependencies)
+    }
 }
 
 #[derive(Deserialize, Serialize, Default)]
@@ -152,3 +183,52 @@ struct DeserializedResolveNode {
 struct DeserializedResolveDep {
     pkg: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locked_dependencies() {
+        let package = |name: &str, version: &str| {
+            serde_json::json!({
+                "id": format!("{name} {version}"),
+                "name": name,
+                "version": version,
+                "dependencies": [],
+                "targets": [],
+                "keywords": [],
+                "features": {},
+            })
+        };
+        let metadata = serde_json::json!({
+            "packages": [
+                package("root", "0.1.0"),
+                package("rand", "0.7.3"),
+                package("rand", "0.8.5"),
+                package("libc", "0.2.140"),
+                package("unused", "1.0.0"),
+            ],
+            "resolve": {
+                "root": "root 0.1.0",
+                "nodes": [
+                    {
+                        "id": "root 0.1.0",
+                        "deps": [{"pkg": "rand 0.8.5"}, {"pkg": "rand 0.7.3"}],
+                    },
+                    {"id": "rand 0.8.5", "deps": [{"pkg": "libc 0.2.140"}]},
+                ],
+            },
+        });
+
+        let metadata = CargoMetadata::load_from_metadata(&metadata.to_string()).unwrap();
+        assert_eq!(metadata.root().name, "root");
+        assert_eq!(
+            metadata.locked_dependencies(),
+            [
+                ("rand".to_owned(), "0.7.3".to_owned()),
+                ("rand".to_owned(), "0.8.5".to_owned()),
+            ]
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use super::data::{Crate, Crates, Release, Releases};
-use crate::Index;
+use crate::{index::sparse::SparseIndex, Index};
 use anyhow::Result;
-use rayon::iter::ParallelIterator;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
 pub(super) fn load(index: &Index) -> Result<Crates> {
     let mut result: Crates = index
@@ -31,3 +31,36 @@ pub(super) fn load(index: &Index) -> Result<Crates> {
 
     Ok(result)
 }
+
+/// Like [`load`], for indexes served over the sparse HTTP protocol.
+///
+/// These can't list their crates, so only the crates with the given names are looked up.
+/// Crates that are in the index but not in `names` are missing from the result.
+pub(super) fn load_sparse(index: &SparseIndex, names: Vec<&str>) -> Result<Crates> {
+    let mut result: Crates = names
+        .into_par_iter()
+        .filter_map(|name| match index.crate_versions(name) {
+            Ok(Some(versions)) => {
+                let mut releases: Releases = versions
+                    .into_iter()
+                    .map(|version| Release {
+                        version: version.version,
+                        yanked: Some(version.yanked),
+                    })
+                    .collect();
+                releases.sort_by(|lhs, rhs| lhs.version.cmp(&rhs.version));
+
+                Some(Ok(Crate {
+                    name: name.into(),
+                    releases,
+                }))
+            }
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        })
+        .collect::<Result<_>>()?;
+
+    result.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+
+    Ok(result)
+}
@@ -1,4 +1,4 @@
-use crate::{db::delete, Context};
+use crate::{db::delete, index::split_qualified_crate_name, Context};
 use anyhow::{Context as _, Result};
 use itertools::Itertools;
 use tracing::{info, warn};
@@ -22,6 +22,9 @@ const BUILD_PRIORITY: i32 = 15;
 /// * crate in our DB, but not in the index => delete the whole crate from our DB & storage.
 /// * different yank-state between DB & Index => update the yank-state in our DB
 ///
+/// When the index is served over the sparse HTTP protocol it can't list its crates, so only
+/// the crates already in our DB are looked up, and crates missing from our DB aren't found.
+///
 /// Even when activities fail, the command can just be re-run. While the diff calculation will
 /// be repeated, we won't re-execute fixing activities.
 pub fn run_check(ctx: &dyn Context, dry_run: bool) -> Result<()> {
@@ -33,8 +36,18 @@ pub fn run_check(ctx: &dyn Context, dry_run: bool) -> Result<()> {
         .context("Loading crate data from database for consistency check")?;
 
     tracing::info!("Loading data from index...");
-    let index_data =
-        index::load(&index).context("Loading crate data from index for consistency check")?;
+    let index_data = match index.sparse() {
+        Some(sparse) => index::load_sparse(
+            sparse,
+            db_data
+                .iter()
+                .map(|krate| krate.name.as_str())
+                .filter(|name| split_qualified_crate_name(name).0.is_none())
+                .collect(),
+        ),
+        None => index::load(&index),
+    }
+    .context("Loading crate data from index for consistency check")?;
 
     let diff = diff::calculate_diff(db_data.iter(), index_data.iter());
     let result = handle_diff(ctx, diff.iter(), dry_run)?;
@@ -4,18 +4,77 @@
 
 use crate::{
     cdn,
-    utils::{queue_builder, report_error},
+    docbuilder::{remove_stale_running_builds, RUNNING_BUILD_TIMEOUT},
+    utils::{queue_builder, queue_campaign_batches, report_error},
     web::start_web_server,
     BuildQueue, Config, Context, Index, RustwideBuilder,
 };
 use anyhow::{anyhow, Context as _, Error};
+use postgres::{fallible_iterator::FallibleIterator, Client, NoTls};
 use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 use tracing::{debug, info};
 
+/// Postgres notification channel used by the index webhook to wake up the registry watchers.
+///
+/// The payload is the name of the alternative registry whose index changed, empty for the main
+/// index.
+const INDEX_WEBHOOK_CHANNEL: &str = "index_webhook";
+
+/// Tell the registry watcher of `registry` to check the index for new crates right away.
+///
+/// This works across processes, so the web server doesn't need to run the registry watcher.
+pub(crate) fn trigger_index_sync(conn: &mut Client, registry: Option<&str>) -> Result<(), Error> {
+    conn.execute(
+        "SELECT pg_notify($1, $2)",
+        &[&INDEX_WEBHOOK_CHANNEL, &registry.unwrap_or_default()],
+    )?;
+    Ok(())
+}
+
+/// Open a connection listening to the webhook channel.
+///
+/// This is a dedicated connection instead of one from the pool, since it's held for as long as
+/// the registry watcher runs.
+fn listen_for_index_sync_triggers(config: &Config) -> Result<Client, Error> {
+    let mut conn = Client::connect(&config.database_url, NoTls)?;
+    conn.batch_execute(&format!("LISTEN {INDEX_WEBHOOK_CHANNEL}"))?;
+    Ok(conn)
+}
+
+/// Wait until [`trigger_index_sync`] is called for `registry` or `timeout` has passed.
+///
+/// Returns whether the sync was triggered. `conn` has to be listening to the webhook channel.
+fn wait_for_index_sync_trigger(
+    conn: &mut Client,
+    registry: Option<&str>,
+    timeout: Duration,
+) -> Result<bool, Error> {
+    let registry = registry.unwrap_or_default();
+    let deadline = Instant::now() + timeout;
+    let mut notifications = conn.notifications();
+
+    let mut triggered = false;
+    while !triggered {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match notifications.timeout_iter(remaining).next()? {
+            Some(notification) => triggered = notification.payload() == registry,
+            None => break,
+        }
+    }
+    // webhooks received while the last sync was running are handled by the next sync as well
+    while notifications.iter().next()?.is_some() {}
+    Ok(triggered)
+}
+
 /// Run the registry watcher
-/// NOTE: this should only be run once, otherwise crates would be added
+///
+/// The index is checked for new crates whenever the index webhook is called, and every
+/// `index_poll_fallback_interval` seconds when it isn't. Errors are reported and retried, the
+/// watcher never stops.
+///
+/// NOTE: this should only be run once per index, otherwise crates would be added
 /// to the queue multiple times.
 pub fn watch_registry(
     build_queue: Arc<BuildQueue>,
@@ -23,26 +82,58 @@ pub fn watch_registry(
     index: Arc<Index>,
 ) -> Result<(), Error> {
     let mut last_gc = Instant::now();
+    let poll_interval = Duration::from_secs(config.index_poll_fallback_interval);
+
+    let mut listener: Option<Client> = None;
+    // how long to wait before trying to listen for the webhook again, doubled on every failure
+    let mut retry_delay = Duration::from_secs(1);
 
     loop {
-        if build_queue.is_locked()? {
-            debug!("Queue is locked, skipping checking new crates");
-        } else {
-            debug!("Checking new crates");
-            match build_queue
-                .get_new_crates(&index)
-                .context("Failed to get new crates")
-            {
-                Ok(n) => debug!("{} crates added to queue", n),
-                Err(e) => report_error(&e),
+        match build_queue.is_locked() {
+            Ok(true) => debug!("Queue is locked, skipping checking new crates"),
+            Ok(false) => {
+                debug!("Checking new crates");
+                match build_queue
+                    .get_new_crates(&index)
+                    .context("Failed to get new crates")
+                {
+                    Ok(n) => debug!("{} crates added to queue", n),
+                    Err(e) => report_error(&e),
+                }
             }
+            Err(err) => report_error(&err.context("failed to check if the queue is locked")),
         }
 
         if last_gc.elapsed().as_secs() >= config.registry_gc_interval {
             index.run_git_gc();
             last_gc = Instant::now();
         }
-        thread::sleep(Duration::from_secs(60));
+
+        if listener.is_none() {
+            match listen_for_index_sync_triggers(&config) {
+                Ok(conn) => {
+                    listener = Some(conn);
+                    retry_delay = Duration::from_secs(1);
+                }
+                Err(err) => {
+                    report_error(&err.context("failed to listen for the index webhook"));
+                    thread::sleep(retry_delay);
+                    retry_delay = (retry_delay * 2).min(poll_interval);
+                    continue;
+                }
+            }
+        }
+
+        if let Some(conn) = listener.as_mut() {
+            match wait_for_index_sync_trigger(conn, index.registry(), poll_interval) {
+                Ok(true) => debug!("index webhook received"),
+                Ok(false) => debug!("no index webhook received, falling back to polling"),
+                Err(err) => {
+                    report_error(&err.context("failed to wait for the index webhook"));
+                    listener = None;
+                }
+            }
+        }
     }
 }
 
@@ -60,6 +151,28 @@ fn start_registry_watcher(context: &dyn Context) -> Result<(), Error> {
             watch_registry(build_queue, config, index)
         })?;
 
+    start_alternative_registry_watchers(context)
+}
+
+/// Start one registry watcher thread for each configured alternative registry.
+pub fn start_alternative_registry_watchers(context: &dyn Context) -> Result<(), Error> {
+    for index in context.alternative_indexes()?.iter() {
+        let build_queue = context.build_queue()?;
+        let config = context.config()?;
+        let index = index.clone();
+
+        thread::Builder::new()
+            .name(format!(
+                "registry index reader ({})",
+                index.registry().unwrap_or_default()
+            ))
+            .spawn(move || {
+                thread::sleep(Duration::from_secs(30));
+
+                watch_registry(build_queue, config, index)
+            })?;
+    }
+
     Ok(())
 }
 
@@ -113,6 +226,35 @@ pub fn start_background_cdn_invalidator(context: &dyn Context) -> Result<(), Err
     Ok(())
 }
 
+/// Queues the next batches of the rebuild campaigns whenever the previous ones were built.
+pub fn start_background_campaign_scheduler(context: &dyn Context) -> Result<(), Error> {
+    let build_queue = context.build_queue()?;
+    let config = context.config()?;
+    let pool = context.pool()?;
+
+    cron("rebuild campaigns", Duration::from_secs(60), move || {
+        let queued = queue_campaign_batches(&mut *pool.get()?, &build_queue, &config)?;
+        debug!("{} releases of rebuild campaigns queued", queued);
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// Removes the running builds of builders that died mid-build, which would otherwise be shown
+/// on the queue page forever.
+pub fn start_background_running_build_reaper(context: &dyn Context) -> Result<(), Error> {
+    let pool = context.pool()?;
+
+    cron("running build reaper", RUNNING_BUILD_TIMEOUT, move || {
+        let removed = remove_stale_running_builds(&mut *pool.get()?)?;
+        if removed > 0 {
+            info!("removed {} running builds of builders that died", removed);
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
 pub fn start_daemon<C: Context + Send + Sync + 'static>(
     context: C,
     enable_registry_watcher: bool,
@@ -128,8 +270,10 @@ pub fn start_daemon<C: Context + Send + Sync + 'static>(
     });
 
     if enable_registry_watcher {
-        // check new crates every minute
+        // check new crates whenever the index webhook is called
         start_registry_watcher(&*context)?;
+        // like new crates, campaign builds are only queued by one instance
+        start_background_campaign_scheduler(&*context)?;
     }
 
     // build new crates every minute
@@ -144,6 +288,7 @@ pub fn start_daemon<C: Context + Send + Sync + 'static>(
 
     start_background_repository_stats_updater(&*context)?;
     start_background_cdn_invalidator(&*context)?;
+    start_background_running_build_reaper(&*context)?;
 
     // NOTE: if a error occurred earlier in `start_daemon`, the server will _not_ be joined -
     // instead it will get killed when the process exits.
@@ -168,3 +313,34 @@ where
         })?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::wrapper;
+
+    #[test]
+    fn index_webhook_wakes_up_registry_watcher() {
+        wrapper(|env| {
+            let mut listener = env.db().pool().get()?;
+            listener.batch_execute(&format!("LISTEN {INDEX_WEBHOOK_CHANNEL}"))?;
+
+            // webhooks of other registries don't wake up the watcher
+            trigger_index_sync(&mut env.db().conn(), Some("other-registry"))?;
+            assert!(!wait_for_index_sync_trigger(
+                &mut listener,
+                Some("daemon-test"),
+                Duration::from_millis(500)
+            )?);
+
+            trigger_index_sync(&mut env.db().conn(), Some("daemon-test"))?;
+            assert!(wait_for_index_sync_trigger(
+                &mut listener,
+                Some("daemon-test"),
+                Duration::from_secs(10)
+            )?);
+
+            Ok(())
+        });
+    }
+}
@@ -1,19 +1,56 @@
 use crate::web::page::TemplateData;
 use lol_html::element;
 use lol_html::errors::RewritingError;
+use std::collections::BTreeMap;
 use tera::Context;
 
+/// Rewrites a link to the latest documentation of a dependency to the version
+/// that was used when building the documentation.
+///
+/// Handles the `latest` and `*` versions in `/{crate}/{version}/...` and
+/// `/crate/{crate}/{version}/...` links, either relative to the root or on `public_url`.
+/// `dependency_versions` is keyed by the crate name with dashes replaced by underscores.
+fn rewrite_dependency_link(
+    href: &str,
+    public_url: &str,
+    dependency_versions: &BTreeMap<String, &String>,
+) -> Option<String> {
+    let (origin, path) = match href.strip_prefix(public_url) {
+        Some(path) => (public_url, path),
+        None if href.starts_with('/') && !href.starts_with("//") => ("", href),
+        None => return None,
+    };
+    let path = path.strip_prefix('/')?;
+    let (prefix, path) = match path.strip_prefix("crate/") {
+        Some(path) => ("/crate", path),
+        None => ("", path),
+    };
+    let (name, path) = path.split_once('/')?;
+    let (version, path) = path.split_once('/').unwrap_or((path, ""));
+    if version != "latest" && version != "*" {
+        return None;
+    }
+
+    let locked_version = dependency_versions.get(&name.replace('-', "_"))?;
+    Some(format!("{origin}{prefix}/{name}/{locked_version}/{path}"))
+}
+
 /// Rewrite a rustdoc page to have the docs.rs topbar
 ///
 /// Given a rustdoc HTML page and a context to serialize it with,
 /// render the `rustdoc/` templates with the `html`.
 /// The output is an HTML page which has not yet been UTF-8 validated.
 /// In practice, the output should always be valid UTF-8.
+///
+/// Links to the latest documentation of a dependency are rewritten to the version
+/// in `locked_dependencies`, see [`rewrite_dependency_link`].
 pub(crate) fn rewrite_lol(
     html: &[u8],
     max_allowed_memory_usage: usize,
     ctx: Context,
     templates: &TemplateData,
+    public_url: &str,
+    locked_dependencies: &BTreeMap<String, String>,
 ) -> Result<Vec<u8>, RewritingError> {
     use lol_html::html_content::{ContentType, Element};
     use lol_html::{HtmlRewriter, MemorySettings, Settings};
@@ -58,7 +95,20 @@ pub(crate) fn rewrite_lol(
         Ok(())
     };
 
-    let settings = Settings {
+    let dependency_versions: BTreeMap<String, &String> = locked_dependencies
+        .iter()
+        .map(|(name, version)| (name.replace('-', "_"), version))
+        .collect();
+    let dependency_link_handler = |link: &mut Element| {
+        if let Some(href) = link.get_attribute("href") {
+            if let Some(href) = rewrite_dependency_link(&href, public_url, &dependency_versions) {
+                link.set_attribute("href", &href)?;
+            }
+        }
+        Ok(())
+    };
+
+    let mut settings = Settings {
         element_content_handlers: vec![
             // Append `style.css` stylesheet after all head elements.
             element!("head", |head: &mut Element| {
@@ -92,6 +142,11 @@ pub(crate) fn rewrite_lol(
         },
         ..Settings::default()
     };
+    if !dependency_versions.is_empty() {
+        settings
+            .element_content_handlers
+            .push(element!("a[href]", dependency_link_handler));
+    }
 
     // The input and output are always strings, we just use `&[u8]` so we only have to validate once.
     let mut buffer = Vec::new();
@@ -108,7 +163,93 @@ pub(crate) fn rewrite_lol(
 
 #[cfg(test)]
 mod test {
+    use super::rewrite_dependency_link;
     use crate::test::wrapper;
+    use kuchikiki::traits::TendrilSink;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn dependency_links() {
+        let version = "1.0.5".to_owned();
+        let versions = BTreeMap::from([("serde_json".to_owned(), &version)]);
+        let rewrite = |href| rewrite_dependency_link(href, "https://docs.rs", &versions);
+
+        assert_eq!(
+            rewrite("/serde_json/latest/serde_json/struct.Value.html").as_deref(),
+            Some("/serde_json/1.0.5/serde_json/struct.Value.html")
+        );
+        assert_eq!(
+            rewrite("https://docs.rs/serde-json/*/serde_json/").as_deref(),
+            Some("https://docs.rs/serde-json/1.0.5/serde_json/")
+        );
+        assert_eq!(
+            rewrite(
+                "/crate/serde_json/latest/target-redirect/x86_64-unknown-linux-gnu/serde_json/"
+            )
+            .as_deref(),
+            Some("/crate/serde_json/1.0.5/target-redirect/x86_64-unknown-linux-gnu/serde_json/")
+        );
+        assert_eq!(
+            rewrite("/serde_json/latest").as_deref(),
+            Some("/serde_json/1.0.5/")
+        );
+
+        // already versioned, other crates, other sites and relative links
+        assert_eq!(rewrite("/serde_json/1.0.0/serde_json/"), None);
+        assert_eq!(rewrite("/serde/latest/serde/"), None);
+        assert_eq!(rewrite("https://example.com/serde_json/latest/"), None);
+        assert_eq!(rewrite("//example.com/serde_json/latest/"), None);
+        assert_eq!(rewrite("../serde_json/latest/index.html"), None);
+    }
+
+    #[test]
+    fn links_to_locked_dependency_versions() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("testing")
+                .version("0.1.0")
+                .add_dependency("serde-json", "^1.0", None)
+                .locked_dependency("serde-json", "1.0.5")
+                .rustdoc_file_with(
+                    "testing/index.html",
+                    br#"<html><head></head><body>
+                        <a id="latest" href="https://docs.rs/serde_json/latest/serde_json/enum.Value.html">Value</a>
+                        <a id="versioned" href="/serde_json/1.0.0/serde_json/">serde_json</a>
+                    </body></html>"#,
+                )
+                .create()?;
+
+            let page = kuchikiki::parse_html().one(
+                env.frontend()
+                    .get("/testing/0.1.0/testing/")
+                    .send()?
+                    .text()?,
+            );
+            let href = |selector: &str| {
+                page.select_first(selector)
+                    .unwrap()
+                    .attributes
+                    .borrow()
+                    .get("href")
+                    .unwrap()
+                    .to_owned()
+            };
+            assert_eq!(
+                href("#latest"),
+                "https://docs.rs/serde_json/1.0.5/serde_json/enum.Value.html"
+            );
+            assert_eq!(href("#versioned"), "/serde_json/1.0.0/serde_json/");
+
+            let dependencies: Vec<String> = page
+                .select(".sub-menu a[href^='/serde-json/']")
+                .unwrap()
+                .map(|link| link.attributes.borrow().get("href").unwrap().to_owned())
+                .collect();
+            assert_eq!(dependencies, vec!["/serde-json/1.0.5"]);
+
+            Ok(())
+        });
+    }
 
     #[test]
     fn rewriting_only_injects_css_once() {
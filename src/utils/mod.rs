@@ -1,5 +1,9 @@
 //! Various utilities for docs.rs
 
+pub use self::campaigns::{
+    campaign_progress, create_campaign, queue_campaign_batches, remove_campaign, CampaignProgress,
+    CampaignSelector, DEFAULT_CAMPAIGN_BATCH_SIZE, DEFAULT_CAMPAIGN_PRIORITY,
+};
 pub(crate) use self::cargo_metadata::{CargoMetadata, Package as MetadataPackage};
 pub(crate) use self::copy::copy_dir_all;
 pub use self::daemon::{start_daemon, watch_registry};
@@ -8,12 +12,13 @@ pub use self::queue::{
     get_crate_pattern_and_priority, get_crate_priority, list_crate_priorities,
     remove_crate_priority, set_crate_priority,
 };
-pub use self::queue_builder::queue_builder;
+pub use self::queue_builder::{build_worker, queue_builder};
 pub(crate) use self::rustc_version::{get_correct_docsrs_style_file, parse_rustc_version};
 
 #[cfg(test)]
 pub(crate) use self::cargo_metadata::{Dependency, Target};
 
+mod campaigns;
 mod cargo_metadata;
 #[cfg(feature = "consistency_check")]
 pub mod consistency;
@@ -55,6 +60,8 @@ pub(crate) fn report_error(err: &anyhow::Error) {
 pub enum ConfigName {
     RustcVersion,
     LastSeenIndexReference,
+    /// The time of the last registry update seen in a sparse index.
+    LastSeenIndexUpdate,
     QueueLocked,
 }
 
@@ -63,7 +70,22 @@ pub fn set_config(
     name: ConfigName,
     value: impl Serialize,
 ) -> anyhow::Result<()> {
-    let name: &'static str = name.into();
+    set_config_by_key(conn, name.into(), value)
+}
+
+pub fn get_config<T>(conn: &mut Client, name: ConfigName) -> Result<Option<T>>
+where
+    T: DeserializeOwned,
+{
+    get_config_by_key(conn, name.into())
+}
+
+/// Like [`set_config`], for settings whose name isn't known at compile time.
+pub(crate) fn set_config_by_key(
+    conn: &mut Client,
+    name: &str,
+    value: impl Serialize,
+) -> anyhow::Result<()> {
     conn.execute(
         "INSERT INTO config (name, value)
         VALUES ($1, $2)
@@ -73,11 +95,11 @@ pub fn set_config(
     Ok(())
 }
 
-pub fn get_config<T>(conn: &mut Client, name: ConfigName) -> Result<Option<T>>
+/// Like [`get_config`], for settings whose name isn't known at compile time.
+pub(crate) fn get_config_by_key<T>(conn: &mut Client, name: &str) -> Result<Option<T>>
 where
     T: DeserializeOwned,
 {
-    let name: &'static str = name.into();
     Ok(
         match conn.query_opt("SELECT value FROM config WHERE name = $1;", &[&name])? {
             Some(row) => serde_json::from_value(row.get("value"))?,
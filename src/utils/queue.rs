@@ -1,7 +1,7 @@
 //! Utilities for interacting with the build queue
 
 use crate::error::Result;
-use postgres::Client;
+use postgres::{Client, GenericClient};
 
 const DEFAULT_PRIORITY: i32 = 0;
 
@@ -44,7 +44,11 @@ pub fn get_crate_priority(conn: &mut Client, name: &str) -> Result<i32> {
 /// Note: `pattern` is used in a `LIKE` statement, so it must follow the postgres like syntax
 ///
 /// [`pattern`]: https://www.postgresql.org/docs/8.3/functions-matching.html
-pub fn set_crate_priority(conn: &mut Client, pattern: &str, priority: i32) -> Result<()> {
+pub fn set_crate_priority(
+    conn: &mut impl GenericClient,
+    pattern: &str,
+    priority: i32,
+) -> Result<()> {
     conn.query(
         "INSERT INTO crate_priorities (pattern, priority) VALUES ($1, $2)",
         &[&pattern, &priority],
@@ -55,7 +59,7 @@ pub fn set_crate_priority(conn: &mut Client, pattern: &str, priority: i32) -> Re
 
 /// Remove a pattern from the priority table, returning the priority that it was associated with or `None`
 /// if nothing was removed
-pub fn remove_crate_priority(conn: &mut Client, pattern: &str) -> Result<Option<i32>> {
+pub fn remove_crate_priority(conn: &mut impl GenericClient, pattern: &str) -> Result<Option<i32>> {
     let query = conn.query(
         "DELETE FROM crate_priorities WHERE pattern = $1 RETURNING priority",
         &[&pattern],
@@ -74,7 +78,7 @@ mod tests {
         wrapper(|env| {
             let db = env.db();
 
-            set_crate_priority(&mut db.conn(), "docsrs-%", -100)?;
+            set_crate_priority(&mut *db.conn(), "docsrs-%", -100)?;
             assert_eq!(get_crate_priority(&mut db.conn(), "docsrs-database")?, -100);
             assert_eq!(get_crate_priority(&mut db.conn(), "docsrs-")?, -100);
             assert_eq!(get_crate_priority(&mut db.conn(), "docsrs-s3")?, -100);
@@ -87,11 +91,11 @@ mod tests {
                 DEFAULT_PRIORITY
             );
 
-            set_crate_priority(&mut db.conn(), "_c_", 100)?;
+            set_crate_priority(&mut *db.conn(), "_c_", 100)?;
             assert_eq!(get_crate_priority(&mut db.conn(), "rcc")?, 100);
             assert_eq!(get_crate_priority(&mut db.conn(), "rc")?, DEFAULT_PRIORITY);
 
-            set_crate_priority(&mut db.conn(), "hexponent", 10)?;
+            set_crate_priority(&mut *db.conn(), "hexponent", 10)?;
             assert_eq!(get_crate_priority(&mut db.conn(), "hexponent")?, 10);
             assert_eq!(
                 get_crate_priority(&mut db.conn(), "hexponents")?,
@@ -111,11 +115,11 @@ mod tests {
         wrapper(|env| {
             let db = env.db();
 
-            set_crate_priority(&mut db.conn(), "docsrs-%", -100)?;
+            set_crate_priority(&mut *db.conn(), "docsrs-%", -100)?;
             assert_eq!(get_crate_priority(&mut db.conn(), "docsrs-")?, -100);
 
             assert_eq!(
-                remove_crate_priority(&mut db.conn(), "docsrs-%")?,
+                remove_crate_priority(&mut *db.conn(), "docsrs-%")?,
                 Some(-100)
             );
             assert_eq!(
@@ -132,7 +136,7 @@ mod tests {
         wrapper(|env| {
             let db = env.db();
 
-            set_crate_priority(&mut db.conn(), "docsrs-%", -100)?;
+            set_crate_priority(&mut *db.conn(), "docsrs-%", -100)?;
 
             assert_eq!(get_crate_priority(&mut db.conn(), "docsrs-database")?, -100);
             assert_eq!(get_crate_priority(&mut db.conn(), "docsrs-")?, -100);
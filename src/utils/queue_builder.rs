@@ -1,6 +1,11 @@
-use crate::{docbuilder::RustwideBuilder, utils::report_error, BuildQueue};
-use anyhow::{Context, Error};
+use crate::build_queue::BuildLease;
+use crate::docbuilder::{RustwideBuilder, WorkerClient};
+use crate::utils::{report_error, retry};
+use crate::BuildQueue;
+use anyhow::{anyhow, Context, Error};
+use chrono::Utc;
 use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::sync::Arc;
 use std::time::Duration;
 use std::{fs, io, thread};
@@ -55,6 +60,100 @@ pub fn queue_builder(
     }
 }
 
+/// Builds crates leased from the build server, like [`queue_builder`] does for the queue in the
+/// database.
+pub fn build_worker(mut builder: RustwideBuilder, client: Arc<WorkerClient>) -> Result<(), Error> {
+    loop {
+        if let Err(e) = remove_tempdirs() {
+            report_error(&anyhow::anyhow!(e).context("failed to remove temporary directories"));
+        }
+
+        debug!("Leasing a build");
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            match build_leased_crate(&mut builder, &client) {
+                Ok(true) => {}
+                Ok(false) => {
+                    debug!("Nothing to build, going back to sleep");
+                    thread::sleep(Duration::from_secs(60));
+                }
+                Err(e) => {
+                    report_error(&e.context("Failed to build leased crate"));
+                    thread::sleep(Duration::from_secs(60));
+                }
+            }
+        }));
+
+        if let Err(e) = res {
+            error!("GRAVE ERROR Building leased crates panicked: {:?}", e);
+            thread::sleep(Duration::from_secs(60));
+        }
+    }
+}
+
+/// Leases the next build and runs it. Returns whether there was something to build.
+///
+/// Builds that can't be run are handed back to the build server, which retries them later.
+fn build_leased_crate(builder: &mut RustwideBuilder, client: &WorkerClient) -> Result<bool, Error> {
+    let Some(leased) = client.lease()? else {
+        return Ok(false);
+    };
+
+    let res = with_heartbeats(client, &leased.lease, || {
+        catch_unwind(AssertUnwindSafe(|| -> Result<(), Error> {
+            if retry(|| builder.update_toolchain(), 3).context("Updating toolchain failed")? {
+                // toolchain has changed, purge caches
+                retry(|| builder.purge_caches(), 3).context("purging rustwide caches failed")?;
+            }
+            builder.build_leased_package(&leased)?;
+            Ok(())
+        }))
+        .unwrap_or_else(|_| Err(anyhow!("the build panicked")))
+    });
+
+    if let Err(err) = res {
+        client.fail(&leased.lease, &format!("{err:?}"))?;
+        return Err(err);
+    }
+    Ok(true)
+}
+
+/// Renews the lease while `f` runs, so it doesn't expire during long builds.
+fn with_heartbeats<T>(client: &WorkerClient, lease: &BuildLease, f: impl FnOnce() -> T) -> T {
+    let (stop, stopped) = mpsc::channel::<()>();
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            let mut expires_at = lease.expires_at;
+            loop {
+                // renew a few times per lease duration, so a single lost heartbeat doesn't matter
+                let interval = ((expires_at - Utc::now()) / 3)
+                    .to_std()
+                    .unwrap_or_default()
+                    .max(Duration::from_secs(1));
+                if let Ok(()) | Err(RecvTimeoutError::Disconnected) = stopped.recv_timeout(interval)
+                {
+                    return;
+                }
+
+                match client.heartbeat(lease, None) {
+                    Ok(Some(renewed)) => expires_at = renewed,
+                    Ok(None) => {
+                        warn!(
+                            "lost the lease of {} {}, the build will be discarded",
+                            lease.name, lease.version
+                        );
+                        return;
+                    }
+                    Err(err) => report_error(&err.context("could not renew the build lease")),
+                }
+            }
+        });
+
+        let result = f();
+        drop(stop);
+        result
+    })
+}
+
 /// Sometimes, when the server hits a hard crash or a build thread panics,
 /// rustwide_builder won't actually remove the temporary directories it creates.
 /// Remove them now to avoid running out of disk space.
@@ -0,0 +1,947 @@
+//! The admin interface, exposing the operations of the `cratesfyi` CLI on the web.
+//!
+//! Admins authenticate with one of the tokens configured in `DOCSRS_ADMIN_TOKENS`, either by
+//! logging in through the form on `/-/admin/login`, which stores a session derived from the token
+//! in a cookie for [`SESSION_MAX_AGE`], or by sending it as `Authorization: Bearer <token>` header.
+//! Every change is recorded in the `admin_audit_log` table together with the name of the token
+//! that was used, in the same transaction as the change itself.
+
+use super::cache::CachePolicy;
+use crate::{
+    db::{
+        self,
+        delete::{
+            delete_crate_from_database, delete_crate_from_storage, delete_version_from_database,
+            delete_version_from_storage,
+        },
+        Overrides, Pool,
+    },
+    impl_axum_webpage,
+    utils::{list_crate_priorities, remove_crate_priority, set_crate_priority, spawn_blocking},
+    web::{
+        error::{AxumNope, AxumResult},
+        AxumErrorPage,
+    },
+    BuildQueue, Config, Storage,
+};
+use anyhow::Context as _;
+use axum::{
+    async_trait,
+    extract::{Extension, Form, FromRequestParts},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Redirect, Response as AxumResponse},
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use postgres::{GenericClient, Transaction};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use sha2::Sha256;
+use std::{fmt::Display, str::FromStr, sync::Arc, time::Duration};
+
+/// Cookie storing the admin session after logging in.
+const SESSION_COOKIE: &str = "docsrs_admin_session";
+
+/// The message signed with the admin token to derive the session stored in the cookie.
+const SESSION_MESSAGE: &[u8] = b"docs.rs admin session";
+
+/// How long a session stays valid after logging in.
+const SESSION_MAX_AGE: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Where the admin interface is mounted, below the `/-/` prefix reserved for docs.rs itself.
+pub(crate) const ADMIN_PATH: &str = "/-/admin";
+
+/// How many entries of the audit log are shown on the admin page.
+const AUDIT_LOG_ENTRIES: i64 = 50;
+
+/// An admin authenticated with one of the configured admin tokens.
+pub(crate) struct Admin {
+    name: String,
+}
+
+impl Admin {
+    fn authenticate(config: &Config, token: &str) -> Option<Self> {
+        config
+            .admin_tokens
+            .iter()
+            .find(|(_, expected)| constant_time_eq(expected.as_bytes(), token.as_bytes()))
+            .map(|(name, _)| Self { name: name.clone() })
+    }
+
+    fn authenticate_session(config: &Config, session: &str) -> Option<Self> {
+        let issued_at: i64 = session.split_once('.')?.0.parse().ok()?;
+        let age = Utc::now().timestamp().checked_sub(issued_at)?;
+        if !(0..=SESSION_MAX_AGE.as_secs() as i64).contains(&age) {
+            return None;
+        }
+
+        config
+            .admin_tokens
+            .iter()
+            .find(|(_, token)| {
+                constant_time_eq(
+                    session_for_token(token, issued_at).as_bytes(),
+                    session.as_bytes(),
+                )
+            })
+            .map(|(name, _)| Self { name: name.clone() })
+    }
+}
+
+/// Compare two tokens without leaking through the timing where they differ.
+pub(super) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// The session stored in the cookie instead of the token itself, so a leaked cookie doesn't
+/// reveal the token. The time of the login is signed into it, so it expires after
+/// [`SESSION_MAX_AGE`] or when the token is removed from the configuration.
+fn session_for_token(token: &str, issued_at: i64) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(token.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(SESSION_MESSAGE);
+    mac.update(issued_at.to_string().as_bytes());
+    format!("{issued_at}.{}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// The attributes of the session cookie, only marked `Secure` when the instance is served over
+/// HTTPS so logging in works on plain HTTP development instances.
+fn session_cookie_attributes(config: &Config) -> String {
+    let mut attributes = format!("Path={ADMIN_PATH}; HttpOnly; SameSite=Strict");
+    if config.public_url.starts_with("https://") {
+        attributes.push_str("; Secure");
+    }
+    attributes
+}
+
+enum Credentials<'a> {
+    Token(&'a str),
+    Session(&'a str),
+}
+
+fn credentials_from_request(parts: &Parts) -> Option<Credentials<'_>> {
+    if let Some(authorization) = parts.headers.get(header::AUTHORIZATION) {
+        return authorization
+            .to_str()
+            .ok()?
+            .strip_prefix("Bearer ")
+            .map(Credentials::Token);
+    }
+
+    parts
+        .headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, session)| Credentials::Session(session))
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Admin
+where
+    S: Send + Sync,
+{
+    type Rejection = AxumNope;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(config) = Extension::<Arc<Config>>::from_request_parts(parts, state)
+            .await
+            .map_err(|err| AxumNope::InternalError(err.into()))?;
+
+        match credentials_from_request(parts) {
+            Some(Credentials::Token(token)) => Admin::authenticate(&config, token),
+            Some(Credentials::Session(session)) => Admin::authenticate_session(&config, session),
+            None => None,
+        }
+        .ok_or(AxumNope::Unauthorized)
+    }
+}
+
+/// Record an action of an admin in the audit log.
+fn record_action(
+    conn: &mut impl GenericClient,
+    admin: &Admin,
+    action: &str,
+    details: &str,
+) -> anyhow::Result<()> {
+    conn.execute(
+        "INSERT INTO admin_audit_log (actor, action, details) VALUES ($1, $2, $3)",
+        &[&admin.name, &action, &details],
+    )
+    .context("could not record admin action")?;
+    Ok(())
+}
+
+/// Failed admin actions are mostly caused by invalid input, so the cause is shown to the admin.
+fn action_failed(err: anyhow::Error) -> AxumResponse {
+    AxumErrorPage {
+        title: "The admin action failed",
+        message: format!("{err:#}").into(),
+        status: StatusCode::BAD_REQUEST,
+    }
+    .into_response()
+}
+
+/// Run an admin action, record it in the audit log and go back to the admin page.
+///
+/// The action and its audit log entry are committed in one transaction, so there are no
+/// unrecorded changes and no entries for actions that were rolled back.
+async fn run_action<F>(pool: Pool, admin: Admin, action: &'static str, f: F) -> AxumResponse
+where
+    F: FnOnce(&mut Transaction<'_>) -> anyhow::Result<String> + Send + 'static,
+{
+    run_action_then(
+        pool,
+        admin,
+        action,
+        |transaction| Ok((f(transaction)?, ())),
+        |()| Ok(()),
+    )
+    .await
+}
+
+/// Like [`run_action`], for actions that also change something outside of the database, which
+/// `then` does with the value returned by `f` once the transaction is committed.
+async fn run_action_then<F, T, G>(
+    pool: Pool,
+    admin: Admin,
+    action: &'static str,
+    f: F,
+    then: G,
+) -> AxumResponse
+where
+    F: FnOnce(&mut Transaction<'_>) -> anyhow::Result<(String, T)> + Send + 'static,
+    G: FnOnce(T) -> anyhow::Result<()> + Send + 'static,
+{
+    let result = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let mut transaction = conn.transaction()?;
+        match f(&mut transaction) {
+            Ok((details, value)) => {
+                record_action(&mut transaction, &admin, action, &details)?;
+                transaction.commit()?;
+                Ok(then(value))
+            }
+            Err(err) => Ok(Err(err)),
+        }
+    })
+    .await;
+
+    match result {
+        Ok(Ok(())) => Redirect::to(ADMIN_PATH).into_response(),
+        Ok(Err(err)) => action_failed(err),
+        Err(err) => AxumNope::InternalError(err).into_response(),
+    }
+}
+
+/// Deserialize empty form fields as `None`.
+fn empty_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = String::deserialize(deserializer)?;
+    let value = value.trim();
+    if value.is_empty() {
+        Ok(None)
+    } else {
+        value.parse().map(Some).map_err(D::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct SandboxOverrides {
+    crate_name: String,
+    memory: Option<usize>,
+    targets: Option<usize>,
+    timeout: Option<u64>,
+    automatic: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct AuditLogEntry {
+    actor: String,
+    action: String,
+    details: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AdminPage {
+    admin: String,
+    queue: Vec<crate::build_queue::QueuedCrate>,
+    priorities: Vec<(String, i32)>,
+    overrides: Vec<SandboxOverrides>,
+    blacklist: Vec<String>,
+    audit_log: Vec<AuditLogEntry>,
+}
+
+impl_axum_webpage! {
+    AdminPage = "admin/index.html",
+    cache_policy = |_| CachePolicy::NoStoreMustRevalidate,
+}
+
+pub(crate) async fn admin_handler(
+    admin: Option<Admin>,
+    Extension(pool): Extension<Pool>,
+    Extension(build_queue): Extension<Arc<BuildQueue>>,
+) -> AxumResult<AxumResponse> {
+    let Some(admin) = admin else {
+        return Ok(Redirect::to(&format!("{ADMIN_PATH}/login")).into_response());
+    };
+
+    let page = spawn_blocking(move || {
+        let mut conn = pool.get()?;
+
+        let audit_log = conn
+            .query(
+                "SELECT actor, action, details, created_at
+                 FROM admin_audit_log
+                 ORDER BY created_at DESC, id DESC
+                 LIMIT $1",
+                &[&AUDIT_LOG_ENTRIES],
+            )?
+            .into_iter()
+            .map(|row| AuditLogEntry {
+                actor: row.get("actor"),
+                action: row.get("action"),
+                details: row.get("details"),
+                created_at: row.get("created_at"),
+            })
+            .collect();
+
+        let mut overrides: Vec<_> = Overrides::all(&mut conn)?
+            .into_iter()
+            .map(|(crate_name, overrides)| SandboxOverrides {
+                crate_name,
+                memory: overrides.memory,
+                targets: overrides.targets,
+                timeout: overrides.timeout.map(|timeout| timeout.as_secs()),
+                automatic: overrides.automatic,
+            })
+            .collect();
+        overrides.sort_by(|a, b| a.crate_name.cmp(&b.crate_name));
+
+        Ok(AdminPage {
+            admin: admin.name,
+            queue: build_queue.queued_crates()?,
+            priorities: list_crate_priorities(&mut conn)?,
+            overrides,
+            blacklist: db::blacklist::list_crates(&mut conn)?,
+            audit_log,
+        })
+    })
+    .await?;
+
+    Ok(page.into_response())
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AdminLoginPage {
+    failed: bool,
+}
+
+impl_axum_webpage! {
+    AdminLoginPage = "admin/login.html",
+    status = |page| if page.failed { StatusCode::UNAUTHORIZED } else { StatusCode::OK },
+    cache_policy = |_| CachePolicy::NoStoreMustRevalidate,
+}
+
+pub(crate) async fn login_page_handler() -> impl IntoResponse {
+    AdminLoginPage { failed: false }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct LoginForm {
+    token: String,
+}
+
+pub(crate) async fn login_handler(
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<LoginForm>,
+) -> AxumResponse {
+    let token = form.token.trim();
+    if Admin::authenticate(&config, token).is_none() {
+        return AdminLoginPage { failed: true }.into_response();
+    }
+
+    (
+        [(
+            header::SET_COOKIE,
+            format!(
+                "{SESSION_COOKIE}={}; {}; Max-Age={}",
+                session_for_token(token, Utc::now().timestamp()),
+                session_cookie_attributes(&config),
+                SESSION_MAX_AGE.as_secs(),
+            ),
+        )],
+        Redirect::to(ADMIN_PATH),
+    )
+        .into_response()
+}
+
+pub(crate) async fn logout_handler(Extension(config): Extension<Arc<Config>>) -> impl IntoResponse {
+    (
+        [(
+            header::SET_COOKIE,
+            format!(
+                "{SESSION_COOKIE}=; {}; Max-Age=0",
+                session_cookie_attributes(&config)
+            ),
+        )],
+        Redirect::to(&format!("{ADMIN_PATH}/login")),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct QueueForm {
+    name: String,
+    version: String,
+    priority: i32,
+}
+
+/// Add a release to the build queue, or rebuild it when it was already built.
+pub(crate) async fn queue_handler(
+    admin: Admin,
+    Extension(pool): Extension<Pool>,
+    Extension(build_queue): Extension<Arc<BuildQueue>>,
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<QueueForm>,
+) -> AxumResponse {
+    run_action(pool, admin, "queue.add", move |transaction| {
+        let (name, version) = (form.name.trim(), form.version.trim());
+        build_queue.add_crate_with(
+            transaction,
+            name,
+            version,
+            form.priority,
+            config.index_url_for_crate(name)?,
+        )?;
+        Ok(format!("{name} {version} with priority {}", form.priority))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PriorityForm {
+    pattern: String,
+    priority: i32,
+}
+
+pub(crate) async fn set_priority_handler(
+    admin: Admin,
+    Extension(pool): Extension<Pool>,
+    Form(form): Form<PriorityForm>,
+) -> AxumResponse {
+    run_action(pool, admin, "priority.set", move |conn| {
+        let pattern = form.pattern.trim();
+        set_crate_priority(conn, pattern, form.priority)?;
+        Ok(format!("{pattern} to {}", form.priority))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PatternForm {
+    pattern: String,
+}
+
+pub(crate) async fn remove_priority_handler(
+    admin: Admin,
+    Extension(pool): Extension<Pool>,
+    Form(form): Form<PatternForm>,
+) -> AxumResponse {
+    run_action(pool, admin, "priority.remove", move |conn| {
+        let priority = remove_crate_priority(conn, &form.pattern)?
+            .with_context(|| format!("there is no priority for pattern {}", form.pattern))?;
+        Ok(format!("{} (was {priority})", form.pattern))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct OverridesForm {
+    crate_name: String,
+    /// Memory limit in bytes
+    #[serde(default, deserialize_with = "empty_as_none")]
+    memory: Option<usize>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    targets: Option<usize>,
+    /// Timeout in seconds
+    #[serde(default, deserialize_with = "empty_as_none")]
+    timeout: Option<u64>,
+}
+
+pub(crate) async fn set_overrides_handler(
+    admin: Admin,
+    Extension(pool): Extension<Pool>,
+    Form(form): Form<OverridesForm>,
+) -> AxumResponse {
+    run_action(pool, admin, "overrides.set", move |conn| {
+        let crate_name = form.crate_name.trim();
+        let overrides = Overrides {
+            memory: form.memory,
+            targets: form.targets,
+            timeout: form.timeout.map(Duration::from_secs),
+            automatic: false,
+        };
+        let previous = Overrides::for_crate(conn, crate_name)?;
+        Overrides::save(conn, crate_name, overrides)?;
+        Ok(format!("{crate_name} from {previous:?} to {overrides:?}"))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CrateForm {
+    crate_name: String,
+}
+
+pub(crate) async fn remove_overrides_handler(
+    admin: Admin,
+    Extension(pool): Extension<Pool>,
+    Form(form): Form<CrateForm>,
+) -> AxumResponse {
+    run_action(pool, admin, "overrides.remove", move |conn| {
+        let previous = Overrides::for_crate(conn, &form.crate_name)?
+            .with_context(|| format!("there are no overrides for {}", form.crate_name))?;
+        Overrides::remove(conn, &form.crate_name)?;
+        Ok(format!("{} (was {previous:?})", form.crate_name))
+    })
+    .await
+}
+
+pub(crate) async fn add_to_blacklist_handler(
+    admin: Admin,
+    Extension(pool): Extension<Pool>,
+    Form(form): Form<CrateForm>,
+) -> AxumResponse {
+    run_action(pool, admin, "blacklist.add", move |conn| {
+        let crate_name = form.crate_name.trim();
+        db::blacklist::add_crate(conn, crate_name)?;
+        Ok(crate_name.to_owned())
+    })
+    .await
+}
+
+pub(crate) async fn remove_from_blacklist_handler(
+    admin: Admin,
+    Extension(pool): Extension<Pool>,
+    Form(form): Form<CrateForm>,
+) -> AxumResponse {
+    run_action(pool, admin, "blacklist.remove", move |conn| {
+        db::blacklist::remove_crate(conn, &form.crate_name)?;
+        Ok(form.crate_name)
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DeleteForm {
+    name: String,
+    /// Delete the whole crate when empty
+    #[serde(default, deserialize_with = "empty_as_none")]
+    version: Option<String>,
+}
+
+pub(crate) async fn delete_handler(
+    admin: Admin,
+    Extension(pool): Extension<Pool>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(config): Extension<Arc<Config>>,
+    Form(form): Form<DeleteForm>,
+) -> AxumResponse {
+    let action = if form.version.is_some() {
+        "delete.version"
+    } else {
+        "delete.crate"
+    };
+    run_action_then(
+        pool,
+        admin,
+        action,
+        move |transaction| {
+            let name = form.name.trim().to_owned();
+            match form.version {
+                Some(version) => {
+                    let is_library = delete_version_from_database(transaction, &name, &version)
+                        .context("failed to delete the version")?;
+                    Ok((
+                        format!("{name} {version}"),
+                        (name, Some(version), is_library),
+                    ))
+                }
+                None => {
+                    let is_library = delete_crate_from_database(transaction, &name)
+                        .context("failed to delete the crate")?;
+                    Ok((name.clone(), (name, None, is_library)))
+                }
+            }
+        },
+        // the files are only deleted once the release is gone from the database
+        move |(name, version, is_library)| match version {
+            Some(version) => {
+                delete_version_from_storage(&storage, &config, &name, &version, is_library)
+                    .context("failed to delete the files of the version")
+            }
+            None => delete_crate_from_storage(&storage, &config, &name, is_library)
+                .context("failed to delete the files of the crate"),
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{assert_cache_control, wrapper, TestEnvironment};
+    use crate::utils::get_crate_priority;
+    use reqwest::StatusCode;
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    fn enable_admin(env: &TestEnvironment) {
+        env.override_config(|config| {
+            config.admin_tokens = vec![("alice".into(), TOKEN.into())];
+        });
+    }
+
+    fn audit_log(env: &TestEnvironment) -> Vec<(String, String, String)> {
+        env.db()
+            .conn()
+            .query(
+                "SELECT actor, action, details FROM admin_audit_log ORDER BY id",
+                &[],
+            )
+            .unwrap()
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2)))
+            .collect()
+    }
+
+    #[test]
+    fn admin_requires_token() {
+        wrapper(|env| {
+            enable_admin(env);
+            let web = env.frontend();
+
+            let response = web.get_no_redirect("/-/admin").send()?;
+            assert_eq!(response.status(), StatusCode::SEE_OTHER);
+            assert_eq!(response.headers()["location"], "/-/admin/login");
+
+            let response = web
+                .get_no_redirect("/-/admin")
+                .bearer_auth("wrong token")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+            let response = web.get("/-/admin").bearer_auth(TOKEN).send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_cache_control(&response, CachePolicy::NoStoreMustRevalidate, &env.config());
+            assert!(response.text()?.contains("alice"));
+
+            let response = web
+                .post("/-/admin/blacklist")
+                .form(&[("crate_name", "foo")])
+                .send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert!(!db::blacklist::is_blacklisted(
+                &mut *env.db().conn(),
+                "foo"
+            )?);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn admin_login() {
+        wrapper(|env| {
+            enable_admin(env);
+            let web = env.frontend();
+
+            let response = web.get("/-/admin/login").send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let response = web
+                .post_no_redirect("/-/admin/login")
+                .form(&[("token", "wrong token")])
+                .send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert!(response.headers().get("set-cookie").is_none());
+
+            let response = web
+                .post_no_redirect("/-/admin/login")
+                .form(&[("token", TOKEN)])
+                .send()?;
+            assert_eq!(response.status(), StatusCode::SEE_OTHER);
+            let cookie = response.headers()["set-cookie"].to_str()?.to_owned();
+            let session = cookie
+                .strip_prefix(&format!("{SESSION_COOKIE}="))
+                .and_then(|cookie| cookie.split(';').next())
+                .unwrap()
+                .to_owned();
+            let issued_at: i64 = session.split_once('.').unwrap().0.parse()?;
+            assert!((Utc::now().timestamp() - issued_at).abs() < 60);
+            assert_eq!(session, session_for_token(TOKEN, issued_at));
+            assert!(!cookie.contains(TOKEN));
+            assert!(cookie.contains("Path=/-/admin"));
+            assert!(cookie.contains("HttpOnly"));
+            assert!(cookie.contains("Secure"));
+            assert!(cookie.contains(&format!("Max-Age={}", SESSION_MAX_AGE.as_secs())));
+
+            let response = web
+                .get("/-/admin")
+                .header("cookie", format!("{SESSION_COOKIE}={session}"))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            // the token itself isn't accepted as session
+            let response = web
+                .get_no_redirect("/-/admin")
+                .header("cookie", format!("{SESSION_COOKIE}={TOKEN}"))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn admin_sessions_expire() {
+        wrapper(|env| {
+            enable_admin(env);
+            let web = env.frontend();
+            let now = Utc::now().timestamp();
+            let max_age = SESSION_MAX_AGE.as_secs() as i64;
+
+            let status = |session: String| -> anyhow::Result<StatusCode> {
+                Ok(web
+                    .get_no_redirect("/-/admin")
+                    .header("cookie", format!("{SESSION_COOKIE}={session}"))
+                    .send()?
+                    .status())
+            };
+
+            assert_eq!(
+                status(session_for_token(TOKEN, now - max_age + 60))?,
+                StatusCode::OK
+            );
+            // too old
+            assert_eq!(
+                status(session_for_token(TOKEN, now - max_age - 60))?,
+                StatusCode::SEE_OTHER
+            );
+            // issued in the future
+            assert_eq!(
+                status(session_for_token(TOKEN, now + 60 * 60))?,
+                StatusCode::SEE_OTHER
+            );
+            // the signature doesn't match the changed timestamp
+            let session = session_for_token(TOKEN, now - max_age - 60);
+            let (_, signature) = session.split_once('.').unwrap();
+            assert_eq!(status(format!("{now}.{signature}"))?, StatusCode::SEE_OTHER);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn admin_login_over_http() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.admin_tokens = vec![("alice".into(), TOKEN.into())];
+                config.public_url = "http://localhost:3000".into();
+            });
+
+            let response = env
+                .frontend()
+                .post_no_redirect("/-/admin/login")
+                .form(&[("token", TOKEN)])
+                .send()?;
+            assert_eq!(response.status(), StatusCode::SEE_OTHER);
+            let cookie = response.headers()["set-cookie"].to_str()?;
+            assert!(!cookie.contains("Secure"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn admin_actions_are_audited() {
+        wrapper(|env| {
+            enable_admin(env);
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let web = env.frontend();
+            let post = |url: &str, form: &[(&str, &str)]| -> anyhow::Result<()> {
+                let response = web
+                    .post_no_redirect(url)
+                    .bearer_auth(TOKEN)
+                    .form(form)
+                    .send()?;
+                assert_eq!(response.status(), StatusCode::SEE_OTHER, "{url}");
+                Ok(())
+            };
+
+            post(
+                "/-/admin/queue",
+                &[("name", "bar"), ("version", "1.0.0"), ("priority", "3")],
+            )?;
+            assert_eq!(
+                env.build_queue()
+                    .queued_crates()?
+                    .iter()
+                    .map(|c| (c.name.as_str(), c.version.as_str(), c.priority))
+                    .collect::<Vec<_>>(),
+                vec![("bar", "1.0.0", 3)]
+            );
+
+            post(
+                "/-/admin/priorities",
+                &[("pattern", "bar-%"), ("priority", "7")],
+            )?;
+            assert_eq!(get_crate_priority(&mut env.db().conn(), "bar-baz")?, 7);
+            post("/-/admin/priorities/remove", &[("pattern", "bar-%")])?;
+            assert_eq!(get_crate_priority(&mut env.db().conn(), "bar-baz")?, 0);
+
+            post(
+                "/-/admin/overrides",
+                &[
+                    ("crate_name", "foo"),
+                    ("memory", "1024"),
+                    ("targets", ""),
+                    ("timeout", "60"),
+                ],
+            )?;
+            assert_eq!(
+                Overrides::for_crate(&mut *env.db().conn(), "foo")?,
+                Some(Overrides {
+                    memory: Some(1024),
+                    targets: None,
+                    timeout: Some(Duration::from_secs(60)),
+                    automatic: false,
+                })
+            );
+            post("/-/admin/overrides/remove", &[("crate_name", "foo")])?;
+            assert_eq!(Overrides::for_crate(&mut *env.db().conn(), "foo")?, None);
+
+            post("/-/admin/blacklist", &[("crate_name", "baz")])?;
+            assert!(db::blacklist::is_blacklisted(&mut *env.db().conn(), "baz")?);
+            post("/-/admin/blacklist/remove", &[("crate_name", "baz")])?;
+            assert!(!db::blacklist::is_blacklisted(
+                &mut *env.db().conn(),
+                "baz"
+            )?);
+
+            post("/-/admin/delete", &[("name", "foo"), ("version", "0.1.0")])?;
+            assert!(env
+                .db()
+                .conn()
+                .query_opt("SELECT id FROM releases", &[])?
+                .is_none());
+
+            assert_eq!(
+                audit_log(env)
+                    .into_iter()
+                    .map(|(actor, action, _)| (actor, action))
+                    .collect::<Vec<_>>(),
+                [
+                    "queue.add",
+                    "priority.set",
+                    "priority.remove",
+                    "overrides.set",
+                    "overrides.remove",
+                    "blacklist.add",
+                    "blacklist.remove",
+                    "delete.version",
+                ]
+                .into_iter()
+                .map(|action| ("alice".to_owned(), action.to_owned()))
+                .collect::<Vec<_>>()
+            );
+
+            let page = web.get("/-/admin").bearer_auth(TOKEN).send()?.text()?;
+            assert!(page.contains("delete.version"));
+            assert!(page.contains("foo 0.1.0"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn failed_admin_action() {
+        wrapper(|env| {
+            enable_admin(env);
+            let web = env.frontend();
+
+            let response = web
+                .post("/-/admin/blacklist/remove")
+                .bearer_auth(TOKEN)
+                .form(&[("crate_name", "foo")])
+                .send()?;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            assert!(response
+                .text()?
+                .contains("crate foo is not on the blacklist"));
+            assert!(audit_log(env).is_empty());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn failed_admin_actions_are_rolled_back() {
+        wrapper(|env| {
+            let admin = Admin {
+                name: "alice".into(),
+            };
+            let response = env.runtime().block_on(run_action(
+                env.db().pool(),
+                admin,
+                "blacklist.add",
+                |transaction| {
+                    db::blacklist::add_crate(transaction, "foo")?;
+                    anyhow::bail!("something went wrong after the change")
+                },
+            ));
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            assert!(!db::blacklist::is_blacklisted(
+                &mut *env.db().conn(),
+                "foo"
+            )?);
+            assert!(audit_log(env).is_empty());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn admin_crate_docs_are_not_shadowed() {
+        wrapper(|env| {
+            enable_admin(env);
+            env.fake_release().name("admin").version("0.1.0").create()?;
+
+            let response = env.frontend().get("/admin/0.1.0/admin/").send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn admin_without_tokens() {
+        wrapper(|env| {
+            let response = env
+                .frontend()
+                .post("/-/admin/login")
+                .form(&[("token", "")])
+                .send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,599 @@
+//! The JSON API, versioned below `/-/api/v1/`.
+//!
+//! Versions in the URL are resolved like on the crate pages, so `latest` or a semver requirement
+//! can be used instead of an exact version. The resolved version is part of every response.
+//! Errors are returned as JSON object with a `title` and a `message`.
+
+use super::{
+    api_diff::api_diff,
+    cache::CachePolicy,
+    crate_details::CrateDetails,
+    error::{AxumNope, JsonAxumNope, JsonAxumResult},
+    match_version_axum,
+    releases::{get_releases, Order, Release},
+    source_diff::match_version_range,
+    MatchSemver,
+};
+use crate::{
+    build_queue::QueuedCrate, db::types::Feature, db::Pool, docbuilder::FailureCategory,
+    utils::spawn_blocking, BuildQueue,
+};
+use axum::{
+    extract::{Extension, Path, Query},
+    http::{header::ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue, Request as AxumHttpRequest},
+    middleware::Next,
+    response::{IntoResponse, Response as AxumResponse},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Number of failed releases per page of `/-/api/v1/releases/failures`.
+const FAILURES_PER_PAGE: i64 = 30;
+
+/// Allows the API to be used from other origins and disables caching, for all API responses.
+pub(crate) async fn api_middleware<B>(request: AxumHttpRequest<B>, next: Next<B>) -> AxumResponse {
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+    response.extensions_mut().insert(CachePolicy::NoCaching);
+    response
+}
+
+/// Unknown API endpoints return a JSON error too.
+///
+/// Fallbacks of nested routers are ignored, so this is registered as catch-all route.
+pub(crate) async fn api_fallback() -> impl IntoResponse {
+    JsonAxumNope(AxumNope::ResourceNotFound)
+}
+
+async fn resolve_version(pool: &Pool, name: &str, req_version: &str) -> JsonAxumResult<String> {
+    let (MatchSemver::Exact((version, _))
+    | MatchSemver::Latest((version, _))
+    | MatchSemver::Semver((version, _))) = match_version_axum(pool, name, Some(req_version))
+        .await?
+        .exact_name_only()?;
+    Ok(version)
+}
+
+async fn crate_details(
+    pool: Pool,
+    name: String,
+    req_version: String,
+) -> JsonAxumResult<CrateDetails> {
+    let version = resolve_version(&pool, &name, &req_version).await?;
+    Ok(spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        Ok(
+            CrateDetails::new(&mut *conn, &name, &version, &version, None)?
+                .ok_or(AxumNope::VersionNotFound)?,
+        )
+    })
+    .await?)
+}
+
+#[derive(Debug, Serialize)]
+struct ApiOwner {
+    login: String,
+    avatar: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ApiCrateDetails {
+    name: String,
+    version: String,
+    registry: Option<String>,
+    description: Option<String>,
+    license: Option<String>,
+    keywords: Option<Value>,
+    repository_url: Option<String>,
+    homepage_url: Option<String>,
+    documentation_url: Option<String>,
+    owners: Vec<ApiOwner>,
+    dependencies: Option<Value>,
+    release_time: DateTime<Utc>,
+    yanked: bool,
+    is_library: bool,
+    build_status: bool,
+    rustdoc_status: bool,
+    last_successful_build: Option<String>,
+    target_name: String,
+    default_target: String,
+    doc_targets: Vec<String>,
+}
+
+pub(crate) async fn crate_details_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let details = crate_details(pool, name, req_version).await?;
+
+    Ok(Json(ApiCrateDetails {
+        owners: details
+            .owners
+            .into_iter()
+            .map(|(login, avatar)| ApiOwner { login, avatar })
+            .collect(),
+        name: details.name,
+        version: details.version,
+        registry: details.registry,
+        description: details.description,
+        license: details.license,
+        keywords: details.keywords,
+        repository_url: details.repository_url,
+        homepage_url: details.homepage_url,
+        documentation_url: details.documentation_url,
+        dependencies: details.dependencies,
+        release_time: details.release_time,
+        yanked: details.metadata.yanked,
+        is_library: details.is_library,
+        build_status: details.build_status,
+        rustdoc_status: details.rustdoc_status,
+        last_successful_build: details.last_successful_build,
+        target_name: details.target_name,
+        default_target: details.metadata.default_target,
+        doc_targets: details.metadata.doc_targets,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+struct ApiRelease {
+    version: String,
+    yanked: bool,
+    is_library: bool,
+    build_status: bool,
+    rustdoc_status: bool,
+}
+
+pub(crate) async fn releases_handler(
+    Path(name): Path<String>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let details = crate_details(pool, name, "latest".into()).await?;
+
+    Ok(Json(serde_json::json!({
+        "name": details.name,
+        "releases": details
+            .releases
+            .into_iter()
+            .map(|release| ApiRelease {
+                version: release.version.to_string(),
+                yanked: release.yanked,
+                is_library: release.is_library,
+                build_status: release.build_status,
+                rustdoc_status: release.rustdoc_status,
+            })
+            .collect::<Vec<_>>(),
+    })))
+}
+
+pub(crate) async fn features_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let version = resolve_version(&pool, &name, &req_version).await?;
+
+    let features: Option<Vec<Feature>> = spawn_blocking({
+        let (name, version) = (name.clone(), version.clone());
+        move || {
+            Ok(pool
+                .get()?
+                .query_opt(
+                    "SELECT releases.features FROM releases
+                     INNER JOIN crates ON crates.id = releases.crate_id
+                     WHERE crates.name = $1 AND releases.version = $2",
+                    &[&name, &version],
+                )?
+                .ok_or(AxumNope::VersionNotFound)?
+                .get(0))
+        }
+    })
+    .await?;
+
+    // releases built before features were recorded don't have them
+    Ok(Json(serde_json::json!({
+        "name": name,
+        "version": version,
+        "features": features,
+    })))
+}
+
+pub(crate) async fn targets_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let details = crate_details(pool, name, req_version).await?;
+
+    Ok(Json(serde_json::json!({
+        "name": details.name,
+        "version": details.version,
+        "default_target": details.metadata.default_target,
+        "doc_targets": details.metadata.doc_targets,
+    })))
+}
+
+pub(crate) async fn coverage_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let details = crate_details(pool, name, req_version).await?;
+
+    // only releases built with a rustdoc supporting `--show-coverage` have a coverage
+    let Some(total_items) = details.total_items else {
+        return Err(AxumNope::ResourceNotFound.into());
+    };
+
+    Ok(Json(serde_json::json!({
+        "name": details.name,
+        "version": details.version,
+        "total_items": total_items,
+        "documented_items": details.documented_items,
+        "total_items_needing_examples": details.total_items_needing_examples,
+        "items_with_examples": details.items_with_examples,
+    })))
+}
+
+/// `version` is a range of two versions, `old...new`.
+pub(crate) async fn api_diff_handler(
+    Path((name, versions)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let (old, new) = match_version_range(&pool, &name, &versions).await?;
+    let (old_version, _) = old.into_parts();
+    let (new_version, _) = new.into_parts();
+
+    let diff = spawn_blocking(move || {
+        api_diff(&mut *pool.get()?, &name, &old_version, &new_version)?
+            .ok_or_else(|| AxumNope::ResourceNotFound.into())
+    })
+    .await?;
+
+    Ok(Json(diff))
+}
+
+pub(crate) async fn queue_handler(
+    Extension(build_queue): Extension<Arc<BuildQueue>>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let queue: Vec<QueuedCrate> =
+        spawn_blocking(move || build_queue.queued_crates_with_estimates()).await?;
+
+    Ok(Json(serde_json::json!({ "queue": queue })))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct FailuresParams {
+    page: Option<i64>,
+    /// Only list the releases that failed for this reason.
+    category: Option<FailureCategory>,
+}
+
+pub(crate) async fn failures_handler(
+    Query(params): Query<FailuresParams>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let page = params.page.unwrap_or(1);
+    if page < 1 {
+        return Err(AxumNope::BadRequest.into());
+    }
+
+    let releases: Vec<Release> = spawn_blocking(move || {
+        get_releases(
+            &mut *pool.get()?,
+            page,
+            FAILURES_PER_PAGE,
+            Order::RecentFailures,
+            true,
+            params.category,
+        )
+    })
+    .await?;
+
+    Ok(Json(serde_json::json!({
+        "page": page,
+        "releases": releases,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::docbuilder::FailureCategory;
+    use crate::test::{assert_cache_control, wrapper, FakeBuild};
+    use crate::web::cache::CachePolicy;
+    use reqwest::StatusCode;
+    use serde_json::{json, Value};
+
+    fn get_json(
+        env: &crate::test::TestEnvironment,
+        path: &str,
+    ) -> anyhow::Result<(StatusCode, Value)> {
+        let response = env.frontend().get(path).send()?;
+        assert_cache_control(&response, CachePolicy::NoCaching, &env.config());
+        assert_eq!(response.headers()["access-control-allow-origin"], "*");
+        Ok((response.status(), response.json()?))
+    }
+
+    #[test]
+    fn crate_details() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .description("a crate")
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.2.0")
+                .add_owner(crate::index::api::CrateOwner {
+                    login: "owner".into(),
+                    avatar: "https://example.com/avatar".into(),
+                })
+                .create()?;
+
+            let (status, value) = get_json(env, "/-/api/v1/crates/foo/0.1.0")?;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(value["name"], "foo");
+            assert_eq!(value["version"], "0.1.0");
+            assert_eq!(value["description"], "a crate");
+            assert_eq!(
+                value["owners"],
+                json!([{"login": "owner", "avatar": "https://example.com/avatar"}])
+            );
+            assert_eq!(value["build_status"], true);
+            assert_eq!(value["default_target"], "x86_64-unknown-linux-gnu");
+
+            let (status, value) = get_json(env, "/-/api/v1/crates/foo/latest")?;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(value["version"], "0.2.0");
+
+            let (status, value) = get_json(env, "/-/api/v1/crates/foo/^0.1")?;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(value["version"], "0.1.0");
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn api_crate_docs_are_not_shadowed() {
+        wrapper(|env| {
+            env.fake_release().name("api").version("0.1.0").create()?;
+
+            let response = env.frontend().get("/api/0.1.0/api/").send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn errors_are_json() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+
+            let (status, value) = get_json(env, "/-/api/v1/crates/bar/0.1.0")?;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(value["title"], "The requested crate does not exist");
+            assert_eq!(value["message"], "no such crate");
+
+            let (status, value) = get_json(env, "/-/api/v1/crates/foo/0.2.0")?;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(value["title"], "The requested version does not exist");
+
+            let (status, value) = get_json(env, "/-/api/v1/does-not-exist")?;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(value["title"], "The requested resource does not exist");
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn releases() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.2.0")
+                .yanked(true)
+                .create()?;
+
+            let (status, value) = get_json(env, "/-/api/v1/crates/foo/releases")?;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(
+                value,
+                json!({
+                    "name": "foo",
+                    "releases": [
+                        {
+                            "version": "0.2.0",
+                            "yanked": true,
+                            "is_library": true,
+                            "build_status": true,
+                            "rustdoc_status": true,
+                        },
+                        {
+                            "version": "0.1.0",
+                            "yanked": false,
+                            "is_library": true,
+                            "build_status": true,
+                            "rustdoc_status": true,
+                        },
+                    ],
+                })
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn features_targets_and_coverage() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .features(
+                    [
+                        ("default".into(), vec!["bar".into()]),
+                        ("bar".into(), vec![]),
+                    ]
+                    .into_iter()
+                    .collect(),
+                )
+                .add_platform("i686-pc-windows-msvc")
+                .doc_coverage(crate::docbuilder::DocCoverage {
+                    total_items: 10,
+                    documented_items: 6,
+                    total_items_needing_examples: 2,
+                    items_with_examples: 1,
+                    files: Vec::new(),
+                    undocumented_items: Vec::new(),
+                })
+                .create()?;
+            env.fake_release().name("bar").version("0.1.0").create()?;
+
+            let (status, value) = get_json(env, "/-/api/v1/crates/foo/0.1.0/features")?;
+            assert_eq!(status, StatusCode::OK);
+            let mut features: Vec<_> = value["features"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|feature| feature["name"].as_str().unwrap().to_owned())
+                .collect();
+            features.sort();
+            assert_eq!(features, ["bar", "default"]);
+
+            let (status, value) = get_json(env, "/-/api/v1/crates/foo/0.1.0/targets")?;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(value["default_target"], "x86_64-unknown-linux-gnu");
+            assert!(value["doc_targets"]
+                .as_array()
+                .unwrap()
+                .contains(&json!("i686-pc-windows-msvc")));
+
+            let (status, value) = get_json(env, "/-/api/v1/crates/foo/0.1.0/coverage")?;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(value["total_items"], 10);
+            assert_eq!(value["documented_items"], 6);
+            assert_eq!(value["total_items_needing_examples"], 2);
+            assert_eq!(value["items_with_examples"], 1);
+
+            let (status, _) = get_json(env, "/-/api/v1/crates/bar/0.1.0/coverage")?;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn api_diff() {
+        wrapper(|env| {
+            let index = |items: &str| {
+                format!(
+                    r#"var searchIndex = JSON.parse('{{"foo":{{"t":"{items}","n":["Foo"],"q":[[0,"foo"]]}}}}');"#
+                )
+            };
+            env.fake_release()
+                .name("foo")
+                .version("1.0.0")
+                .rustdoc_file_with("search-index.js", index("D").as_bytes())
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("1.0.1")
+                .rustdoc_file_with("search-index.js", index("E").as_bytes())
+                .create()?;
+            env.fake_release().name("foo").version("1.1.0").create()?;
+
+            let (status, value) = get_json(env, "/-/api/v1/crates/foo/1.0.0...~1.0/api-diff")?;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(value["new_version"], "1.0.1");
+            assert_eq!(value["compatible_bump"], true);
+            assert_eq!(value["semver_hazards"], json!(["foo::Foo"]));
+            assert_eq!(
+                value["modules"][0]["changed"][0],
+                json!({
+                    "name": "Foo",
+                    "path": "foo::Foo",
+                    "old_kind": "struct",
+                    "new_kind": "enum",
+                    "old_url": "/foo/1.0.0/foo/struct.Foo.html",
+                    "new_url": "/foo/1.0.1/foo/enum.Foo.html",
+                })
+            );
+
+            // 1.1.0 has no items
+            let (status, _) = get_json(env, "/-/api/v1/crates/foo/1.0.0...1.1.0/api-diff")?;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn queue_and_failures() {
+        wrapper(|env| {
+            env.build_queue().add_crate("queued", "1.0.0", 0, None)?;
+            env.fake_release()
+                .name("failed")
+                .version("0.1.0")
+                .builds(vec![FakeBuild::default().successful(false)])
+                .create()?;
+            env.fake_release()
+                .name("successful")
+                .version("0.1.0")
+                .create()?;
+
+            let (status, mut value) = get_json(env, "/-/api/v1/queue")?;
+            assert_eq!(status, StatusCode::OK);
+            let estimated_start = value["queue"][0]
+                .as_object_mut()
+                .unwrap()
+                .remove("estimated_start")
+                .unwrap();
+            assert!(estimated_start.is_string());
+            assert_eq!(
+                value["queue"],
+                json!([{
+                    "name": "queued",
+                    "version": "1.0.0",
+                    "priority": 0,
+                    "registry": null,
+                    "owner": null,
+                    "superseded_by": null,
+                }])
+            );
+
+            let (status, value) = get_json(env, "/-/api/v1/releases/failures")?;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(value["page"], 1);
+            let releases = value["releases"].as_array().unwrap();
+            assert_eq!(releases.len(), 1);
+            assert_eq!(releases[0]["name"], "failed");
+
+            assert_eq!(releases[0]["failure_category"], Value::Null);
+
+            env.fake_release()
+                .name("out-of-memory")
+                .version("0.1.0")
+                .builds(vec![
+                    FakeBuild::default().failure_category(FailureCategory::Oom)
+                ])
+                .create()?;
+            let (status, value) = get_json(env, "/-/api/v1/releases/failures?category=oom")?;
+            assert_eq!(status, StatusCode::OK);
+            let releases = value["releases"].as_array().unwrap();
+            assert_eq!(releases.len(), 1);
+            assert_eq!(releases[0]["name"], "out-of-memory");
+            assert_eq!(releases[0]["failure_category"], "oom");
+
+            let (status, _) = get_json(env, "/-/api/v1/releases/failures?page=0")?;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+
+            Ok(())
+        });
+    }
+}
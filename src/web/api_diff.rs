@@ -0,0 +1,746 @@
+//! Compares the public items of two releases of a crate.
+//!
+//! The items are the ones read from the rustdoc JSON output of every build, including members
+//! like methods and fields with a hash of their signature, see [`crate::docbuilder::PublicItem`].
+//! Releases built before those were recorded are compared by the items from the rustdoc search
+//! index instead, see [`crate::docbuilder::SearchIndexItem`], which only has items with their
+//! own page and no signatures.
+
+use crate::{
+    db::Pool,
+    impl_axum_webpage,
+    utils::spawn_blocking,
+    web::{
+        cache::CachePolicy, error::AxumResult, source_diff::match_version_range, MatchSemver,
+        MetaData,
+    },
+};
+use anyhow::Result;
+use axum::{
+    extract::{Extension, Path},
+    response::IntoResponse,
+};
+use postgres::Client;
+use semver::Version;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tracing::instrument;
+
+/// A public item of a release, linked to its rustdoc page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct ApiItem {
+    name: String,
+    path: String,
+    kind: String,
+    url: String,
+    /// The hash of the signature, `None` for items from the search index.
+    #[serde(skip)]
+    signature: Option<String>,
+}
+
+/// An item that exists in both releases, but as a different kind of item,
+/// for example a struct that became an enum, or with a different signature,
+/// when the kinds are the same.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct ChangedItem {
+    name: String,
+    path: String,
+    old_kind: String,
+    new_kind: String,
+    old_url: String,
+    new_url: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub(crate) struct ModuleDiff {
+    /// The path of the module, for example `serde::de`, or the path of the item
+    /// for members like methods.
+    module: String,
+    added: Vec<ApiItem>,
+    removed: Vec<ApiItem>,
+    changed: Vec<ChangedItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct ApiDiff {
+    name: String,
+    old_version: String,
+    new_version: String,
+    /// Whether cargo considers the new version compatible with the old one,
+    /// so it would be picked up by `cargo update`.
+    compatible_bump: bool,
+    /// Paths of the items removed or changed in a compatible bump.
+    semver_hazards: Vec<String>,
+    /// The modules with changes, sorted by their path.
+    modules: Vec<ModuleDiff>,
+    /// Whether members like methods, fields and variants and the signatures of the items were
+    /// compared, or only the items with their own page.
+    members_compared: bool,
+}
+
+/// Whether `new` is semver compatible with `old` in the way cargo interprets it,
+/// where `0.x.y` only allows patch releases and every `0.0.x` release is breaking.
+fn is_compatible_bump(old: &str, new: &str) -> bool {
+    let (Ok(old), Ok(new)) = (Version::parse(old), Version::parse(new)) else {
+        return false;
+    };
+    if new <= old {
+        false
+    } else if old.major > 0 {
+        new.major == old.major
+    } else if old.minor > 0 {
+        new.major == 0 && new.minor == old.minor
+    } else {
+        false
+    }
+}
+
+/// The module containing the item at `path`.
+fn module_of(path: &str) -> &str {
+    path.rsplit_once("::").map_or(path, |(module, _)| module)
+}
+
+/// Loads the items of a release.
+///
+/// Returns `None` when no items were stored, either because the documentation
+/// failed to build, or the release was built before items were indexed.
+fn load_items(conn: &mut Client, name: &str, version: &str) -> Result<Option<Vec<ApiItem>>> {
+    let items: Vec<ApiItem> = conn
+        .query(
+            "SELECT
+                release_items.name,
+                release_items.path,
+                release_items.kind,
+                release_items.link
+             FROM release_items
+             INNER JOIN releases ON releases.id = release_items.release_id
+             INNER JOIN crates ON crates.id = releases.crate_id
+             WHERE crates.name = $1 AND releases.version = $2
+             ORDER BY release_items.path, release_items.kind",
+            &[&name, &version],
+        )?
+        .into_iter()
+        .map(|row| ApiItem {
+            name: row.get(0),
+            path: row.get(1),
+            kind: row.get(2),
+            url: format!("/{name}/{version}/{}", row.get::<_, String>(3)),
+            signature: None,
+        })
+        .collect();
+
+    Ok((!items.is_empty()).then_some(items))
+}
+
+/// Loads the public items of a release read from its rustdoc JSON output.
+///
+/// Returns `None` when no items were stored, because the release was built before
+/// they were recorded, or the JSON output couldn't be built.
+fn load_public_items(conn: &mut Client, name: &str, version: &str) -> Result<Option<Vec<ApiItem>>> {
+    let items: Vec<ApiItem> = conn
+        .query(
+            "SELECT
+                release_public_items.path,
+                release_public_items.kind,
+                release_public_items.link,
+                release_public_items.signature
+             FROM release_public_items
+             INNER JOIN releases ON releases.id = release_public_items.release_id
+             INNER JOIN crates ON crates.id = releases.crate_id
+             WHERE crates.name = $1 AND releases.version = $2
+             ORDER BY release_public_items.path, release_public_items.kind",
+            &[&name, &version],
+        )?
+        .into_iter()
+        .map(|row| {
+            let path: String = row.get(0);
+            ApiItem {
+                name: path.rsplit("::").next().unwrap_or_default().to_owned(),
+                path,
+                kind: row.get(1),
+                url: format!("/{name}/{version}/{}", row.get::<_, String>(2)),
+                signature: row.get(3),
+            }
+        })
+        .collect();
+
+    Ok((!items.is_empty()).then_some(items))
+}
+
+/// The changes of the module containing the item at `path`.
+fn module_entry<'a>(
+    modules: &'a mut BTreeMap<String, ModuleDiff>,
+    path: &str,
+) -> &'a mut ModuleDiff {
+    let module = module_of(path);
+    modules
+        .entry(module.to_owned())
+        .or_insert_with(|| ModuleDiff {
+            module: module.to_owned(),
+            ..Default::default()
+        })
+}
+
+/// Compares the items of two releases.
+///
+/// Items are identified by their path and kind. When the only difference at a path is
+/// one removed and one added item, the item is reported as changed instead. Items of the
+/// same path and kind are reported as changed when both have different signatures.
+fn compare(
+    name: &str,
+    old_version: &str,
+    old_items: Vec<ApiItem>,
+    new_version: &str,
+    new_items: Vec<ApiItem>,
+) -> ApiDiff {
+    let key = |item: &ApiItem| (item.path.clone(), item.kind.clone());
+    let old_keys: HashSet<_> = old_items.iter().map(key).collect();
+    let new_keys: HashSet<_> = new_items.iter().map(key).collect();
+
+    let new_signatures: HashMap<_, _> = new_items
+        .iter()
+        .filter_map(|item| Some((key(item), (item.signature.as_ref()?, &item.url))))
+        .collect();
+    let signature_changes: Vec<ChangedItem> = old_items
+        .iter()
+        .filter_map(|item| {
+            let (new_signature, new_url) = new_signatures.get(&key(item))?;
+            (item.signature.as_ref()? != *new_signature).then(|| ChangedItem {
+                name: item.name.clone(),
+                path: item.path.clone(),
+                old_kind: item.kind.clone(),
+                new_kind: item.kind.clone(),
+                old_url: item.url.clone(),
+                new_url: (*new_url).clone(),
+            })
+        })
+        .collect();
+
+    let removed: Vec<ApiItem> = old_items
+        .into_iter()
+        .filter(|item| !new_keys.contains(&key(item)))
+        .collect();
+    let added: Vec<ApiItem> = new_items
+        .into_iter()
+        .filter(|item| !old_keys.contains(&key(item)))
+        .collect();
+
+    let count_paths = |items: &[ApiItem]| {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for item in items {
+            *counts.entry(item.path.clone()).or_default() += 1;
+        }
+        counts
+    };
+    let removed_paths = count_paths(&removed);
+    let added_paths = count_paths(&added);
+    let is_changed =
+        |path: &str| removed_paths.get(path) == Some(&1) && added_paths.get(path) == Some(&1);
+
+    let mut modules: BTreeMap<String, ModuleDiff> = BTreeMap::new();
+    let mut changed_new: HashMap<String, ApiItem> = HashMap::new();
+    for item in added {
+        if is_changed(&item.path) {
+            changed_new.insert(item.path.clone(), item);
+        } else {
+            module_entry(&mut modules, &item.path).added.push(item);
+        }
+    }
+    for item in removed {
+        match changed_new.remove(&item.path) {
+            Some(new) => module_entry(&mut modules, &item.path)
+                .changed
+                .push(ChangedItem {
+                    name: item.name,
+                    path: item.path,
+                    old_kind: item.kind,
+                    new_kind: new.kind,
+                    old_url: item.url,
+                    new_url: new.url,
+                }),
+            None => module_entry(&mut modules, &item.path).removed.push(item),
+        }
+    }
+    for item in signature_changes {
+        module_entry(&mut modules, &item.path).changed.push(item);
+    }
+
+    let compatible_bump = is_compatible_bump(old_version, new_version);
+    let semver_hazards = if compatible_bump {
+        let mut hazards: Vec<String> = modules
+            .values()
+            .flat_map(|module| {
+                module
+                    .removed
+                    .iter()
+                    .map(|item| item.path.clone())
+                    .chain(module.changed.iter().map(|item| item.path.clone()))
+            })
+            .collect();
+        hazards.sort();
+        hazards.dedup();
+        hazards
+    } else {
+        Vec::new()
+    };
+
+    ApiDiff {
+        name: name.to_owned(),
+        old_version: old_version.to_owned(),
+        new_version: new_version.to_owned(),
+        compatible_bump,
+        semver_hazards,
+        modules: modules.into_values().collect(),
+        members_compared: false,
+    }
+}
+
+/// Compares the public items of two releases of `name`.
+///
+/// Returns `None` when the items of one of the releases aren't known.
+pub(crate) fn api_diff(
+    conn: &mut Client,
+    name: &str,
+    old_version: &str,
+    new_version: &str,
+) -> Result<Option<ApiDiff>> {
+    if let (Some(old_items), Some(new_items)) = (
+        load_public_items(conn, name, old_version)?,
+        load_public_items(conn, name, new_version)?,
+    ) {
+        return Ok(Some(ApiDiff {
+            members_compared: true,
+            ..compare(name, old_version, old_items, new_version, new_items)
+        }));
+    }
+
+    let (Some(old_items), Some(new_items)) = (
+        load_items(conn, name, old_version)?,
+        load_items(conn, name, new_version)?,
+    ) else {
+        return Ok(None);
+    };
+    Ok(Some(compare(
+        name,
+        old_version,
+        old_items,
+        new_version,
+        new_items,
+    )))
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ApiDiffPage {
+    metadata: MetaData,
+    old_version: String,
+    new_version: String,
+    /// `None` when the items of one of the releases aren't known.
+    diff: Option<ApiDiff>,
+    use_direct_platform_links: bool,
+}
+
+impl_axum_webpage! {
+    ApiDiffPage = "crate/api_diff.html",
+    cache_policy = |_| CachePolicy::ForeverInCdnAndStaleInBrowser,
+}
+
+#[instrument(skip(pool))]
+pub(crate) async fn api_diff_handler(
+    Path((name, versions)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+) -> AxumResult<impl IntoResponse> {
+    let (old, new) = match_version_range(&pool, &name, &versions).await?;
+    if !matches!(old, MatchSemver::Exact(_)) || !matches!(new, MatchSemver::Exact(_)) {
+        let (old, _) = old.into_parts();
+        let (new, _) = new.into_parts();
+        return Ok(super::axum_cached_redirect(
+            &format!("/crate/{name}/{old}...{new}/api-diff"),
+            CachePolicy::ForeverInCdn,
+        )?
+        .into_response());
+    }
+    let (old_version, _) = old.into_parts();
+    let (new_version, _) = new.into_parts();
+
+    Ok(spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        Ok(ApiDiffPage {
+            metadata: MetaData::from_crate(&mut conn, &name, &new_version, &new_version)?,
+            diff: api_diff(&mut conn, &name, &old_version, &new_version)?,
+            old_version,
+            new_version,
+            use_direct_platform_links: true,
+        })
+    })
+    .await?
+    .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{assert_cache_control, assert_redirect_cached, wrapper};
+    use kuchikiki::traits::TendrilSink;
+    use reqwest::StatusCode;
+
+    fn item(path: &str, kind: &str, version: &str) -> ApiItem {
+        let (module, name) = path.rsplit_once("::").unwrap();
+        ApiItem {
+            name: name.into(),
+            path: path.into(),
+            kind: kind.into(),
+            url: format!(
+                "/foo/{version}/{}/{kind}.{name}.html",
+                module.replace("::", "/")
+            ),
+            signature: None,
+        }
+    }
+
+    /// A search index with the struct `Foo`, the function `helper`, and the
+    /// module `de` with the trait `Deserializer`.
+    const OLD_INDEX: &str = r#"var searchIndex = JSON.parse('{\
+"foo":{"t":"DFAI","n":["Foo","helper","de","Deserializer"],"q":[[0,"foo"],[3,"foo::de"]]}\
+}');"#;
+
+    /// `Foo` became an enum, `helper` was removed and `de::from_str` added.
+    const NEW_INDEX: &str = r#"var searchIndex = JSON.parse('{\
+"foo":{"t":"EAIF","n":["Foo","de","Deserializer","from_str"],"q":[[0,"foo"],[2,"foo::de"]]}\
+}');"#;
+
+    #[test]
+    fn compatible_bumps() {
+        assert!(is_compatible_bump("1.2.3", "1.3.0"));
+        assert!(is_compatible_bump("1.2.3", "1.2.4"));
+        assert!(!is_compatible_bump("1.2.3", "2.0.0"));
+        assert!(is_compatible_bump("0.2.3", "0.2.4"));
+        assert!(!is_compatible_bump("0.2.3", "0.3.0"));
+        assert!(!is_compatible_bump("0.0.1", "0.0.2"));
+        // comparing with an older version
+        assert!(!is_compatible_bump("1.3.0", "1.2.0"));
+        assert!(!is_compatible_bump("1.2.3", "1.2.3"));
+    }
+
+    #[test]
+    fn compare_items() {
+        let diff = compare(
+            "foo",
+            "1.0.0",
+            vec![
+                item("foo::Foo", "struct", "1.0.0"),
+                item("foo::helper", "fn", "1.0.0"),
+                item("foo::de::Deserializer", "trait", "1.0.0"),
+            ],
+            "1.1.0",
+            vec![
+                item("foo::Foo", "enum", "1.1.0"),
+                item("foo::de::Deserializer", "trait", "1.1.0"),
+                item("foo::de::from_str", "fn", "1.1.0"),
+            ],
+        );
+
+        assert!(diff.compatible_bump);
+        assert_eq!(diff.semver_hazards, vec!["foo::Foo", "foo::helper"]);
+        assert_eq!(
+            diff.modules,
+            vec![
+                ModuleDiff {
+                    module: "foo".into(),
+                    added: Vec::new(),
+                    removed: vec![item("foo::helper", "fn", "1.0.0")],
+                    changed: vec![ChangedItem {
+                        name: "Foo".into(),
+                        path: "foo::Foo".into(),
+                        old_kind: "struct".into(),
+                        new_kind: "enum".into(),
+                        old_url: "/foo/1.0.0/foo/struct.Foo.html".into(),
+                        new_url: "/foo/1.1.0/foo/enum.Foo.html".into(),
+                    }],
+                },
+                ModuleDiff {
+                    module: "foo::de".into(),
+                    added: vec![item("foo::de::from_str", "fn", "1.1.0")],
+                    removed: Vec::new(),
+                    changed: Vec::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn removals_in_major_bump_are_not_hazards() {
+        let diff = compare(
+            "foo",
+            "1.0.0",
+            vec![item("foo::helper", "fn", "1.0.0")],
+            "2.0.0",
+            vec![item("foo::other", "fn", "2.0.0")],
+        );
+        assert!(!diff.compatible_bump);
+        assert!(diff.semver_hazards.is_empty());
+        assert_eq!(diff.modules[0].removed.len(), 1);
+    }
+
+    #[test]
+    fn items_with_the_same_path() {
+        // a derive macro with the same name as its trait, like `serde::Serialize`
+        let diff = compare(
+            "foo",
+            "1.0.0",
+            vec![
+                item("foo::Ser", "derive", "1.0.0"),
+                item("foo::Ser", "trait", "1.0.0"),
+            ],
+            "1.0.1",
+            vec![item("foo::Ser", "trait", "1.0.1")],
+        );
+        assert_eq!(
+            diff.modules[0].removed,
+            vec![item("foo::Ser", "derive", "1.0.0")]
+        );
+        assert!(diff.modules[0].changed.is_empty());
+    }
+
+    #[test]
+    fn changed_signatures() {
+        let with_signature = |path: &str, kind: &str, version: &str, signature: &str| ApiItem {
+            signature: Some(signature.into()),
+            ..item(path, kind, version)
+        };
+        let diff = compare(
+            "foo",
+            "1.0.0",
+            vec![
+                with_signature("foo::bar", "fn", "1.0.0", "a"),
+                with_signature("foo::baz", "fn", "1.0.0", "b"),
+            ],
+            "1.0.1",
+            vec![
+                with_signature("foo::bar", "fn", "1.0.1", "c"),
+                with_signature("foo::baz", "fn", "1.0.1", "b"),
+            ],
+        );
+        assert_eq!(diff.semver_hazards, vec!["foo::bar"]);
+        assert_eq!(
+            diff.modules[0].changed,
+            vec![ChangedItem {
+                name: "bar".into(),
+                path: "foo::bar".into(),
+                old_kind: "fn".into(),
+                new_kind: "fn".into(),
+                old_url: "/foo/1.0.0/foo/fn.bar.html".into(),
+                new_url: "/foo/1.0.1/foo/fn.bar.html".into(),
+            }]
+        );
+    }
+
+    /// The rustdoc JSON output of a crate with the struct `Foo` and its method `new`,
+    /// with `new` taking the `inputs`.
+    fn rustdoc_json(inputs: serde_json::Value, field: bool) -> Vec<u8> {
+        let item = |name: &str, kind: &str, inner: serde_json::Value| {
+            serde_json::json!({
+                "crate_id": 0,
+                "name": name,
+                "docs": null,
+                "visibility": "public",
+                "inner": { kind: inner },
+            })
+        };
+        let mut index = serde_json::json!({
+            "0": item("foo", "module", serde_json::json!({ "items": [1] })),
+            "1": item("Foo", "struct", serde_json::json!({
+                "kind": { "plain": { "fields": if field { vec![4] } else { vec![] } } },
+            })),
+            "2": item("new", "function", serde_json::json!({ "decl": { "inputs": inputs } })),
+            "3": {
+                "crate_id": 0,
+                "visibility": "default",
+                "inner": { "impl": {
+                    "trait": null,
+                    "for": { "resolved_path": { "name": "Foo", "id": 1 } },
+                    "items": [2],
+                } },
+            },
+        });
+        if field {
+            index["4"] = item("field", "struct_field", serde_json::json!({}));
+        }
+        serde_json::to_vec(&serde_json::json!({
+            "index": index,
+            "paths": {
+                "0": { "crate_id": 0, "path": ["foo"], "kind": "module" },
+                "1": { "crate_id": 0, "path": ["foo", "Foo"], "kind": "struct" },
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn api_diff_page_with_members() {
+        wrapper(|env| {
+            let old = rustdoc_json(serde_json::json!([]), false);
+            let new = rustdoc_json(serde_json::json!([["x", { "primitive": "u8" }]]), true);
+            env.fake_release()
+                .name("foo")
+                .version("1.0.0")
+                .rustdoc_json("x86_64-unknown-linux-gnu", &old)
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("1.0.1")
+                .rustdoc_json("x86_64-unknown-linux-gnu", &new)
+                .create()?;
+
+            let diff = api_diff(&mut env.db().conn(), "foo", "1.0.0", "1.0.1")?.unwrap();
+            assert!(diff.members_compared);
+            assert_eq!(diff.semver_hazards, vec!["foo::Foo::new"]);
+
+            let page = kuchikiki::parse_html().one(
+                env.frontend()
+                    .get("/crate/foo/1.0.0...1.0.1/api-diff")
+                    .send()?
+                    .text()?,
+            );
+            let module = page
+                .select_first(".api-module[data-module='foo::Foo']")
+                .unwrap();
+            let links = |selector: &str| -> Vec<String> {
+                module
+                    .as_node()
+                    .select(selector)
+                    .unwrap()
+                    .map(|link| link.attributes.borrow().get("href").unwrap().to_owned())
+                    .collect()
+            };
+            assert_eq!(
+                links(".api-item.added a"),
+                vec!["/foo/1.0.1/foo/struct.Foo.html#structfield.field"]
+            );
+            assert_eq!(
+                links(".api-item.changed a"),
+                vec![
+                    "/foo/1.0.0/foo/struct.Foo.html#method.new",
+                    "/foo/1.0.1/foo/struct.Foo.html#method.new",
+                ]
+            );
+            assert!(page
+                .select_first(".api-diff-scope")
+                .unwrap()
+                .text_contents()
+                .contains("including methods"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn api_diff_page() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("1.0.0")
+                .rustdoc_file_with("search-index.js", OLD_INDEX.as_bytes())
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("1.1.0")
+                .rustdoc_file_with("search-index.js", NEW_INDEX.as_bytes())
+                .create()?;
+
+            let response = env
+                .frontend()
+                .get("/crate/foo/1.0.0...1.1.0/api-diff")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_cache_control(
+                &response,
+                CachePolicy::ForeverInCdnAndStaleInBrowser,
+                &env.config(),
+            );
+
+            let page = kuchikiki::parse_html().one(response.text()?);
+            let modules: Vec<String> = page
+                .select(".api-module")
+                .unwrap()
+                .map(|module| {
+                    module
+                        .attributes
+                        .borrow()
+                        .get("data-module")
+                        .unwrap()
+                        .to_owned()
+                })
+                .collect();
+            assert_eq!(modules, vec!["foo", "foo::de"]);
+
+            let hazards: Vec<String> = page
+                .select(".api-item.semver-hazard a")
+                .unwrap()
+                .map(|link| link.attributes.borrow().get("href").unwrap().to_owned())
+                .collect();
+            assert_eq!(
+                hazards,
+                vec![
+                    "/foo/1.0.0/foo/fn.helper.html",
+                    "/foo/1.0.0/foo/struct.Foo.html",
+                    "/foo/1.1.0/foo/enum.Foo.html",
+                ]
+            );
+
+            let added = page.select_first(".api-item.added a").unwrap();
+            assert_eq!(
+                added.attributes.borrow().get("href"),
+                Some("/foo/1.1.0/foo/de/fn.from_str.html")
+            );
+
+            // without the rustdoc JSON output only the items with their own page are compared
+            assert!(page
+                .select_first(".api-diff-scope")
+                .unwrap()
+                .text_contents()
+                .contains("not detected"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn release_without_items() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("1.0.0")
+                .rustdoc_file_with("search-index.js", OLD_INDEX.as_bytes())
+                .create()?;
+            env.fake_release().name("foo").version("1.1.0").create()?;
+
+            assert!(api_diff(&mut env.db().conn(), "foo", "1.0.0", "1.1.0")?.is_none());
+
+            let response = env
+                .frontend()
+                .get("/crate/foo/1.0.0...1.1.0/api-diff")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(response.text()?.contains("No item data"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn semver_versions_redirect() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("1.0.0").create()?;
+            env.fake_release().name("foo").version("1.1.0").create()?;
+
+            assert_redirect_cached(
+                "/crate/foo/~1.0...latest/api-diff",
+                "/crate/foo/1.0.0...1.1.0/api-diff",
+                CachePolicy::ForeverInCdn,
+                env.frontend(),
+                &env.config(),
+            )?;
+            Ok(())
+        });
+    }
+}
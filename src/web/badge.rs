@@ -0,0 +1,312 @@
+use crate::{
+    db::Pool,
+    impl_axum_webpage,
+    utils::spawn_blocking,
+    web::{
+        cache::CachePolicy,
+        error::{AxumNope, AxumResult},
+        match_version_axum,
+    },
+};
+use axum::{
+    extract::{Extension, Path, Query},
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+const COLOR_PASSING: &str = "#4c1";
+const COLOR_FAILING: &str = "#e05d44";
+const COLOR_UNKNOWN: &str = "#9f9f9f";
+const COLOR_LABEL: &str = "#555";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum BadgeStyle {
+    #[default]
+    Flat,
+    ForTheBadge,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct BadgeQueryParams {
+    version: Option<String>,
+    #[serde(default)]
+    style: BadgeStyle,
+}
+
+/// The rendered badge, see `templates/badge.svg`.
+#[derive(Debug, Clone, Serialize)]
+struct Badge {
+    style: BadgeStyle,
+    label: String,
+    message: String,
+    color: &'static str,
+    label_color: &'static str,
+    label_width: u32,
+    message_width: u32,
+}
+
+impl_axum_webpage! {
+    Badge = "badge.svg",
+    content_type = "image/svg+xml",
+    // the badge of a crate changes with every build, the CDN is invalidated
+    // through `cdn::queue_crate_invalidation` when a build finishes.
+    cache_policy = |_| CachePolicy::ForeverInCdn,
+}
+
+/// Approximates the width of `c` in pixels when rendered in 11px Verdana,
+/// the font used in the badges.
+fn char_width(c: char) -> f32 {
+    match c {
+        'i' | 'j' | 'l' | '|' | '\'' | '!' => 3.0,
+        ' ' | '.' | ',' | ':' | ';' => 3.9,
+        'f' | 'r' | 't' | 'I' => 4.3,
+        '(' | ')' | '[' | ']' | '-' | '/' => 4.6,
+        'm' | 'w' => 9.9,
+        'M' | 'W' => 10.8,
+        '%' => 11.9,
+        '0'..='9' => 7.0,
+        c if c.is_ascii_uppercase() => 7.5,
+        _ => 6.6,
+    }
+}
+
+/// The width of a text segment of the badge, including its padding.
+fn segment_width(text: &str, style: BadgeStyle) -> u32 {
+    let width = match style {
+        BadgeStyle::Flat => text.chars().map(char_width).sum::<f32>() + 10.0,
+        // bold 10px text with letter spacing and more padding
+        BadgeStyle::ForTheBadge => {
+            text.chars()
+                .map(|c| char_width(c.to_ascii_uppercase()) + 1.25)
+                .sum::<f32>()
+                + 18.0
+        }
+    };
+    width.ceil() as u32
+}
+
+impl Badge {
+    fn new(style: BadgeStyle, label: &str, message: String, color: &'static str) -> Self {
+        let (label, message) = match style {
+            BadgeStyle::Flat => (label.to_owned(), message),
+            BadgeStyle::ForTheBadge => (label.to_uppercase(), message.to_uppercase()),
+        };
+        Self {
+            style,
+            label_width: segment_width(&label, style),
+            message_width: segment_width(&message, style),
+            label,
+            message,
+            color,
+            label_color: COLOR_LABEL,
+        }
+    }
+}
+
+#[instrument(skip(pool))]
+pub(crate) async fn badge_handler(
+    Path(name): Path<String>,
+    Query(query): Query<BadgeQueryParams>,
+    Extension(pool): Extension<Pool>,
+) -> AxumResult<impl IntoResponse> {
+    let requested_version = query.version.as_deref().unwrap_or("latest");
+
+    let release = match match_version_axum(&pool, &name, Some(requested_version))
+        .await
+        .map_err(AxumNope::from)
+        .and_then(|matched| matched.exact_name_only())
+    {
+        Ok(matched) => Some(matched.into_parts()),
+        Err(AxumNope::CrateNotFound | AxumNope::VersionNotFound) => None,
+        Err(err) => return Err(err),
+    };
+
+    let Some((version, release_id)) = release else {
+        return Ok(Badge::new(
+            query.style,
+            "docs",
+            "not found".into(),
+            COLOR_UNKNOWN,
+        ));
+    };
+
+    let (rustdoc_status, total_items, documented_items) = spawn_blocking(move || {
+        let row = pool.get()?.query_one(
+            "SELECT
+                releases.rustdoc_status,
+                doc_coverage.total_items,
+                doc_coverage.documented_items
+             FROM releases
+             LEFT JOIN doc_coverage ON doc_coverage.release_id = releases.id
+             WHERE releases.id = $1",
+            &[&release_id],
+        )?;
+        Ok((
+            row.get::<_, bool>(0),
+            row.get::<_, Option<i32>>(1),
+            row.get::<_, Option<i32>>(2),
+        ))
+    })
+    .await?;
+
+    let label = if query.version.is_some() {
+        format!("docs {version}")
+    } else {
+        "docs".into()
+    };
+
+    Ok(if rustdoc_status {
+        let message = match (total_items, documented_items) {
+            (Some(total), Some(documented)) if total > 0 => {
+                format!("passing ({}%)", documented * 100 / total)
+            }
+            _ => "passing".into(),
+        };
+        Badge::new(query.style, &label, message, COLOR_PASSING)
+    } else {
+        Badge::new(query.style, &label, "failing".into(), COLOR_FAILING)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        docbuilder::DocCoverage,
+        test::{assert_cache_control, wrapper},
+    };
+    use kuchikiki::traits::TendrilSink;
+    use reqwest::StatusCode;
+
+    fn badge_text(body: &str) -> Vec<String> {
+        kuchikiki::parse_html()
+            .one(body)
+            .select("text.visible")
+            .unwrap()
+            .map(|text| text.text_contents())
+            .collect()
+    }
+
+    #[test]
+    fn passing_with_coverage() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .doc_coverage(DocCoverage {
+                    total_items: 8,
+                    documented_items: 6,
+                    total_items_needing_examples: 0,
+                    items_with_examples: 0,
+                    files: Vec::new(),
+                    undocumented_items: Vec::new(),
+                })
+                .create()?;
+
+            let response = env.frontend().get("/foo/badge.svg").send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_cache_control(&response, CachePolicy::ForeverInCdn, &env.config());
+            assert_eq!(response.headers()["content-type"], "image/svg+xml");
+
+            let body = response.text()?;
+            assert_eq!(badge_text(&body), vec!["docs", "passing (75%)"]);
+            assert!(body.contains(COLOR_PASSING));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn failing_version() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.2.0")
+                .build_result_failed()
+                .create()?;
+
+            let web = env.frontend();
+            let body = web.get("/foo/badge.svg?version=0.2.0").send()?.text()?;
+            assert_eq!(badge_text(&body), vec!["docs 0.2.0", "failing"]);
+            assert!(body.contains(COLOR_FAILING));
+
+            let body = web.get("/foo/badge.svg?version=0.1").send()?.text()?;
+            assert_eq!(badge_text(&body), vec!["docs 0.1.0", "passing"]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn version_is_urlencoded() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("zstd")
+                .version("0.5.1+zstd.1.4.4")
+                .create()?;
+
+            let body = env
+                .frontend()
+                .get("/zstd/badge.svg?version=0.5.1%2Bzstd.1.4.4")
+                .send()?
+                .text()?;
+            assert_eq!(badge_text(&body), vec!["docs 0.5.1+zstd.1.4.4", "passing"]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn unknown_crate_or_version() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+
+            let web = env.frontend();
+            for url in ["/bar/badge.svg", "/foo/badge.svg?version=0.3.0"] {
+                let response = web.get(url).send()?;
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = response.text()?;
+                assert_eq!(badge_text(&body)[1], "not found");
+                assert!(body.contains(COLOR_UNKNOWN));
+            }
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn for_the_badge_style() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+
+            let web = env.frontend();
+            let body = web
+                .get("/foo/badge.svg?style=for-the-badge")
+                .send()?
+                .text()?;
+            assert_eq!(badge_text(&body), vec!["DOCS", "PASSING"]);
+            assert!(body.contains(r#"height="28""#));
+
+            let response = web.get("/foo/badge.svg?style=plastic").send()?;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn segment_widths() {
+        assert_eq!(segment_width("docs", BadgeStyle::Flat), 37);
+        assert!(
+            segment_width("DOCS", BadgeStyle::ForTheBadge)
+                > segment_width("docs", BadgeStyle::Flat)
+        );
+        assert!(
+            segment_width("passing (100%)", BadgeStyle::Flat)
+                > segment_width("passing", BadgeStyle::Flat)
+        );
+    }
+}
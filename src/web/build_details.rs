@@ -1,5 +1,6 @@
 use crate::{
     db::Pool,
+    docbuilder::FailureCategory,
     impl_axum_webpage,
     utils::spawn_blocking,
     web::{
@@ -14,7 +15,7 @@ use axum::{
     response::IntoResponse,
 };
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
@@ -25,6 +26,20 @@ pub(crate) struct BuildDetails {
     build_status: bool,
     build_time: DateTime<Utc>,
     output: String,
+    /// The target the shown log belongs to.
+    target: String,
+    /// All targets a log was stored for.
+    log_targets: Vec<String>,
+    failure_category: Option<FailureCategory>,
+    /// The resources the build of the shown target used, not known for older builds.
+    resource_usage: Option<ResourceUsage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct ResourceUsage {
+    wall_time_secs: i64,
+    peak_memory_bytes: Option<i64>,
+    output_size_bytes: i64,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
@@ -38,15 +53,29 @@ impl_axum_webpage! {
     BuildDetailsPage = "crate/build_details.html",
 }
 
+#[derive(Debug, Deserialize)]
+pub(crate) struct BuildDetailsParams {
+    name: String,
+    version: String,
+    id: String,
+    target: Option<String>,
+}
+
 pub(crate) async fn build_details_handler(
-    Path((name, version, id)): Path<(String, String, String)>,
+    Path(params): Path<BuildDetailsParams>,
     Extension(pool): Extension<Pool>,
     Extension(config): Extension<Arc<Config>>,
     Extension(storage): Extension<Arc<Storage>>,
 ) -> AxumResult<impl IntoResponse> {
+    let BuildDetailsParams {
+        name,
+        version,
+        id,
+        target,
+    } = params;
     let id: i32 = id.parse().map_err(|_| AxumNope::BuildNotFound)?;
 
-    let (row, output, metadata) = spawn_blocking(move || {
+    let (row, output, target, log_targets, resource_usage, metadata) = spawn_blocking(move || {
         let mut conn = pool.get()?;
         let row = conn
             .query_opt(
@@ -56,6 +85,8 @@ pub(crate) async fn build_details_handler(
                      builds.build_status,
                      builds.build_time,
                      builds.output,
+                     builds.log_targets,
+                     builds.failure_category,
                      releases.default_target
                  FROM builds
                  INNER JOIN releases ON releases.id = builds.rid
@@ -65,18 +96,48 @@ pub(crate) async fn build_details_handler(
             )?
             .ok_or(AxumNope::BuildNotFound)?;
 
-        let output = if let Some(output) = row.get("output") {
-            output
-        } else {
-            let target: String = row.get("default_target");
-            let path = format!("build-logs/{id}/{target}.txt");
-            let file = File::from_path(&storage, &path, &config)?;
-            String::from_utf8(file.0.content)?
+        let default_target: String = row.get("default_target");
+        let mut log_targets: Vec<String> = row.get("log_targets");
+        if log_targets.is_empty() {
+            // older builds only stored the log of the default target
+            log_targets.push(default_target.clone());
+        }
+
+        let target = match target {
+            Some(target) if log_targets.contains(&target) => target,
+            Some(_) => return Err(AxumNope::ResourceNotFound.into()),
+            None if log_targets.contains(&default_target) => default_target.clone(),
+            None => log_targets[0].clone(),
         };
 
+        let output = match row.get("output") {
+            Some(output) if target == default_target => output,
+            _ => {
+                let path = format!("build-logs/{id}/{target}.txt");
+                let file = File::from_path(&storage, &path, &config)?;
+                String::from_utf8(file.0.content)?
+            }
+        };
+
+        let resource_usage = conn
+            .query_opt(
+                "SELECT wall_time_ms, peak_memory_bytes, output_size_bytes
+                 FROM build_resource_usage
+                 WHERE build_id = $1 AND target = $2",
+                &[&id, &target],
+            )?
+            .map(|row| ResourceUsage {
+                wall_time_secs: row.get::<_, i64>("wall_time_ms") / 1000,
+                peak_memory_bytes: row.get("peak_memory_bytes"),
+                output_size_bytes: row.get("output_size_bytes"),
+            });
+
         Ok((
             row,
             output,
+            target,
+            log_targets,
+            resource_usage,
             MetaData::from_crate(&mut conn, &name, &version, &version)?,
         ))
     })
@@ -91,6 +152,12 @@ pub(crate) async fn build_details_handler(
             build_status: row.get("build_status"),
             build_time: row.get("build_time"),
             output,
+            target,
+            log_targets,
+            failure_category: row
+                .get::<_, Option<&str>>("failure_category")
+                .and_then(|category| category.parse().ok()),
+            resource_usage,
         },
         use_direct_platform_links: true,
     }
@@ -99,8 +166,10 @@ pub(crate) async fn build_details_handler(
 
 #[cfg(test)]
 mod tests {
+    use crate::docbuilder::{FailureCategory, ResourceUsage};
     use crate::test::{wrapper, FakeBuild};
     use kuchikiki::traits::TendrilSink;
+    use std::time::Duration;
     use test_case::test_case;
 
     #[test]
@@ -135,6 +204,69 @@ mod tests {
         });
     }
 
+    #[test]
+    fn failure_category() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .builds(vec![FakeBuild::default()
+                    .failure_category(FailureCategory::MissingSystemLibrary)
+                    .s3_build_log("A build log")])
+                .create()?;
+
+            let id: i32 = env
+                .db()
+                .conn()
+                .query_one("SELECT id FROM builds", &[])?
+                .get(0);
+            let page = kuchikiki::parse_html().one(
+                env.frontend()
+                    .get(&format!("/crate/foo/0.1.0/builds/{id}"))
+                    .send()?
+                    .text()?,
+            );
+            let log = page.select("pre").unwrap().next().unwrap().text_contents();
+            assert!(log.contains("# failure category\nMissing system library\n"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn resource_usage() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .builds(vec![FakeBuild::default().resource_usage(ResourceUsage {
+                    target: "x86_64-unknown-linux-gnu".into(),
+                    wall_time: Duration::from_millis(95_500),
+                    peak_memory: Some(3 * 1024 * 1024 * 1024),
+                    output_size: 2048,
+                })])
+                .create()?;
+
+            let id: i32 = env
+                .db()
+                .conn()
+                .query_one("SELECT id FROM builds", &[])?
+                .get(0);
+            let page = kuchikiki::parse_html().one(
+                env.frontend()
+                    .get(&format!("/crate/foo/0.1.0/builds/{id}"))
+                    .send()?
+                    .text()?,
+            );
+            let log = page.select("pre").unwrap().next().unwrap().text_contents();
+            assert!(log.contains(
+                "# resource usage\nwall time: 95s\npeak memory: 3 GB\noutput size: 2 kB\n"
+            ));
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn s3_build_logs() {
         wrapper(|env| {
@@ -198,6 +330,79 @@ mod tests {
         });
     }
 
+    #[test]
+    fn multiple_target_build_logs() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .builds(vec![FakeBuild::default()
+                    .s3_build_log("A build log")
+                    .target_build_log(
+                        "i686-pc-windows-msvc",
+                        "A windows build log",
+                    )])
+                .create()?;
+
+            let page = kuchikiki::parse_html().one(
+                env.frontend()
+                    .get("/crate/foo/0.1.0/builds")
+                    .send()?
+                    .text()?,
+            );
+
+            let node = page.select("ul > li a.release").unwrap().next().unwrap();
+            let attrs = node.attributes.borrow();
+            let url = attrs.get("href").unwrap();
+
+            let page = kuchikiki::parse_html().one(env.frontend().get(url).send()?.text()?);
+
+            let log = page.select("pre").unwrap().next().unwrap().text_contents();
+            assert!(log.contains("A build log"));
+
+            let tabs: Vec<_> = page
+                .select(".build-log-targets a")
+                .unwrap()
+                .map(|node| {
+                    let attrs = node.attributes.borrow();
+                    (
+                        node.text_contents().trim().to_owned(),
+                        attrs.get("href").unwrap().to_owned(),
+                        attrs.get("class").unwrap().contains("pure-menu-active"),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                tabs,
+                vec![
+                    (
+                        "x86_64-unknown-linux-gnu".to_owned(),
+                        format!("{url}/x86_64-unknown-linux-gnu"),
+                        true
+                    ),
+                    (
+                        "i686-pc-windows-msvc".to_owned(),
+                        format!("{url}/i686-pc-windows-msvc"),
+                        false
+                    ),
+                ]
+            );
+
+            let page = kuchikiki::parse_html().one(env.frontend().get(&tabs[1].1).send()?.text()?);
+
+            let log = page.select("pre").unwrap().next().unwrap().text_contents();
+            assert!(log.contains("A windows build log"));
+
+            let res = env
+                .frontend()
+                .get(&format!("{url}/x86_64-apple-darwin"))
+                .send()?;
+            assert_eq!(res.status(), 404);
+
+            Ok(())
+        });
+    }
+
     #[test_case("42")]
     #[test_case("nan")]
     fn non_existing_build(build_id: &str) {
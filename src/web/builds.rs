@@ -1,6 +1,6 @@
 use super::{cache::CachePolicy, headers::CanonicalUrl, MatchSemver};
 use crate::{
-    db::Pool,
+    db::{Overrides, Pool},
     docbuilder::Limits,
     impl_axum_webpage,
     utils::spawn_blocking,
@@ -32,6 +32,8 @@ struct BuildsPage {
     metadata: MetaData,
     builds: Vec<Build>,
     limits: Limits,
+    /// Whether the limits were raised automatically after a build hit them.
+    limits_raised_automatically: bool,
     canonical_url: CanonicalUrl,
     use_direct_platform_links: bool,
 }
@@ -61,12 +63,15 @@ pub(crate) async fn build_list_handler(
         }
     };
 
-    let (limits, builds, metadata) = spawn_blocking({
+    let (limits, limits_raised_automatically, builds, metadata) = spawn_blocking({
         let name = name.clone();
+        let config = config.clone();
         move || {
             let mut conn = pool.get()?;
             Ok((
                 Limits::for_crate(&config, &mut conn, &name)?,
+                Overrides::for_crate(&mut *conn, &name)?
+                    .is_some_and(|overrides| overrides.automatic),
                 get_builds(&mut conn, &name, &version)?,
                 MetaData::from_crate(&mut conn, &name, &version, &version_or_latest)?,
             ))
@@ -78,7 +83,11 @@ pub(crate) async fn build_list_handler(
         metadata,
         builds,
         limits,
-        canonical_url: CanonicalUrl::from_path(format!("/crate/{name}/latest/builds")),
+        limits_raised_automatically,
+        canonical_url: CanonicalUrl::from_path(
+            &config.public_url,
+            format!("/crate/{name}/latest/builds"),
+        ),
         use_direct_platform_links: true,
     }
     .into_response())
@@ -152,6 +161,7 @@ fn get_builds(conn: &mut postgres::Client, name: &str, version: &str) -> Result<
 #[cfg(test)]
 mod tests {
     use crate::{
+        db::Overrides,
         test::{assert_cache_control, wrapper, FakeBuild},
         web::cache::CachePolicy,
     };
@@ -322,6 +332,33 @@ mod tests {
             assert!(values.contains(&"100 kB"));
             assert!(values.contains(&"blocked"));
             assert!(values.contains(&"1"));
+            assert!(page.select_first(".limits-raised").is_err());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn limits_raised_automatically() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            Overrides::save(
+                &mut *env.db().conn(),
+                "foo",
+                Overrides {
+                    memory: Some(6 * 1024 * 1024 * 1024),
+                    automatic: true,
+                    ..Overrides::default()
+                },
+            )?;
+
+            let page = kuchikiki::parse_html().one(
+                env.frontend()
+                    .get("/crate/foo/0.1.0/builds")
+                    .send()?
+                    .text()?,
+            );
+            assert!(page.select_first(".limits-raised").is_ok());
 
             Ok(())
         });
@@ -0,0 +1,435 @@
+use super::headers::CanonicalUrl;
+use super::MatchSemver;
+use crate::{
+    db::Pool,
+    docbuilder::{FileDocCoverage, UndocumentedItem},
+    impl_axum_webpage,
+    utils::spawn_blocking,
+    web::{cache::CachePolicy, error::AxumResult, match_version_axum, MetaData},
+    Config,
+};
+use axum::{
+    extract::{Extension, Path},
+    response::IntoResponse,
+};
+use postgres::Client;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// The documentation coverage of one release, used for the chart comparing versions.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct CoverageHistoryEntry {
+    version: String,
+    documented_percent: f32,
+    examples_percent: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CoveragePage {
+    metadata: MetaData,
+    /// The coverage of the whole release, `None` if none was collected.
+    total: Option<FileDocCoverage>,
+    /// The coverage of every source file, files with the most undocumented items first.
+    files: Vec<FileDocCoverage>,
+    /// The public items without documentation, in the order they are defined in.
+    undocumented_items: Vec<UndocumentedItem>,
+    history: Vec<CoverageHistoryEntry>,
+    canonical_url: CanonicalUrl,
+    is_latest_url: bool,
+    use_direct_platform_links: bool,
+}
+
+impl_axum_webpage! {
+    CoveragePage = "crate/coverage.html",
+    cache_policy = |page| if page.is_latest_url {
+        CachePolicy::ForeverInCdn
+    } else {
+        CachePolicy::ForeverInCdnAndStaleInBrowser
+    },
+}
+
+fn percent(part: i32, total: i32) -> Option<f32> {
+    (total > 0).then(|| part as f32 * 100.0 / total as f32)
+}
+
+fn get_total(
+    conn: &mut Client,
+    name: &str,
+    version: &str,
+) -> anyhow::Result<Option<FileDocCoverage>> {
+    Ok(conn
+        .query_opt(
+            "SELECT
+                doc_coverage.total_items,
+                doc_coverage.documented_items,
+                doc_coverage.total_items_needing_examples,
+                doc_coverage.items_with_examples
+             FROM doc_coverage
+             INNER JOIN releases ON releases.id = doc_coverage.release_id
+             INNER JOIN crates ON crates.id = releases.crate_id
+             WHERE crates.name = $1 AND releases.version = $2",
+            &[&name, &version],
+        )?
+        .map(|row| FileDocCoverage {
+            path: String::new(),
+            total_items: row.get::<_, Option<i32>>(0).unwrap_or(0),
+            documented_items: row.get::<_, Option<i32>>(1).unwrap_or(0),
+            total_items_needing_examples: row.get::<_, Option<i32>>(2).unwrap_or(0),
+            items_with_examples: row.get::<_, Option<i32>>(3).unwrap_or(0),
+        }))
+}
+
+fn get_files(conn: &mut Client, name: &str, version: &str) -> anyhow::Result<Vec<FileDocCoverage>> {
+    Ok(conn
+        .query(
+            "SELECT
+                doc_coverage_files.path,
+                doc_coverage_files.total_items,
+                doc_coverage_files.documented_items,
+                doc_coverage_files.total_items_needing_examples,
+                doc_coverage_files.items_with_examples
+             FROM doc_coverage_files
+             INNER JOIN releases ON releases.id = doc_coverage_files.release_id
+             INNER JOIN crates ON crates.id = releases.crate_id
+             WHERE crates.name = $1 AND releases.version = $2
+             ORDER BY
+                doc_coverage_files.total_items - doc_coverage_files.documented_items DESC,
+                doc_coverage_files.path",
+            &[&name, &version],
+        )?
+        .into_iter()
+        .map(|row| FileDocCoverage {
+            path: row.get(0),
+            total_items: row.get(1),
+            documented_items: row.get(2),
+            total_items_needing_examples: row.get(3),
+            items_with_examples: row.get(4),
+        })
+        .collect())
+}
+
+fn get_undocumented_items(
+    conn: &mut Client,
+    name: &str,
+    version: &str,
+) -> anyhow::Result<Vec<UndocumentedItem>> {
+    Ok(conn
+        .query(
+            "SELECT
+                doc_coverage_items.path,
+                doc_coverage_items.line,
+                doc_coverage_items.name,
+                doc_coverage_items.kind
+             FROM doc_coverage_items
+             INNER JOIN releases ON releases.id = doc_coverage_items.release_id
+             INNER JOIN crates ON crates.id = releases.crate_id
+             WHERE crates.name = $1 AND releases.version = $2
+             ORDER BY doc_coverage_items.path, doc_coverage_items.line, doc_coverage_items.name",
+            &[&name, &version],
+        )?
+        .into_iter()
+        .map(|row| UndocumentedItem {
+            path: row.get(0),
+            line: row.get(1),
+            name: row.get(2),
+            kind: row.get(3),
+        })
+        .collect())
+}
+
+fn get_history(conn: &mut Client, name: &str) -> anyhow::Result<Vec<CoverageHistoryEntry>> {
+    Ok(conn
+        .query(
+            "SELECT
+                releases.version,
+                doc_coverage.total_items,
+                doc_coverage.documented_items,
+                doc_coverage.total_items_needing_examples,
+                doc_coverage.items_with_examples
+             FROM doc_coverage
+             INNER JOIN releases ON releases.id = doc_coverage.release_id
+             INNER JOIN crates ON crates.id = releases.crate_id
+             WHERE crates.name = $1
+             ORDER BY releases.release_time, releases.id",
+            &[&name],
+        )?
+        .into_iter()
+        .filter_map(|row| {
+            let total: i32 = row.get::<_, Option<i32>>(1)?;
+            let documented: i32 = row.get::<_, Option<i32>>(2)?;
+            Some(CoverageHistoryEntry {
+                version: row.get(0),
+                documented_percent: percent(documented, total)?,
+                examples_percent: row
+                    .get::<_, Option<i32>>(3)
+                    .zip(row.get::<_, Option<i32>>(4))
+                    .and_then(|(total, with_examples)| percent(with_examples, total)),
+            })
+        })
+        .collect())
+}
+
+pub(crate) async fn coverage_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+    Extension(config): Extension<Arc<Config>>,
+) -> AxumResult<impl IntoResponse> {
+    let (version, version_or_latest, is_latest_url) =
+        match match_version_axum(&pool, &name, Some(&req_version))
+            .await?
+            .exact_name_only()?
+        {
+            MatchSemver::Exact((version, _)) => (version.clone(), version, false),
+            MatchSemver::Latest((version, _)) => (version, "latest".to_string(), true),
+
+            MatchSemver::Semver((version, _)) => {
+                return Ok(super::axum_cached_redirect(
+                    &format!("/crate/{}/{}/coverage", &name, version),
+                    CachePolicy::ForeverInCdn,
+                )?
+                .into_response());
+            }
+        };
+
+    let (metadata, total, files, undocumented_items, history) = spawn_blocking({
+        let name = name.clone();
+        move || {
+            let mut conn = pool.get()?;
+            Ok((
+                MetaData::from_crate(&mut conn, &name, &version, &version_or_latest)?,
+                get_total(&mut conn, &name, &version)?,
+                get_files(&mut conn, &name, &version)?,
+                get_undocumented_items(&mut conn, &name, &version)?,
+                get_history(&mut conn, &name)?,
+            ))
+        }
+    })
+    .await?;
+
+    Ok(CoveragePage {
+        metadata,
+        total,
+        files,
+        undocumented_items,
+        history,
+        is_latest_url,
+        canonical_url: CanonicalUrl::from_path(
+            &config.public_url,
+            format!("/crate/{}/latest/coverage", &name),
+        ),
+        use_direct_platform_links: true,
+    }
+    .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docbuilder::DocCoverage;
+    use crate::test::{assert_cache_control, assert_redirect_cached, wrapper};
+    use kuchikiki::traits::TendrilSink;
+    use reqwest::StatusCode;
+
+    fn coverage(documented_items: i32, files: Vec<FileDocCoverage>) -> DocCoverage {
+        DocCoverage {
+            total_items: 10,
+            documented_items,
+            total_items_needing_examples: 4,
+            items_with_examples: 1,
+            files,
+            undocumented_items: Vec::new(),
+        }
+    }
+
+    fn file(path: &str, total_items: i32, documented_items: i32) -> FileDocCoverage {
+        FileDocCoverage {
+            path: path.into(),
+            total_items,
+            documented_items,
+            total_items_needing_examples: 0,
+            items_with_examples: 0,
+        }
+    }
+
+    #[test]
+    fn lists_files_with_undocumented_items_first() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .doc_coverage(coverage(
+                    6,
+                    vec![file("src/lib.rs", 3, 3), file("src/util.rs", 7, 3)],
+                ))
+                .create()?;
+
+            let response = env.frontend().get("/crate/foo/0.1.0/coverage").send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_cache_control(
+                &response,
+                CachePolicy::ForeverInCdnAndStaleInBrowser,
+                &env.config(),
+            );
+
+            let page = kuchikiki::parse_html().one(response.text()?);
+            let rows: Vec<Vec<String>> = page
+                .select("#coverage-files tbody tr")
+                .unwrap()
+                .map(|row| {
+                    row.as_node()
+                        .select("td")
+                        .unwrap()
+                        .map(|cell| cell.text_contents().trim().to_owned())
+                        .collect()
+                })
+                .collect();
+            assert_eq!(rows.len(), 2);
+            assert_eq!(rows[0][0], "src/util.rs");
+            assert_eq!(rows[0][2], "4");
+            assert_eq!(rows[1][0], "src/lib.rs");
+            assert_eq!(rows[1][2], "0");
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn lists_undocumented_items() {
+        wrapper(|env| {
+            let item = |path: &str, line, name: &str| UndocumentedItem {
+                path: path.into(),
+                line,
+                name: name.into(),
+                kind: "function".into(),
+            };
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .doc_coverage(DocCoverage {
+                    undocumented_items: vec![
+                        item("src/util.rs", 3, "foo::util::helper"),
+                        item("src/lib.rs", 12, "foo::Foo::new"),
+                    ],
+                    ..coverage(8, vec![file("src/lib.rs", 5, 4), file("src/util.rs", 5, 4)])
+                })
+                .create()?;
+
+            let page = kuchikiki::parse_html().one(
+                env.frontend()
+                    .get("/crate/foo/0.1.0/coverage")
+                    .send()?
+                    .text()?,
+            );
+            let rows: Vec<(String, String)> = page
+                .select("#coverage-undocumented-items tbody tr")
+                .unwrap()
+                .map(|row| {
+                    let cells: Vec<_> = row.as_node().select("td").unwrap().collect();
+                    let link = cells[2].as_node().select_first("a").unwrap();
+                    let href = link.attributes.borrow().get("href").unwrap().to_owned();
+                    (cells[0].text_contents().trim().to_owned(), href)
+                })
+                .collect();
+            assert_eq!(
+                rows,
+                vec![
+                    (
+                        "foo::Foo::new".to_owned(),
+                        "/crate/foo/0.1.0/source/src/lib.rs".to_owned()
+                    ),
+                    (
+                        "foo::util::helper".to_owned(),
+                        "/crate/foo/0.1.0/source/src/util.rs".to_owned()
+                    ),
+                ]
+            );
+
+            // rebuilding the release replaces the items
+            let mut conn = env.db().conn();
+            let release_id: i32 = conn.query_one("SELECT id FROM releases", &[])?.get(0);
+            crate::db::add_doc_coverage(&mut conn, release_id, coverage(10, Vec::new()))?;
+            assert!(get_undocumented_items(&mut conn, "foo", "0.1.0")?.is_empty());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn history_covers_all_versions() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .doc_coverage(coverage(5, Vec::new()))
+                .create()?;
+            env.fake_release().name("foo").version("0.2.0").create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.3.0")
+                .doc_coverage(coverage(8, Vec::new()))
+                .create()?;
+
+            let mut conn = env.db().conn();
+            assert_eq!(
+                get_history(&mut conn, "foo")?,
+                vec![
+                    CoverageHistoryEntry {
+                        version: "0.1.0".into(),
+                        documented_percent: 50.0,
+                        examples_percent: Some(25.0),
+                    },
+                    CoverageHistoryEntry {
+                        version: "0.3.0".into(),
+                        documented_percent: 80.0,
+                        examples_percent: Some(25.0),
+                    },
+                ]
+            );
+
+            let page = env.frontend().get("/crate/foo/latest/coverage").send()?;
+            assert_eq!(page.status(), StatusCode::OK);
+            assert_cache_control(&page, CachePolicy::ForeverInCdn, &env.config());
+            assert!(page.text()?.contains("coverage-history-chart"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn release_without_coverage() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+
+            let page = kuchikiki::parse_html().one(
+                env.frontend()
+                    .get("/crate/foo/0.1.0/coverage")
+                    .send()?
+                    .text()?,
+            );
+            assert!(page.select_first("#coverage-files").is_err());
+            assert!(page
+                .select_first("#main")
+                .unwrap()
+                .text_contents()
+                .contains("No documentation coverage"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn semver_redirect() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.2.1").create()?;
+
+            assert_redirect_cached(
+                "/crate/foo/~0.2/coverage",
+                "/crate/foo/0.2.1/coverage",
+                CachePolicy::ForeverInCdn,
+                env.frontend(),
+                &env.config(),
+            )?;
+            Ok(())
+        });
+    }
+}
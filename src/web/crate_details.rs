@@ -1,4 +1,5 @@
 use super::{markdown, match_version, MatchSemver, MetaData};
+use crate::index::split_qualified_crate_name;
 use crate::utils::{get_correct_docsrs_style_file, report_error, spawn_blocking};
 use crate::web::rustdoc::RustdocHtmlParams;
 use crate::web::{axum_cached_redirect, match_version_axum};
@@ -25,42 +26,49 @@ use postgres::GenericClient;
 use serde::Deserialize;
 use serde::{ser::Serializer, Serialize};
 use serde_json::Value;
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 use tracing::{instrument, trace};
 
 // TODO: Add target name and versions
 
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct CrateDetails {
-    name: String,
-    version: String,
-    description: Option<String>,
-    owners: Vec<(String, String)>,
-    dependencies: Option<Value>,
+    pub(crate) name: String,
+    /// The alternative registry the crate is published to, `None` for crates.io.
+    pub(crate) registry: Option<String>,
+    pub(crate) version: String,
+    pub(crate) description: Option<String>,
+    pub(crate) owners: Vec<(String, String)>,
+    pub(crate) dependencies: Option<Value>,
     #[serde(serialize_with = "optional_markdown")]
     readme: Option<String>,
     #[serde(serialize_with = "optional_markdown")]
     rustdoc: Option<String>, // this is description_long in database
-    release_time: DateTime<Utc>,
-    build_status: bool,
-    last_successful_build: Option<String>,
+    pub(crate) release_time: DateTime<Utc>,
+    pub(crate) build_status: bool,
+    pub(crate) last_successful_build: Option<String>,
     pub rustdoc_status: bool,
     pub archive_storage: bool,
-    repository_url: Option<String>,
-    homepage_url: Option<String>,
-    keywords: Option<Value>,
+    pub(crate) repository_url: Option<String>,
+    pub(crate) homepage_url: Option<String>,
+    pub(crate) keywords: Option<Value>,
     have_examples: bool, // need to check this manually
     pub target_name: String,
-    releases: Vec<Release>,
+    pub(crate) releases: Vec<Release>,
     repository_metadata: Option<RepositoryMetadata>,
     pub(crate) metadata: MetaData,
-    is_library: bool,
-    license: Option<String>,
+    pub(crate) is_library: bool,
+    pub(crate) license: Option<String>,
     pub(crate) documentation_url: Option<String>,
-    total_items: Option<i32>,
-    documented_items: Option<i32>,
-    total_items_needing_examples: Option<i32>,
-    items_with_examples: Option<i32>,
+    pub(crate) total_items: Option<i32>,
+    pub(crate) documented_items: Option<i32>,
+    pub(crate) total_items_needing_examples: Option<i32>,
+    pub(crate) items_with_examples: Option<i32>,
+    /// The number of crates depending on any version of this crate in their latest release.
+    pub(crate) dependents: i64,
+    /// The versions of the dependencies resolved in the build, by crate name.
+    /// Crates the release depends on in more than one version are left out.
+    pub(crate) locked_dependencies: BTreeMap<String, String>,
     /// Database id for this crate
     pub(crate) crate_id: i32,
     /// Database id for this release
@@ -185,8 +193,10 @@ impl CrateDetails {
             rustdoc_css_file: get_correct_docsrs_style_file(krate.get("doc_rustc_version"))?,
         };
 
+        let name: String = krate.get("name");
         let mut crate_details = CrateDetails {
-            name: krate.get("name"),
+            registry: split_qualified_crate_name(&name).0.map(Into::into),
+            name,
             version: krate.get("version"),
             description: krate.get("description"),
             owners: Vec::new(),
@@ -213,6 +223,8 @@ impl CrateDetails {
             total_items: krate.get("total_items"),
             total_items_needing_examples: krate.get("total_items_needing_examples"),
             items_with_examples: krate.get("items_with_examples"),
+            dependents: 0,
+            locked_dependencies: BTreeMap::new(),
             crate_id,
             release_id,
         };
@@ -231,6 +243,28 @@ impl CrateDetails {
             .map(|row| (row.get("login"), row.get("avatar")))
             .collect();
 
+        crate_details.dependents = conn
+            .query_one(
+                "SELECT COUNT(DISTINCT release_id)
+                 FROM release_dependencies
+                 WHERE dependency = $1 AND latest",
+                &[&crate_details.name],
+            )?
+            .get(0);
+
+        crate_details.locked_dependencies = conn
+            .query(
+                "SELECT name, MIN(version)
+                 FROM release_locked_dependencies
+                 WHERE release_id = $1
+                 GROUP BY name
+                 HAVING COUNT(*) = 1",
+                &[&release_id],
+            )?
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect();
+
         if !crate_details.build_status {
             crate_details.last_successful_build = crate_details
                 .releases
@@ -1520,4 +1554,31 @@ mod tests {
             Ok(())
         });
     }
+
+    #[test]
+    fn alternative_registry_crate() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("acme~foo")
+                .version("0.1.0")
+                .rustdoc_file("foo/index.html")
+                .create()?;
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            let web = env.frontend();
+
+            let details = web.get("/crate/acme~foo/0.1.0").send()?;
+            assert!(details.status().is_success());
+            assert!(!details.text()?.contains("https://crates.io/crates/"));
+
+            let rustdoc = web.get("/acme~foo/0.1.0/foo/index.html").send()?;
+            assert!(rustdoc.status().is_success());
+            assert!(!rustdoc.text()?.contains("https://crates.io/crates/"));
+
+            // crates.io crates with the same name are unaffected
+            let details = web.get("/crate/foo/0.1.0").send()?;
+            assert!(details.text()?.contains("https://crates.io/crates/foo"));
+
+            Ok(())
+        });
+    }
 }
@@ -0,0 +1,386 @@
+//! The crates depending on a release, found through `release_dependencies`.
+
+use crate::{
+    db::Pool,
+    impl_axum_webpage,
+    utils::spawn_blocking,
+    web::{
+        cache::CachePolicy,
+        error::{AxumNope, AxumResult},
+        match_version_axum, MatchSemver, MetaData,
+    },
+};
+use anyhow::Result;
+use axum::{
+    extract::{Extension, Path, Query},
+    response::IntoResponse,
+};
+use postgres::Client;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+
+/// Number of dependents per page.
+const DEPENDENTS_PER_PAGE: usize = 30;
+
+/// The last page that can be requested, far beyond the dependents of any crate, so the offsets
+/// of the pages can't overflow.
+const MAX_DEPENDENTS_PAGE: usize = 10_000;
+
+/// The latest release of a crate depending on the crate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Dependent {
+    name: String,
+    version: String,
+    description: Option<String>,
+    /// The version requirement on the crate.
+    req: String,
+    /// The kinds of the dependency, for example `normal` and `dev`
+    /// when the crate is used in both.
+    kinds: Vec<String>,
+}
+
+/// Loads the distinct requirements on `name` of the latest releases of crates, and keeps the
+/// ones `version` satisfies.
+///
+/// There are far fewer distinct requirements than dependents, so matching them here lets the
+/// database filter and paginate the dependents.
+fn matching_requirements(conn: &mut Client, name: &str, version: &Version) -> Result<Vec<String>> {
+    Ok(conn
+        .query(
+            "SELECT DISTINCT req
+             FROM release_dependencies
+             WHERE dependency = $1 AND latest",
+            &[&name],
+        )?
+        .into_iter()
+        .map(|row| row.get::<_, String>("req"))
+        .filter(|req| matches_version(req, version))
+        .collect())
+}
+
+/// Counts the crates whose latest release depends on `name`, and the dependents with one of
+/// the requirements in `reqs`.
+fn count_dependents(conn: &mut Client, name: &str, reqs: &[String]) -> Result<(i64, i64)> {
+    let row = conn.query_one(
+        "SELECT
+            COUNT(DISTINCT release_id) AS total,
+            COUNT(DISTINCT (release_id, req)) FILTER (WHERE req = ANY($2)) AS matching
+         FROM release_dependencies
+         WHERE dependency = $1 AND latest",
+        &[&name, &reqs],
+    )?;
+    Ok((row.get("total"), row.get("matching")))
+}
+
+/// Loads a page of the crates whose latest release depends on `name` with one of the
+/// requirements in `reqs`, sorted by name.
+fn get_dependents(
+    conn: &mut Client,
+    name: &str,
+    reqs: &[String],
+    offset: i64,
+    limit: i64,
+) -> Result<Vec<Dependent>> {
+    Ok(conn
+        .query(
+            "SELECT
+                crates.name,
+                releases.version,
+                releases.description,
+                release_dependencies.req,
+                ARRAY_AGG(release_dependencies.kind ORDER BY release_dependencies.kind) AS kinds
+             FROM release_dependencies
+             INNER JOIN releases ON releases.id = release_dependencies.release_id
+             INNER JOIN crates ON crates.id = releases.crate_id
+             WHERE
+                release_dependencies.dependency = $1 AND
+                release_dependencies.latest AND
+                release_dependencies.req = ANY($2)
+             GROUP BY crates.name, releases.version, releases.description, release_dependencies.req
+             ORDER BY crates.name, release_dependencies.req
+             OFFSET $3
+             LIMIT $4",
+            &[&name, &reqs, &offset, &limit],
+        )?
+        .into_iter()
+        .map(|row| Dependent {
+            name: row.get("name"),
+            version: row.get("version"),
+            description: row.get("description"),
+            req: row.get("req"),
+            kinds: row.get("kinds"),
+        })
+        .collect())
+}
+
+/// Whether the version requirement `req` of a dependent is satisfied by `version`.
+///
+/// Requirements `semver` can't parse never match.
+fn matches_version(req: &str, version: &Version) -> bool {
+    VersionReq::parse(req).is_ok_and(|req| req.matches(version))
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct DependentsPage {
+    metadata: MetaData,
+    /// The dependents on this page, with a requirement matching the version.
+    dependents: Vec<Dependent>,
+    /// The number of dependents with a requirement matching the version.
+    matching: i64,
+    /// The number of dependents on any version of the crate.
+    total: i64,
+    previous_page_link: Option<String>,
+    next_page_link: Option<String>,
+    use_direct_platform_links: bool,
+}
+
+impl_axum_webpage! {
+    DependentsPage = "crate/dependents.html",
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DependentsParams {
+    page: Option<usize>,
+}
+
+pub(crate) async fn dependents_handler(
+    Path((name, req_version)): Path<(String, String)>,
+    Query(params): Query<DependentsParams>,
+    Extension(pool): Extension<Pool>,
+) -> AxumResult<impl IntoResponse> {
+    let page = params.page.unwrap_or(1);
+    if !(1..=MAX_DEPENDENTS_PAGE).contains(&page) {
+        return Err(AxumNope::BadRequest);
+    }
+
+    let (version, version_or_latest) = match match_version_axum(&pool, &name, Some(&req_version))
+        .await?
+        .exact_name_only()?
+    {
+        MatchSemver::Exact((version, _)) => (version.clone(), version),
+        MatchSemver::Latest((version, _)) => (version, "latest".to_string()),
+
+        MatchSemver::Semver((version, _)) => {
+            return Ok(super::axum_cached_redirect(
+                &format!("/crate/{}/{}/dependents", &name, version),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
+    };
+    let parsed_version = Version::parse(&version).map_err(anyhow::Error::from)?;
+
+    Ok(spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let metadata = MetaData::from_crate(&mut conn, &name, &version, &version_or_latest)?;
+        let reqs = matching_requirements(&mut conn, &name, &parsed_version)?;
+        let (total, matching) = count_dependents(&mut conn, &name, &reqs)?;
+        let dependents = get_dependents(
+            &mut conn,
+            &name,
+            &reqs,
+            ((page - 1) * DEPENDENTS_PER_PAGE) as i64,
+            DEPENDENTS_PER_PAGE as i64,
+        )?;
+
+        let page_link =
+            |page: usize| format!("/crate/{name}/{version_or_latest}/dependents?page={page}");
+        let previous_page_link = (page > 1).then(|| page_link(page - 1));
+        let next_page_link =
+            (matching > (page * DEPENDENTS_PER_PAGE) as i64).then(|| page_link(page + 1));
+
+        Ok(DependentsPage {
+            metadata,
+            dependents,
+            matching,
+            total,
+            previous_page_link,
+            next_page_link,
+            use_direct_platform_links: true,
+        })
+    })
+    .await?
+    .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{assert_redirect_cached, wrapper};
+    use kuchikiki::traits::TendrilSink;
+    use reqwest::StatusCode;
+
+    fn dependent_names(body: &str) -> Vec<String> {
+        kuchikiki::parse_html()
+            .one(body)
+            .select(".dependent .name")
+            .unwrap()
+            .map(|name| name.text_contents().trim().to_owned())
+            .collect()
+    }
+
+    fn pagination_links(body: &str) -> Vec<String> {
+        kuchikiki::parse_html()
+            .one(body)
+            .select(".pagination a")
+            .unwrap()
+            .map(|link| link.attributes.borrow().get("href").unwrap().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn version_requirements() {
+        let version = Version::parse("1.2.3").unwrap();
+        assert!(matches_version("^1.0", &version));
+        assert!(matches_version("1.2.3", &version));
+        assert!(matches_version("*", &version));
+        assert!(!matches_version("^2", &version));
+        assert!(!matches_version("~1.1", &version));
+        assert!(!matches_version("not a requirement", &version));
+    }
+
+    #[test]
+    fn lists_matching_dependents() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            env.fake_release().name("foo").version("1.0.0").create()?;
+            env.fake_release()
+                .name("uses-old")
+                .version("1.0.0")
+                .add_dependency("foo", "^0.1", None)
+                .create()?;
+            env.fake_release()
+                .name("uses-new")
+                .version("1.0.0")
+                .add_dependency("foo", "^1.0", None)
+                .add_dependency("foo", "^1.0", Some("dev"))
+                .create()?;
+            // only the latest release of a dependent counts
+            env.fake_release()
+                .name("used-to")
+                .version("1.0.0")
+                .add_dependency("foo", "^1.0", None)
+                .create()?;
+            env.fake_release()
+                .name("used-to")
+                .version("2.0.0")
+                .create()?;
+
+            let web = env.frontend();
+            let response = web.get("/crate/foo/1.0.0/dependents").send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = response.text()?;
+            assert_eq!(dependent_names(&body), vec!["uses-new"]);
+            assert!(body.contains("dev"));
+
+            let body = web.get("/crate/foo/0.1.0/dependents").send()?.text()?;
+            assert_eq!(dependent_names(&body), vec!["uses-old"]);
+
+            let details = kuchikiki::parse_html().one(web.get("/crate/foo/latest").send()?.text()?);
+            assert_eq!(
+                details
+                    .select_first(".dependents-link")
+                    .unwrap()
+                    .text_contents()
+                    .trim(),
+                "2 dependent crates"
+            );
+
+            // deleting the latest release makes the previous one count again
+            crate::db::delete_version(
+                &mut *env.db().conn(),
+                &env.storage(),
+                &env.config(),
+                "used-to",
+                "2.0.0",
+            )?;
+            let body = web.get("/crate/foo/1.0.0/dependents").send()?.text()?;
+            assert_eq!(dependent_names(&body), vec!["used-to", "uses-new"]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn pagination() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("1.0.0").create()?;
+            for idx in 0..DEPENDENTS_PER_PAGE + 2 {
+                env.fake_release()
+                    .name(&format!("dependent-{idx:02}"))
+                    .version("1.0.0")
+                    .add_dependency("foo", "1", None)
+                    .create()?;
+            }
+
+            let web = env.frontend();
+            let body = web.get("/crate/foo/1.0.0/dependents").send()?.text()?;
+            assert_eq!(dependent_names(&body).len(), DEPENDENTS_PER_PAGE);
+            assert_eq!(
+                pagination_links(&body),
+                vec!["/crate/foo/1.0.0/dependents?page=2"]
+            );
+
+            let body = web
+                .get("/crate/foo/1.0.0/dependents?page=2")
+                .send()?
+                .text()?;
+            assert_eq!(dependent_names(&body), vec!["dependent-30", "dependent-31"]);
+            assert_eq!(
+                pagination_links(&body),
+                vec!["/crate/foo/1.0.0/dependents?page=1"]
+            );
+
+            let response = web.get("/crate/foo/1.0.0/dependents?page=0").send()?;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            let response = web
+                .get(&format!("/crate/foo/1.0.0/dependents?page={}", usize::MAX))
+                .send()?;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn backfill() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("1.0.0").create()?;
+            env.fake_release()
+                .name("bar")
+                .version("1.0.0")
+                .add_dependency("foo", "^1", None)
+                .create()?;
+
+            let mut conn = env.db().conn();
+            conn.execute("DELETE FROM release_dependencies", &[])?;
+            let reqs = vec!["^1".to_owned()];
+            assert!(get_dependents(&mut conn, "foo", &reqs, 0, 10)?.is_empty());
+
+            assert_eq!(crate::db::backfill_dependencies(&mut conn)?, 2);
+            let dependents = get_dependents(&mut conn, "foo", &reqs, 0, 10)?;
+            assert_eq!(dependents.len(), 1);
+            assert_eq!(dependents[0].name, "bar");
+            assert_eq!(dependents[0].kinds, vec!["normal"]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn semver_redirect() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("1.2.0").create()?;
+
+            assert_redirect_cached(
+                "/crate/foo/~1.2/dependents",
+                "/crate/foo/1.2.0/dependents",
+                CachePolicy::ForeverInCdn,
+                env.frontend(),
+                &env.config(),
+            )?;
+            Ok(())
+        });
+    }
+}
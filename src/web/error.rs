@@ -7,6 +7,7 @@ use crate::{
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response as AxumResponse},
+    Json,
 };
 
 #[derive(Debug, thiserror::Error)]
@@ -30,10 +31,17 @@ pub enum AxumNope {
     InternalError(anyhow::Error),
     #[error("bad request")]
     BadRequest,
+    #[error("unauthorized")]
+    Unauthorized,
+    #[error("the build worker doesn't hold the lease")]
+    LeaseLost,
+    #[error("payload too large")]
+    PayloadTooLarge,
 }
 
-impl IntoResponse for AxumNope {
-    fn into_response(self) -> AxumResponse {
+impl AxumNope {
+    /// The title, message and status code shown for this error.
+    fn error_page(&self) -> AxumErrorPage {
         match self {
             AxumNope::ResourceNotFound => {
                 // user tried to navigate to a resource (doc page/file) that doesn't exist
@@ -42,15 +50,13 @@ impl IntoResponse for AxumNope {
                     message: "no such resource".into(),
                     status: StatusCode::NOT_FOUND,
                 }
-                .into_response()
             }
 
             AxumNope::BuildNotFound => AxumErrorPage {
                 title: "The requested build does not exist",
                 message: "no such build".into(),
                 status: StatusCode::NOT_FOUND,
-            }
-            .into_response(),
+            },
 
             AxumNope::CrateNotFound => {
                 // user tried to navigate to a crate that doesn't exist
@@ -60,15 +66,13 @@ impl IntoResponse for AxumNope {
                     message: "no such crate".into(),
                     status: StatusCode::NOT_FOUND,
                 }
-                .into_response()
             }
 
             AxumNope::OwnerNotFound => AxumErrorPage {
                 title: "The requested owner does not exist",
                 message: "no such owner".into(),
                 status: StatusCode::NOT_FOUND,
-            }
-            .into_response(),
+            },
 
             AxumNope::VersionNotFound => {
                 // user tried to navigate to a crate with a version that does not exist
@@ -78,23 +82,35 @@ impl IntoResponse for AxumNope {
                     message: "no such version for this crate".into(),
                     status: StatusCode::NOT_FOUND,
                 }
-                .into_response()
             }
             AxumNope::NoResults => {
                 // user did a search with no search terms
-                Search {
-                    title: "No results given for empty search query".to_owned(),
+                AxumErrorPage {
+                    title: "No results given for empty search query",
+                    message: "no results".into(),
                     status: StatusCode::NOT_FOUND,
-                    ..Default::default()
                 }
-                .into_response()
             }
             AxumNope::BadRequest => AxumErrorPage {
                 title: "Bad request",
                 message: "Bad request".into(),
                 status: StatusCode::BAD_REQUEST,
-            }
-            .into_response(),
+            },
+            AxumNope::Unauthorized => AxumErrorPage {
+                title: "Unauthorized",
+                message: "Unauthorized".into(),
+                status: StatusCode::UNAUTHORIZED,
+            },
+            AxumNope::LeaseLost => AxumErrorPage {
+                title: "The lease was lost",
+                message: "the lease expired or is held by another build worker".into(),
+                status: StatusCode::CONFLICT,
+            },
+            AxumNope::PayloadTooLarge => AxumErrorPage {
+                title: "Payload too large",
+                message: "the upload is larger than allowed".into(),
+                status: StatusCode::PAYLOAD_TOO_LARGE,
+            },
             AxumNope::InternalServerError => {
                 // something went wrong, details should have been logged
                 AxumErrorPage {
@@ -102,20 +118,69 @@ impl IntoResponse for AxumNope {
                     message: "internal server error".into(),
                     status: StatusCode::INTERNAL_SERVER_ERROR,
                 }
+            }
+            AxumNope::InternalError(source) => AxumErrorPage {
+                title: "Internal Server Error",
+                message: Cow::Owned(source.to_string()),
+                status: StatusCode::INTERNAL_SERVER_ERROR,
+            },
+        }
+    }
+}
+
+impl IntoResponse for AxumNope {
+    fn into_response(self) -> AxumResponse {
+        match self {
+            AxumNope::NoResults => {
+                // user did a search with no search terms
+                let page = self.error_page();
+                Search {
+                    title: page.title.to_owned(),
+                    status: page.status,
+                    ..Default::default()
+                }
                 .into_response()
             }
-            AxumNope::InternalError(source) => {
-                let web_error = crate::web::AxumErrorPage {
-                    title: "Internal Server Error",
-                    message: Cow::Owned(source.to_string()),
-                    status: StatusCode::INTERNAL_SERVER_ERROR,
-                };
+            AxumNope::InternalError(ref source) => {
+                crate::utils::report_error(source);
+                self.error_page().into_response()
+            }
+            _ => self.error_page().into_response(),
+        }
+    }
+}
 
-                crate::utils::report_error(&source);
+/// An [`AxumNope`] returned as JSON object instead of an HTML page, used by the API.
+#[derive(Debug)]
+pub(crate) struct JsonAxumNope(pub(crate) AxumNope);
 
-                web_error.into_response()
-            }
+impl IntoResponse for JsonAxumNope {
+    fn into_response(self) -> AxumResponse {
+        if let AxumNope::InternalError(source) = &self.0 {
+            crate::utils::report_error(source);
         }
+
+        let page = self.0.error_page();
+        (
+            page.status,
+            Json(serde_json::json!({
+                "title": page.title,
+                "message": page.message,
+            })),
+        )
+            .into_response()
+    }
+}
+
+impl From<AxumNope> for JsonAxumNope {
+    fn from(err: AxumNope) -> Self {
+        JsonAxumNope(err)
+    }
+}
+
+impl From<anyhow::Error> for JsonAxumNope {
+    fn from(err: anyhow::Error) -> Self {
+        JsonAxumNope(err.into())
     }
 }
 
@@ -132,6 +197,7 @@ impl From<anyhow::Error> for AxumNope {
 }
 
 pub(crate) type AxumResult<T> = Result<T, AxumNope>;
+pub(crate) type JsonAxumResult<T> = Result<T, JsonAxumNope>;
 
 #[cfg(test)]
 mod tests {
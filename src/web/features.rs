@@ -6,6 +6,7 @@ use crate::{
     impl_axum_webpage,
     utils::spawn_blocking,
     web::{cache::CachePolicy, error::AxumResult, match_version_axum, MetaData},
+    Config,
 };
 use anyhow::anyhow;
 use axum::{
@@ -13,7 +14,10 @@ use axum::{
     response::IntoResponse,
 };
 use serde::Serialize;
-use std::collections::{HashMap, VecDeque};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
 
 const DEFAULT_NAME: &str = "default";
 
@@ -39,6 +43,7 @@ impl_axum_webpage! {
 pub(crate) async fn build_features_handler(
     Path((name, req_version)): Path<(String, String)>,
     Extension(pool): Extension<Pool>,
+    Extension(config): Extension<Arc<Config>>,
 ) -> AxumResult<impl IntoResponse> {
     let (version, version_or_latest, is_latest_url) =
         match match_version_axum(&pool, &name, Some(&req_version))
@@ -89,7 +94,10 @@ pub(crate) async fn build_features_handler(
         features,
         default_len,
         is_latest_url,
-        canonical_url: CanonicalUrl::from_path(format!("/crate/{}/latest/features", &name)),
+        canonical_url: CanonicalUrl::from_path(
+            &config.public_url,
+            format!("/crate/{}/latest/features", &name),
+        ),
         use_direct_platform_links: true,
     }
     .into_response())
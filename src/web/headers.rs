@@ -7,26 +7,28 @@ use axum::{
 use serde::Serialize;
 
 /// simplified typed header for a `Link rel=canonical` header in the response.
-/// Only takes the path to be used, url-encodes it and attaches domain & schema to it.
+/// Only takes the path to be used, url-encodes it and attaches it to the public URL
+/// of this instance (`Config::public_url`).
 #[derive(Debug, Clone)]
-pub struct CanonicalUrl(PathAndQuery);
+pub struct CanonicalUrl {
+    public_url: String,
+    path: PathAndQuery,
+}
 
 impl CanonicalUrl {
-    pub fn from_path<P: AsRef<str>>(path: P) -> Self {
-        Self(
-            encode_url_path(path.as_ref())
+    pub fn from_path<P: AsRef<str>>(public_url: &str, path: P) -> Self {
+        Self {
+            public_url: public_url.trim_end_matches('/').to_owned(),
+            path: encode_url_path(path.as_ref())
                 .try_into()
                 .expect("invalid URI path characters even after encoding them"),
-        )
+        }
     }
 
     fn build_full_uri(&self) -> Uri {
-        Uri::builder()
-            .scheme("https")
-            .authority("docs.rs")
-            .path_and_query(self.0.clone())
-            .build()
-            .expect("this unwrap can't fail because PathAndQuery is valid")
+        format!("{}{}", self.public_url, self.path)
+            .parse()
+            .expect("this unwrap can't fail because the public URL and PathAndQuery are valid")
     }
 }
 
@@ -72,7 +74,7 @@ mod tests {
 
     #[test]
     fn test_serialize_canonical() {
-        let url = CanonicalUrl::from_path("/some/path/");
+        let url = CanonicalUrl::from_path("https://docs.rs", "/some/path/");
 
         assert_eq!(
             serde_json::to_string(&url).unwrap(),
@@ -83,7 +85,7 @@ mod tests {
     #[test]
     fn test_encode_canonical() {
         let mut map = HeaderMap::new();
-        map.typed_insert(CanonicalUrl::from_path("/some/path/"));
+        map.typed_insert(CanonicalUrl::from_path("https://docs.rs", "/some/path/"));
         assert_eq!(
             map["link"],
             "<https://docs.rs/some/path/>; rel=\"canonical\""
@@ -93,10 +95,20 @@ mod tests {
     #[test]
     fn test_encode_canonical_with_encoding() {
         let mut map = HeaderMap::new();
-        map.typed_insert(CanonicalUrl::from_path("/some/äöü/"));
+        map.typed_insert(CanonicalUrl::from_path("https://docs.rs", "/some/äöü/"));
         assert_eq!(
             map["link"],
             "<https://docs.rs/some/%C3%A4%C3%B6%C3%BC/>; rel=\"canonical\""
         );
     }
+
+    #[test]
+    fn test_canonical_with_custom_public_url() {
+        let url = CanonicalUrl::from_path("https://docs.example.com/mirror/", "/some/path/");
+
+        assert_eq!(
+            serde_json::to_string(&url).unwrap(),
+            "\"https://docs.example.com/mirror/some/path/\""
+        );
+    }
 }
@@ -78,11 +78,56 @@ pub fn with_lang(lang: Option<&str>, code: &str) -> String {
     }
 }
 
+/// Highlights `code` as a whole and returns the HTML of every line on its own, with the spans
+/// that continue from the previous line opened again.
+pub fn lines_with_lang(lang: Option<&str>, code: &str) -> Vec<String> {
+    let line_count = code.lines().count();
+    let highlighted = with_lang(lang, code);
+
+    let mut lines = Vec::with_capacity(line_count);
+    // the opening tags of the spans that are open at the current position
+    let mut open: Vec<&str> = Vec::new();
+    let mut line = String::new();
+    let mut rest = highlighted.as_str();
+    while lines.len() < line_count {
+        let Some(pos) = rest.find(['<', '\n']) else {
+            line.push_str(rest);
+            break;
+        };
+        line.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        if rest.starts_with('\n') {
+            if line.ends_with('\r') {
+                line.pop();
+            }
+            for _ in &open {
+                line.push_str("</span>");
+            }
+            lines.push(std::mem::replace(&mut line, open.concat()));
+            rest = &rest[1..];
+        } else {
+            let end = rest.find('>').map_or(rest.len(), |end| end + 1);
+            let tag = &rest[..end];
+            if tag.starts_with("</") {
+                open.pop();
+            } else {
+                open.push(tag);
+            }
+            line.push_str(tag);
+            rest = &rest[end..];
+        }
+    }
+    if lines.len() < line_count {
+        lines.push(line);
+    }
+    lines
+}
+
 #[cfg(test)]
 mod tests {
     use super::{
-        select_syntax, try_with_lang, with_lang, LimitsExceeded, PER_LINE_BYTE_LENGTH_LIMIT,
-        TOTAL_CODE_BYTE_LENGTH_LIMIT,
+        lines_with_lang, select_syntax, try_with_lang, with_lang, LimitsExceeded,
+        PER_LINE_BYTE_LENGTH_LIMIT, TOTAL_CODE_BYTE_LENGTH_LIMIT,
     };
 
     #[test]
@@ -117,4 +162,26 @@ mod tests {
         let highlighted = with_lang(Some("toml"), &text);
         assert!(highlighted.starts_with("&lt;p&gt;\n"));
     }
+
+    #[test]
+    fn lines_are_balanced() {
+        let code = "/* a\r\nb */\nfn c() {}\n";
+        let lines = lines_with_lang(Some("rs"), code);
+        assert_eq!(lines.len(), 3);
+        for line in &lines {
+            assert_eq!(
+                line.matches("<span").count(),
+                line.matches("</span>").count(),
+                "{line}"
+            );
+            assert!(!line.contains('\n') && !line.contains('\r'), "{line}");
+        }
+        // the comment continues on the second line
+        assert!(lines[1].starts_with("<span class=\"syntax-source syntax-rust\">"));
+        assert!(lines[1].contains("syntax-comment"));
+        assert!(!lines[2].contains("syntax-comment"));
+
+        assert_eq!(lines_with_lang(Some("rs"), "a\n\nb").len(), 3);
+        assert!(lines_with_lang(Some("rs"), "").is_empty());
+    }
 }
@@ -0,0 +1,168 @@
+use super::cache::CachePolicy;
+use crate::{
+    db::Pool,
+    utils::{daemon::trigger_index_sync, spawn_blocking},
+    web::error::{AxumNope, AxumResult},
+    Config,
+};
+use axum::{
+    body::Bytes,
+    extract::{Extension, Query},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::sync::Arc;
+
+/// Header containing the signature of the payload, in the format GitHub uses for webhooks.
+const SIGNATURE_HEADER: &str = "x-hub-signature-256";
+
+/// Check the `sha256=<hex>` signature of a webhook payload against the configured secret.
+fn verify_signature(secret: &str, signature: &str, payload: &[u8]) -> bool {
+    let Some(signature) = signature
+        .strip_prefix("sha256=")
+        .and_then(|signature| hex::decode(signature).ok())
+    else {
+        return false;
+    };
+
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(payload);
+    mac.verify_slice(&signature).is_ok()
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct IndexWebhookParams {
+    /// The alternative registry whose index changed, the main index when missing.
+    registry: Option<String>,
+}
+
+/// Called by the registry index whenever it changes.
+///
+/// The payload is ignored, we only wake up the registry watcher so it checks the index for new
+/// crates right away. When a secret is configured, the signature of the payload has to match.
+pub(crate) async fn index_webhook_handler(
+    Query(params): Query<IndexWebhookParams>,
+    Extension(pool): Extension<Pool>,
+    Extension(config): Extension<Arc<Config>>,
+    headers: HeaderMap,
+    payload: Bytes,
+) -> AxumResult<impl IntoResponse> {
+    if let Some(secret) = config.index_webhook_secret.as_deref() {
+        let signature = headers
+            .get(SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or(AxumNope::Unauthorized)?;
+
+        if !verify_signature(secret, signature, &payload) {
+            return Err(AxumNope::Unauthorized);
+        }
+    }
+
+    if let Some(registry) = params.registry.as_deref() {
+        if config.alternative_registry(registry).is_none() {
+            return Err(AxumNope::ResourceNotFound);
+        }
+    }
+
+    spawn_blocking(move || trigger_index_sync(&mut *pool.get()?, params.registry.as_deref()))
+        .await?;
+
+    Ok((Extension(CachePolicy::NoCaching), StatusCode::ACCEPTED))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::wrapper;
+    use reqwest::StatusCode;
+
+    fn sign(secret: &str, payload: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(payload);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn webhook_without_secret() {
+        wrapper(|env| {
+            let response = env.frontend().post("/_/index-webhook").body("{}").send()?;
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn webhook_with_secret() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.index_webhook_secret = Some("secret".into());
+            });
+            let web = env.frontend();
+
+            let response = web.post("/_/index-webhook").body("{}").send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            let response = web
+                .post("/_/index-webhook")
+                .header(SIGNATURE_HEADER, sign("other secret", b"{}"))
+                .body("{}")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            let response = web
+                .post("/_/index-webhook")
+                .header(SIGNATURE_HEADER, sign("secret", b"{}"))
+                .body("{}")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn webhook_for_alternative_registry() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.alternative_registries = vec![crate::config::AlternativeRegistry {
+                    name: "acme".into(),
+                    index_url: "https://example.com/index.git".into(),
+                    index_path: "/nonexistent".into(),
+                }];
+            });
+            let response = env
+                .frontend()
+                .post("/_/index-webhook?registry=unknown")
+                .body("{}")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            let response = env
+                .frontend()
+                .post("/_/index-webhook?registry=acme")
+                .body("{}")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn signature_format() {
+        let signature = sign("secret", b"payload");
+        assert!(verify_signature("secret", &signature, b"payload"));
+        assert!(!verify_signature("secret", &signature, b"other payload"));
+        assert!(!verify_signature(
+            "secret",
+            signature.trim_start_matches("sha256="),
+            b"payload"
+        ));
+        assert!(!verify_signature("secret", "sha256=not-hex", b"payload"));
+    }
+}
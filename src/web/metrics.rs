@@ -299,6 +299,31 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_rebuild_campaign_metrics() {
+        wrapper(|env| {
+            env.fake_release().name("foo").create()?;
+            let selector = crate::utils::CampaignSelector::CratePattern {
+                pattern: "foo".into(),
+            };
+            crate::utils::create_campaign(&mut env.db().conn(), "everything", &selector, 20, 10)?;
+
+            let web = env.frontend();
+            let body = web.get("/about/metrics/service").send()?.text()?;
+            assert!(
+                body.contains(r#"docsrs_rebuild_campaign_remaining{campaign="everything"} 1"#),
+                "{}",
+                body
+            );
+
+            // removed campaigns aren't exported anymore
+            crate::utils::remove_campaign(&mut env.db().conn(), "everything")?;
+            let body = web.get("/about/metrics/service").send()?.text()?;
+            assert!(!body.contains("everything"), "{}", body);
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_instance_metrics_page_success() {
         wrapper(|env| {
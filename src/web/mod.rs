@@ -9,16 +9,23 @@ use axum_extra::middleware::option_layer;
 use serde_json::Value;
 use tracing::{info, instrument};
 
+mod admin;
+mod api;
+mod api_diff;
+mod badge;
 mod build_details;
 mod builds;
 pub(crate) mod cache;
+mod coverage;
 pub(crate) mod crate_details;
 mod csp;
+mod dependents;
 pub(crate) mod error;
 mod features;
 mod file;
 mod headers;
 mod highlight;
+mod index_webhook;
 mod markdown;
 pub(crate) mod metrics;
 mod releases;
@@ -26,13 +33,15 @@ mod routes;
 mod rustdoc;
 mod sitemap;
 mod source;
+mod source_diff;
 mod statics;
 mod status;
+mod worker;
 
 use crate::{db::Pool, impl_axum_webpage, Context};
 use anyhow::Error;
 use axum::{
-    extract::Extension,
+    extract::{Extension, State},
     http::Request as AxumRequest,
     http::StatusCode,
     middleware,
@@ -52,9 +61,10 @@ use std::{
     borrow::{Borrow, Cow},
     net::SocketAddr,
     sync::Arc,
+    time::Duration,
 };
 use tower::ServiceBuilder;
-use tower_http::{catch_panic::CatchPanicLayer, timeout::TimeoutLayer, trace::TraceLayer};
+use tower_http::{catch_panic::CatchPanicLayer, trace::TraceLayer};
 use url::form_urlencoded;
 
 // from https://github.com/servo/rust-url/blob/master/url/src/parser.rs
@@ -267,6 +277,24 @@ async fn log_timeouts_to_sentry<B>(req: AxumRequest<B>, next: Next<B>) -> AxumRe
     response
 }
 
+/// Answers requests taking longer than `timeout` with `408 Request Timeout`.
+///
+/// The endpoints of build workers are exempt, their uploads take as long as the crates are large.
+async fn request_timeout<B>(
+    State(timeout): State<Duration>,
+    req: AxumRequest<B>,
+    next: Next<B>,
+) -> AxumResponse {
+    if req.uri().path().starts_with("/_/worker/") {
+        return next.run(req).await;
+    }
+
+    match tokio::time::timeout(timeout, next.run(req)).await {
+        Ok(response) => response,
+        Err(_) => StatusCode::REQUEST_TIMEOUT.into_response(),
+    }
+}
+
 fn apply_middleware(
     router: AxumRouter,
     context: &dyn Context,
@@ -285,7 +313,9 @@ fn apply_middleware(
                     .report_request_timeouts
                     .then_some(middleware::from_fn(log_timeouts_to_sentry)),
             ))
-            .layer(option_layer(config.request_timeout.map(TimeoutLayer::new)))
+            .layer(option_layer(config.request_timeout.map(|timeout| {
+                middleware::from_fn_with_state(timeout, request_timeout)
+            })))
             .layer(Extension(context.pool()?))
             .layer(Extension(context.build_queue()?))
             .layer(Extension(context.service_metrics()?))
@@ -345,7 +375,7 @@ pub fn start_background_metrics_webserver(
 pub fn start_web_server(addr: Option<SocketAddr>, context: &dyn Context) -> Result<(), Error> {
     let template_data = Arc::new(TemplateData::new(
         &mut *context.pool()?.get()?,
-        context.config()?.render_threads,
+        &*context.config()?,
     )?);
 
     let axum_addr = addr.unwrap_or(DEFAULT_BIND);
@@ -402,6 +432,9 @@ async fn shutdown_signal() {
 /// Converts Timespec to nice readable relative time string
 fn duration_to_str(init: DateTime<Utc>) -> String {
     let now = Utc::now();
+    if init > now {
+        return future_duration_to_str(init.signed_duration_since(now));
+    }
     let delta = now.signed_duration_since(init);
 
     let delta = (
@@ -427,6 +460,31 @@ fn duration_to_str(init: DateTime<Utc>) -> String {
     }
 }
 
+/// Formats the time until an estimate in the future, like the time passed in
+/// [`duration_to_str`].
+fn future_duration_to_str(delta: chrono::Duration) -> String {
+    let delta = (
+        delta.num_days(),
+        delta.num_hours(),
+        delta.num_minutes(),
+        delta.num_seconds(),
+    );
+
+    match delta {
+        (days, ..) if days > 1 => format!("in {days} days"),
+        (1, ..) => "in one day".to_string(),
+
+        (_, hours, ..) if hours > 1 => format!("in {hours} hours"),
+        (_, 1, ..) => "in an hour".to_string(),
+
+        (_, _, minutes, _) if minutes > 1 => format!("in {minutes} minutes"),
+        (_, _, 1, _) => "in one minute".to_string(),
+
+        (_, _, _, seconds) if seconds > 0 => format!("in {seconds} seconds"),
+        _ => "just now".to_string(),
+    }
+}
+
 #[instrument]
 fn axum_redirect<U>(uri: U) -> Result<impl IntoResponse, Error>
 where
@@ -631,6 +689,56 @@ mod test {
         });
     }
 
+    #[test]
+    fn branding() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.public_url = "https://docs.example.com".into();
+                config.site_name = "Example Docs".into();
+                config.site_logo = Some("https://example.com/logo.svg".into());
+                config.footer_links =
+                    vec![("Imprint".into(), "https://example.com/imprint".into())];
+            });
+            env.fake_release().name("foo").version("0.1.0").create()?;
+
+            let page =
+                kuchikiki::parse_html().one(env.frontend().get("/crate/foo/0.1.0").send()?.text()?);
+            assert_eq!(
+                page.select_first("title").unwrap().text_contents().trim(),
+                "foo 0.1.0 - Example Docs"
+            );
+            assert_eq!(
+                page.select_first(".docsrs-logo img")
+                    .unwrap()
+                    .attributes
+                    .borrow()
+                    .get("src"),
+                Some("https://example.com/logo.svg")
+            );
+            assert_eq!(
+                page.select_first("link[rel=canonical]")
+                    .unwrap()
+                    .attributes
+                    .borrow()
+                    .get("href"),
+                Some("https://docs.example.com/crate/foo/latest")
+            );
+            let footer = page.select_first(".site-footer a").unwrap();
+            assert_eq!(footer.text_contents(), "Imprint");
+            assert_eq!(
+                footer.attributes.borrow().get("href"),
+                Some("https://example.com/imprint")
+            );
+
+            let feed = env.frontend().get("/releases/feed").send()?.text()?;
+            assert!(feed
+                .contains(r#"<link href="https://docs.example.com/releases/feed" rel="self" />"#));
+            assert!(feed.contains(r#"<link href="https://docs.example.com/foo/0.1.0/foo/" />"#));
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn test_doc_coverage_for_crate_pages() {
         wrapper(|env| {
@@ -643,6 +751,8 @@ mod test {
                     documented_items: 6,
                     total_items_needing_examples: 2,
                     items_with_examples: 1,
+                    files: Vec::new(),
+                    undocumented_items: Vec::new(),
                 })
                 .create()?;
             let web = env.frontend();
@@ -1,6 +1,8 @@
 use crate::{
+    docbuilder::FailureCategory,
     error::Result,
     utils::{get_config, ConfigName},
+    Config,
 };
 use anyhow::Context;
 use chrono::{DateTime, Utc};
@@ -31,13 +33,13 @@ pub(crate) struct TemplateData {
 }
 
 impl TemplateData {
-    pub(crate) fn new(conn: &mut Client, num_threads: usize) -> Result<Self> {
+    pub(crate) fn new(conn: &mut Client, config: &Config) -> Result<Self> {
         trace!("Loading templates");
 
         let data = Self {
-            templates: load_templates(conn)?,
+            templates: load_templates(conn, config)?,
             rendering_threadpool: rayon::ThreadPoolBuilder::new()
-                .num_threads(num_threads)
+                .num_threads(config.render_threads)
                 .thread_name(move |idx| format!("docsrs-render {idx}"))
                 .build()?,
         };
@@ -85,7 +87,7 @@ fn load_rustc_resource_suffix(conn: &mut Client) -> Result<String> {
     anyhow::bail!("failed to parse the rustc version");
 }
 
-fn load_templates(conn: &mut Client) -> Result<Tera> {
+fn load_templates(conn: &mut Client, config: &Config) -> Result<Tera> {
     // This uses a custom function to find the templates in the filesystem instead of Tera's
     // builtin way (passing a glob expression to Tera::new), speeding up the startup of the
     // application and running the tests.
@@ -129,6 +131,35 @@ fn load_templates(conn: &mut Client) -> Result<Tera> {
         })),
     );
 
+    // These functions return the branding of this instance, see `Config::public_url`
+    // and `Config::site_name`.
+    ReturnValue::add_function_to(
+        &mut tera,
+        "public_url",
+        Value::String(config.public_url.clone()),
+    );
+    ReturnValue::add_function_to(
+        &mut tera,
+        "site_name",
+        Value::String(config.site_name.clone()),
+    );
+    ReturnValue::add_function_to(
+        &mut tera,
+        "site_logo",
+        serde_json::to_value(&config.site_logo)?,
+    );
+    ReturnValue::add_function_to(
+        &mut tera,
+        "footer_links",
+        Value::Array(
+            config
+                .footer_links
+                .iter()
+                .map(|(title, url)| serde_json::json!({ "title": title, "url": url }))
+                .collect(),
+        ),
+    );
+
     // Custom filters
     tera.register_filter("timeformat", timeformat);
     tera.register_filter("dbg", dbg);
@@ -137,6 +168,7 @@ fn load_templates(conn: &mut Client) -> Result<Tera> {
     tera.register_filter("far", IconType::Regular);
     tera.register_filter("fab", IconType::Brand);
     tera.register_filter("highlight", Highlight);
+    tera.register_filter("failure_category", failure_category);
 
     Ok(tera)
 }
@@ -229,6 +261,13 @@ fn dbg(value: &Value, _args: &HashMap<String, Value>) -> TeraResult<Value> {
     Ok(value.clone())
 }
 
+/// Turns the name of a [`FailureCategory`] into its description
+fn failure_category(value: &Value, _args: &HashMap<String, Value>) -> TeraResult<Value> {
+    let category: FailureCategory = serde_json::from_value(value.clone())
+        .map_err(|err| tera::Error::msg(format!("invalid failure category: {err}")))?;
+    Ok(Value::String(category.description().to_owned()))
+}
+
 /// Dedent a string by removing all leading whitespace
 #[allow(clippy::unnecessary_wraps)]
 fn dedent(value: &Value, args: &HashMap<String, Value>) -> TeraResult<Value> {
@@ -379,7 +418,7 @@ mod tests {
         crate::test::wrapper(|env| {
             let db = env.db();
 
-            let tera = load_templates(&mut db.conn()).unwrap();
+            let tera = load_templates(&mut db.conn(), &env.config()).unwrap();
             tera.check_macro_files().unwrap();
 
             Ok(())
@@ -4,10 +4,13 @@ use crate::{
     build_queue::QueuedCrate,
     cdn,
     db::Pool,
+    docbuilder::FailureCategory,
     impl_axum_webpage,
-    utils::{report_error, retry_async, spawn_blocking},
+    utils::{campaign_progress, report_error, retry_async, spawn_blocking, CampaignProgress},
     web::{
-        axum_parse_uri_with_params, axum_redirect, encode_url_path,
+        axum_parse_uri_with_params, axum_redirect,
+        cache::CachePolicy,
+        encode_url_path,
         error::{AxumNope, AxumResult},
         match_version_axum,
     },
@@ -16,6 +19,7 @@ use crate::{
 use anyhow::{anyhow, Context as _, Result};
 use axum::{
     extract::{Extension, Path, Query},
+    http::header::CONTENT_TYPE,
     response::{IntoResponse, Response as AxumResponse},
 };
 use base64::{engine::general_purpose::STANDARD as b64, Engine};
@@ -25,6 +29,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::str;
 use std::sync::Arc;
+use strum::IntoEnumIterator;
 use tracing::{debug, warn};
 use url::form_urlencoded;
 
@@ -34,6 +39,10 @@ const RELEASES_IN_HOME: i64 = 15;
 const RELEASES_IN_RELEASES: i64 = 30;
 /// Releases in recent releases feed
 const RELEASES_IN_FEED: i64 = 150;
+/// Maximum page size for search results
+const MAX_SEARCH_RESULTS_PER_PAGE: i64 = 100;
+/// Maximum page of search results, so the offsets of the pages can't overflow
+const MAX_SEARCH_PAGE: i64 = 1_000;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Release {
@@ -44,6 +53,8 @@ pub struct Release {
     rustdoc_status: bool,
     pub(crate) build_time: DateTime<Utc>,
     stars: i32,
+    /// Why the build failed, if it did.
+    failure_category: Option<FailureCategory>,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -60,12 +71,16 @@ impl Default for Order {
     }
 }
 
+/// Get a page of releases in `order`.
+///
+/// `failure_category` limits the failed releases to the ones that failed for that reason.
 pub(crate) fn get_releases(
     conn: &mut Client,
     page: i64,
     limit: i64,
     order: Order,
     latest_only: bool,
+    failure_category: Option<FailureCategory>,
 ) -> Result<Vec<Release>> {
     let offset = (page - 1) * limit;
 
@@ -84,13 +99,15 @@ pub(crate) fn get_releases(
             releases.target_name,
             releases.rustdoc_status,
             builds.build_time,
-            repositories.stars
+            repositories.stars,
+            builds.failure_category
         FROM crates
         {1}
         INNER JOIN builds ON releases.id = builds.rid
         LEFT JOIN repositories ON releases.repository_id = repositories.id
         WHERE
             ((NOT $3) OR (releases.build_status = FALSE AND releases.is_library = TRUE))
+            AND ($4::TEXT IS NULL OR builds.failure_category = $4)
             AND {0} IS NOT NULL
 
         ORDER BY {0} DESC
@@ -104,7 +121,15 @@ pub(crate) fn get_releases(
     );
 
     Ok(conn
-        .query(query.as_str(), &[&limit, &offset, &filter_failed])?
+        .query(
+            query.as_str(),
+            &[
+                &limit,
+                &offset,
+                &filter_failed,
+                &failure_category.map(|category| category.to_string()),
+            ],
+        )?
         .into_iter()
         .map(|row| Release {
             name: row.get(0),
@@ -114,6 +139,9 @@ pub(crate) fn get_releases(
             rustdoc_status: row.get(4),
             build_time: row.get(5),
             stars: row.get::<_, Option<i32>>(6).unwrap_or(0),
+            failure_category: row
+                .get::<_, Option<&str>>(7)
+                .and_then(|category| category.parse().ok()),
         })
         .collect())
 }
@@ -127,11 +155,181 @@ struct SearchResult {
 
 /// Get the search results for a crate search query
 ///
-/// This delegates to the crates.io search API.
+/// The query is run against our own full-text search index. When that doesn't
+/// find anything or fails, and `search_crates_io_fallback` is enabled, the
+/// query is forwarded to the crates.io search API instead.
 async fn get_search_results(
     pool: Pool,
     config: &Config,
     query_params: &str,
+) -> Result<SearchResult, anyhow::Error> {
+    let mut query = None;
+    let mut page = 1;
+    let mut per_page = RELEASES_IN_RELEASES;
+    for (key, value) in form_urlencoded::parse(query_params.trim_start_matches('?').as_bytes()) {
+        match &*key {
+            "q" => query = Some(value.into_owned()),
+            "page" => page = value.parse::<i64>().unwrap_or(1).clamp(1, MAX_SEARCH_PAGE),
+            "per_page" => {
+                per_page = value
+                    .parse::<i64>()
+                    .unwrap_or(RELEASES_IN_RELEASES)
+                    .clamp(1, MAX_SEARCH_RESULTS_PER_PAGE)
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(query) = query.filter(|q| !q.trim().is_empty()) {
+        let result = spawn_blocking({
+            let pool = pool.clone();
+            move || {
+                let mut conn = pool.get()?;
+                search_database(&mut conn, &query, page, per_page)
+            }
+        })
+        .await;
+
+        match result {
+            Ok(result) if !result.results.is_empty() || !config.search_crates_io_fallback => {
+                return Ok(result)
+            }
+            Ok(_) => {}
+            Err(err) if config.search_crates_io_fallback => {
+                report_error(&err.context("full-text search failed, falling back to crates.io"));
+            }
+            Err(err) => return Err(err),
+        }
+    } else if !config.search_crates_io_fallback {
+        return Ok(SearchResult {
+            results: Vec::new(),
+            executed_query: None,
+            prev_page: None,
+            next_page: None,
+        });
+    }
+
+    get_crates_io_search_results(pool, config, query_params).await
+}
+
+/// Search for crates in our own database.
+///
+/// Matches the query against the full-text search vector of the latest release of
+/// each crate (crate name, description, keywords and readme). The text rank is
+/// weighted with the download count and the repository stars of the crate,
+/// while exact name matches always come first.
+fn search_database(
+    conn: &mut Client,
+    query: &str,
+    page: i64,
+    per_page: i64,
+) -> Result<SearchResult> {
+    let offset = (page - 1) * per_page;
+
+    let mut results: Vec<Release> = conn
+        .query(
+            "WITH
+                search AS (
+                    SELECT websearch_to_tsquery('english', $1) AS query
+                ),
+                -- every branch is served by its own index
+                matches AS (
+                    SELECT crates.id AS crate_id, TRUE AS exact, 0::REAL AS rank
+                    FROM crates
+                    WHERE normalize_crate_name(crates.name) = normalize_crate_name($1)
+                    UNION ALL
+                    SELECT
+                        crates.id,
+                        FALSE,
+                        ts_rank(setweight(to_tsvector('english', crates.name), 'A'), search.query)
+                    FROM search, crates
+                    WHERE to_tsvector('english', crates.name) @@ search.query
+                    UNION ALL
+                    SELECT crates.id, FALSE, ts_rank(releases.search_vector, search.query)
+                    FROM search, releases
+                    INNER JOIN crates ON crates.latest_version_id = releases.id
+                    WHERE releases.search_vector @@ search.query
+                ),
+                ranked AS (
+                    SELECT crate_id, BOOL_OR(exact) AS exact, SUM(rank) AS rank
+                    FROM matches
+                    GROUP BY crate_id
+                )
+            SELECT
+                crates.name,
+                releases.version,
+                releases.description,
+                releases.target_name,
+                releases.rustdoc_status,
+                builds.build_time,
+                repositories.stars
+            FROM ranked
+            INNER JOIN crates ON crates.id = ranked.crate_id
+            INNER JOIN releases ON crates.latest_version_id = releases.id
+            INNER JOIN LATERAL (
+                SELECT build_time
+                FROM builds
+                WHERE builds.rid = releases.id
+                ORDER BY build_time DESC NULLS LAST
+                LIMIT 1
+            ) AS builds ON TRUE
+            LEFT JOIN repositories ON releases.repository_id = repositories.id
+            ORDER BY
+                ranked.exact DESC,
+                ranked.rank * (
+                    1 +
+                    LOG(1 + COALESCE(crates.downloads_total, 0)::FLOAT8) +
+                    LOG(1 + COALESCE(repositories.stars, 0)::FLOAT8)
+                ) DESC,
+                crates.name
+            LIMIT $2 OFFSET $3",
+            // fetch one more row than needed to know if there is a next page
+            &[&query.trim(), &(per_page + 1), &offset],
+        )?
+        .into_iter()
+        .map(|row| Release {
+            name: row.get("name"),
+            version: row.get("version"),
+            description: row.get("description"),
+            target_name: row.get("target_name"),
+            rustdoc_status: row.get("rustdoc_status"),
+            build_time: row.get("build_time"),
+            stars: row.get::<_, Option<i32>>("stars").unwrap_or(0),
+            failure_category: None,
+        })
+        .collect();
+
+    let page_params = |page: i64| {
+        let params = form_urlencoded::Serializer::new(String::new())
+            .append_pair("q", query)
+            .append_pair("per_page", &per_page.to_string())
+            .append_pair("page", &page.to_string())
+            .finish();
+        format!("?{params}")
+    };
+
+    let next_page = if results.len() as i64 > per_page {
+        results.truncate(per_page as usize);
+        Some(page_params(page + 1))
+    } else {
+        None
+    };
+
+    Ok(SearchResult {
+        results,
+        executed_query: Some(query.to_owned()),
+        prev_page: (page > 1).then(|| page_params(page - 1)),
+        next_page,
+    })
+}
+
+/// Get the search results for a crate search query from crates.io
+///
+/// This delegates to the crates.io search API.
+async fn get_crates_io_search_results(
+    pool: Pool,
+    config: &Config,
+    query_params: &str,
 ) -> Result<SearchResult, anyhow::Error> {
     #[derive(Deserialize)]
     struct CratesIoSearchResult {
@@ -246,6 +444,7 @@ async fn get_search_results(
                             target_name: row.get("target_name"),
                             rustdoc_status: row.get("rustdoc_status"),
                             stars: stars.unwrap_or(0),
+                            failure_category: None,
                         },
                     )
                 })
@@ -281,7 +480,14 @@ impl_axum_webpage! {
 pub(crate) async fn home_page(Extension(pool): Extension<Pool>) -> AxumResult<impl IntoResponse> {
     let recent_releases = spawn_blocking(move || {
         let mut conn = pool.get()?;
-        get_releases(&mut conn, 1, RELEASES_IN_HOME, Order::ReleaseTime, true)
+        get_releases(
+            &mut conn,
+            1,
+            RELEASES_IN_HOME,
+            Order::ReleaseTime,
+            true,
+            None,
+        )
     })
     .await?;
 
@@ -303,7 +509,14 @@ pub(crate) async fn releases_feed_handler(
 ) -> AxumResult<impl IntoResponse> {
     let recent_releases = spawn_blocking(move || {
         let mut conn = pool.get()?;
-        get_releases(&mut conn, 1, RELEASES_IN_FEED, Order::ReleaseTime, true)
+        get_releases(
+            &mut conn,
+            1,
+            RELEASES_IN_FEED,
+            Order::ReleaseTime,
+            true,
+            None,
+        )
     })
     .await?;
 
@@ -319,6 +532,11 @@ struct ViewReleases {
     show_previous_page: bool,
     page_number: i64,
     owner: Option<String>,
+    /// The categories the failures can be filtered by, empty for other release types.
+    failure_categories: Vec<FailureCategory>,
+    failure_category: Option<FailureCategory>,
+    /// Query string of the page links.
+    query: String,
 }
 
 impl_axum_webpage! {
@@ -339,8 +557,13 @@ pub(crate) async fn releases_handler(
     pool: Pool,
     page: Option<i64>,
     release_type: ReleaseType,
+    failure_category: Option<FailureCategory>,
 ) -> AxumResult<impl IntoResponse> {
     let page_number = page.unwrap_or(1);
+    let is_failures = matches!(
+        release_type,
+        ReleaseType::RecentFailures | ReleaseType::Failures
+    );
 
     let (description, release_order, latest_only) = match release_type {
         ReleaseType::Recent => ("Recently uploaded crates", Order::ReleaseTime, false),
@@ -369,6 +592,7 @@ pub(crate) async fn releases_handler(
             RELEASES_IN_RELEASES,
             release_order,
             latest_only,
+            failure_category,
         )
     })
     .await?;
@@ -387,35 +611,63 @@ pub(crate) async fn releases_handler(
         show_previous_page,
         page_number,
         owner: None,
+        failure_categories: if is_failures {
+            FailureCategory::iter().collect()
+        } else {
+            Vec::new()
+        },
+        failure_category,
+        query: failure_category
+            .map(|category| format!("?category={category}"))
+            .unwrap_or_default(),
     })
 }
 
+#[derive(Debug, Deserialize)]
+pub(crate) struct FailuresParams {
+    category: Option<FailureCategory>,
+}
+
 pub(crate) async fn recent_releases_handler(
     page: Option<Path<i64>>,
     Extension(pool): Extension<Pool>,
 ) -> AxumResult<impl IntoResponse> {
-    releases_handler(pool, page.map(|p| p.0), ReleaseType::Recent).await
+    releases_handler(pool, page.map(|p| p.0), ReleaseType::Recent, None).await
 }
 
 pub(crate) async fn releases_by_stars_handler(
     page: Option<Path<i64>>,
     Extension(pool): Extension<Pool>,
 ) -> AxumResult<impl IntoResponse> {
-    releases_handler(pool, page.map(|p| p.0), ReleaseType::Stars).await
+    releases_handler(pool, page.map(|p| p.0), ReleaseType::Stars, None).await
 }
 
 pub(crate) async fn releases_recent_failures_handler(
     page: Option<Path<i64>>,
+    Query(params): Query<FailuresParams>,
     Extension(pool): Extension<Pool>,
 ) -> AxumResult<impl IntoResponse> {
-    releases_handler(pool, page.map(|p| p.0), ReleaseType::RecentFailures).await
+    releases_handler(
+        pool,
+        page.map(|p| p.0),
+        ReleaseType::RecentFailures,
+        params.category,
+    )
+    .await
 }
 
 pub(crate) async fn releases_failures_by_stars_handler(
     page: Option<Path<i64>>,
+    Query(params): Query<FailuresParams>,
     Extension(pool): Extension<Pool>,
 ) -> AxumResult<impl IntoResponse> {
-    releases_handler(pool, page.map(|p| p.0), ReleaseType::Failures).await
+    releases_handler(
+        pool,
+        page.map(|p| p.0),
+        ReleaseType::Failures,
+        params.category,
+    )
+    .await
 }
 
 pub(crate) async fn owner_handler(Path(owner): Path<String>) -> AxumResult<impl IntoResponse> {
@@ -515,12 +767,167 @@ impl_axum_webpage! {
     status = |search| search.status,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct ItemSearchResult {
+    krate: String,
+    version: String,
+    name: String,
+    path: String,
+    kind: String,
+    link: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ItemSearch {
+    title: String,
+    items: Vec<ItemSearchResult>,
+    items_query: String,
+    previous_page_link: Option<String>,
+    next_page_link: Option<String>,
+    /// This should always be `ReleaseType::Search`
+    release_type: ReleaseType,
+}
+
+impl_axum_webpage! {
+    ItemSearch = "releases/search_items.html",
+}
+
+/// Search for items defined in the latest release of any crate.
+///
+/// The last segment of `query` is matched case-insensitive against the item name.
+/// Any leading segments have to be found in the path of the item in the same order,
+/// so `serde::Deserializer` will find `serde::de::Deserializer`.
+fn search_items(
+    conn: &mut Client,
+    query: &str,
+    page: i64,
+    per_page: i64,
+) -> Result<(Vec<ItemSearchResult>, bool)> {
+    let segments: Vec<_> = query.split("::").map(str::trim).collect();
+    if segments.iter().any(|segment| {
+        segment.is_empty() || !segment.chars().all(|c| c.is_alphanumeric() || c == '_')
+    }) {
+        return Ok((Vec::new(), false));
+    }
+
+    let (name, parents) = segments
+        .split_last()
+        .expect("split always returns one item");
+    let path_pattern = if parents.is_empty() {
+        String::new()
+    } else {
+        // segments only contain identifier characters, so they don't need escaping
+        let parents: String = parents
+            .iter()
+            .map(|segment| format!("{segment}::(.*::)?"))
+            .collect();
+        format!("^(.*::)?{parents}{name}$")
+    };
+
+    let mut items: Vec<_> = conn
+        .query(
+            "SELECT
+                crates.name AS krate,
+                releases.version,
+                release_items.name,
+                release_items.path,
+                release_items.kind,
+                release_items.link
+            FROM release_items
+            INNER JOIN releases ON release_items.release_id = releases.id
+            INNER JOIN crates ON crates.latest_version_id = releases.id
+            LEFT JOIN repositories ON releases.repository_id = repositories.id
+            WHERE
+                LOWER(release_items.name) = LOWER($1) AND
+                ($2 = '' OR release_items.path ~* $2)
+            ORDER BY
+                release_items.name = $1 DESC,
+                repositories.stars DESC NULLS LAST,
+                crates.name,
+                release_items.path
+            LIMIT $3 OFFSET $4",
+            &[
+                name,
+                &path_pattern,
+                &(per_page + 1),
+                &((page - 1) * per_page),
+            ],
+        )?
+        .into_iter()
+        .map(|row| {
+            let krate: String = row.get("krate");
+            let version: String = row.get("version");
+            let link: String = row.get("link");
+            ItemSearchResult {
+                link: format!("/{krate}/{version}/{link}"),
+                krate,
+                version,
+                name: row.get("name"),
+                path: row.get("path"),
+                kind: row.get("kind"),
+            }
+        })
+        .collect();
+
+    let has_next_page = items.len() as i64 > per_page;
+    items.truncate(per_page as usize);
+    Ok((items, has_next_page))
+}
+
+async fn item_search_handler(pool: Pool, query: String, page: i64) -> AxumResult<AxumResponse> {
+    let (items, has_next_page) = spawn_blocking({
+        let query = query.clone();
+        move || {
+            let mut conn = pool.get()?;
+            search_items(&mut conn, &query, page, RELEASES_IN_RELEASES)
+        }
+    })
+    .await?;
+
+    let page_link = |page: i64| {
+        let params = form_urlencoded::Serializer::new(String::new())
+            .append_pair("items", &query)
+            .append_pair("page", &page.to_string())
+            .finish();
+        format!("/releases/search?{params}")
+    };
+
+    let title = if items.is_empty() {
+        format!("No items found for '{query}'")
+    } else {
+        format!("Items matching '{query}'")
+    };
+
+    Ok(ItemSearch {
+        title,
+        items,
+        previous_page_link: (page > 1).then(|| page_link(page - 1)),
+        next_page_link: has_next_page.then(|| page_link(page + 1)),
+        items_query: query,
+        release_type: ReleaseType::Search,
+    }
+    .into_response())
+}
+
 pub(crate) async fn search_handler(
     Extension(pool): Extension<Pool>,
     Extension(config): Extension<Arc<Config>>,
     Extension(metrics): Extension<Arc<InstanceMetrics>>,
     Query(mut params): Query<HashMap<String, String>>,
 ) -> AxumResult<AxumResponse> {
+    if let Some(items) = params.get("items") {
+        let items = items.trim();
+        if items.is_empty() {
+            return Err(AxumNope::NoResults);
+        }
+        let page = params
+            .get("page")
+            .and_then(|page| page.parse::<i64>().ok())
+            .unwrap_or(1)
+            .clamp(1, MAX_SEARCH_PAGE);
+        return item_search_handler(pool, items.to_string(), page).await;
+    }
+
     let query = params
         .get("query")
         .map(|q| q.to_string())
@@ -696,10 +1103,30 @@ pub(crate) async fn activity_handler(
     })
 }
 
+/// A build which is currently running on one of the builders.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct RunningBuild {
+    name: String,
+    version: String,
+    /// The target which is currently being built, not set before the first target starts.
+    target: Option<String>,
+    build_server: String,
+    started_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct QueueOwner {
+    owner: String,
+    crates: Vec<QueuedCrate>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize)]
 struct BuildQueuePage {
     description: &'static str,
     queue: Vec<QueuedCrate>,
+    /// The queued builds grouped by the owner they are scheduled for, most builds first.
+    owners: Vec<QueueOwner>,
+    running_builds: Vec<RunningBuild>,
     active_deployments: Vec<String>,
 }
 
@@ -711,8 +1138,8 @@ pub(crate) async fn build_queue_handler(
     Extension(build_queue): Extension<Arc<BuildQueue>>,
     Extension(pool): Extension<Pool>,
 ) -> AxumResult<impl IntoResponse> {
-    let (queue, active_deployments) = spawn_blocking(move || {
-        let mut queue = build_queue.queued_crates()?;
+    let (queue, running_builds, active_deployments) = spawn_blocking(move || {
+        let mut queue = build_queue.queued_crates_with_estimates()?;
         for krate in queue.iter_mut() {
             // The priority here is inverted: in the database if a crate has a higher priority it
             // will be built after everything else, which is counter-intuitive for people not
@@ -721,6 +1148,23 @@ pub(crate) async fn build_queue_handler(
         }
 
         let mut conn = pool.get()?;
+        let running_builds = conn
+            .query(
+                "SELECT name, version, target, build_server, started_at
+                 FROM running_builds
+                 ORDER BY started_at",
+                &[],
+            )?
+            .into_iter()
+            .map(|row| RunningBuild {
+                name: row.get("name"),
+                version: row.get("version"),
+                target: row.get("target"),
+                build_server: row.get("build_server"),
+                started_at: row.get("started_at"),
+            })
+            .collect();
+
         let mut active_deployments: Vec<_> = cdn::queued_or_active_crate_invalidations(&mut *conn)?
             .into_iter()
             .map(|i| i.krate)
@@ -733,23 +1177,91 @@ pub(crate) async fn build_queue_handler(
         // reverse the list, so the oldest comes first
         active_deployments.reverse();
 
-        Ok((queue, active_deployments))
+        Ok((queue, running_builds, active_deployments))
     })
     .await?;
 
+    let mut owners: Vec<QueueOwner> = Vec::new();
+    let mut owner_indexes: HashMap<&str, usize> = HashMap::new();
+    for krate in &queue {
+        let Some(owner) = &krate.owner else { continue };
+        match owner_indexes.get(owner.as_str()) {
+            Some(&index) => owners[index].crates.push(krate.clone()),
+            None => {
+                owner_indexes.insert(owner, owners.len());
+                owners.push(QueueOwner {
+                    owner: owner.clone(),
+                    crates: vec![krate.clone()],
+                });
+            }
+        }
+    }
+    // stable, so owners with as many builds stay in build order
+    owners.sort_by_key(|group| std::cmp::Reverse(group.crates.len()));
+
     Ok(BuildQueuePage {
         description: "crate documentation scheduled to build & deploy",
         queue,
+        owners,
+        running_builds,
         active_deployments,
     })
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct RebuildCampaignsPage {
+    description: &'static str,
+    campaigns: Vec<CampaignProgress>,
+}
+
+impl_axum_webpage! {
+    RebuildCampaignsPage = "releases/campaigns.html",
+}
+
+pub(crate) async fn rebuild_campaigns_handler(
+    Extension(pool): Extension<Pool>,
+) -> AxumResult<impl IntoResponse> {
+    let campaigns = spawn_blocking(move || campaign_progress(&mut *pool.get()?)).await?;
+
+    Ok(RebuildCampaignsPage {
+        description: "mass rebuilds of crate documentation",
+        campaigns,
+    })
+}
+
+/// Returns the log of a running build as plain text, as far as the builder published it.
+///
+/// The queue page polls this to tail the log while the build is running.
+pub(crate) async fn running_build_log_handler(
+    Path((name, version)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+) -> AxumResult<impl IntoResponse> {
+    let log: String = spawn_blocking(move || {
+        Ok(pool
+            .get()?
+            .query_opt(
+                "SELECT log FROM running_builds WHERE name = $1 AND version = $2",
+                &[&name, &version],
+            )?
+            .ok_or(AxumNope::BuildNotFound)?
+            .get(0))
+    })
+    .await?;
+
+    Ok((
+        Extension(CachePolicy::NoStoreMustRevalidate),
+        [(CONTENT_TYPE, "text/plain; charset=utf-8")],
+        log,
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::index::api::CrateOwner;
     use crate::test::{
-        assert_redirect, assert_redirect_unchecked, assert_success, wrapper, TestFrontend,
+        assert_cache_control, assert_redirect, assert_redirect_unchecked, assert_success, wrapper,
+        FakeBuild, TestFrontend,
     };
     use anyhow::Error;
     use chrono::{Duration, TimeZone};
@@ -778,7 +1290,8 @@ mod tests {
             // release without stars will not be shown
             env.fake_release().name("baz").version("1.0.0").create()?;
 
-            let releases = get_releases(&mut db.conn(), 1, 10, Order::GithubStars, true).unwrap();
+            let releases =
+                get_releases(&mut db.conn(), 1, 10, Order::GithubStars, true, None).unwrap();
             assert_eq!(
                 vec![
                     "bar", // 20 stars
@@ -900,6 +1413,7 @@ mod tests {
             let mut crates_io = mockito::Server::new();
             env.override_config(|config| {
                 config.registry_api_host = crates_io.url();
+                config.search_crates_io_fallback = true;
             });
 
             let web = env.frontend();
@@ -908,7 +1422,7 @@ mod tests {
             let _m = crates_io
                 .mock("GET", "/api/v1/crates")
                 .match_query(Matcher::AllOf(vec![
-                    Matcher::UrlEncoded("q".into(), "some_random_crate".into()),
+                    Matcher::UrlEncoded("q".into(), "unindexed".into()),
                     Matcher::UrlEncoded("per_page".into(), "30".into()),
                 ]))
                 .with_status(200)
@@ -927,7 +1441,7 @@ mod tests {
                 )
                 .create();
 
-            let response = web.get("/releases/search?query=some_random_crate").send()?;
+            let response = web.get("/releases/search?query=unindexed").send()?;
             assert!(response.status().is_success());
 
             let page = kuchikiki::parse_html().one(response.text()?);
@@ -986,6 +1500,7 @@ mod tests {
             env.override_config(|config| {
                 config.crates_io_api_call_retries = 0;
                 config.registry_api_host = crates_io.url();
+                config.search_crates_io_fallback = true;
             });
 
             let _m = crates_io
@@ -1014,6 +1529,7 @@ mod tests {
             let mut crates_io = mockito::Server::new();
             env.override_config(|config| {
                 config.registry_api_host = crates_io.url();
+                config.search_crates_io_fallback = true;
             });
 
             let web = env.frontend();
@@ -1058,37 +1574,9 @@ mod tests {
     #[test]
     fn search_lucky_with_unknown_crate() {
         wrapper(|env| {
-            let mut crates_io = mockito::Server::new();
-            env.override_config(|config| {
-                config.registry_api_host = crates_io.url();
-            });
-
             let web = env.frontend();
             env.fake_release().name("some_random_crate").create()?;
 
-            let _m = crates_io
-                .mock("GET", "/api/v1/crates")
-                .match_query(Matcher::AllOf(vec![
-                    Matcher::UrlEncoded("q".into(), "some_random_".into()),
-                    Matcher::UrlEncoded("per_page".into(), "30".into()),
-                ]))
-                .with_status(200)
-                .with_header("content-type", "application/json")
-                .with_body(
-                    json!({
-                        "crates": [
-                            { "name": "some_random_crate" },
-                            { "name": "some_other_crate" },
-                        ],
-                        "meta": {
-                            "next_page": null,
-                            "prev_page": null,
-                        }
-                    })
-                    .to_string(),
-                )
-                .create();
-
             // when clicking "I'm feeling lucky" and the query doesn't match any crate,
             // just fallback to the normal search results.
             let links = get_release_links(
@@ -1104,10 +1592,197 @@ mod tests {
 
     #[test]
     fn search() {
+        wrapper(|env| {
+            let web = env.frontend();
+            env.fake_release()
+                .name("some_random_crate")
+                .version("2.0.0")
+                .create()?;
+            env.fake_release()
+                .name("some_random_crate")
+                .version("1.0.0")
+                .create()?;
+            env.fake_release()
+                .name("and_another_one")
+                .version("0.0.1")
+                .description("not so random after all")
+                .create()?;
+            env.fake_release()
+                .name("unrelated")
+                .version("0.0.1")
+                .create()?;
+
+            let links = get_release_links("/releases/search?query=random", web)?;
+
+            // * version used is the highest semver following our own "latest version" logic
+            // * a match in the crate name ranks higher than a match in the description
+            assert_eq!(
+                links,
+                vec![
+                    "/some_random_crate/2.0.0/some_random_crate/",
+                    "/and_another_one/0.0.1/and_another_one/",
+                ]
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn search_matches_keywords_and_readme() {
+        wrapper(|env| {
+            let web = env.frontend();
+            env.fake_release()
+                .name("with_keyword")
+                .keywords(vec!["serialization".into()])
+                .create()?;
+            env.fake_release()
+                .name("with_readme")
+                .readme_only_database("# Readme\n\nfast serialization of things")
+                .create()?;
+            env.fake_release().name("without_anything").create()?;
+
+            let links = get_release_links("/releases/search?query=serialization", web)?;
+
+            assert_eq!(
+                links,
+                vec![
+                    "/with_keyword/1.0.0/with_keyword/",
+                    "/with_readme/1.0.0/with_readme/",
+                ]
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn search_ranking_uses_stars() {
+        wrapper(|env| {
+            let web = env.frontend();
+            env.fake_release()
+                .name("few_stars")
+                .description("a parser")
+                .github_stats("ghost/few", 1, 0, 0)
+                .create()?;
+            env.fake_release()
+                .name("many_stars")
+                .description("a parser")
+                .github_stats("ghost/many", 10000, 0, 0)
+                .create()?;
+            env.fake_release()
+                .name("parser")
+                .description("something else")
+                .create()?;
+
+            let links = get_release_links("/releases/search?query=parser", web)?;
+
+            // exact name matches always come first
+            assert_eq!(
+                links,
+                vec![
+                    "/parser/1.0.0/parser/",
+                    "/many_stars/1.0.0/many_stars/",
+                    "/few_stars/1.0.0/few_stars/",
+                ]
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn search_exact_name_match_with_dashes() {
+        wrapper(|env| {
+            let web = env.frontend();
+            env.fake_release().name("some-crate").create()?;
+
+            let links = get_release_links("/releases/search?query=Some_Crate", web)?;
+            assert_eq!(links, vec!["/some-crate/1.0.0/some_crate/"]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn search_pagination() {
+        wrapper(|env| {
+            let web = env.frontend();
+            for name in ["parser_a", "parser_b", "parser_c"] {
+                env.fake_release().name(name).create()?;
+            }
+
+            let first_page = format!(
+                "/releases/search?paginate={}",
+                b64.encode("?q=parser&per_page=2&page=1")
+            );
+            let response = web.get(&first_page).send()?;
+            assert!(response.status().is_success());
+            let page = kuchikiki::parse_html().one(response.text()?);
+            let links: Vec<_> = page
+                .select("a.release")
+                .unwrap()
+                .map(|el| el.attributes.borrow().get("href").unwrap().to_string())
+                .collect();
+            assert_eq!(
+                links,
+                vec!["/parser_a/1.0.0/parser_a/", "/parser_b/1.0.0/parser_b/",]
+            );
+            let next_page = format!(
+                "/releases/search?paginate={}",
+                b64.encode("?q=parser&per_page=2&page=2")
+            );
+            assert!(page
+                .select("a")
+                .unwrap()
+                .any(|el| el.attributes.borrow().get("href") == Some(next_page.as_str())));
+
+            let links = get_release_links(&next_page, web)?;
+            assert_eq!(links, vec!["/parser_c/1.0.0/parser_c/"]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn search_page_is_limited() {
+        wrapper(|env| {
+            let web = env.frontend();
+            env.fake_release().name("parser_a").create()?;
+
+            let last_page = format!(
+                "/releases/search?paginate={}",
+                b64.encode(format!("?q=parser&per_page=2&page={}", i64::MAX))
+            );
+            let response = web.get(&last_page).send()?;
+            assert!(response.status().is_success());
+            let page = kuchikiki::parse_html().one(response.text()?);
+            assert_eq!(page.select("a.release").unwrap().count(), 0);
+            let previous_page = format!(
+                "/releases/search?paginate={}",
+                b64.encode(format!("?q=parser&per_page=2&page={}", MAX_SEARCH_PAGE - 1))
+            );
+            assert!(page
+                .select("a")
+                .unwrap()
+                .any(|el| el.attributes.borrow().get("href") == Some(previous_page.as_str())));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn search_without_results_doesnt_request_cratesio() {
+        wrapper(|env| {
+            env.fake_release().name("some_random_crate").create()?;
+
+            let links = get_release_links("/releases/search?query=unindexed", env.frontend())?;
+            assert!(links.is_empty());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn search_falls_back_to_cratesio() {
         wrapper(|env| {
             let mut crates_io = mockito::Server::new();
             env.override_config(|config| {
                 config.registry_api_host = crates_io.url();
+                config.search_crates_io_fallback = true;
             });
 
             let web = env.frontend();
@@ -1128,7 +1803,7 @@ mod tests {
             let _m = crates_io
                 .mock("GET", "/api/v1/crates")
                 .match_query(Matcher::AllOf(vec![
-                    Matcher::UrlEncoded("q".into(), "some_random_crate".into()),
+                    Matcher::UrlEncoded("q".into(), "unindexed".into()),
                     Matcher::UrlEncoded("per_page".into(), "30".into()),
                 ]))
                 .with_status(200)
@@ -1149,7 +1824,7 @@ mod tests {
                 )
                 .create();
 
-            let links = get_release_links("/releases/search?query=some_random_crate", web)?;
+            let links = get_release_links("/releases/search?query=unindexed", web)?;
 
             // `some_other_crate` won't be shown since we don't have it yet
             assert_eq!(links.len(), 2);
@@ -1163,6 +1838,122 @@ mod tests {
         })
     }
 
+    const SEARCH_INDEX: &str = r#"var searchIndex = JSON.parse('{\
+"$NAME":{"doc":"","t":"ADIFA","n":["$NAME","Foo","Deserializer","helper","de"],"q":[[1,"$NAME"],[3,"$NAME::de"],[4,"$NAME"]]}\
+}');"#;
+
+    #[test]
+    fn search_items() {
+        wrapper(|env| {
+            let web = env.frontend();
+            let with_index = |name: &str| SEARCH_INDEX.replace("$NAME", name);
+
+            let index = with_index("serde");
+            env.fake_release()
+                .name("serde")
+                .version("1.0.0")
+                .github_stats("serde-rs/serde", 100, 0, 0)
+                .rustdoc_file_with("search-index.js", index.as_bytes())
+                .create()?;
+            let index = with_index("other");
+            env.fake_release()
+                .name("other")
+                .version("0.1.0")
+                .rustdoc_file_with("search-index.js", index.as_bytes())
+                .create()?;
+            // only the latest release is searched
+            let index = with_index("old");
+            env.fake_release()
+                .name("old")
+                .version("0.1.0")
+                .rustdoc_file_with("search-index.js", index.as_bytes())
+                .create()?;
+            env.fake_release().name("old").version("0.2.0").create()?;
+
+            let links = get_release_links("/releases/search?items=deserializer", web)?;
+            assert_eq!(
+                links,
+                vec![
+                    "/serde/1.0.0/serde/trait.Deserializer.html",
+                    "/other/0.1.0/other/trait.Deserializer.html",
+                ]
+            );
+
+            let links = get_release_links("/releases/search?items=serde::helper", web)?;
+            assert_eq!(links, vec!["/serde/1.0.0/serde/de/fn.helper.html"]);
+
+            let links = get_release_links("/releases/search?items=other::de", web)?;
+            assert_eq!(links, vec!["/other/0.1.0/other/de/index.html"]);
+
+            let links = get_release_links("/releases/search?items=de::serde::helper", web)?;
+            assert!(links.is_empty());
+
+            let links = get_release_links("/releases/search?items=Foo.*", web)?;
+            assert!(links.is_empty());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn search_items_pagination() {
+        wrapper(|env| {
+            let web = env.frontend();
+            let names: Vec<_> = (0..RELEASES_IN_RELEASES + 1)
+                .map(|i| format!("krate_{i:02}"))
+                .collect();
+            for name in &names {
+                let index = SEARCH_INDEX.replace("$NAME", name);
+                env.fake_release()
+                    .name(name)
+                    .rustdoc_file_with("search-index.js", index.as_bytes())
+                    .create()?;
+            }
+
+            let response = web.get("/releases/search?items=Foo").send()?;
+            assert!(response.status().is_success());
+            let page = kuchikiki::parse_html().one(response.text()?);
+            assert_eq!(
+                page.select("a.release").unwrap().count(),
+                RELEASES_IN_RELEASES as usize
+            );
+            assert!(page.select("a").unwrap().any(|el| {
+                el.attributes.borrow().get("href") == Some("/releases/search?items=Foo&page=2")
+            }));
+
+            let links = get_release_links("/releases/search?items=Foo&page=2", web)?;
+            assert_eq!(links, vec!["/krate_30/1.0.0/krate_30/struct.Foo.html"]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn search_items_page_is_limited() {
+        wrapper(|env| {
+            let index = SEARCH_INDEX.replace("$NAME", "krate");
+            env.fake_release()
+                .name("krate")
+                .rustdoc_file_with("search-index.js", index.as_bytes())
+                .create()?;
+
+            let links = get_release_links(
+                &format!("/releases/search?items=Foo&page={}", i64::MAX),
+                env.frontend(),
+            )?;
+            assert!(links.is_empty());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn search_items_empty_query() {
+        wrapper(|env| {
+            let response = env.frontend().get("/releases/search?items=").send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            Ok(())
+        })
+    }
+
     fn get_release_links(path: &str, web: &TestFrontend) -> Result<Vec<String>, Error> {
         let response = web.get(path).send()?;
         assert!(response.status().is_success());
@@ -1297,6 +2088,59 @@ mod tests {
         })
     }
 
+    #[test]
+    fn releases_failed_by_category() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("crate_that_ran_out_of_memory")
+                .version("0.1.0")
+                .builds(vec![
+                    FakeBuild::default().failure_category(FailureCategory::Oom)
+                ])
+                .create()?;
+            env.fake_release()
+                .name("crate_that_timed_out")
+                .version("0.1.0")
+                .builds(vec![
+                    FakeBuild::default().failure_category(FailureCategory::Timeout)
+                ])
+                .create()?;
+
+            let links = get_release_links("/releases/recent-failures", env.frontend())?;
+            assert_eq!(links.len(), 2);
+
+            let links =
+                get_release_links("/releases/recent-failures?category=oom", env.frontend())?;
+            assert_eq!(links.len(), 1);
+            assert!(links[0].contains("crate_that_ran_out_of_memory"));
+
+            let page = kuchikiki::parse_html().one(
+                env.frontend()
+                    .get("/releases/recent-failures?category=oom")
+                    .send()?
+                    .text()?,
+            );
+            let category = page
+                .select_first("a.release .failure-category")
+                .expect("missing failure category")
+                .text_contents();
+            assert_eq!(category, "Out of memory:");
+            let active = page
+                .select_first(".failure-categories .pure-menu-active")
+                .expect("missing active category")
+                .text_contents();
+            assert_eq!(active.trim(), "Out of memory");
+
+            let response = env
+                .frontend()
+                .get("/releases/recent-failures?category=unknown")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn releases_homepage_and_recent() {
         wrapper(|env| {
@@ -1488,6 +2332,159 @@ mod tests {
                 }
             }
 
+            let estimates = full
+                .select(".queue-list .estimated-start")
+                .expect("missing estimates")
+                .map(|el| el.text_contents())
+                .collect::<Vec<_>>();
+            assert_eq!(estimates.len(), 3);
+            assert!(estimates[0].contains("expected to start just now"));
+            assert!(estimates[1].contains("expected to start in"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_releases_queue_running_builds() {
+        wrapper(|env| {
+            let web = env.frontend();
+
+            let empty = kuchikiki::parse_html().one(web.get("/releases/queue").send()?.text()?);
+            assert!(empty.select_first(".running-builds").is_err());
+
+            env.db().conn().execute(
+                "INSERT INTO running_builds (name, version, target, build_server, log)
+                 VALUES ('foo', '1.0.0', 'x86_64-unknown-linux-gnu', 'builder-1', 'Compiling foo')",
+                &[],
+            )?;
+
+            let page = kuchikiki::parse_html().one(web.get("/releases/queue").send()?.text()?);
+            let items = page
+                .select(".running-builds > li")
+                .expect("missing list items")
+                .collect::<Vec<_>>();
+            assert_eq!(items.len(), 1);
+
+            let text = items[0].text_contents();
+            assert!(text.contains("foo 1.0.0 (x86_64-unknown-linux-gnu)"));
+            assert!(text.contains("builder-1"));
+
+            let details = items[0]
+                .as_node()
+                .select_first("details")
+                .expect("missing details");
+            assert_eq!(
+                details.attributes.borrow().get("data-log-url"),
+                Some("/releases/queue/foo/1.0.0/log")
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn rebuild_campaigns() {
+        wrapper(|env| {
+            let web = env.frontend();
+
+            let empty = kuchikiki::parse_html().one(web.get("/releases/campaigns").send()?.text()?);
+            assert!(empty.select_first(".rebuild-campaigns").is_err());
+
+            env.fake_release().name("foo").create()?;
+            env.fake_release()
+                .name("bar")
+                .builds(vec![FakeBuild::default().successful(false)])
+                .create()?;
+            crate::utils::create_campaign(
+                &mut env.db().conn(),
+                "everything",
+                &crate::utils::CampaignSelector::CratePattern {
+                    pattern: "%".into(),
+                },
+                20,
+                10,
+            )?;
+
+            let page = kuchikiki::parse_html().one(web.get("/releases/campaigns").send()?.text()?);
+            let rows: Vec<_> = page
+                .select(".rebuild-campaigns tbody tr")
+                .expect("missing rows")
+                .collect();
+            assert_eq!(rows.len(), 1);
+            let text = rows[0].text_contents();
+            assert!(text.contains("everything"));
+            assert!(text.contains("releases of crates matching `%`"));
+            assert!(text.contains("0 / 2"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_releases_queue_by_owner() {
+        wrapper(|env| {
+            for (name, owner) in [("a1", "alice"), ("a2", "alice"), ("b1", "bob")] {
+                env.fake_release()
+                    .name(name)
+                    .add_owner(CrateOwner {
+                        login: owner.into(),
+                        avatar: String::new(),
+                    })
+                    .create()?;
+            }
+            let queue = env.build_queue();
+            for name in ["b1", "a1", "a2", "new"] {
+                queue.add_crate(name, "2.0.0", 0, None)?;
+            }
+
+            let page =
+                kuchikiki::parse_html().one(env.frontend().get("/releases/queue").send()?.text()?);
+            let owners: Vec<_> = page
+                .select(".queue-owners > li")
+                .expect("missing list items")
+                .map(|li| {
+                    li.text_contents()
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect();
+            assert_eq!(
+                owners,
+                vec![
+                    "alice (2 builds): a1 2.0.0, a2 2.0.0",
+                    "bob (1 build): b1 2.0.0"
+                ]
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn running_build_log() {
+        wrapper(|env| {
+            let web = env.frontend();
+
+            let response = web.get("/releases/queue/foo/1.0.0/log").send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            env.db().conn().execute(
+                "INSERT INTO running_builds (name, version, build_server, log)
+                 VALUES ('foo', '1.0.0', 'builder-1', 'Compiling foo')",
+                &[],
+            )?;
+
+            let response = web.get("/releases/queue/foo/1.0.0/log").send()?;
+            assert!(response.status().is_success());
+            assert_cache_control(&response, CachePolicy::NoStoreMustRevalidate, &env.config());
+            assert_eq!(
+                response.headers().get("content-type").unwrap(),
+                "text/plain; charset=utf-8"
+            );
+            assert_eq!(response.text()?, "Compiling foo");
+
             Ok(())
         });
     }
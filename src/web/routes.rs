@@ -2,19 +2,20 @@ use super::{
     cache::CachePolicy, error::AxumNope, metrics::request_recorder, statics::build_static_router,
 };
 use axum::{
+    extract::DefaultBodyLimit,
     handler::Handler as AxumHandler,
     http::Request as AxumHttpRequest,
     middleware::{self, Next},
     response::{IntoResponse, Redirect},
-    routing::get,
     routing::MethodRouter,
+    routing::{get, post},
     Router as AxumRouter,
 };
 use axum_extra::routing::RouterExt;
 use std::convert::Infallible;
 use tracing::{debug, instrument};
 
-const INTERNAL_PREFIXES: &[&str] = &["-", "about", "crate", "releases", "sitemap.xml"];
+const INTERNAL_PREFIXES: &[&str] = &["-", "_", "about", "crate", "releases", "sitemap.xml"];
 
 #[instrument(skip_all)]
 pub(crate) fn get_static<H, T, S, B>(handler: H) -> MethodRouter<S, B, Infallible>
@@ -42,6 +43,19 @@ where
     }))
 }
 
+#[instrument(skip_all)]
+fn post_internal<H, T, S, B>(handler: H) -> MethodRouter<S, B, Infallible>
+where
+    H: AxumHandler<T, S, B>,
+    B: Send + 'static + hyper::body::HttpBody,
+    T: 'static,
+    S: Clone + Send + Sync + 'static,
+{
+    post(handler).route_layer(middleware::from_fn(|request, next| async {
+        request_recorder(request, next, None).await
+    }))
+}
+
 #[instrument(skip_all)]
 fn get_rustdoc<H, T, S, B>(handler: H) -> MethodRouter<S, B, Infallible>
 where
@@ -93,6 +107,41 @@ pub(super) fn build_metric_routes() -> AxumRouter {
         )
 }
 
+fn build_api_routes() -> AxumRouter {
+    AxumRouter::new()
+        .route("/queue", get_internal(super::api::queue_handler))
+        .route(
+            "/releases/failures",
+            get_internal(super::api::failures_handler),
+        )
+        .route(
+            "/crates/:name/releases",
+            get_internal(super::api::releases_handler),
+        )
+        .route(
+            "/crates/:name/:version",
+            get_internal(super::api::crate_details_handler),
+        )
+        .route(
+            "/crates/:name/:version/features",
+            get_internal(super::api::features_handler),
+        )
+        .route(
+            "/crates/:name/:version/targets",
+            get_internal(super::api::targets_handler),
+        )
+        .route(
+            "/crates/:name/:version/coverage",
+            get_internal(super::api::coverage_handler),
+        )
+        .route(
+            "/crates/:name/:version/api-diff",
+            get_internal(super::api::api_diff_handler),
+        )
+        .route("/*path", get_internal(super::api::api_fallback))
+        .layer(middleware::from_fn(super::api::api_middleware))
+}
+
 pub(super) fn build_axum_routes() -> AxumRouter {
     // hint for naming axum routes:
     // when routes overlap, the route parameters at the same position
@@ -133,6 +182,78 @@ pub(super) fn build_axum_routes() -> AxumRouter {
             "/-/sitemap/:letter/sitemap.xml",
             get_internal(super::sitemap::sitemap_handler),
         )
+        .route(
+            "/_/index-webhook",
+            post_internal(super::index_webhook::index_webhook_handler),
+        )
+        .route(
+            "/_/worker/lease",
+            post_internal(super::worker::lease_handler),
+        )
+        .route(
+            "/_/worker/builds/:id/heartbeat",
+            post_internal(super::worker::heartbeat_handler),
+        )
+        .route(
+            "/_/worker/builds/:id/archives/:kind",
+            post_internal(super::worker::archive_handler),
+        )
+        .route(
+            "/_/worker/builds/:id/rustdoc-json/:target",
+            post_internal(super::worker::rustdoc_json_handler),
+        )
+        .route(
+            "/_/worker/builds/:id/complete",
+            post_internal(super::worker::complete_handler).layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/_/worker/builds/:id/fail",
+            post_internal(super::worker::fail_handler),
+        )
+        .route(
+            "/_/worker/essential-files",
+            post_internal(super::worker::essential_files_handler),
+        )
+        .nest("/-/api/v1", build_api_routes())
+        .route_with_tsr("/-/admin", get_internal(super::admin::admin_handler))
+        .route(
+            "/-/admin/login",
+            get_internal(super::admin::login_page_handler)
+                .merge(post_internal(super::admin::login_handler)),
+        )
+        .route(
+            "/-/admin/logout",
+            post_internal(super::admin::logout_handler),
+        )
+        .route("/-/admin/queue", post_internal(super::admin::queue_handler))
+        .route(
+            "/-/admin/priorities",
+            post_internal(super::admin::set_priority_handler),
+        )
+        .route(
+            "/-/admin/priorities/remove",
+            post_internal(super::admin::remove_priority_handler),
+        )
+        .route(
+            "/-/admin/overrides",
+            post_internal(super::admin::set_overrides_handler),
+        )
+        .route(
+            "/-/admin/overrides/remove",
+            post_internal(super::admin::remove_overrides_handler),
+        )
+        .route(
+            "/-/admin/blacklist",
+            post_internal(super::admin::add_to_blacklist_handler),
+        )
+        .route(
+            "/-/admin/blacklist/remove",
+            post_internal(super::admin::remove_from_blacklist_handler),
+        )
+        .route(
+            "/-/admin/delete",
+            post_internal(super::admin::delete_handler),
+        )
         .route_with_tsr(
             "/about/builds",
             get_internal(super::sitemap::about_builds_handler),
@@ -208,6 +329,14 @@ pub(super) fn build_axum_routes() -> AxumRouter {
             "/releases/queue",
             get_internal(super::releases::build_queue_handler),
         )
+        .route(
+            "/releases/queue/:name/:version/log",
+            get_internal(super::releases::running_build_log_handler),
+        )
+        .route_with_tsr(
+            "/releases/campaigns",
+            get_internal(super::releases::rebuild_campaigns_handler),
+        )
         .route_with_tsr(
             "/crate/:name/:version/builds",
             get_internal(super::builds::build_list_handler),
@@ -224,10 +353,30 @@ pub(super) fn build_axum_routes() -> AxumRouter {
             "/crate/:name/:version/builds/:id",
             get_internal(super::build_details::build_details_handler),
         )
+        .route_with_tsr(
+            "/crate/:name/:version/builds/:id/:target",
+            get_internal(super::build_details::build_details_handler),
+        )
         .route_with_tsr(
             "/crate/:name/:version/features",
             get_internal(super::features::build_features_handler),
         )
+        .route_with_tsr(
+            "/crate/:name/:version/coverage",
+            get_internal(super::coverage::coverage_handler),
+        )
+        .route_with_tsr(
+            "/crate/:name/:version/dependents",
+            get_internal(super::dependents::dependents_handler),
+        )
+        .route_with_tsr(
+            "/crate/:name/:version/api-diff",
+            get_internal(super::api_diff::api_diff_handler),
+        )
+        .route_with_tsr(
+            "/crate/:name/:version/diff",
+            get_internal(super::source_diff::source_diff_handler),
+        )
         .route_with_tsr(
             "/crate/:name/:version/source/",
             get_internal(super::source::source_browser_handler),
@@ -301,13 +450,18 @@ pub(super) fn build_axum_routes() -> AxumRouter {
             get_internal(super::rustdoc::download_handler),
         )
         .route(
-            "/crate/:name/:version/target-redirect/*path",
-            get_internal(super::rustdoc::target_redirect_handler),
+            "/crate/:name/:version/json",
+            get_internal(super::rustdoc::json_download_handler),
+        )
+        .route(
+            "/crate/:name/:version/:target/json",
+            get_internal(super::rustdoc::json_download_handler),
         )
         .route(
-            "/:name/badge.svg",
-            get_rustdoc(super::rustdoc::badge_handler),
+            "/crate/:name/:version/target-redirect/*path",
+            get_internal(super::rustdoc::target_redirect_handler),
         )
+        .route("/:name/badge.svg", get_rustdoc(super::badge::badge_handler))
         .route(
             "/:name",
             get_rustdoc(super::rustdoc::rustdoc_redirector_handler),
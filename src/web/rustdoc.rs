@@ -3,7 +3,7 @@
 use crate::{
     db::Pool,
     repositories::RepositoryStatsUpdater,
-    storage::rustdoc_archive_path,
+    storage::{rustdoc_archive_path, rustdoc_json_path},
     utils::{self, spawn_blocking},
     web::{
         axum_cached_redirect, axum_parse_uri_with_params,
@@ -315,6 +315,7 @@ impl RustdocPage {
         file_path: &str,
     ) -> AxumResult<AxumResponse> {
         let is_latest_url = self.is_latest_url;
+        let locked_dependencies = self.krate.locked_dependencies.clone();
 
         // Build the page of documentation
         let mut ctx = tera::Context::from_serialize(self).context("error creating tera context")?;
@@ -322,7 +323,14 @@ impl RustdocPage {
 
         // Extract the head and body of the rustdoc file so that we can insert it into our own html
         // while logging OOM errors from html rewriting
-        let html = match utils::rewrite_lol(rustdoc_html, max_parse_memory, ctx, templates) {
+        let html = match utils::rewrite_lol(
+            rustdoc_html,
+            max_parse_memory,
+            ctx,
+            templates,
+            &config.public_url,
+            &locked_dependencies,
+        ) {
             Err(RewritingError::MemoryLimitExceeded(..)) => {
                 metrics.html_rewrite_ooms.inc();
 
@@ -849,28 +857,6 @@ pub(crate) async fn target_redirect_handler(
     )?)
 }
 
-#[derive(Deserialize, Debug)]
-pub(crate) struct BadgeQueryParams {
-    version: Option<String>,
-}
-
-#[instrument]
-pub(crate) async fn badge_handler(
-    Path(name): Path<String>,
-    Query(query): Query<BadgeQueryParams>,
-) -> AxumResult<impl IntoResponse> {
-    let version = query.version.unwrap_or_else(|| "latest".to_string());
-
-    let url = url::Url::parse(&format!("https://img.shields.io/docsrs/{name}/{version}"))
-        .context("could not parse URL")?;
-
-    Ok((
-        StatusCode::MOVED_PERMANENTLY,
-        [(http::header::LOCATION, url.to_string())],
-        Extension(CachePolicy::ForeverInCdnAndBrowser),
-    ))
-}
-
 pub(crate) async fn download_handler(
     Path((name, req_version)): Path<(String, String)>,
     Extension(pool): Extension<Pool>,
@@ -917,6 +903,71 @@ pub(crate) async fn download_handler(
     )?)
 }
 
+#[derive(Deserialize, Debug)]
+pub(crate) struct JsonDownloadParams {
+    name: String,
+    version: String,
+    target: Option<String>,
+}
+
+/// Serves the rustdoc JSON output of a release, for the default target or a given one.
+pub(crate) async fn json_download_handler(
+    Path(params): Path<JsonDownloadParams>,
+    Extension(pool): Extension<Pool>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> AxumResult<impl IntoResponse> {
+    let JsonDownloadParams {
+        name,
+        version: req_version,
+        target,
+    } = params;
+
+    let (version, id) = match match_version_axum(&pool, &name, Some(&req_version))
+        .await?
+        .exact_name_only()?
+    {
+        MatchSemver::Exact((version, id)) => (version, id),
+        MatchSemver::Latest((version, _)) | MatchSemver::Semver((version, _)) => {
+            let target_segment = target.map(|t| format!("{t}/")).unwrap_or_default();
+            return Ok(axum_cached_redirect(
+                encode_url_path(&format!("/crate/{name}/{version}/{target_segment}json")),
+                CachePolicy::ForeverInCdn,
+            )?
+            .into_response());
+        }
+    };
+
+    let file = spawn_blocking(move || {
+        let row = pool.get()?.query_one(
+            "SELECT default_target, json_targets
+             FROM releases
+             WHERE releases.id = $1",
+            &[&id],
+        )?;
+
+        let target = target.unwrap_or_else(|| row.get("default_target"));
+        let json_targets = MetaData::parse_doc_targets(row.get("json_targets"));
+        if !json_targets.contains(&target) {
+            return Err(AxumNope::ResourceNotFound.into());
+        }
+
+        File::from_path(
+            &storage,
+            &rustdoc_json_path(&name, &version, &target),
+            &config,
+        )
+    })
+    .await?;
+
+    let mut response = file.into_response();
+    // rebuilds replace the JSON, so we can't cache it forever in the browser.
+    response
+        .extensions_mut()
+        .insert(CachePolicy::ForeverInCdnAndStaleInBrowser);
+    Ok(response)
+}
+
 /// Serves shared resources used by rustdoc-generated documentation.
 ///
 /// This serves files from S3, and is pointed to by the `--static-root-path` flag to rustdoc.
@@ -1392,55 +1443,6 @@ mod test {
         })
     }
 
-    #[test]
-    fn badges_are_urlencoded() {
-        wrapper(|env| {
-            use reqwest::Url;
-            use url::Host;
-
-            env.fake_release()
-                .name("zstd")
-                .version("0.5.1+zstd.1.4.4")
-                .create()?;
-
-            let frontend = env.override_frontend(|frontend| {
-                use reqwest::blocking::Client;
-                use reqwest::redirect::Policy;
-                // avoid making network requests
-                frontend.client = Client::builder().redirect(Policy::none()).build().unwrap();
-            });
-            let mut last_url = "/zstd/badge.svg".to_owned();
-            let mut response = frontend.get(&last_url).send()?;
-            let mut current_url = response.url().clone();
-            // follow redirects until it actually goes out into the internet
-            while !matches!(current_url.host(), Some(Host::Domain(_))) {
-                println!("({last_url} -> {current_url})");
-                assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
-                last_url = response.url().to_string();
-                response = frontend.get(response.url().as_str()).send().unwrap();
-                current_url = Url::parse(response.headers()[reqwest::header::LOCATION].to_str()?)?;
-            }
-            println!("({last_url} -> {current_url})");
-            assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
-            assert_cache_control(
-                &response,
-                CachePolicy::ForeverInCdnAndBrowser,
-                &env.config(),
-            );
-            assert_eq!(
-                current_url.as_str(),
-                "https://img.shields.io/docsrs/zstd/latest"
-            );
-            // make sure we aren't actually making network requests
-            assert_ne!(
-                response.url().as_str(),
-                "https://img.shields.io/docsrs/zstd/latest"
-            );
-
-            Ok(())
-        })
-    }
-
     #[test_case(true)]
     #[test_case(false)]
     fn crate_name_percent_decoded_redirect(archive_storage: bool) {
@@ -2536,6 +2538,103 @@ mod test {
         });
     }
 
+    #[test]
+    fn json_download_default_target() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(true)
+                .rustdoc_json("x86_64-unknown-linux-gnu", br#"{"format_version":26}"#)
+                .create()?;
+
+            let web = env.frontend();
+
+            let response = web.get("/crate/dummy/0.1.0/json").send()?;
+            assert!(response.status().is_success());
+            assert_cache_control(
+                &response,
+                CachePolicy::ForeverInCdnAndStaleInBrowser,
+                &env.config(),
+            );
+            assert_eq!(response.headers()["content-type"], "application/json");
+            assert_eq!(response.text()?, r#"{"format_version":26}"#);
+
+            let response = web
+                .get("/crate/dummy/0.1.0/x86_64-unknown-linux-gnu/json")
+                .send()?;
+            assert!(response.status().is_success());
+            assert_eq!(response.text()?, r#"{"format_version":26}"#);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn json_download_other_target() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(true)
+                .add_target("x86_64-pc-windows-msvc")
+                .rustdoc_json("x86_64-unknown-linux-gnu", b"{}")
+                .rustdoc_json("x86_64-pc-windows-msvc", br#"{"windows":true}"#)
+                .create()?;
+
+            let response = env
+                .frontend()
+                .get("/crate/dummy/0.1.0/x86_64-pc-windows-msvc/json")
+                .send()?;
+            assert!(response.status().is_success());
+            assert_eq!(response.text()?, r#"{"windows":true}"#);
+            Ok(())
+        });
+    }
+
+    #[test_case("/crate/dummy/0.1/json", "/crate/dummy/0.1.0/json")]
+    #[test_case("/crate/dummy/latest/json", "/crate/dummy/0.1.0/json")]
+    #[test_case(
+        "/crate/dummy/latest/x86_64-unknown-linux-gnu/json",
+        "/crate/dummy/0.1.0/x86_64-unknown-linux-gnu/json"
+    )]
+    fn json_download_redirect(path: &str, expected: &str) {
+        wrapper(|env| {
+            env.fake_release()
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(true)
+                .rustdoc_json("x86_64-unknown-linux-gnu", b"{}")
+                .create()?;
+
+            assert_redirect_cached(
+                path,
+                expected,
+                CachePolicy::ForeverInCdn,
+                env.frontend(),
+                &env.config(),
+            )?;
+            Ok(())
+        });
+    }
+
+    #[test_case("/crate/dummy/0.1.0/json")]
+    #[test_case("/crate/dummy/0.1.0/x86_64-pc-windows-msvc/json")]
+    #[test_case("/crate/other/0.1.0/json")]
+    fn json_download_not_found(path: &str) {
+        wrapper(|env| {
+            env.fake_release()
+                .name("dummy")
+                .version("0.1.0")
+                .archive_storage(true)
+                .add_target("x86_64-pc-windows-msvc")
+                .create()?;
+
+            let response = env.frontend().get(path).send()?;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            Ok(())
+        });
+    }
+
     #[test_case("search-1234.js")]
     #[test_case("settings-1234.js")]
     fn fallback_to_root_storage_for_some_js_assets(path: &str) {
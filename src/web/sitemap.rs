@@ -236,6 +236,27 @@ mod tests {
         })
     }
 
+    #[test]
+    fn sitemap_uses_public_url() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.public_url = "https://docs.example.com/mirror".into();
+            });
+            env.fake_release().name("some_random_crate").create()?;
+
+            let web = env.frontend();
+            let index = web.get("/sitemap.xml").send()?.text()?;
+            assert!(index
+                .contains("<loc>https://docs.example.com/mirror/-/sitemap/s/sitemap.xml</loc>"));
+
+            let sitemap = web.get("/-/sitemap/s/sitemap.xml").send()?.text()?;
+            assert!(sitemap.contains(
+                "<loc>https://docs.example.com/mirror/some_random_crate/latest/some_random_crate/</loc>"
+            ));
+            Ok(())
+        })
+    }
+
     #[test]
     fn sitemap_max_age() {
         wrapper(|env| {
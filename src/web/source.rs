@@ -7,7 +7,7 @@ use crate::{
         cache::CachePolicy, error::AxumNope, file::File as DbFile, headers::CanonicalUrl,
         MatchSemver, MetaData,
     },
-    Storage,
+    Config, Storage,
 };
 use anyhow::Result;
 use axum::{extract::Path, headers::HeaderMapExt, response::IntoResponse, Extension};
@@ -193,7 +193,7 @@ pub(crate) struct SourceBrowserHandlerParams {
     path: String,
 }
 
-#[instrument(skip(pool, storage))]
+#[instrument(skip(pool, storage, config))]
 pub(crate) async fn source_browser_handler(
     Path(SourceBrowserHandlerParams {
         mut name,
@@ -202,6 +202,7 @@ pub(crate) async fn source_browser_handler(
     }): Path<SourceBrowserHandlerParams>,
     Extension(storage): Extension<Arc<Storage>>,
     Extension(pool): Extension<Pool>,
+    Extension(config): Extension<Arc<Config>>,
 ) -> AxumResult<impl IntoResponse> {
     let v = match_version_axum(&pool, &name, Some(&version)).await?;
 
@@ -254,7 +255,10 @@ pub(crate) async fn source_browser_handler(
     })
     .await?;
 
-    let canonical_url = CanonicalUrl::from_path(format!("/crate/{name}/latest/source/{path}"));
+    let canonical_url = CanonicalUrl::from_path(
+        &config.public_url,
+        format!("/crate/{name}/latest/source/{path}"),
+    );
 
     let (file, file_content) = if let Some(blob) = blob {
         let is_text = blob.mime.starts_with("text") || blob.mime == "application/json";
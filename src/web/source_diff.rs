@@ -0,0 +1,803 @@
+//! Unified diff of the sources of two releases of a crate.
+
+use crate::{
+    db::Pool,
+    impl_axum_webpage,
+    utils::spawn_blocking,
+    web::{
+        cache::CachePolicy,
+        error::{AxumNope, AxumResult},
+        highlight, match_version_axum, MatchSemver, MetaData,
+    },
+    Config, Storage,
+};
+use anyhow::Result;
+use axum::{
+    extract::{Extension, Path},
+    response::IntoResponse,
+};
+use postgres::Client;
+use serde::Serialize;
+use serde_json::Value;
+use similar::{Algorithm, DiffTag};
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::instrument;
+
+/// The number of unchanged lines shown around every change.
+const CONTEXT_LINES: usize = 3;
+
+/// How long comparing a single file may take before the diff gets less precise.
+const FILE_DIFF_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// How much source is diffed for one page, the other files are only listed.
+#[derive(Debug, Clone, Copy)]
+struct DiffLimits {
+    /// The maximum size of one version of a file.
+    file_size: usize,
+    /// The maximum number of lines of one version of a file.
+    file_lines: usize,
+    /// The maximum size of all diffed files together.
+    page_size: usize,
+    /// The maximum number of lines of all diffed files together.
+    page_lines: usize,
+    /// The maximum number of file versions fetched from the storage for one page.
+    page_files: usize,
+}
+
+impl DiffLimits {
+    fn new(config: &Config) -> Self {
+        Self {
+            file_size: config.max_file_size,
+            file_lines: 5_000,
+            page_size: config.max_file_size.saturating_mul(4),
+            page_lines: 20_000,
+            page_files: 1_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum LineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct DiffLine {
+    kind: LineKind,
+    /// The line, without the trailing newline.
+    text: String,
+}
+
+/// A hunk of a unified diff, the line numbers are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Hunk {
+    old_start: usize,
+    old_len: usize,
+    new_start: usize,
+    new_len: usize,
+    lines: Vec<DiffLine>,
+}
+
+/// Builds the hunks of a unified diff between `old` and `new`,
+/// with `context` unchanged lines around every change.
+///
+/// When the diff takes longer than `deadline`, the rest of the file is compared less thoroughly,
+/// which can result in larger changes than necessary.
+fn unified_diff(old: &str, new: &str, context: usize, deadline: Option<Instant>) -> Vec<Hunk> {
+    // `(kind, old line number, new line number, text)` of every line, 0-based
+    let mut lines = Vec::new();
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    for op in
+        similar::capture_diff_slices_deadline(Algorithm::Myers, &old_lines, &new_lines, deadline)
+    {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        if tag == DiffTag::Equal {
+            lines.extend(
+                old_range
+                    .zip(new_range)
+                    .map(|(old, new)| (LineKind::Context, old, new, old_lines[old])),
+            );
+            continue;
+        }
+        lines.extend(
+            old_range
+                .clone()
+                .map(|old| (LineKind::Removed, old, new_range.start, old_lines[old])),
+        );
+        lines.extend(new_range.map(|new| (LineKind::Added, old_range.end, new, new_lines[new])));
+    }
+
+    // ranges of lines that are part of a hunk, overlapping ranges are merged
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (idx, _) in lines
+        .iter()
+        .enumerate()
+        .filter(|(_, (kind, ..))| *kind != LineKind::Context)
+    {
+        let start = idx.saturating_sub(context);
+        let end = (idx + context + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let lines = &lines[start..end];
+            let old_len = lines
+                .iter()
+                .filter(|(kind, ..)| *kind != LineKind::Added)
+                .count();
+            let new_len = lines
+                .iter()
+                .filter(|(kind, ..)| *kind != LineKind::Removed)
+                .count();
+            let (_, old_start, new_start, _) = lines[0];
+            Hunk {
+                // like `diff -u`, empty ranges start at the line before them
+                old_start: if old_len == 0 {
+                    old_start
+                } else {
+                    old_start + 1
+                },
+                old_len,
+                new_start: if new_len == 0 {
+                    new_start
+                } else {
+                    new_start + 1
+                },
+                new_len,
+                lines: lines
+                    .iter()
+                    .map(|(kind, _, _, text)| DiffLine {
+                        kind: *kind,
+                        text: (*text).to_owned(),
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum FileStatus {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// Why the diff of a file isn't shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Omitted {
+    /// The file isn't a text file.
+    Binary,
+    /// One version of the file is larger than the limits for a single file.
+    TooLarge,
+    /// The diffs of the previous files already reached the limits of the page.
+    LimitReached,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct FileDiff {
+    path: String,
+    status: FileStatus,
+    hunks: Vec<Hunk>,
+    omitted: Option<Omitted>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SourceDiffPage {
+    metadata: MetaData,
+    old_version: String,
+    new_version: String,
+    files: Vec<FileDiff>,
+    unchanged_files: usize,
+    use_direct_platform_links: bool,
+}
+
+impl_axum_webpage! {
+    SourceDiffPage = "crate/source_diff.html",
+    cache_policy = |_| CachePolicy::ForeverInCdnAndStaleInBrowser,
+    cpu_intensive_rendering = true,
+}
+
+/// A release whose source files are compared.
+struct Release {
+    name: String,
+    version: String,
+    archive_storage: bool,
+    /// The mime type of every file, by path.
+    files: BTreeMap<String, String>,
+}
+
+impl Release {
+    fn load(conn: &mut Client, name: &str, version: &str) -> Result<Option<Self>> {
+        let Some(row) = conn.query_opt(
+            "SELECT releases.archive_storage, releases.files
+             FROM releases
+             INNER JOIN crates ON crates.id = releases.crate_id
+             WHERE crates.name = $1 AND releases.version = $2",
+            &[&name, &version],
+        )?
+        else {
+            return Ok(None);
+        };
+
+        let files = row
+            .get::<_, Option<Value>>(1)
+            .as_ref()
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|file| {
+                let mime = file.get(0)?.as_str()?;
+                let path = file.get(1)?.as_str()?;
+                // skip .cargo-ok generated by cargo
+                (path != ".cargo-ok").then(|| (path.to_owned(), mime.to_owned()))
+            })
+            .collect();
+
+        Ok(Some(Self {
+            name: name.to_owned(),
+            version: version.to_owned(),
+            archive_storage: row.get(0),
+            files,
+        }))
+    }
+
+    /// Fetches the content of a file, `None` when it's larger than the size limit.
+    fn fetch(&self, storage: &Storage, path: &str) -> Result<Option<Vec<u8>>> {
+        match storage.fetch_source_file(&self.name, &self.version, path, self.archive_storage) {
+            Ok(blob) if blob.is_empty() => Ok(Some(Vec::new())),
+            Ok(blob) => Ok(Some(blob.content)),
+            Err(err) if is_size_limit_error(&err) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn is_size_limit_error(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<std::io::Error>()
+        .and_then(|io| io.get_ref())
+        .is_some_and(|err| err.is::<crate::error::SizeLimitReached>())
+}
+
+fn is_text(mime: &str) -> bool {
+    mime.starts_with("text") || mime == "application/json"
+}
+
+/// Compares the source files of two releases.
+///
+/// Files larger than the limits for a single file aren't diffed, and once the fetched files reach
+/// the limits for the page, the remaining files are only listed.
+fn diff_releases(
+    storage: &Storage,
+    old: &Release,
+    new: &Release,
+    limits: DiffLimits,
+) -> Result<(Vec<FileDiff>, usize)> {
+    let mut paths: Vec<&String> = old.files.keys().chain(new.files.keys()).collect();
+    paths.sort();
+    paths.dedup();
+
+    let mut files = Vec::new();
+    let mut unchanged_files = 0;
+    let (mut total_size, mut total_lines, mut fetched_files) = (0, 0, 0);
+    for path in paths {
+        let (status, mime) = match (old.files.get(path), new.files.get(path)) {
+            (Some(_), Some(mime)) => (FileStatus::Modified, mime),
+            (None, Some(mime)) => (FileStatus::Added, mime),
+            (Some(mime), None) => (FileStatus::Removed, mime),
+            (None, None) => unreachable!(),
+        };
+
+        let mut file = FileDiff {
+            path: path.clone(),
+            status,
+            hunks: Vec::new(),
+            omitted: None,
+        };
+        if total_size >= limits.page_size
+            || total_lines >= limits.page_lines
+            || fetched_files >= limits.page_files
+        {
+            // without the content we can't tell whether a file that is in both releases changed,
+            // so they are listed as modified.
+            file.omitted = Some(Omitted::LimitReached);
+            files.push(file);
+            continue;
+        }
+
+        let too_large = |content: &[u8]| {
+            content.len() > limits.file_size
+                || content.iter().filter(|&&b| b == b'\n').count() > limits.file_lines
+        };
+        let old_content = match status {
+            FileStatus::Added => Some(Vec::new()),
+            _ => {
+                fetched_files += 1;
+                old.fetch(storage, path)?
+            }
+        };
+        // the new version isn't fetched when the old one is too large already
+        let new_content = match (status, &old_content) {
+            (FileStatus::Removed, _) => Some(Vec::new()),
+            (_, Some(old_content)) if !too_large(old_content) => {
+                fetched_files += 1;
+                new.fetch(storage, path)?
+            }
+            _ => None,
+        };
+        let (Some(old_content), Some(new_content)) = (old_content, new_content) else {
+            file.omitted = Some(Omitted::TooLarge);
+            files.push(file);
+            continue;
+        };
+        if status == FileStatus::Modified && old_content == new_content {
+            // unchanged files are fetched as well, so they count against the limits of the page
+            total_size += old_content.len() + new_content.len();
+            total_lines += old_content.iter().filter(|&&b| b == b'\n').count();
+            unchanged_files += 1;
+            continue;
+        }
+
+        let (old_content, new_content) = match (
+            String::from_utf8(old_content),
+            String::from_utf8(new_content),
+        ) {
+            (Ok(old_content), Ok(new_content)) if is_text(mime) => (old_content, new_content),
+            _ => {
+                file.omitted = Some(Omitted::Binary);
+                files.push(file);
+                continue;
+            }
+        };
+
+        let lines = old_content.lines().count().max(new_content.lines().count());
+        let size = old_content.len().max(new_content.len());
+        if size > limits.file_size || lines > limits.file_lines {
+            file.omitted = Some(Omitted::TooLarge);
+            files.push(file);
+            continue;
+        }
+        total_size += old_content.len() + new_content.len();
+        total_lines += lines;
+
+        file.hunks = unified_diff(
+            &old_content,
+            &new_content,
+            CONTEXT_LINES,
+            Some(Instant::now() + FILE_DIFF_TIMEOUT),
+        );
+        highlight_hunks(path, &old_content, &new_content, &mut file.hunks);
+        files.push(file);
+    }
+
+    Ok((files, unchanged_files))
+}
+
+/// Replaces the text of the lines in `hunks` with their highlighted HTML.
+///
+/// Both versions of the file are highlighted as a whole, so that a line is highlighted the same
+/// way as on the source page, even when it's inside a comment or string starting outside of
+/// the hunk.
+fn highlight_hunks(path: &str, old: &str, new: &str, hunks: &mut [Hunk]) {
+    if hunks.is_empty() {
+        return;
+    }
+    let old = highlight::lines_with_lang(Some(path), old);
+    let new = highlight::lines_with_lang(Some(path), new);
+
+    for hunk in hunks {
+        // the 0-based line numbers of the next line of both files, see `unified_diff` for the
+        // start of empty ranges
+        let mut old_line = match hunk.old_len {
+            0 => hunk.old_start,
+            _ => hunk.old_start - 1,
+        };
+        let mut new_line = match hunk.new_len {
+            0 => hunk.new_start,
+            _ => hunk.new_start - 1,
+        };
+        for line in &mut hunk.lines {
+            let highlighted = match line.kind {
+                LineKind::Removed => old.get(old_line),
+                LineKind::Context | LineKind::Added => new.get(new_line),
+            };
+            if let Some(highlighted) = highlighted {
+                line.text = highlighted.clone();
+            }
+            if line.kind != LineKind::Added {
+                old_line += 1;
+            }
+            if line.kind != LineKind::Removed {
+                new_line += 1;
+            }
+        }
+    }
+}
+
+/// Resolves both versions of an `old...new` range, like the versions of the other crate pages.
+pub(super) async fn match_version_range(
+    pool: &Pool,
+    name: &str,
+    versions: &str,
+) -> AxumResult<(MatchSemver, MatchSemver)> {
+    let (old_req, new_req) = versions
+        .split_once("...")
+        .ok_or(AxumNope::VersionNotFound)?;
+
+    let old = match_version_axum(pool, name, Some(old_req))
+        .await?
+        .exact_name_only()?;
+    let new = match_version_axum(pool, name, Some(new_req))
+        .await?
+        .exact_name_only()?;
+    Ok((old, new))
+}
+
+#[instrument(skip(pool, storage, config))]
+pub(crate) async fn source_diff_handler(
+    Path((name, versions)): Path<(String, String)>,
+    Extension(pool): Extension<Pool>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(config): Extension<Arc<Config>>,
+) -> AxumResult<impl IntoResponse> {
+    let (old, new) = match_version_range(&pool, &name, &versions).await?;
+    if !matches!(old, MatchSemver::Exact(_)) || !matches!(new, MatchSemver::Exact(_)) {
+        let (old, _) = old.into_parts();
+        let (new, _) = new.into_parts();
+        return Ok(super::axum_cached_redirect(
+            &format!("/crate/{name}/{old}...{new}/diff"),
+            CachePolicy::ForeverInCdn,
+        )?
+        .into_response());
+    }
+    let (old_version, _) = old.into_parts();
+    let (new_version, _) = new.into_parts();
+
+    Ok(spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        let (Some(old), Some(new)) = (
+            Release::load(&mut conn, &name, &old_version)?,
+            Release::load(&mut conn, &name, &new_version)?,
+        ) else {
+            return Err(AxumNope::VersionNotFound.into());
+        };
+        let metadata = MetaData::from_crate(&mut conn, &name, &new_version, &new_version)?;
+        drop(conn);
+
+        let (files, unchanged_files) =
+            diff_releases(&storage, &old, &new, DiffLimits::new(&config))?;
+
+        Ok(SourceDiffPage {
+            metadata,
+            old_version,
+            new_version,
+            files,
+            unchanged_files,
+            use_direct_platform_links: true,
+        })
+    })
+    .await?
+    .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{assert_cache_control, assert_redirect_cached, wrapper};
+    use kuchikiki::traits::TendrilSink;
+    use reqwest::StatusCode;
+
+    fn lines(hunk: &Hunk) -> Vec<String> {
+        hunk.lines
+            .iter()
+            .map(|line| {
+                let prefix = match line.kind {
+                    LineKind::Context => ' ',
+                    LineKind::Added => '+',
+                    LineKind::Removed => '-',
+                };
+                format!("{prefix}{}", line.text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unified_diff_hunks() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+
+        let hunks = unified_diff(old, new, 2, None);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(
+            (hunks[0].old_start, hunks[0].old_len),
+            (1, 4),
+            "{:?}",
+            hunks[0]
+        );
+        assert_eq!((hunks[0].new_start, hunks[0].new_len), (1, 4));
+        assert_eq!(lines(&hunks[0]), vec![" a", "-b", "+B", " c", " d"]);
+        assert_eq!((hunks[1].old_start, hunks[1].old_len), (9, 2));
+        assert_eq!((hunks[1].new_start, hunks[1].new_len), (9, 3));
+        assert_eq!(lines(&hunks[1]), vec![" i", " j", "+k"]);
+
+        // close changes are merged into one hunk
+        assert_eq!(unified_diff(old, new, 4, None).len(), 1);
+        assert!(unified_diff(old, old, 3, None).is_empty());
+    }
+
+    #[test]
+    fn unified_diff_of_new_file() {
+        let hunks = unified_diff("", "a\nb\n", 3, None);
+        assert_eq!(hunks.len(), 1);
+        assert_eq!((hunks[0].old_start, hunks[0].old_len), (0, 0));
+        assert_eq!((hunks[0].new_start, hunks[0].new_len), (1, 2));
+        assert_eq!(lines(&hunks[0]), vec!["+a", "+b"]);
+    }
+
+    #[test]
+    fn diff_page() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .source_file("src/lib.rs", b"pub fn a() {}\npub fn b() {}\n")
+                .source_file("src/old.rs", b"// removed\n")
+                .source_file("README.md", b"readme\n")
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.2.0")
+                .source_file("src/lib.rs", b"pub fn a() {}\npub fn c() {}\n")
+                .source_file("src/new.rs", b"// added\n")
+                .source_file("README.md", b"readme\n")
+                .create()?;
+
+            let response = env.frontend().get("/crate/foo/0.1.0...0.2.0/diff").send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_cache_control(
+                &response,
+                CachePolicy::ForeverInCdnAndStaleInBrowser,
+                &env.config(),
+            );
+
+            let page = kuchikiki::parse_html().one(response.text()?);
+            let files: Vec<(String, String)> = page
+                .select(".diff-file")
+                .unwrap()
+                .map(|file| {
+                    let attributes = file.attributes.borrow();
+                    (
+                        attributes.get("data-path").unwrap().to_owned(),
+                        attributes.get("data-status").unwrap().to_owned(),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                files,
+                vec![
+                    // the generated manifest contains the version
+                    ("Cargo.toml".to_owned(), "modified".to_owned()),
+                    ("src/lib.rs".to_owned(), "modified".to_owned()),
+                    ("src/new.rs".to_owned(), "added".to_owned()),
+                    ("src/old.rs".to_owned(), "removed".to_owned()),
+                ]
+            );
+
+            let lib = page
+                .select_first(".diff-file[data-path='src/lib.rs']")
+                .unwrap();
+            let changes: Vec<String> = lib
+                .as_node()
+                .select(".diff-line.added, .diff-line.removed")
+                .unwrap()
+                .map(|line| line.text_contents())
+                .collect();
+            assert_eq!(changes, vec!["pub fn b() {}", "pub fn c() {}"]);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn large_files_are_not_diffed() {
+        wrapper(|env| {
+            env.override_config(|config| config.max_file_size = 64);
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .source_file("src/lib.rs", &[b'a'; 100])
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.2.0")
+                .source_file("src/lib.rs", &[b'b'; 100])
+                .create()?;
+
+            let mut conn = env.db().conn();
+            let old = Release::load(&mut conn, "foo", "0.1.0")?.unwrap();
+            let new = Release::load(&mut conn, "foo", "0.2.0")?.unwrap();
+            let (files, _) =
+                diff_releases(&env.storage(), &old, &new, DiffLimits::new(&env.config()))?;
+            let lib = files.iter().find(|file| file.path == "src/lib.rs").unwrap();
+            assert_eq!(lib.omitted, Some(Omitted::TooLarge));
+            assert!(lib.hunks.is_empty());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn diff_limits() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .source_file("Cargo.toml", b"")
+                .source_file("src/a.rs", b"a\n")
+                .source_file("src/b.rs", "b\n".repeat(20).as_bytes())
+                .source_file("src/c.rs", b"c\n")
+                .source_file("src/d.rs", b"d\n")
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.2.0")
+                .source_file("Cargo.toml", b"")
+                .source_file("src/a.rs", b"A\n")
+                .source_file("src/b.rs", "B\n".repeat(20).as_bytes())
+                .source_file("src/c.rs", "C\n".repeat(5).as_bytes())
+                .source_file("src/d.rs", b"D\n")
+                .create()?;
+
+            let mut conn = env.db().conn();
+            let old = Release::load(&mut conn, "foo", "0.1.0")?.unwrap();
+            let new = Release::load(&mut conn, "foo", "0.2.0")?.unwrap();
+            let limits = DiffLimits {
+                file_size: 1024,
+                file_lines: 10,
+                page_size: 1024,
+                page_lines: 6,
+                page_files: 100,
+            };
+            let (files, _) = diff_releases(&env.storage(), &old, &new, limits)?;
+            let files: Vec<_> = files
+                .iter()
+                .map(|file| (file.path.as_str(), file.omitted, file.hunks.len()))
+                .collect();
+            assert_eq!(
+                files,
+                vec![
+                    ("src/a.rs", None, 1),
+                    // more lines than a single file may have
+                    ("src/b.rs", Some(Omitted::TooLarge), 0),
+                    // reaches the line limit of the page
+                    ("src/c.rs", None, 1),
+                    ("src/d.rs", Some(Omitted::LimitReached), 0),
+                ]
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn unchanged_files_count_against_the_limits() {
+        wrapper(|env| {
+            env.fake_release()
+                .name("foo")
+                .version("0.1.0")
+                .source_file("Cargo.toml", b"")
+                .source_file("src/a.rs", "a\n".repeat(10).as_bytes())
+                .source_file("src/b.rs", b"b\n")
+                .create()?;
+            env.fake_release()
+                .name("foo")
+                .version("0.2.0")
+                .source_file("Cargo.toml", b"")
+                .source_file("src/a.rs", "a\n".repeat(10).as_bytes())
+                .source_file("src/b.rs", b"B\n")
+                .create()?;
+
+            let mut conn = env.db().conn();
+            let old = Release::load(&mut conn, "foo", "0.1.0")?.unwrap();
+            let new = Release::load(&mut conn, "foo", "0.2.0")?.unwrap();
+            let limits = DiffLimits {
+                file_size: 1024,
+                file_lines: 100,
+                page_size: 1024,
+                page_lines: 100,
+                page_files: 100,
+            };
+            let diff = |limits| -> Result<_> {
+                let (files, unchanged_files) = diff_releases(&env.storage(), &old, &new, limits)?;
+                let b = files.into_iter().find(|file| file.path == "src/b.rs");
+                Ok((b.unwrap().omitted, unchanged_files))
+            };
+
+            assert_eq!(diff(limits)?, (None, 2));
+            // the lines of the unchanged file reach the line limit
+            assert_eq!(
+                diff(DiffLimits {
+                    page_lines: 10,
+                    ..limits
+                })?,
+                (Some(Omitted::LimitReached), 2)
+            );
+            // both versions of the unchanged files were fetched already
+            assert_eq!(
+                diff(DiffLimits {
+                    page_files: 4,
+                    ..limits
+                })?,
+                (Some(Omitted::LimitReached), 2)
+            );
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn multi_line_comments_are_highlighted() {
+        let old = "/*\n a\n*/\nfn x() {}\n";
+        let new = "/*\n b\n*/\nfn x() {}\n";
+        let mut hunks = unified_diff(old, new, 0, None);
+        highlight_hunks("src/lib.rs", old, new, &mut hunks);
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].lines.len(), 2);
+        for line in &hunks[0].lines {
+            assert!(line.text.contains("syntax-comment"), "{}", line.text);
+        }
+    }
+
+    #[test]
+    fn semver_versions_redirect() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            env.fake_release().name("foo").version("0.2.0").create()?;
+
+            assert_redirect_cached(
+                "/crate/foo/0.1...latest/diff",
+                "/crate/foo/0.1.0...0.2.0/diff",
+                CachePolicy::ForeverInCdn,
+                env.frontend(),
+                &env.config(),
+            )?;
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn invalid_versions() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+
+            let web = env.frontend();
+            for url in [
+                "/crate/foo/0.1.0/diff",
+                "/crate/foo/0.1.0...0.3.0/diff",
+                "/crate/bar/0.1.0...0.1.0/diff",
+            ] {
+                assert_eq!(
+                    web.get(url).send()?.status(),
+                    StatusCode::NOT_FOUND,
+                    "{url}"
+                );
+            }
+            Ok(())
+        });
+    }
+}
@@ -1,7 +1,7 @@
 use super::{cache::CachePolicy, metrics::request_recorder, routes::get_static};
+use crate::impl_axum_webpage;
 use axum::{
     extract::Extension,
-    headers::HeaderValue,
     http::{header::CONTENT_TYPE, Request},
     middleware,
     middleware::Next,
@@ -9,6 +9,7 @@ use axum::{
     routing::get_service,
     Router as AxumRouter,
 };
+use serde::Serialize;
 use tower_http::services::ServeDir;
 
 const VENDORED_CSS: &str = include_str!(concat!(env!("OUT_DIR"), "/vendored.css"));
@@ -26,8 +27,6 @@ fn build_static_css_response(content: &'static str) -> impl IntoResponse {
 }
 
 async fn set_needed_static_headers<B>(req: Request<B>, next: Next<B>) -> Response {
-    let is_opensearch_xml = req.uri().path().ends_with("/opensearch.xml");
-
     let mut response = next.run(req).await;
 
     if response.status().is_success() {
@@ -36,18 +35,19 @@ async fn set_needed_static_headers<B>(req: Request<B>, next: Next<B>) -> Respons
             .insert(CachePolicy::ForeverInCdnAndBrowser);
     }
 
-    if is_opensearch_xml {
-        // overwrite the content type for opensearch.xml,
-        // otherwise mime-guess would return `text/xml`.
-        response.headers_mut().insert(
-            CONTENT_TYPE,
-            HeaderValue::from_static("application/opensearchdescription+xml"),
-        );
-    }
-
     response
 }
 
+/// The search plugin description, rendered so it points to the public URL of this instance.
+#[derive(Debug, Clone, Serialize)]
+struct OpenSearchXml {}
+
+impl_axum_webpage! {
+    OpenSearchXml = "core/opensearch.xml",
+    content_type = "application/opensearchdescription+xml",
+    cache_policy = |_| CachePolicy::ForeverInCdnAndBrowser,
+}
+
 pub(crate) fn build_static_router() -> AxumRouter {
     AxumRouter::new()
         .route(
@@ -66,6 +66,7 @@ pub(crate) fn build_static_router() -> AxumRouter {
             "/rustdoc-2021-12-05.css",
             get_static(|| async { build_static_css_response(RUSTDOC_2021_12_05_CSS) }),
         )
+        .route("/opensearch.xml", get_static(|| async { OpenSearchXml {} }))
         .nest_service(
             "/",
             get_service(ServeDir::new("static").fallback(ServeDir::new("vendor")))
@@ -167,6 +168,30 @@ mod tests {
         });
     }
 
+    #[test]
+    fn opensearch_xml() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.public_url = "https://docs.example.com".into();
+                config.site_name = "Example Docs".into();
+            });
+            let resp = env.frontend().get("/-/static/opensearch.xml").send()?;
+            assert!(resp.status().is_success());
+            assert_cache_control(&resp, CachePolicy::ForeverInCdnAndBrowser, &env.config());
+            assert_eq!(
+                resp.headers().get("Content-Type"),
+                Some(&"application/opensearchdescription+xml".parse().unwrap()),
+            );
+            let body = resp.text()?;
+            assert!(body.contains("<ShortName>Example Docs</ShortName>"));
+            assert!(body.contains(
+                r#"template="https://docs.example.com/releases/search?query={searchTerms}""#
+            ));
+
+            Ok(())
+        });
+    }
+
     #[test]
     fn static_files() {
         wrapper(|env| {
@@ -2,7 +2,12 @@ use super::cache::CachePolicy;
 use crate::{
     db::Pool,
     utils::spawn_blocking,
-    web::{axum_redirect, error::AxumResult, match_version_axum, MatchSemver},
+    web::{
+        axum_redirect,
+        error::{AxumNope, AxumResult},
+        match_version_axum, MatchSemver,
+    },
+    BuildQueue,
 };
 use axum::{
     extract::{Extension, Path},
@@ -10,10 +15,12 @@ use axum::{
     response::IntoResponse,
     Json,
 };
+use std::sync::Arc;
 
 pub(crate) async fn status_handler(
     Path((name, req_version)): Path<(String, String)>,
     Extension(pool): Extension<Pool>,
+    Extension(build_queue): Extension<Arc<BuildQueue>>,
 ) -> impl IntoResponse {
     (
         Extension(CachePolicy::NoStoreMustRevalidate),
@@ -21,38 +28,70 @@ pub(crate) async fn status_handler(
         // We use an async block to emulate a try block so that we can apply the above CORS header
         // and cache policy to both successful and failed responses
         async move {
-            let (version, id) = match match_version_axum(&pool, &name, Some(&req_version))
-                .await?
-                .exact_name_only()?
-            {
-                MatchSemver::Exact((version, id)) | MatchSemver::Latest((version, id)) => {
-                    (version, id)
+            let matched = match_version_axum(&pool, &name, Some(&req_version))
+                .await
+                .map_err(AxumNope::from)
+                .and_then(|matched| matched.exact_name_only());
+            let (version, id) = match matched {
+                Ok(MatchSemver::Exact((version, id)) | MatchSemver::Latest((version, id))) => {
+                    (version, Ok(id))
                 }
-                MatchSemver::Semver((version, _)) => {
+                Ok(MatchSemver::Semver((version, _))) => {
                     let redirect = axum_redirect(format!("/crate/{name}/{version}/status.json"))?;
                     return Ok(redirect.into_response());
                 }
+                // The first build of a release is still queued, CI waiting for the docs can
+                // poll the exact version until it's built.
+                Err(err @ (AxumNope::CrateNotFound | AxumNope::VersionNotFound)) => (
+                    req_version
+                        .strip_prefix('=')
+                        .unwrap_or(&req_version)
+                        .to_string(),
+                    Err(err),
+                ),
+                Err(err) => return Err(err),
             };
 
-            let rustdoc_status: bool = spawn_blocking({
+            let release_id = id.as_ref().ok().copied();
+            let (rustdoc_status, queued) = spawn_blocking({
+                let name = name.clone();
+                let version = version.clone();
                 move || {
-                    Ok(pool
-                        .get()?
-                        .query_one(
-                            "SELECT releases.rustdoc_status
-                             FROM releases
-                             WHERE releases.id = $1
-                            ",
-                            &[&id],
-                        )?
-                        .get("rustdoc_status"))
+                    let rustdoc_status: Option<bool> = match release_id {
+                        Some(id) => Some(
+                            pool.get()?
+                                .query_one(
+                                    "SELECT releases.rustdoc_status
+                                     FROM releases
+                                     WHERE releases.id = $1
+                                    ",
+                                    &[&id],
+                                )?
+                                .get("rustdoc_status"),
+                        ),
+                        None => None,
+                    };
+
+                    let queued = if build_queue.has_build_queued(&name, &version)? {
+                        build_queue.queued_crate_with_estimate(&name, &version)?
+                    } else {
+                        None
+                    };
+
+                    Ok((rustdoc_status, queued))
                 }
             })
             .await?;
 
+            if let (Err(err), None) = (id, &queued) {
+                return Err(err);
+            }
+
             let json = Json(serde_json::json!({
                 "version": version,
-                "doc_status": rustdoc_status,
+                "doc_status": rustdoc_status.unwrap_or(false),
+                "queued": queued.is_some(),
+                "estimated_build_start": queued.and_then(|krate| krate.estimated_start),
             }));
 
             AxumResult::Ok(json.into_response())
@@ -92,6 +131,8 @@ mod tests {
                 serde_json::json!({
                     "version": "0.1.0",
                     "doc_status": true,
+                    "queued": false,
+                    "estimated_build_start": null,
                 })
             );
 
@@ -143,6 +184,8 @@ mod tests {
                 serde_json::json!({
                     "version": "0.1.0",
                     "doc_status": false,
+                    "queued": false,
+                    "estimated_build_start": null,
                 })
             );
 
@@ -150,6 +193,31 @@ mod tests {
         });
     }
 
+    #[test]
+    fn queued() {
+        wrapper(|env| {
+            env.fake_release().name("foo").version("0.1.0").create()?;
+            env.build_queue().add_crate("foo", "0.1.0", 0, None)?;
+            env.build_queue().add_crate("foo", "0.2.0", 0, None)?;
+
+            for (version, doc_status) in [("0.1.0", true), ("=0.2.0", false)] {
+                let response = env
+                    .frontend()
+                    .get(&format!("/crate/foo/{version}/status.json"))
+                    .send()?;
+                assert_eq!(response.status(), StatusCode::OK);
+                let value: serde_json::Value = serde_json::from_str(&response.text()?)?;
+
+                assert_eq!(value["version"], version.trim_start_matches('='));
+                assert_eq!(value["doc_status"], doc_status);
+                assert_eq!(value["queued"], true);
+                assert!(value["estimated_build_start"].is_string());
+            }
+
+            Ok(())
+        });
+    }
+
     // crate not found
     #[test_case("bar", "0.1")]
     #[test_case("bar", "0.1.0")]
@@ -0,0 +1,647 @@
+//! The endpoints build workers lease their builds from and send the results to.
+//!
+//! Workers authenticate with one of the tokens configured in `DOCSRS_WORKER_TOKENS`, sent as
+//! `Authorization: Bearer <token>` header. The name of the token is recorded as the build server
+//! of the builds, and a worker can only act on the leases it holds itself.
+//!
+//! Uploads are written to temporary files as they arrive and can be at most
+//! `DOCSRS_WORKER_UPLOAD_MAX_SIZE` bytes. These endpoints aren't subject to the request timeout.
+
+use super::{
+    admin::constant_time_eq,
+    cache::CachePolicy,
+    error::{AxumNope, JsonAxumNope, JsonAxumResult},
+};
+use crate::{
+    build_queue::BuildLease,
+    db::{add_path_into_database, add_path_into_remote_archive, Pool},
+    docbuilder::{
+        record_build, unzip_into, update_running_build_log, BuildFailure, BuildLog, BuildReport,
+        Heartbeat, LeasedBuild, Limits, StoredArchive,
+    },
+    repositories::RepositoryStatsUpdater,
+    storage::{rustdoc_archive_path, rustdoc_json_path, source_archive_path},
+    utils::{set_config, spawn_blocking, ConfigName},
+    BuildQueue, Config, InstanceMetrics, Storage, RUSTDOC_STATIC_STORAGE_PREFIX,
+};
+use anyhow::Context as _;
+use axum::{
+    async_trait,
+    extract::{BodyStream, Extension, FromRequestParts, Path, Query},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response as AxumResponse},
+    Json,
+};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use std::{
+    fs::File,
+    io::{Read, Seek},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::io::AsyncWriteExt;
+
+/// A build worker authenticated with one of the configured worker tokens.
+pub(crate) struct Worker {
+    name: String,
+}
+
+impl Worker {
+    fn authenticate(config: &Config, token: &str) -> Option<Self> {
+        config
+            .worker_tokens
+            .iter()
+            .find(|(_, expected)| constant_time_eq(expected.as_bytes(), token.as_bytes()))
+            .map(|(name, _)| Self { name: name.clone() })
+    }
+
+    /// Loads the lease `id`, failing when this worker doesn't hold it anymore.
+    fn lease(&self, build_queue: &BuildQueue, id: i32) -> anyhow::Result<BuildLease> {
+        Ok(build_queue
+            .active_lease(id, &self.name)?
+            .ok_or(AxumNope::LeaseLost)?)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Worker
+where
+    S: Send + Sync,
+{
+    type Rejection = JsonAxumNope;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(config) = Extension::<Arc<Config>>::from_request_parts(parts, state)
+            .await
+            .map_err(|err| AxumNope::InternalError(err.into()))?;
+
+        parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .and_then(|token| Worker::authenticate(&config, token))
+            .ok_or(JsonAxumNope(AxumNope::Unauthorized))
+    }
+}
+
+fn lease_duration(config: &Config) -> Duration {
+    Duration::from_secs(config.build_lease_duration)
+}
+
+/// Writes the upload of a worker into a temporary file, which is deleted once it's dropped.
+///
+/// Fails with [`AxumNope::PayloadTooLarge`] once the upload is larger than
+/// `worker_upload_max_size`.
+async fn receive_upload(mut body: BodyStream, config: &Config) -> anyhow::Result<File> {
+    let mut file = tokio::fs::File::from_std(tempfile::tempfile()?);
+    let mut size = 0;
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.context("could not receive upload")?;
+        size += chunk.len() as u64;
+        if size > config.worker_upload_max_size {
+            return Err(AxumNope::PayloadTooLarge.into());
+        }
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+
+    let mut file = file.into_std().await;
+    file.rewind()?;
+    Ok(file)
+}
+
+/// Leases the next queued build to the worker, answering `204 No Content` when there is none.
+pub(crate) async fn lease_handler(
+    worker: Worker,
+    Extension(build_queue): Extension<Arc<BuildQueue>>,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(pool): Extension<Pool>,
+) -> JsonAxumResult<AxumResponse> {
+    let leased = spawn_blocking(move || {
+        let Some(lease) = build_queue.lease_next_crate(&worker.name, lease_duration(&config))?
+        else {
+            return Ok(None);
+        };
+        let limits = Limits::for_crate(&config, &mut *pool.get()?, &lease.name)?;
+        Ok(Some(LeasedBuild { lease, limits }))
+    })
+    .await?;
+
+    Ok(match leased {
+        Some(leased) => (Extension(CachePolicy::NoCaching), Json(leased)).into_response(),
+        None => (Extension(CachePolicy::NoCaching), StatusCode::NO_CONTENT).into_response(),
+    })
+}
+
+/// Renews the lease of a build, and publishes the log of the build when the worker sent one.
+pub(crate) async fn heartbeat_handler(
+    worker: Worker,
+    Path(id): Path<i32>,
+    Extension(build_queue): Extension<Arc<BuildQueue>>,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(pool): Extension<Pool>,
+    log: Option<Json<BuildLog>>,
+) -> JsonAxumResult<impl IntoResponse> {
+    let expires_at = spawn_blocking(move || {
+        let expires_at = build_queue
+            .renew_lease(id, &worker.name, lease_duration(&config))?
+            .ok_or(AxumNope::LeaseLost)?;
+        if let Some(Json(log)) = log {
+            let lease = worker.lease(&build_queue, id)?;
+            update_running_build_log(
+                &mut *pool.get()?,
+                &lease.name,
+                &lease.version,
+                &log.target,
+                &log.log,
+            )?;
+        }
+        Ok(expires_at)
+    })
+    .await?;
+
+    Ok((
+        Extension(CachePolicy::NoCaching),
+        Json(Heartbeat { expires_at }),
+    ))
+}
+
+/// Stores the `rustdoc` or `sources` archive of a build, uploaded as zip file.
+pub(crate) async fn archive_handler(
+    worker: Worker,
+    Path((id, kind)): Path<(i32, String)>,
+    Extension(build_queue): Extension<Arc<BuildQueue>>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(config): Extension<Arc<Config>>,
+    body: BodyStream,
+) -> JsonAxumResult<impl IntoResponse> {
+    let upload = receive_upload(body, &config).await?;
+    let archive = spawn_blocking(move || {
+        let lease = worker.lease(&build_queue, id)?;
+        let (archive_path, public) = match kind.as_str() {
+            "rustdoc" => (rustdoc_archive_path(&lease.name, &lease.version), true),
+            "sources" => (source_archive_path(&lease.name, &lease.version), false),
+            _ => return Err(AxumNope::ResourceNotFound.into()),
+        };
+
+        let dir = tempfile::Builder::new()
+            .prefix(crate::utils::queue_builder::TEMPDIR_PREFIX)
+            .tempdir()?;
+        unzip_into(upload, dir.path())?;
+        let (files, algorithm) =
+            add_path_into_remote_archive(&storage, &archive_path, dir.path(), public)?;
+        Ok(StoredArchive { files, algorithm })
+    })
+    .await?;
+
+    Ok((Extension(CachePolicy::NoCaching), Json(archive)))
+}
+
+/// Whether `target` looks like a target triple, which rules out path separators and `..`.
+fn is_valid_target(target: &str) -> bool {
+    !target.is_empty()
+        && !target.starts_with('.')
+        && target
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Stores the rustdoc JSON output of a build for a target.
+pub(crate) async fn rustdoc_json_handler(
+    worker: Worker,
+    Path((id, target)): Path<(i32, String)>,
+    Extension(build_queue): Extension<Arc<BuildQueue>>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(config): Extension<Arc<Config>>,
+    body: BodyStream,
+) -> JsonAxumResult<impl IntoResponse> {
+    // the target is part of the storage path
+    if !is_valid_target(&target) {
+        return Err(AxumNope::BadRequest.into());
+    }
+
+    let mut upload = receive_upload(body, &config).await?;
+    spawn_blocking(move || {
+        let lease = worker.lease(&build_queue, id)?;
+        let mut content = Vec::new();
+        upload.read_to_end(&mut content)?;
+        storage.store_one(
+            rustdoc_json_path(&lease.name, &lease.version, &target),
+            content,
+        )?;
+        Ok(())
+    })
+    .await?;
+
+    Ok((Extension(CachePolicy::NoCaching), StatusCode::NO_CONTENT))
+}
+
+/// Adds the finished build to the database and removes it from the queue.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn complete_handler(
+    worker: Worker,
+    Path(id): Path<i32>,
+    Extension(build_queue): Extension<Arc<BuildQueue>>,
+    Extension(pool): Extension<Pool>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(metrics): Extension<Arc<InstanceMetrics>>,
+    Extension(repository_stats_updater): Extension<Arc<RepositoryStatsUpdater>>,
+    Json(report): Json<BuildReport>,
+) -> JsonAxumResult<impl IntoResponse> {
+    spawn_blocking(move || {
+        let lease = worker.lease(&build_queue, id)?;
+        if report.package.name != lease.name || report.package.version != lease.version {
+            return Err(AxumNope::BadRequest.into());
+        }
+
+        record_build(
+            &mut *pool.get()?,
+            &storage,
+            &metrics,
+            &repository_stats_updater,
+            report,
+            &worker.name,
+        )?;
+        if !build_queue.finish_lease(&lease, &worker.name, Ok(()))? {
+            return Err(AxumNope::LeaseLost.into());
+        }
+        Ok(())
+    })
+    .await?;
+
+    Ok((Extension(CachePolicy::NoCaching), StatusCode::NO_CONTENT))
+}
+
+/// Hands a build the worker couldn't run back to the queue.
+pub(crate) async fn fail_handler(
+    worker: Worker,
+    Path(id): Path<i32>,
+    Extension(build_queue): Extension<Arc<BuildQueue>>,
+    Json(failure): Json<BuildFailure>,
+) -> JsonAxumResult<impl IntoResponse> {
+    spawn_blocking(move || {
+        let lease = worker.lease(&build_queue, id)?;
+        if !build_queue.finish_lease(&lease, &worker.name, Err(failure.error))? {
+            return Err(AxumNope::LeaseLost.into());
+        }
+        Ok(())
+    })
+    .await?;
+
+    Ok((Extension(CachePolicy::NoCaching), StatusCode::NO_CONTENT))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct EssentialFilesParams {
+    rustc_version: String,
+}
+
+/// Stores the static rustdoc files of a new toolchain of the worker, uploaded as zip file.
+pub(crate) async fn essential_files_handler(
+    _worker: Worker,
+    Query(params): Query<EssentialFilesParams>,
+    Extension(pool): Extension<Pool>,
+    Extension(storage): Extension<Arc<Storage>>,
+    Extension(config): Extension<Arc<Config>>,
+    body: BodyStream,
+) -> JsonAxumResult<impl IntoResponse> {
+    let upload = receive_upload(body, &config).await?;
+    spawn_blocking(move || {
+        let dir = tempfile::Builder::new()
+            .prefix(crate::utils::queue_builder::TEMPDIR_PREFIX)
+            .tempdir()?;
+        unzip_into(upload, dir.path())?;
+        add_path_into_database(&storage, RUSTDOC_STATIC_STORAGE_PREFIX, dir.path())?;
+        set_config(
+            &mut *pool.get()?,
+            ConfigName::RustcVersion,
+            params.rustc_version,
+        )?;
+        Ok(())
+    })
+    .await?;
+
+    Ok((Extension(CachePolicy::NoCaching), StatusCode::NO_CONTENT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docbuilder::{BuildResult, ResourceUsage, WorkerClient};
+    use crate::test::{wrapper, TestEnvironment};
+    use crate::utils::MetadataPackage;
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    const TOKEN: &str = "worker secret";
+
+    fn worker_env(env: &TestEnvironment) -> WorkerClient {
+        worker_env_with(env, |_| {})
+    }
+
+    fn worker_env_with(env: &TestEnvironment, f: impl FnOnce(&mut Config)) -> WorkerClient {
+        env.override_config(|config| {
+            config.worker_tokens = vec![("worker-1".into(), TOKEN.into())];
+            f(config);
+        });
+        WorkerClient::with_server(
+            &format!("http://{}", env.frontend().server_addr()),
+            TOKEN.into(),
+            0,
+        )
+        .unwrap()
+    }
+
+    fn attempt(env: &TestEnvironment, name: &str) -> anyhow::Result<i32> {
+        Ok(env
+            .db()
+            .conn()
+            .query_one("SELECT attempt FROM queue WHERE name = $1", &[&name])?
+            .get(0))
+    }
+
+    #[test]
+    fn unauthenticated_workers_are_rejected() {
+        wrapper(|env| {
+            env.override_config(|config| {
+                config.worker_tokens = vec![("worker-1".into(), TOKEN.into())];
+            });
+            env.build_queue().add_crate("foo", "0.1.0", 0, None)?;
+            let web = env.frontend();
+
+            let response = web.post("/_/worker/lease").send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            let response = web
+                .post("/_/worker/lease")
+                .bearer_auth("wrong token")
+                .send()?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            assert_eq!(env.build_queue().queued_crates()?.len(), 1);
+            assert!(env.build_queue().active_lease(1, "worker-1")?.is_none());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn lease_heartbeat_and_fail() {
+        wrapper(|env| {
+            let client = worker_env(env);
+            assert!(client.lease()?.is_none());
+
+            env.build_queue().add_crate("foo", "0.1.0", 0, None)?;
+            let leased = client.lease()?.unwrap();
+            assert_eq!(leased.lease.name, "foo");
+            assert_eq!(leased.limits, Limits::new(&env.config()));
+            assert!(client.lease()?.is_none());
+
+            let expires_at = client.heartbeat(&leased.lease, None)?.unwrap();
+            assert!(expires_at >= leased.lease.expires_at);
+
+            client.fail(&leased.lease, "the build machine caught fire")?;
+            assert_eq!(attempt(env, "foo")?, 1);
+
+            // the lease is gone
+            assert!(client.heartbeat(&leased.lease, None)?.is_none());
+            assert!(client.fail(&leased.lease, "again").is_err());
+            assert_eq!(attempt(env, "foo")?, 1);
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn heartbeats_publish_the_build_log() {
+        wrapper(|env| {
+            let client = worker_env(env);
+            env.build_queue().add_crate("foo", "0.1.0", 0, None)?;
+            let leased = client.lease()?.unwrap();
+
+            let running = || -> anyhow::Result<(Option<String>, String)> {
+                let row = env.db().conn().query_one(
+                    "SELECT target, log FROM running_builds WHERE name = 'foo'",
+                    &[],
+                )?;
+                Ok((row.get(0), row.get(1)))
+            };
+            assert_eq!(running()?, (None, "".into()));
+
+            let log = BuildLog {
+                target: "x86_64-unknown-linux-gnu".into(),
+                log: "compiling foo".into(),
+            };
+            assert!(client.heartbeat(&leased.lease, Some(&log))?.is_some());
+            let published = (Some(log.target.clone()), log.log.clone());
+            assert_eq!(running()?, published);
+
+            // heartbeats without a log keep the published one
+            assert!(client.heartbeat(&leased.lease, None)?.is_some());
+            assert_eq!(running()?, published);
+
+            // the build is listed until the lease ends
+            client.fail(&leased.lease, "the build machine caught fire")?;
+            assert!(client.heartbeat(&leased.lease, Some(&log))?.is_none());
+            assert!(env
+                .db()
+                .conn()
+                .query_opt("SELECT 1 FROM running_builds WHERE name = 'foo'", &[])?
+                .is_none());
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn valid_targets() {
+        assert!(is_valid_target("x86_64-unknown-linux-gnu"));
+        assert!(is_valid_target("thumbv7em-none-eabihf"));
+        assert!(is_valid_target("wasm32-wasip1"));
+        for target in ["", ".", "..", "../foo", "foo/bar", "foo\\bar"] {
+            assert!(!is_valid_target(target), "{target}");
+        }
+    }
+
+    #[test]
+    fn uploads_are_limited() {
+        wrapper(|env| {
+            let client = worker_env_with(env, |config| config.worker_upload_max_size = 100);
+            env.build_queue().add_crate("foo", "0.1.0", 0, None)?;
+            let leased = client.lease()?.unwrap();
+
+            let response = env
+                .frontend()
+                .post(&format!(
+                    "/_/worker/builds/{}/rustdoc-json/x86_64-unknown-linux-gnu",
+                    leased.lease.id
+                ))
+                .bearer_auth(TOKEN)
+                .body(vec![b' '; 101])
+                .send()?;
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+            client.upload_rustdoc_json(&leased.lease, "x86_64-unknown-linux-gnu", b"{}".into())?;
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn uploads_are_exempt_from_the_request_timeout() {
+        wrapper(|env| {
+            let client = worker_env_with(env, |config| {
+                config.request_timeout = Some(Duration::ZERO);
+            });
+            env.build_queue().add_crate("foo", "0.1.0", 0, None)?;
+
+            let response = env.frontend().get("/releases/queue").send()?;
+            assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+
+            let leased = client.lease()?.unwrap();
+            let docs = tempfile::tempdir()?;
+            std::fs::create_dir(docs.path().join("foo"))?;
+            std::fs::write(docs.path().join("foo/index.html"), "<html>foo</html>")?;
+            client.upload_archive(&leased.lease, "rustdoc", docs.path())?;
+            assert!(env
+                .storage()
+                .exists_in_archive("rustdoc/foo/0.1.0.zip", "foo/index.html")?);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn upload_and_complete_build() {
+        wrapper(|env| {
+            let client = worker_env(env);
+            env.build_queue().add_crate("foo", "0.1.0", 0, None)?;
+            let leased = client.lease()?.unwrap();
+            let lease = &leased.lease;
+
+            let sources = tempfile::tempdir()?;
+            std::fs::create_dir(sources.path().join("src"))?;
+            std::fs::write(sources.path().join("src/lib.rs"), "//! docs")?;
+            let docs = tempfile::tempdir()?;
+            std::fs::create_dir(docs.path().join("foo"))?;
+            std::fs::write(docs.path().join("foo/index.html"), "<html>foo</html>")?;
+
+            assert!(client
+                .upload_archive(lease, "binaries", docs.path())
+                .is_err());
+            let (source_files, source_alg) =
+                client.upload_archive(lease, "sources", sources.path())?;
+            let (_, rustdoc_alg) = client.upload_archive(lease, "rustdoc", docs.path())?;
+            client.upload_rustdoc_json(lease, "x86_64-unknown-linux-gnu", b"{}".to_vec())?;
+            // targets can't escape the storage path of the release
+            for target in ["..%2F..%2Ffoo", "x86_64%2F..%2F.."] {
+                let response = env
+                    .frontend()
+                    .post(&format!(
+                        "/_/worker/builds/{}/rustdoc-json/{target}",
+                        lease.id
+                    ))
+                    .bearer_auth(TOKEN)
+                    .body("{}")
+                    .send()?;
+                assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            }
+
+            let storage = env.storage();
+            assert!(storage.exists_in_archive("rustdoc/foo/0.1.0.zip", "foo/index.html")?);
+            assert!(storage.exists_in_archive("sources/foo/0.1.0.zip", "src/lib.rs")?);
+            assert!(storage.exists(&rustdoc_json_path(
+                "foo",
+                "0.1.0",
+                "x86_64-unknown-linux-gnu"
+            ))?);
+
+            let report = |name: &str| BuildReport {
+                package: MetadataPackage {
+                    id: format!("{name} 0.1.0"),
+                    name: name.into(),
+                    version: "0.1.0".into(),
+                    targets: vec![crate::utils::Target::dummy_lib(name.into(), None)],
+                    ..Default::default()
+                },
+                locked_dependencies: Vec::new(),
+                docs: Default::default(),
+                result: BuildResult {
+                    rustc_version: "rustc 1.69.0-nightly (c8e6a9e8b 2023-01-23)".into(),
+                    docsrs_version: "docsrs 0.0.0".into(),
+                    successful: true,
+                    failure_category: None,
+                },
+                default_target: "x86_64-unknown-linux-gnu".into(),
+                source_files: source_files.clone(),
+                doc_targets: vec!["x86_64-unknown-linux-gnu".into()],
+                json_targets: vec!["x86_64-unknown-linux-gnu".into()],
+                has_docs: true,
+                has_examples: false,
+                compression_algorithms: HashSet::from([source_alg, rustdoc_alg]),
+                doc_coverage: None,
+                search_items: Vec::new(),
+                public_items: Vec::new(),
+                build_logs: vec![("x86_64-unknown-linux-gnu".into(), "build log".into())],
+                resource_usage: vec![ResourceUsage {
+                    target: "x86_64-unknown-linux-gnu".into(),
+                    wall_time: Duration::from_secs(90),
+                    peak_memory: Some(1024 * 1024 * 1024),
+                    output_size: 2048,
+                }],
+                release_data: None,
+                crate_data: None,
+            };
+
+            // the report has to be about the leased crate
+            assert!(client.complete(lease, &report("bar")).is_err());
+            client.complete(lease, &report("foo"))?;
+
+            let row = env.db().conn().query_one(
+                "SELECT builds.build_server, releases.rustdoc_status
+                 FROM builds
+                 INNER JOIN releases ON releases.id = builds.rid
+                 INNER JOIN crates ON crates.id = releases.crate_id
+                 WHERE crates.name = 'foo' AND releases.version = '0.1.0'",
+                &[],
+            )?;
+            assert_eq!(row.get::<_, String>(0), "worker-1");
+            assert!(row.get::<_, bool>(1));
+            let usage = env.db().conn().query_one(
+                "SELECT target, wall_time_ms, peak_memory_bytes, output_size_bytes
+                 FROM build_resource_usage",
+                &[],
+            )?;
+            assert_eq!(usage.get::<_, String>(0), "x86_64-unknown-linux-gnu");
+            assert_eq!(usage.get::<_, i64>(1), 90_000);
+            assert_eq!(usage.get::<_, Option<i64>>(2), Some(1024 * 1024 * 1024));
+            assert_eq!(usage.get::<_, i64>(3), 2048);
+            assert!(env.build_queue().queued_crates()?.is_empty());
+
+            let response = env.frontend().get("/foo/0.1.0/foo/").send()?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(response.text()?.contains("foo"));
+
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn upload_essential_files() {
+        wrapper(|env| {
+            let client = worker_env(env);
+
+            let files = tempfile::tempdir()?;
+            std::fs::write(files.path().join("rustdoc-1.0.0.css"), "body {}")?;
+            client.upload_essential_files("rustc 1.0.0 (000000000 2015-05-15)", files.path())?;
+
+            assert!(env
+                .storage()
+                .exists(&format!("{RUSTDOC_STATIC_STORAGE_PREFIX}rustdoc-1.0.0.css"))?);
+            assert_eq!(
+                crate::utils::get_config::<String>(&mut env.db().conn(), ConfigName::RustcVersion)?
+                    .as_deref(),
+                Some("rustc 1.0.0 (000000000 2015-05-15)")
+            );
+            Ok(())
+        });
+    }
+}